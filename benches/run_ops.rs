@@ -0,0 +1,76 @@
+//! `LpPool::run_ops` against calling the pool once per operation, run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use liquidity_pool::lp_pool::{
+    bulk::{OpOutput, PoolOp},
+    data::{
+        fee::FeeRange,
+        token::{LpTokenAmount, StakedTokenAmount, TokenAmount, LAMPORTS_PER_SOL},
+    },
+    LpPool, PoolParams,
+};
+
+const OPS: u64 = 10_000;
+
+fn pool() -> LpPool {
+    let mut pool = LpPool::init(PoolParams {
+        price: "1.5".parse().unwrap(),
+        fee_range: FeeRange::new("0.1".parse().unwrap(), "9".parse().unwrap()).unwrap(),
+        liquidity_target: TokenAmount::from_lamports(90 * LAMPORTS_PER_SOL),
+    })
+    .unwrap();
+    pool.add_liquidity(TokenAmount::from_lamports(100 * LAMPORTS_PER_SOL))
+        .unwrap();
+    pool
+}
+
+fn ops() -> impl Iterator<Item = PoolOp> {
+    (0..OPS).map(|index| match index % 4 {
+        0 => PoolOp::AddLiquidity(TokenAmount::from_lamports(LAMPORTS_PER_SOL / 100)),
+        3 => PoolOp::RemoveLiquidity(LpTokenAmount::from_lamports(LAMPORTS_PER_SOL / 100)),
+        _ => PoolOp::Swap(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL / 200)),
+    })
+}
+
+fn run_ops(c: &mut Criterion) {
+    let mut group = c.benchmark_group("run_ops");
+    group.bench_function("run_ops", |b| {
+        b.iter_batched(
+            pool,
+            |mut pool| {
+                pool.run_ops(ops(), |index, result| {
+                    black_box((index, result));
+                });
+                pool
+            },
+            BatchSize::SmallInput,
+        );
+    });
+    group.bench_function("individual_calls", |b| {
+        b.iter_batched(
+            pool,
+            |mut pool| {
+                for op in ops() {
+                    let result = match op {
+                        PoolOp::AddLiquidity(tokens) => {
+                            pool.add_liquidity(tokens).map(OpOutput::LpTokens)
+                        }
+                        PoolOp::RemoveLiquidity(lp_tokens) => pool
+                            .remove_liquidity(lp_tokens)
+                            .map(|(tokens, _)| OpOutput::Tokens(tokens)),
+                        PoolOp::Swap(staked_tokens) => {
+                            pool.swap(staked_tokens).map(OpOutput::Tokens)
+                        }
+                    };
+                    black_box(result).ok();
+                }
+                pool
+            },
+            BatchSize::SmallInput,
+        );
+    });
+    group.finish();
+}
+
+criterion_group!(benches, run_ops);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+//! Seeded backtest of the balanced and swap-heavy simulation presets.
+//!
+//! `cargo run --example backtest`, the same seed always prints the same reports.
+
+use liquidity_pool::prelude::*;
+use liquidity_pool::sim::{SimConfig, Simulation};
+
+const SEED: u64 = 42;
+
+fn main() -> Result<()> {
+    for (name, config) in [
+        ("balanced", SimConfig::balanced()),
+        ("swap-heavy", SimConfig::swap_heavy()),
+    ] {
+        println!(
+            "== {name}: {} epochs of {} operations, seed {SEED}",
+            config.epochs, config.ops_per_epoch
+        );
+        let report = Simulation::new(SEED, config)?.run();
+        println!("{report}");
+        println!();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_runs_to_completion() {
+        main().unwrap();
+    }
+}
@@ -0,0 +1,63 @@
+//! The `lp-pool` demo flow, printing the pool after every step.
+//!
+//! `cargo run --example basic`.
+
+use liquidity_pool::prelude::*;
+
+fn print_step(step: &str, pool: &LpPool) {
+    println!("== {step}");
+    println!("{pool:#}");
+    println!("{}", pool.status_line());
+    if let Ok(lp_price) = pool.lp_price() {
+        println!("1 lp = {}", lp_price.tokens.whole_tokens());
+    }
+    println!();
+}
+
+fn main() -> Result<()> {
+    let mut pool = LpPool::init(PoolParams {
+        price: Price::try_from(1.5)?,
+        fee_range: FeeRange::new(Fee::from_basis_points(10), Fee::from_basis_points(900))?,
+        liquidity_target: TokenAmount::from_lamports(90_000),
+    })?;
+    print_step("init", &pool);
+
+    let lp_tokens = pool.add_liquidity(TokenAmount::from_lamports(100_000_000))?;
+    print_step(
+        &format!("add_liquidity: minted {}", lp_tokens.whole_tokens()),
+        &pool,
+    );
+
+    let tokens_out = pool.swap(StakedTokenAmount::from_lamports(6_000))?;
+    print_step(
+        &format!("swap: paid out {}", tokens_out.whole_tokens()),
+        &pool,
+    );
+
+    let lp_tokens = pool.add_liquidity(TokenAmount::from_lamports(10_000))?;
+    print_step(
+        &format!("add_liquidity: minted {}", lp_tokens.whole_tokens()),
+        &pool,
+    );
+
+    let (tokens, staked_tokens) = pool.remove_liquidity(LpTokenAmount::from_lamports(2_000))?;
+    print_step(
+        &format!(
+            "remove_liquidity: paid out {} and {}",
+            tokens.whole_tokens(),
+            staked_tokens.whole_tokens()
+        ),
+        &pool,
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_runs_to_completion() {
+        main().unwrap();
+    }
+}
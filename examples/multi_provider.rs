@@ -0,0 +1,143 @@
+//! Three liquidity providers sharing the swap fees of one pool, then withdrawing their
+//! positions, with a table reconciling what went in against what came out.
+//!
+//! `cargo run --example multi_provider`.
+
+use liquidity_pool::calc::proportional;
+use liquidity_pool::prelude::*;
+use liquidity_pool::{LAMPORTS_PER_SOL, MAX_BASIS_POINTS};
+
+struct Provider {
+    name: &'static str,
+    deposited: TokenAmount,
+    lp_tokens: LpTokenAmount,
+    /// Share of the LP supply right before the withdrawals, in basis points.
+    share_bps: u32,
+    tokens_out: TokenAmount,
+    staked_out: StakedTokenAmount,
+}
+
+impl Provider {
+    /// Tokens and staked tokens paid out, the staked tokens at `pool`'s price.
+    fn value_out(&self, pool: &LpPool) -> Result<TokenAmount> {
+        let staked_value = TokenAmount::from_staked_tokens(self.staked_out, pool.state().price)?;
+        Ok(self.tokens_out + staked_value)
+    }
+}
+
+fn sol(lamports: u64) -> TokenAmount {
+    TokenAmount::from_lamports(lamports * LAMPORTS_PER_SOL)
+}
+
+fn main() -> Result<()> {
+    let mut pool = LpPool::init(PoolParams {
+        price: "1.2".parse()?,
+        fee_range: FeeRange::new("0.1".parse::<Fee>()?, "9".parse::<Fee>()?)?,
+        liquidity_target: sol(100),
+    })?;
+
+    let mut providers = Vec::new();
+    for (name, deposit) in [("alice", sol(40)), ("bob", sol(25)), ("carol", sol(60))] {
+        providers.push(Provider {
+            name,
+            deposited: deposit,
+            lp_tokens: pool.add_liquidity(deposit)?,
+            share_bps: 0,
+            tokens_out: TokenAmount::from_lamports(0),
+            staked_out: StakedTokenAmount::from_lamports(0),
+        });
+    }
+
+    let mut staked_in = StakedTokenAmount::from_lamports(0);
+    let mut swap_payouts = TokenAmount::from_lamports(0);
+    for staked in [1, 3, 2, 4, 2] {
+        let staked = StakedTokenAmount::from_lamports(staked * LAMPORTS_PER_SOL);
+        swap_payouts += pool.swap(staked)?;
+        staked_in += staked;
+    }
+    let swap_fees = pool.state().fees_collected;
+    println!("after swaps: {}", pool.status_line());
+    println!();
+
+    for provider in &mut providers {
+        provider.share_bps = pool.share_bps(provider.lp_tokens)?;
+    }
+    for provider in &mut providers {
+        (provider.tokens_out, provider.staked_out) = pool.remove_liquidity(provider.lp_tokens)?;
+    }
+
+    println!(
+        "{:<8}{:>14}{:>7}{:>18}{:>14}{:>18}{:>16}{:>16}",
+        "provider",
+        "deposited",
+        "share",
+        "sol out",
+        "msol out",
+        "value out",
+        "gain lamports",
+        "swap fees"
+    );
+    let mut withdrawn = TokenAmount::from_lamports(0);
+    for provider in &providers {
+        let value_out = provider.value_out(&pool)?;
+        withdrawn += value_out;
+        // fees stay with the pool, this is the provider's share of the swap fees
+        let fees_at_share = proportional(
+            swap_fees.into(),
+            provider.share_bps.into(),
+            MAX_BASIS_POINTS.into(),
+        )?;
+        println!(
+            "{:<8}{:>14}{:>6}%{:>18}{:>14}{:>18}{:>16}{:>16}",
+            provider.name,
+            provider.deposited.whole_tokens().to_string(),
+            provider.share_bps / 100,
+            provider.tokens_out.whole_tokens().to_string(),
+            provider.staked_out.whole_tokens().to_string(),
+            value_out.whole_tokens().to_string(),
+            TokenAmount::signed_diff(value_out, provider.deposited).to_string(),
+            TokenAmount::from_lamports(fees_at_share)
+                .whole_tokens()
+                .to_string(),
+        );
+    }
+
+    let deposited = providers
+        .iter()
+        .fold(TokenAmount::from_lamports(0), |total, provider| {
+            total + provider.deposited
+        });
+    let state = pool.state();
+    let swap_value = TokenAmount::from_staked_tokens(staked_in, state.price)?;
+    println!();
+    println!(
+        "in:  {} deposited + {} swapped in at {}",
+        deposited.whole_tokens(),
+        staked_in.whole_tokens(),
+        state.price
+    );
+    println!(
+        "out: {} withdrawn + {} paid to swappers + {} fees kept by the pool",
+        withdrawn.whole_tokens(),
+        swap_payouts.whole_tokens(),
+        state.fees_collected.whole_tokens()
+    );
+    println!(
+        "unclaimed: {} lamports",
+        TokenAmount::signed_diff(
+            deposited + swap_value,
+            withdrawn + swap_payouts + state.fees_collected
+        )
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_runs_to_completion() {
+        main().unwrap();
+    }
+}
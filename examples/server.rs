@@ -0,0 +1,260 @@
+//! HTTP service quoting and executing swaps against one shared pool.
+//!
+//! `cargo run --example server --features server`, then
+//! `curl 'localhost:3000/quote/swap?staked=1000000'`.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use liquidity_pool::error::ErrorCategory;
+use liquidity_pool::lp_pool::{quote::Quote, shared::SharedLpPool};
+use liquidity_pool::prelude::{
+    Error, Fee, FeeRange, LpPool, LpPoolError, PoolParams, PoolState, StakedTokenAmount,
+    TokenAmount,
+};
+use liquidity_pool::LAMPORTS_PER_SOL;
+use serde::{Deserialize, Serialize};
+
+/// Library error as a response, with the status picked from its `Error::code`.
+struct ApiError(Error);
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        Self(error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ErrorBody {
+    code: u32,
+    error: String,
+}
+
+fn status(error: &Error) -> StatusCode {
+    if matches!(error, Error::LpPool(LpPoolError::StaleQuote { .. })) {
+        return StatusCode::CONFLICT;
+    }
+    match error.category() {
+        ErrorCategory::Validation => StatusCode::BAD_REQUEST,
+        ErrorCategory::Liquidity => StatusCode::UNPROCESSABLE_ENTITY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            code: self.0.code(),
+            error: self.0.display_chain().to_string(),
+        };
+        (status(&self.0), Json(body)).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SwapQuery {
+    /// Lamports of staked tokens.
+    staked: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SwapRequest {
+    staked: u64,
+    /// Fails with `SlippageExceeded` if the swap would pay out fewer lamports.
+    #[serde(default)]
+    min_out: Option<u64>,
+    /// `pool_sequence` of the quote the swap was based on, fails with `StaleQuote` if the
+    /// pool changed since.
+    #[serde(default)]
+    pool_sequence: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SwapResponse {
+    tokens_out: TokenAmount,
+    pool_sequence: u64,
+}
+
+async fn state(State(pool): State<SharedLpPool>) -> Json<PoolState> {
+    Json(pool.state())
+}
+
+async fn quote_swap(
+    State(pool): State<SharedLpPool>,
+    Query(query): Query<SwapQuery>,
+) -> Result<Json<Quote<StakedTokenAmount, TokenAmount>>, ApiError> {
+    let quote =
+        pool.with(|pool| pool.quote_swap(StakedTokenAmount::from_lamports(query.staked)))?;
+    Ok(Json(quote))
+}
+
+async fn swap(
+    State(pool): State<SharedLpPool>,
+    Json(request): Json<SwapRequest>,
+) -> Result<Json<SwapResponse>, ApiError> {
+    let swapped = pool.with(|pool| {
+        let quote = pool.quote_swap(StakedTokenAmount::from_lamports(request.staked))?;
+        if let Some(min_out) = request.min_out.map(TokenAmount::from_lamports) {
+            if quote.amount_out < min_out {
+                return Err(Error::LpPool(LpPoolError::SlippageExceeded {
+                    min_out,
+                    quoted: quote.amount_out,
+                }));
+            }
+        }
+        let tokens_out = pool.execute_quote(&Quote {
+            pool_sequence: request.pool_sequence.unwrap_or(quote.pool_sequence),
+            ..quote
+        })?;
+        Ok(SwapResponse {
+            tokens_out,
+            pool_sequence: pool.sequence(),
+        })
+    })?;
+    Ok(Json(swapped))
+}
+
+fn app(pool: SharedLpPool) -> Router {
+    Router::new()
+        .route("/state", get(state))
+        .route("/quote/swap", get(quote_swap))
+        .route("/swap", post(swap))
+        .with_state(pool)
+}
+
+fn demo_pool() -> Result<LpPool, Error> {
+    let mut pool = LpPool::init(PoolParams {
+        price: "1.5".parse()?,
+        fee_range: FeeRange::new("0.1".parse::<Fee>()?, "9".parse::<Fee>()?)?,
+        liquidity_target: TokenAmount::from_lamports(90 * LAMPORTS_PER_SOL),
+    })?;
+    pool.add_liquidity(TokenAmount::from_lamports(100 * LAMPORTS_PER_SOL))?;
+    Ok(pool)
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let pool = demo_pool().map_err(|error| std::io::Error::other(error.to_string()))?;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await?;
+    axum::serve(listener, app(SharedLpPool::new(pool))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use serde::de::DeserializeOwned;
+    use tower::ServiceExt;
+
+    fn shared() -> SharedLpPool {
+        SharedLpPool::new(demo_pool().unwrap())
+    }
+
+    async fn send<T: DeserializeOwned>(
+        pool: &SharedLpPool,
+        request: Request<Body>,
+    ) -> (StatusCode, T) {
+        let response = app(pool.clone()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    fn post_swap(request: &SwapRequest) -> Request<Body> {
+        Request::post("/swap")
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_vec(request).unwrap()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_serves_state_and_quote() {
+        let pool = shared();
+
+        let (status, state) = send::<PoolState>(&pool, get("/state")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(state, pool.state());
+
+        let (status, quote) = send::<Quote<StakedTokenAmount, TokenAmount>>(
+            &pool,
+            get("/quote/swap?staked=1000000000"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            quote,
+            pool.with(|pool| pool.quote_swap(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL)))
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_executes_swap_with_min_out() {
+        let pool = shared();
+        let quote = pool
+            .with(|pool| pool.quote_swap(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL)))
+            .unwrap();
+
+        let (status, swapped) = send::<SwapResponse>(
+            &pool,
+            post_swap(&SwapRequest {
+                staked: LAMPORTS_PER_SOL,
+                min_out: Some(quote.amount_out.into()),
+                pool_sequence: Some(quote.pool_sequence),
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(swapped.tokens_out, quote.amount_out);
+        assert_eq!(swapped.pool_sequence, pool.with(|pool| pool.sequence()));
+    }
+
+    #[tokio::test]
+    async fn it_maps_errors_to_status_codes() {
+        let pool = shared();
+        let sequence = pool.with(|pool| pool.sequence());
+        let request = |staked, min_out, pool_sequence| {
+            post_swap(&SwapRequest {
+                staked,
+                min_out,
+                pool_sequence,
+            })
+        };
+
+        let capped = demo_pool()
+            .unwrap()
+            .with_max_swap_size(Some(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL)))
+            .unwrap();
+        let (status, body) = send::<ErrorBody>(
+            &SharedLpPool::new(capped),
+            get("/quote/swap?staked=2000000000"),
+        )
+        .await;
+        assert_eq!((status, body.code), (StatusCode::BAD_REQUEST, 111));
+
+        let (status, body) =
+            send::<ErrorBody>(&pool, request(1_000 * LAMPORTS_PER_SOL, None, None)).await;
+        assert_eq!((status, body.code), (StatusCode::UNPROCESSABLE_ENTITY, 201));
+
+        let (status, body) =
+            send::<ErrorBody>(&pool, request(LAMPORTS_PER_SOL, Some(u64::MAX), None)).await;
+        assert_eq!((status, body.code), (StatusCode::UNPROCESSABLE_ENTITY, 208));
+
+        let (status, body) = send::<ErrorBody>(
+            &pool,
+            request(LAMPORTS_PER_SOL, None, Some(sequence.wrapping_sub(1))),
+        )
+        .await;
+        assert_eq!((status, body.code), (StatusCode::CONFLICT, 117));
+        assert_eq!(pool.with(|pool| pool.sequence()), sequence);
+    }
+}
@@ -1,13 +1,253 @@
+//! Integer helpers behind the pool math. Everything is computed in `u128` and fails with
+//! `Error::CalculationError` naming the operation and its inputs when the result does not fit
+//! `u64`.
+
 use super::error::{Error, Result};
+use super::lp_pool::consts::MAX_BASIS_POINTS;
+
+/// `amount * numerator / denominator` rounded down, `amount` when `denominator` is zero.
+///
+/// # Errors
+///
+/// Returns `Error::CalculationError` when the result does not fit `u64`.
+///
+/// ```
+/// use liquidity_pool::calc::proportional;
+///
+/// assert_eq!(proportional(10, 2, 3).unwrap(), 6);
+/// assert_eq!(proportional(10, 2, 0).unwrap(), 10);
+/// assert!(proportional(u64::MAX, 2, 1).is_err());
+/// ```
 pub fn proportional(amount: u64, numerator: u64, denominator: u64) -> Result<u64> {
     if denominator == 0 {
         return Ok(amount);
     }
     u64::try_from((u128::from(amount)) * (u128::from(numerator)) / (u128::from(denominator)))
-        .map_err(|_| Error::CalculationError)
+        .map_err(|_| Error::CalculationError {
+            op: "proportional",
+            a: amount,
+            b: numerator,
+            c: denominator,
+        })
+}
+
+/// `proportional` rounded up.
+///
+/// # Errors
+///
+/// Returns `Error::CalculationError` when the result does not fit `u64`.
+///
+/// ```
+/// use liquidity_pool::calc::proportional_ceil;
+///
+/// assert_eq!(proportional_ceil(10, 2, 3).unwrap(), 7);
+/// assert_eq!(proportional_ceil(9, 2, 3).unwrap(), 6);
+/// ```
+pub fn proportional_ceil(amount: u64, numerator: u64, denominator: u64) -> Result<u64> {
+    if denominator == 0 {
+        return Ok(amount);
+    }
+    u64::try_from((u128::from(amount) * u128::from(numerator)).div_ceil(u128::from(denominator)))
+        .map_err(|_| Error::CalculationError {
+            op: "proportional_ceil",
+            a: amount,
+            b: numerator,
+            c: denominator,
+        })
 }
 
+/// Value of `shares` out of `total_shares` claiming `total_value`, rounded down.
+///
+/// # Errors
+///
+/// Returns `Error::CalculationError` when the value does not fit `u64`.
+///
+/// ```
+/// use liquidity_pool::calc::value_from_shares;
+///
+/// assert_eq!(value_from_shares(25, 1_000, 100).unwrap(), 250);
+/// ```
 #[inline]
 pub fn value_from_shares(shares: u64, total_value: u64, total_shares: u64) -> Result<u64> {
     proportional(shares, total_value, total_shares)
 }
+
+/// Shares worth `value` out of `total_shares` claiming `total_value`, rounded down.
+///
+/// # Errors
+///
+/// Returns `Error::CalculationError` when the shares do not fit `u64`.
+///
+/// ```
+/// use liquidity_pool::calc::shares_from_value;
+///
+/// assert_eq!(shares_from_value(250, 1_000, 100).unwrap(), 25);
+/// ```
+#[inline]
+pub fn shares_from_value(value: u64, total_value: u64, total_shares: u64) -> Result<u64> {
+    proportional(value, total_shares, total_value)
+}
+
+/// `bps` basis points of `amount`, rounded down. More than 100% is allowed.
+///
+/// # Errors
+///
+/// Returns `Error::CalculationError` when the result does not fit `u64`.
+///
+/// ```
+/// use liquidity_pool::calc::bps_of;
+///
+/// assert_eq!(bps_of(1_000, 25).unwrap(), 2);
+/// assert_eq!(bps_of(1_000, 20_000).unwrap(), 2_000);
+/// ```
+pub fn bps_of(amount: u64, bps: u32) -> Result<u64> {
+    checked_bps_of(amount, bps).ok_or(Error::CalculationError {
+        op: "bps_of",
+        a: amount,
+        b: u64::from(bps),
+        c: u64::from(MAX_BASIS_POINTS),
+    })
+}
+
+/// `bps_of` returning `None` when the result does not fit `u64`, for `const` contexts.
+///
+/// ```
+/// use liquidity_pool::calc::checked_bps_of;
+///
+/// const FEE: Option<u64> = checked_bps_of(1_000, 25);
+/// assert_eq!(FEE, Some(2));
+/// assert_eq!(checked_bps_of(u64::MAX, 20_000), None);
+/// ```
+#[must_use]
+pub const fn checked_bps_of(amount: u64, bps: u32) -> Option<u64> {
+    let value = amount as u128 * bps as u128 / MAX_BASIS_POINTS as u128;
+    if value > u64::MAX as u128 {
+        return None;
+    }
+    #[allow(
+        clippy::cast_possible_truncation,
+        reason = "checked against `u64::MAX` above"
+    )]
+    Some(value as u64)
+}
+
+/// Splits `amount` into a fee of `bps` basis points, rounded up in favor of the pool, and the
+/// remainder. Fails above 100%.
+///
+/// # Errors
+///
+/// Returns `Error::CalculationError` for `bps` above `MAX_BASIS_POINTS`.
+///
+/// ```
+/// use liquidity_pool::calc::apply_bps_fee;
+///
+/// assert_eq!(apply_bps_fee(1_000, 25).unwrap(), (3, 997));
+/// assert!(apply_bps_fee(1_000, 10_001).is_err());
+/// ```
+pub fn apply_bps_fee(amount: u64, bps: u32) -> Result<(u64, u64)> {
+    if bps > MAX_BASIS_POINTS {
+        return Err(Error::CalculationError {
+            op: "apply_bps_fee",
+            a: amount,
+            b: u64::from(bps),
+            c: u64::from(MAX_BASIS_POINTS),
+        });
+    }
+    let fee = proportional_ceil(amount, u64::from(bps), u64::from(MAX_BASIS_POINTS))?;
+    Ok((fee, amount - fee))
+}
+
+/// Parses a non-negative decimal into an integer scaled by `10^decimals`, `None` when it is
+/// malformed, has more significant decimals or overflows.
+///
+/// ```
+/// use liquidity_pool::calc::parse_decimal;
+///
+/// assert_eq!(parse_decimal("1.5", 2), Some(150));
+/// assert_eq!(parse_decimal("1.555", 2), None);
+/// ```
+#[must_use]
+pub fn parse_decimal(value: &str, decimals: u32) -> Option<u64> {
+    let value = value.trim();
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if whole.is_empty() || !whole.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let (kept, rest) = fraction.split_at(fraction.len().min(decimals as usize));
+    if rest.bytes().any(|b| b != b'0') {
+        return None;
+    }
+
+    let mut scaled: u64 = whole.parse().ok()?;
+    for position in 0..decimals as usize {
+        let digit = kept
+            .as_bytes()
+            .get(position)
+            .map_or(0, |b| u64::from(b - b'0'));
+        scaled = scaled.checked_mul(10)?.checked_add(digit)?;
+    }
+    Some(scaled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn it_parses_decimals_exactly() {
+        assert_eq!(parse_decimal("1.5", 2), Some(150));
+        assert_eq!(parse_decimal("0.01", 2), Some(1));
+        assert_eq!(parse_decimal("7", 3), Some(7000));
+        assert_eq!(parse_decimal("1.2500", 2), Some(125));
+    }
+
+    #[test]
+    fn it_rejects_malformed_or_too_precise_decimals() {
+        assert_eq!(parse_decimal("1.255", 2), None);
+        assert_eq!(parse_decimal(".5", 2), None);
+        assert_eq!(parse_decimal("1,5", 2), None);
+        assert_eq!(parse_decimal("18446744073709551616", 0), None);
+    }
+
+    proptest! {
+        #[test]
+        fn it_splits_amount_into_fee_and_remainder(amount: u64, bps in 0..=MAX_BASIS_POINTS) {
+            let (fee, remainder) = apply_bps_fee(amount, bps).unwrap();
+
+            prop_assert_eq!(u128::from(fee) + u128::from(remainder), u128::from(amount));
+            prop_assert!(fee >= bps_of(amount, bps).unwrap());
+        }
+
+        #[test]
+        fn it_rounds_ceil_at_or_above_floor(amount: u32, numerator: u32, denominator: u32) {
+            let (amount, numerator, denominator) =
+                (u64::from(amount), u64::from(numerator), u64::from(denominator));
+            let floor = proportional(amount, numerator, denominator).unwrap();
+            let ceil = proportional_ceil(amount, numerator, denominator).unwrap();
+
+            prop_assert!(ceil >= floor);
+            prop_assert!(ceil - floor <= 1);
+        }
+
+        #[test]
+        fn it_keeps_proportional_monotone(
+            amount: u32,
+            numerator: u32,
+            denominator in 1..=u32::MAX,
+            step in 0..=1_000u64,
+        ) {
+            let (amount, numerator, denominator) =
+                (u64::from(amount), u64::from(numerator), u64::from(denominator));
+            let value = proportional(amount, numerator, denominator).unwrap();
+
+            prop_assert!(proportional(amount + step, numerator, denominator).unwrap() >= value);
+            prop_assert!(proportional(amount, numerator + step, denominator).unwrap() >= value);
+            prop_assert!(proportional(amount, numerator, denominator + step).unwrap() <= value);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use liquidity_pool::sim::compare::ComparisonReport;
+#[cfg(feature = "compare")]
+use liquidity_pool::sim::compare::{compare_scenarios, PoolConfig};
+
+use super::Flags;
+
+#[cfg(feature = "compare")]
+fn read(flags: &Flags, name: &str) -> Result<String, String> {
+    let path = flags.get(name).ok_or_else(|| format!("missing --{name}"))?;
+    std::fs::read_to_string(path).map_err(|error| format!("cannot read {path}: {error}"))
+}
+
+#[cfg(feature = "compare")]
+fn config(flags: &Flags, name: &str) -> Result<PoolConfig, String> {
+    toml::from_str(&read(flags, name)?).map_err(|error| format!("invalid --{name}: {error}"))
+}
+
+#[cfg(feature = "compare")]
+pub fn run(flags: &Flags) -> Result<ComparisonReport, String> {
+    let ops = serde_json::from_str::<Vec<_>>(&read(flags, "ops")?)
+        .map_err(|error| format!("invalid --ops: {error}"))?;
+    let config_a = config(flags, "config-a")?;
+    let config_b = config(flags, "config-b")?;
+    compare_scenarios(&ops, &config_a, &config_b).map_err(|error| error.display_chain().to_string())
+}
+
+#[cfg(not(feature = "compare"))]
+pub fn run(_flags: &Flags) -> Result<ComparisonReport, String> {
+    Err("compare requires lp-pool built with the `compare` feature".to_string())
+}
+
+#[cfg(all(test, feature = "compare"))]
+mod tests {
+    use super::*;
+    use liquidity_pool::lp_pool::data::token::SignedTokenAmount;
+    use liquidity_pool::prelude::*;
+    use liquidity_pool::sim::compare::Rejected;
+
+    fn flags(ops: &str, config_b: &str) -> Flags {
+        let args: Vec<String> = [
+            "--ops",
+            ops,
+            "--config-a",
+            "tests/fixtures/compare_a.toml",
+            "--config-b",
+            config_b,
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+        Flags::parse(&args).unwrap()
+    }
+
+    #[test]
+    fn it_compares_fixture_stream() {
+        let report = run(&flags(
+            "tests/fixtures/compare_ops.json",
+            "tests/fixtures/compare_b.toml",
+        ))
+        .unwrap();
+
+        assert_eq!(report.operations.len(), 7);
+        let diverging: Vec<_> = report
+            .diverging()
+            .map(|compared| compared.sequence)
+            .collect();
+        assert_eq!(diverging, vec![3]);
+        assert_eq!(report.operations[3].rejected, Rejected::OnlyB);
+        assert_eq!(report.a.rejected, 0);
+        assert_eq!(report.rejected_delta, 1);
+        assert_eq!(
+            report.fees_collected_delta,
+            TokenAmount::signed_diff(report.b.fees_collected, report.a.fees_collected)
+        );
+        assert_ne!(report.fees_collected_delta, SignedTokenAmount::ZERO);
+    }
+
+    #[test]
+    fn it_reports_same_outcome_for_same_config() {
+        let report = run(&flags(
+            "tests/fixtures/compare_ops.json",
+            "tests/fixtures/compare_a.toml",
+        ))
+        .unwrap();
+
+        assert!(report
+            .operations
+            .iter()
+            .all(|compared| compared.output_delta == Some(0)));
+        assert_eq!(report.a, report.b);
+    }
+
+    #[test]
+    fn it_reports_unreadable_input() {
+        assert_eq!(
+            run(&flags("missing.json", "tests/fixtures/compare_b.toml"))
+                .unwrap_err()
+                .split(':')
+                .next(),
+            Some("cannot read missing.json")
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use liquidity_pool::prelude::*;
+
+use super::Flags;
+
+fn price(flags: &Flags) -> std::result::Result<Price, String> {
+    match (flags.get("price"), flags.get("price-from-url")) {
+        (Some(price), None) => price
+            .parse::<Price>()
+            .map_err(|error| error.display_chain().to_string()),
+        (None, Some(url)) => fetch_price(url, flags.get("json-pointer").unwrap_or_default()),
+        (Some(_), Some(_)) => {
+            Err("--price and --price-from-url are mutually exclusive".to_string())
+        }
+        (None, None) => Err("missing --price or --price-from-url".to_string()),
+    }
+}
+
+#[cfg(feature = "net")]
+fn fetch_price(url: &str, json_pointer: &str) -> std::result::Result<Price, String> {
+    liquidity_pool::net::fetch_price(url, json_pointer)
+        .map_err(|error| error.display_chain().to_string())
+}
+
+#[cfg(not(feature = "net"))]
+fn fetch_price(_url: &str, _json_pointer: &str) -> std::result::Result<Price, String> {
+    Err("--price-from-url requires lp-pool built with the `net` feature".to_string())
+}
+
+fn required<'a>(flags: &'a Flags, name: &str) -> std::result::Result<&'a str, String> {
+    flags.get(name).ok_or_else(|| format!("missing --{name}"))
+}
+
+pub fn run(flags: &Flags) -> std::result::Result<LpPool, String> {
+    let price = price(flags)?;
+    let min_fee = required(flags, "min-fee")?;
+    let max_fee = required(flags, "max-fee")?;
+    let target = required(flags, "target")?;
+
+    let pool = (|| -> Result<LpPool> {
+        LpPool::init(PoolParams {
+            price,
+            fee_range: FeeRange::new(min_fee.parse::<Fee>()?, max_fee.parse::<Fee>()?)?,
+            liquidity_target: target.parse::<TokenAmount>()?,
+        })?
+        .with_max_swap_size(flags.get("max-swap").map(str::parse).transpose()?)?
+        .with_token_cap(flags.get("token-cap").map(str::parse).transpose()?)
+    })();
+    pool.map_err(|error| error.display_chain().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(args: &[&str]) -> Flags {
+        let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+        Flags::parse(&args).unwrap()
+    }
+
+    #[test]
+    fn it_initializes_pool_from_flags() {
+        let pool = run(&flags(&[
+            "--price",
+            "1.5",
+            "--min-fee",
+            "0.1",
+            "--max-fee",
+            "9",
+            "--target",
+            "90sol",
+        ]))
+        .unwrap();
+
+        assert_eq!(pool.state().price, Price::try_from_points(150).unwrap());
+        assert_eq!(pool.state().max_fee, Fee::from_basis_points(900));
+    }
+
+    #[test]
+    fn it_applies_optional_cap_flags() {
+        let mut pool = run(&flags(&[
+            "--price",
+            "1",
+            "--min-fee",
+            "0.1",
+            "--max-fee",
+            "9",
+            "--target",
+            "90sol",
+            "--token-cap",
+            "100sol",
+        ]))
+        .unwrap();
+
+        assert!(pool.add_liquidity("101sol".parse().unwrap()).is_err());
+        assert!(pool.add_liquidity("100sol".parse().unwrap()).is_ok());
+
+        let error = run(&flags(&[
+            "--price",
+            "1",
+            "--min-fee",
+            "0.1",
+            "--max-fee",
+            "9",
+            "--target",
+            "90sol",
+            "--max-swap",
+            "0",
+        ]))
+        .unwrap_err();
+        assert_eq!(error, "LpPool: IncorrectMaxSwapSize(0)");
+    }
+
+    #[test]
+    fn it_reports_missing_and_conflicting_price_flags() {
+        assert_eq!(
+            run(&flags(&["--min-fee", "0.1"])).err(),
+            Some("missing --price or --price-from-url".to_string())
+        );
+        assert_eq!(
+            run(&flags(&[
+                "--price",
+                "1",
+                "--price-from-url",
+                "http://localhost"
+            ]))
+            .err(),
+            Some("--price and --price-from-url are mutually exclusive".to_string())
+        );
+        assert_eq!(
+            run(&flags(&["--price", "1", "--min-fee", "0.1"])).err(),
+            Some("missing --max-fee".to_string())
+        );
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn it_reports_price_fetch_failures() {
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let url = format!("http://{address}/");
+        let error = run(&flags(&["--price-from-url", &url])).unwrap_err();
+        assert!(error.starts_with("PriceFetch(request to"), "{error}");
+    }
+}
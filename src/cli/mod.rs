@@ -0,0 +1,122 @@
+pub mod compare;
+pub mod init;
+pub mod repl;
+pub mod stress;
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+usage: lp-pool [command]
+
+commands:
+  (none)    run the demo scenario
+  repl      start an interactive session
+  init      initialize a pool and print its state
+            --price <price> | --price-from-url <url> [--json-pointer <pointer>]
+            --min-fee <percent> --max-fee <percent> --target <amount>
+            [--max-swap <amount>] [--token-cap <amount>]
+  compare   run recorded operations against two pool configurations
+            --ops <ops.json> --config-a <a.toml> --config-b <b.toml>
+  stress    fund a pool and run a stress preset against it
+            --preset <mass-unstake|bank-run|price-shock> [--seed <seed>]
+            --price <price> --min-fee <percent> --max-fee <percent> --target <amount>
+            [--max-swap <amount>] [--token-cap <amount>]";
+
+/// `--name value` pairs following a subcommand.
+pub struct Flags(HashMap<String, String>);
+
+impl Flags {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut flags = HashMap::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument `{arg}`"))?;
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for --{name}"))?;
+            flags.insert(name.to_string(), value.clone());
+        }
+        Ok(Self(flags))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+}
+
+fn fail(message: &str) -> ExitCode {
+    eprintln!("lp-pool: {message}");
+    ExitCode::FAILURE
+}
+
+pub fn run(args: &[String], demo: fn()) -> ExitCode {
+    match args.first().map(String::as_str) {
+        None => {
+            demo();
+            ExitCode::SUCCESS
+        }
+        Some("repl") => {
+            let stdin = io::stdin();
+            let mut stdout = io::stdout();
+            match repl::run(stdin.lock(), &mut stdout).and_then(|()| stdout.flush()) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => fail(&error.to_string()),
+            }
+        }
+        Some("init") => match Flags::parse(&args[1..]).and_then(|flags| init::run(&flags)) {
+            Ok(pool) => {
+                println!("{}", pool.state());
+                ExitCode::SUCCESS
+            }
+            Err(message) => fail(&message),
+        },
+        Some("compare") => match Flags::parse(&args[1..]).and_then(|flags| compare::run(&flags)) {
+            Ok(report) => {
+                println!("{report}");
+                ExitCode::SUCCESS
+            }
+            Err(message) => fail(&message),
+        },
+        Some("stress") => match Flags::parse(&args[1..]).and_then(|flags| stress::run(&flags)) {
+            Ok(report) => {
+                println!("{report}");
+                ExitCode::SUCCESS
+            }
+            Err(message) => fail(&message),
+        },
+        Some(command) => {
+            eprintln!("lp-pool: unknown command `{command}`\n{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_flag_pairs() {
+        let args: Vec<String> = ["--price", "1.5", "--target", "90sol"]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let flags = Flags::parse(&args).unwrap();
+
+        assert_eq!(flags.get("price"), Some("1.5"));
+        assert_eq!(flags.get("target"), Some("90sol"));
+        assert_eq!(flags.get("min-fee"), None);
+        assert_eq!(
+            Flags::parse(&args[..3]).err(),
+            Some("missing value for --target".to_string())
+        );
+        assert_eq!(
+            Flags::parse(&args[1..]).err(),
+            Some("unexpected argument `1.5`".to_string())
+        );
+    }
+}
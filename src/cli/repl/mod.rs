@@ -0,0 +1,291 @@
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+
+use liquidity_pool::prelude::*;
+
+const HELP: &str = "\
+commands:
+  init <price> <min_fee%> <max_fee%> <liquidity_target>
+  add <amount>sol            add liquidity
+  remove <amount>lp          remove liquidity
+  swap <amount>msol          swap staked tokens for tokens
+  quote swap <amount>msol    preview a swap without executing it
+  price <price>              update the price
+  state                      print pool state and session totals
+  status                     print pool balances on one line
+  history                    list executed commands
+  undo                       revert the last executed command
+  help
+  quit";
+
+#[derive(Clone, Copy, Default)]
+struct Totals {
+    tokens_added: u64,
+    lp_minted: u64,
+    lp_burned: u64,
+    tokens_removed: u64,
+    staked_removed: u64,
+    staked_swapped: u64,
+    tokens_swapped_out: u64,
+}
+
+struct JournalEntry {
+    command: String,
+    pool: LpPool,
+    totals: Totals,
+}
+
+#[derive(Default)]
+struct Session {
+    pool: Option<LpPool>,
+    totals: Totals,
+    journal: Vec<JournalEntry>,
+}
+
+enum Outcome {
+    Continue,
+    Quit,
+}
+
+fn parse<T>(argument: Option<&str>) -> Result<T>
+where
+    T: std::str::FromStr<Err = Error>,
+{
+    argument.unwrap_or_default().parse()
+}
+
+fn error_message(error: impl Display) -> String {
+    format!("error: {error}")
+}
+
+impl Session {
+    fn prompt(&self) -> &'static str {
+        if self.pool.is_some() {
+            "lp-pool> "
+        } else {
+            "init> "
+        }
+    }
+
+    fn execute(&mut self, line: &str, output: &mut impl Write) -> io::Result<Outcome> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let message = match words.as_slice() {
+            [] => return Ok(Outcome::Continue),
+            ["quit" | "exit"] => return Ok(Outcome::Quit),
+            ["help"] => HELP.to_string(),
+            ["init", price, min_fee, max_fee, target] => {
+                self.init(price, min_fee, max_fee, target)
+            }
+            _ if self.pool.is_none() => {
+                "error: initialize the pool first: init <price> <min_fee%> <max_fee%> <liquidity_target>"
+                    .to_string()
+            }
+            ["state"] => self.state(),
+            ["status"] => self.pool().status_line(),
+            ["history"] => self.history(),
+            ["undo"] => self.undo(),
+            ["quote", "swap", amount] => self.quote_swap(amount),
+            ["add" | "remove" | "swap" | "price", _] => self.mutate(line, &words),
+            _ => format!("error: unknown command `{line}`, type `help` for a list of commands"),
+        };
+        writeln!(output, "{message}")?;
+        Ok(Outcome::Continue)
+    }
+
+    fn init(&mut self, price: &str, min_fee: &str, max_fee: &str, target: &str) -> String {
+        let pool = (|| {
+            LpPool::init(PoolParams {
+                price: price.parse::<Price>()?,
+                fee_range: FeeRange::new(min_fee.parse::<Fee>()?, max_fee.parse::<Fee>()?)?,
+                liquidity_target: target.parse::<TokenAmount>()?,
+            })
+        })();
+        match pool {
+            Ok(pool) => {
+                self.pool = Some(pool);
+                self.totals = Totals::default();
+                self.journal.clear();
+                "pool initialized".to_string()
+            }
+            Err(error) => error_message(error.display_chain()),
+        }
+    }
+
+    fn pool(&mut self) -> &mut LpPool {
+        self.pool
+            .as_mut()
+            .expect("commands other than init are only dispatched once the pool exists")
+    }
+
+    fn mutate(&mut self, line: &str, words: &[&str]) -> String {
+        let snapshot = JournalEntry {
+            command: line.trim().to_string(),
+            pool: self.pool().clone(),
+            totals: self.totals,
+        };
+        let argument = words.get(1).copied();
+        let mut totals = self.totals;
+        let result = match words[0] {
+            "add" => parse::<TokenAmount>(argument).and_then(|tokens| {
+                let lp_tokens = self.pool().add_liquidity(tokens)?;
+                totals.tokens_added += u64::from(tokens);
+                totals.lp_minted += u64::from(lp_tokens);
+                Ok(format!("minted {lp_tokens} lp"))
+            }),
+            "remove" => parse::<LpTokenAmount>(argument).and_then(|lp_tokens| {
+                let (tokens, staked_tokens) = self.pool().remove_liquidity(lp_tokens)?;
+                totals.lp_burned += u64::from(lp_tokens);
+                totals.tokens_removed += u64::from(tokens);
+                totals.staked_removed += u64::from(staked_tokens);
+                Ok(format!(
+                    "received {tokens} tokens and {staked_tokens} staked tokens"
+                ))
+            }),
+            "swap" => parse::<StakedTokenAmount>(argument).and_then(|staked_tokens| {
+                let tokens = self.pool().swap(staked_tokens)?;
+                totals.staked_swapped += u64::from(staked_tokens);
+                totals.tokens_swapped_out += u64::from(tokens);
+                Ok(format!("received {tokens} tokens"))
+            }),
+            "price" => parse::<Price>(argument).and_then(|price| {
+                self.pool().update_price(price)?;
+                Ok(format!("price updated to {price}"))
+            }),
+            command => unreachable!("`{command}` is not a mutating command"),
+        };
+
+        match result {
+            Ok(message) => {
+                self.totals = totals;
+                self.journal.push(snapshot);
+                message
+            }
+            Err(error) => error_message(error.display_chain()),
+        }
+    }
+
+    fn quote_swap(&mut self, amount: &str) -> String {
+        match amount
+            .parse::<StakedTokenAmount>()
+            .and_then(|staked_tokens| self.pool().quote_swap(staked_tokens))
+        {
+            Ok(quote) => format!("quote: {} tokens", quote.amount_out),
+            Err(error) => error_message(error.display_chain()),
+        }
+    }
+
+    fn state(&mut self) -> String {
+        let state = self.pool().state();
+        let totals = self.totals;
+        format!(
+            "{}\ntotals: added {} tokens, minted {} lp, burned {} lp, removed {} tokens and {} staked tokens, swapped {} staked tokens for {} tokens",
+            state,
+            totals.tokens_added,
+            totals.lp_minted,
+            totals.lp_burned,
+            totals.tokens_removed,
+            totals.staked_removed,
+            totals.staked_swapped,
+            totals.tokens_swapped_out,
+        )
+    }
+
+    fn history(&self) -> String {
+        if self.journal.is_empty() {
+            return "history is empty".to_string();
+        }
+        self.journal
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| format!("{}: {}", index + 1, entry.command))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn undo(&mut self) -> String {
+        match self.journal.pop() {
+            Some(entry) => {
+                self.pool = Some(entry.pool);
+                self.totals = entry.totals;
+                format!("undone: {}", entry.command)
+            }
+            None => "nothing to undo".to_string(),
+        }
+    }
+}
+
+pub fn run(input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    let mut session = Session::default();
+    write!(output, "{}", session.prompt())?;
+    output.flush()?;
+    for line in input.lines() {
+        if let Outcome::Quit = session.execute(&line?, output)? {
+            return Ok(());
+        }
+        write!(output, "{}", session.prompt())?;
+        output.flush()?;
+    }
+    writeln!(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_script(script: &str) -> String {
+        let mut output = Vec::new();
+        run(script.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn it_requires_init_before_other_commands() {
+        let output = run_script("state\ninit 1 0.1 9 0sol\ninit 1 0.1 9 90sol\nquit\n");
+
+        assert!(output.starts_with("init> error: initialize the pool first"));
+        assert!(output.contains("error: LpPool: IncorrectLiquidityTarget(0)"));
+        assert!(output.contains("pool initialized\nlp-pool> "));
+    }
+
+    #[test]
+    fn it_executes_commands_and_keeps_running_totals() {
+        let output = run_script(
+            "init 1 0.1 9 1000\nadd 100000\nquote swap 60\nswap 60\nremove 1000\nprice 1.52\nstate\nstatus\n",
+        );
+
+        assert!(output.contains("minted 100000 lp"));
+        assert!(output.contains("quote: 60 tokens\nlp-pool> received 60 tokens"));
+        assert!(output.contains("price updated to 1.52%"));
+        assert!(output.contains("lp_token_amount: 99000"));
+        assert!(output.contains(
+            "tok=0.000 sol staked=0.000 msol lp=0.000 target=0.000 fee=0.10% util=9894%"
+        ));
+        assert!(output.contains(
+            "totals: added 100000 tokens, minted 100000 lp, burned 1000 lp, removed 999 tokens and 0 staked tokens, swapped 60 staked tokens for 60 tokens"
+        ));
+    }
+
+    #[test]
+    fn it_keeps_session_alive_after_invalid_commands() {
+        let output = run_script("init 1 0.1 9 1000\nswap 1msol\nfly\nadd lots\nadd 1000\n");
+
+        assert!(output.contains("error: LpPool: EmptyPool"));
+        assert!(output.contains("error: unknown command `fly`"));
+        assert!(output.contains("error: LpPool: AmountConversionError(converted_from: lots)"));
+        assert!(output.contains("minted 1000 lp"));
+    }
+
+    #[test]
+    fn it_lists_history_and_undoes_last_command() {
+        let output = run_script(
+            "init 1 0.1 9 1000\nadd 100000\nswap 60\nhistory\nundo\nhistory\nstate\nundo\nundo\n",
+        );
+
+        assert!(output.contains("1: add 100000\n2: swap 60\n"));
+        assert!(output.contains("undone: swap 60"));
+        assert!(output.contains("lp-pool> 1: add 100000\nlp-pool> "));
+        assert!(output.contains("token_amount: 100000\n"));
+        assert!(output.contains("swapped 0 staked tokens for 0 tokens"));
+        assert!(output.contains("undone: add 100000\nlp-pool> nothing to undo"));
+    }
+}
@@ -0,0 +1,90 @@
+use liquidity_pool::prelude::*;
+use liquidity_pool::sim::{
+    compare::PoolConfig,
+    stress::{self, StressPreset, StressReport},
+};
+
+use super::Flags;
+
+fn required<'a>(flags: &'a Flags, name: &str) -> std::result::Result<&'a str, String> {
+    flags.get(name).ok_or_else(|| format!("missing --{name}"))
+}
+
+pub fn run(flags: &Flags) -> std::result::Result<StressReport, String> {
+    let preset = required(flags, "preset")?;
+    let preset = StressPreset::from_name(preset).ok_or_else(|| {
+        let names: Vec<_> = StressPreset::ALL
+            .iter()
+            .map(|preset| preset.name())
+            .collect();
+        format!(
+            "unknown preset `{preset}`, expected one of {}",
+            names.join(", ")
+        )
+    })?;
+    let seed = flags
+        .get("seed")
+        .map_or(Ok(0), str::parse::<u64>)
+        .map_err(|error| format!("invalid --seed: {error}"))?;
+    let price = required(flags, "price")?;
+    let min_fee = required(flags, "min-fee")?;
+    let max_fee = required(flags, "max-fee")?;
+    let target = required(flags, "target")?;
+
+    let report = (|| -> Result<StressReport> {
+        let pool_config = PoolConfig {
+            price: price.parse()?,
+            min_fee: min_fee.parse()?,
+            max_fee: max_fee.parse()?,
+            liquidity_target: target.parse::<TokenAmount>()?,
+            max_swap_size: flags.get("max-swap").map(str::parse).transpose()?,
+            token_cap: flags.get("token-cap").map(str::parse).transpose()?,
+        };
+        stress::run(preset, &pool_config, seed)
+    })();
+    report.map_err(|error| error.display_chain().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(args: &[&str]) -> Flags {
+        let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+        Flags::parse(&args).unwrap()
+    }
+
+    fn run_preset(preset: &str) -> std::result::Result<StressReport, String> {
+        run(&flags(&[
+            "--preset",
+            preset,
+            "--seed",
+            "3",
+            "--price",
+            "1",
+            "--min-fee",
+            "0.1",
+            "--max-fee",
+            "9",
+            "--target",
+            "1000000",
+        ]))
+    }
+
+    #[test]
+    fn it_runs_preset_from_flags() {
+        let report = run_preset("bank-run").unwrap();
+
+        assert_eq!(report.preset, StressPreset::BankRun);
+        assert_eq!(report.solvency, Ok(()));
+        assert!(report.to_string().ends_with("solvency: ok"));
+    }
+
+    #[test]
+    fn it_rejects_unknown_preset() {
+        assert_eq!(
+            run_preset("panic").unwrap_err(),
+            "unknown preset `panic`, expected one of mass-unstake, bank-run, price-shock"
+        );
+    }
+}
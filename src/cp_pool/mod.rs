@@ -0,0 +1,495 @@
+//! Constant-product pool, swaps keep `token_amount * staked_token_amount` from decreasing.
+
+use crate::calc::{proportional, shares_from_value, value_from_shares};
+use crate::error::{Error, Result};
+use crate::lp_pool::{
+    data::{
+        fee::Fee,
+        price::Price,
+        token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    },
+    error::Error as LpPoolError,
+    overflow,
+    state::PoolState,
+    Pool,
+};
+
+#[derive(Clone, Debug)]
+pub struct CpPool {
+    fee: Fee,
+    token_amount: TokenAmount,
+    staked_token_amount: StakedTokenAmount,
+    lp_token_amount: LpTokenAmount,
+}
+
+impl CpPool {
+    /// Flat `fee` charged on the input of every swap, it stays in the pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` for a fee above 100%.
+    pub fn init(fee: Fee) -> Result<Self> {
+        fee.check()?;
+        Ok(Self {
+            fee,
+            token_amount: TokenAmount::from_lamports(0),
+            staked_token_amount: StakedTokenAmount::from_lamports(0),
+            lp_token_amount: LpTokenAmount::from_lamports(0),
+        })
+    }
+
+    /// The first deposit mints the geometric mean of both amounts, later ones mint the
+    /// smaller of the two proportional shares and leave the excess in the pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InitialLiquidityIncorrect` when the first deposit leaves either side empty,
+    /// and `ArithmeticOverflow` when a balance would overflow.
+    pub fn add_liquidity_pair(
+        &mut self,
+        tokens_to_add: TokenAmount,
+        staked_tokens_to_add: StakedTokenAmount,
+    ) -> Result<LpTokenAmount> {
+        let lp_tokens = if self.lp_token_amount == LpTokenAmount::from_lamports(0) {
+            if tokens_to_add == TokenAmount::from_lamports(0)
+                || staked_tokens_to_add == StakedTokenAmount::from_lamports(0)
+            {
+                return Err(Error::LpPool(LpPoolError::InitialLiquidityIncorrect {
+                    tokens: tokens_to_add,
+                    staked: staked_tokens_to_add,
+                }));
+            }
+            sqrt(u128::from(u64::from(tokens_to_add)) * u128::from(u64::from(staked_tokens_to_add)))
+        } else {
+            let supply = self.lp_token_amount.into();
+            shares_from_value(tokens_to_add.into(), self.token_amount.into(), supply)?.min(
+                shares_from_value(
+                    staked_tokens_to_add.into(),
+                    self.staked_token_amount.into(),
+                    supply,
+                )?,
+            )
+        };
+        let lp_tokens = LpTokenAmount::from_lamports(lp_tokens);
+
+        let token_amount = self
+            .token_amount
+            .checked_add(tokens_to_add)
+            .ok_or_else(|| {
+                overflow(
+                    "token_amount + tokens_to_add",
+                    self.token_amount,
+                    tokens_to_add,
+                )
+            })?;
+        let staked_token_amount = self
+            .staked_token_amount
+            .checked_add(staked_tokens_to_add)
+            .ok_or_else(|| {
+                overflow(
+                    "staked_token_amount + staked_tokens_to_add",
+                    self.staked_token_amount,
+                    staked_tokens_to_add,
+                )
+            })?;
+        let lp_token_amount = self.lp_token_amount.checked_add(lp_tokens).ok_or_else(|| {
+            overflow(
+                "lp_token_amount + lp_tokens",
+                self.lp_token_amount,
+                lp_tokens,
+            )
+        })?;
+
+        self.token_amount = token_amount;
+        self.staked_token_amount = staked_token_amount;
+        self.lp_token_amount = lp_token_amount;
+        Ok(lp_tokens)
+    }
+
+    /// Single-sided deposit, minted as if half of it was swapped for staked tokens, which
+    /// is also the half charged the swap fee.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` before the first `add_liquidity_pair`, and `ArithmeticOverflow`
+    /// when the token balance would overflow.
+    pub fn add_liquidity(&mut self, tokens_to_add: TokenAmount) -> Result<LpTokenAmount> {
+        self.ensure_not_empty()?;
+        let half = u64::from(tokens_to_add) / 2;
+        let fee = half - self.fee.apply(half)?;
+        let token_amount = self
+            .token_amount
+            .checked_add(tokens_to_add)
+            .ok_or_else(|| {
+                overflow(
+                    "token_amount + tokens_to_add",
+                    self.token_amount,
+                    tokens_to_add,
+                )
+            })?;
+
+        let staked = u128::from(u64::from(self.staked_token_amount));
+        let root_before = sqrt_ceil(u128::from(u64::from(self.token_amount)) * staked);
+        let root_after = sqrt(u128::from(u64::from(token_amount) - fee) * staked);
+        let lp_token_amount = LpTokenAmount::from_lamports(proportional(
+            self.lp_token_amount.into(),
+            root_after,
+            root_before,
+        )?);
+        // rounding both roots against the depositor can leave nothing to mint
+        let lp_tokens = lp_token_amount
+            .checked_sub(self.lp_token_amount)
+            .unwrap_or(LpTokenAmount::from_lamports(0));
+
+        self.token_amount = token_amount;
+        self.lp_token_amount = lp_token_amount;
+        Ok(lp_tokens)
+    }
+
+    /// Burns `lp_tokens_to_remove` for their share of both reserves, rounded down.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` without LP supply and `InsufficientLpTokens` for more than the
+    /// supply.
+    pub fn remove_liquidity(
+        &mut self,
+        lp_tokens_to_remove: LpTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount)> {
+        self.ensure_not_empty()?;
+        let lp_token_amount = self
+            .lp_token_amount
+            .checked_sub(lp_tokens_to_remove)
+            .ok_or(LpPoolError::InsufficientLpTokens {
+                requested: lp_tokens_to_remove,
+                available: self.lp_token_amount,
+            })?;
+        let tokens = TokenAmount::from_lamports(value_from_shares(
+            lp_tokens_to_remove.into(),
+            self.token_amount.into(),
+            self.lp_token_amount.into(),
+        )?);
+        let staked_tokens = StakedTokenAmount::from_lamports(value_from_shares(
+            lp_tokens_to_remove.into(),
+            self.staked_token_amount.into(),
+            self.lp_token_amount.into(),
+        )?);
+
+        self.token_amount -= tokens;
+        self.staked_token_amount -= staked_tokens;
+        self.lp_token_amount = lp_token_amount;
+        Ok((tokens, staked_tokens))
+    }
+
+    /// Swaps staked tokens for tokens along the curve.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `quote_swap`, or with `ArithmeticOverflow` when the staked reserve would
+    /// overflow.
+    pub fn swap(&mut self, staked_tokens_to_swap: StakedTokenAmount) -> Result<TokenAmount> {
+        let tokens = self.quote_swap(staked_tokens_to_swap)?;
+        let staked_token_amount = self
+            .staked_token_amount
+            .checked_add(staked_tokens_to_swap)
+            .ok_or_else(|| {
+                overflow(
+                    "staked_token_amount + staked_tokens_to_swap",
+                    self.staked_token_amount,
+                    staked_tokens_to_swap,
+                )
+            })?;
+
+        self.token_amount -= tokens;
+        self.staked_token_amount = staked_token_amount;
+        Ok(tokens)
+    }
+
+    /// Tokens `swap` would pay out for `staked_tokens_to_swap`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` without LP supply, `ArithmeticOverflow` when the curve overflows
+    /// and `InsufficientTokenLiquidity` when rounding would pay out the whole reserve.
+    pub fn quote_swap(&self, staked_tokens_to_swap: StakedTokenAmount) -> Result<TokenAmount> {
+        self.ensure_not_empty()?;
+        let tokens = out_amount(
+            self.fee.apply(staked_tokens_to_swap.into())?,
+            self.staked_token_amount.into(),
+            self.token_amount.into(),
+        )
+        .ok_or_else(|| {
+            overflow(
+                "staked_token_amount + staked_tokens_to_swap",
+                self.staked_token_amount,
+                staked_tokens_to_swap,
+            )
+        })?;
+        let tokens = TokenAmount::from_lamports(tokens);
+
+        if tokens >= self.token_amount {
+            // the curve never pays out the whole reserve, only rounding on tiny reserves
+            // ends up here and no input is known to fill
+            return Err(Error::LpPool(LpPoolError::InsufficientTokenLiquidity {
+                requested: tokens,
+                available: self.token_amount,
+                max_fillable_input: StakedTokenAmount::from_lamports(0),
+            }));
+        }
+        Ok(tokens)
+    }
+
+    /// Swap in the other direction, tokens for staked tokens.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `quote_swap_tokens`, or with `ArithmeticOverflow` when the token reserve
+    /// would overflow.
+    pub fn swap_tokens(&mut self, tokens_to_swap: TokenAmount) -> Result<StakedTokenAmount> {
+        let staked_tokens = self.quote_swap_tokens(tokens_to_swap)?;
+        let token_amount = self
+            .token_amount
+            .checked_add(tokens_to_swap)
+            .ok_or_else(|| {
+                overflow(
+                    "token_amount + tokens_to_swap",
+                    self.token_amount,
+                    tokens_to_swap,
+                )
+            })?;
+
+        self.staked_token_amount -= staked_tokens;
+        self.token_amount = token_amount;
+        Ok(staked_tokens)
+    }
+
+    /// Staked tokens `swap_tokens` would pay out for `tokens_to_swap`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` without LP supply, `ArithmeticOverflow` when the curve overflows
+    /// and `InsufficientStakedLiquidity` when rounding would pay out the whole reserve.
+    pub fn quote_swap_tokens(&self, tokens_to_swap: TokenAmount) -> Result<StakedTokenAmount> {
+        self.ensure_not_empty()?;
+        let staked_tokens = out_amount(
+            self.fee.apply(tokens_to_swap.into())?,
+            self.token_amount.into(),
+            self.staked_token_amount.into(),
+        )
+        .ok_or_else(|| {
+            overflow(
+                "token_amount + tokens_to_swap",
+                self.token_amount,
+                tokens_to_swap,
+            )
+        })?;
+        let staked_tokens = StakedTokenAmount::from_lamports(staked_tokens);
+
+        if staked_tokens >= self.staked_token_amount {
+            return Err(Error::LpPool(LpPoolError::InsufficientStakedLiquidity {
+                requested: staked_tokens,
+                available: self.staked_token_amount,
+            }));
+        }
+        Ok(staked_tokens)
+    }
+
+    /// Spot price in the `price` field, the flat fee as both fee bounds. Fees stay in the
+    /// reserves, so nothing is reported as collected.
+    #[must_use]
+    pub fn state(&self) -> PoolState {
+        let zero = TokenAmount::from_lamports(0);
+        PoolState {
+            price: Price::from_ratio(self.token_amount.into(), self.staked_token_amount.into())
+                .unwrap_or(Price::from_raw_points(0)),
+            min_fee: self.fee,
+            max_fee: self.fee,
+            liquidity_target: zero,
+            token_amount: self.token_amount,
+            staked_token_amount: self.staked_token_amount,
+            lp_token_amount: self.lp_token_amount,
+            fees_collected: zero,
+            referral_fees_owed: zero,
+            dust: zero,
+            protocol_fees_owed: zero,
+        }
+    }
+
+    fn ensure_not_empty(&self) -> Result<()> {
+        if self.lp_token_amount == LpTokenAmount::from_lamports(0) {
+            return Err(Error::LpPool(LpPoolError::EmptyPool));
+        }
+        Ok(())
+    }
+}
+
+impl Pool for CpPool {
+    fn add_liquidity(&mut self, tokens_to_add: TokenAmount) -> Result<LpTokenAmount> {
+        CpPool::add_liquidity(self, tokens_to_add)
+    }
+
+    fn remove_liquidity(
+        &mut self,
+        lp_tokens_to_remove: LpTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount)> {
+        CpPool::remove_liquidity(self, lp_tokens_to_remove)
+    }
+
+    fn swap(&mut self, staked_tokens_to_swap: StakedTokenAmount) -> Result<TokenAmount> {
+        CpPool::swap(self, staked_tokens_to_swap)
+    }
+
+    fn quote_swap(&self, staked_tokens_to_swap: StakedTokenAmount) -> Result<TokenAmount> {
+        CpPool::quote_swap(self, staked_tokens_to_swap)
+    }
+
+    fn state(&self) -> PoolState {
+        CpPool::state(self)
+    }
+}
+
+/// `reserve_out * amount_in / (reserve_in + amount_in)`, rounded down so `k` never drops.
+fn out_amount(amount_in: u64, reserve_in: u64, reserve_out: u64) -> Option<u64> {
+    let reserve_in_after = reserve_in.checked_add(amount_in)?;
+    proportional(reserve_out, amount_in, reserve_in_after).ok()
+}
+
+fn sqrt(value: u128) -> u64 {
+    // PANIC SAFETY: the square root of a value below 2^128 is below 2^64.
+    u64::try_from(value.isqrt()).expect("square root of a u128 fits u64")
+}
+
+fn sqrt_ceil(value: u128) -> u64 {
+    let root = sqrt(value);
+    if u128::from(root) * u128::from(root) == value {
+        root
+    } else {
+        root + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{Step, SCENARIOS};
+    use proptest::prelude::*;
+
+    fn k(pool: &CpPool) -> u128 {
+        u128::from(u64::from(pool.token_amount)) * u128::from(u64::from(pool.staked_token_amount))
+    }
+
+    fn seeded_pool(fee_basis_points: u32) -> CpPool {
+        let mut cp_pool = CpPool::init(Fee::from_basis_points(fee_basis_points)).unwrap();
+        cp_pool
+            .add_liquidity_pair(
+                TokenAmount::from_lamports(400_000),
+                StakedTokenAmount::from_lamports(100_000),
+            )
+            .unwrap();
+        cp_pool
+    }
+
+    #[test]
+    fn it_mints_geometric_mean_then_proportional_shares() {
+        let mut cp_pool = seeded_pool(30);
+
+        assert_eq!(
+            cp_pool.lp_token_amount,
+            LpTokenAmount::from_lamports(200_000)
+        );
+        assert_eq!(
+            cp_pool.add_liquidity_pair(
+                TokenAmount::from_lamports(40_000),
+                StakedTokenAmount::from_lamports(20_000),
+            ),
+            Ok(LpTokenAmount::from_lamports(20_000))
+        );
+    }
+
+    #[test]
+    fn it_returns_err_if_initial_deposit_has_zero_side() {
+        let mut cp_pool = CpPool::init(Fee::ZERO).unwrap();
+
+        assert_eq!(
+            cp_pool.add_liquidity_pair(
+                TokenAmount::from_lamports(100),
+                StakedTokenAmount::from_lamports(0),
+            ),
+            Err(Error::LpPool(LpPoolError::InitialLiquidityIncorrect {
+                tokens: TokenAmount::from_lamports(100),
+                staked: StakedTokenAmount::from_lamports(0),
+            }))
+        );
+        assert_eq!(
+            cp_pool.swap(StakedTokenAmount::from_lamports(1)),
+            Err(Error::LpPool(LpPoolError::EmptyPool))
+        );
+    }
+
+    #[test]
+    fn it_loses_only_fees_on_round_trip_swap() {
+        for fee_basis_points in [0, 30, 100] {
+            let mut cp_pool = seeded_pool(fee_basis_points);
+            let staked_tokens = StakedTokenAmount::from_lamports(10_000);
+
+            let tokens = cp_pool.swap(staked_tokens).unwrap();
+            let returned = u64::from(cp_pool.swap_tokens(tokens).unwrap());
+
+            let fee = Fee::from_basis_points(fee_basis_points);
+            let fees = 10_000 - fee.apply(fee.apply(10_000).unwrap()).unwrap();
+            // one lamport per swap for rounding the output down
+            assert!(returned <= 10_000);
+            assert!(10_000 - returned <= fees + 2, "lost {}", 10_000 - returned);
+        }
+    }
+
+    #[test]
+    fn it_runs_shared_scenarios() {
+        fn state_k(pool: &dyn Pool) -> u128 {
+            let state = pool.state();
+            u128::from(u64::from(state.token_amount))
+                * u128::from(u64::from(state.staked_token_amount))
+        }
+
+        for (name, steps) in SCENARIOS {
+            let mut cp_pool = seeded_pool(30);
+            let pool: &mut dyn Pool = &mut cp_pool;
+
+            for step in *steps {
+                let k_before = state_k(pool);
+                let result = step.run(pool);
+
+                let state = pool.state();
+                assert!(
+                    state.token_amount != TokenAmount::from_lamports(0)
+                        && state.staked_token_amount != StakedTokenAmount::from_lamports(0),
+                    "{name}: {step:?} -> {result:?}"
+                );
+                if let Step::Swap(_) = step {
+                    assert!(state_k(pool) >= k_before, "{name}: {step:?}");
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn it_never_decreases_k_across_swaps(
+            swaps in prop::collection::vec((any::<bool>(), 1..50_000u64), 1..20),
+            fee_basis_points in 0..=1_000u32,
+        ) {
+            let mut cp_pool = seeded_pool(fee_basis_points);
+
+            for (staked_in, amount) in swaps {
+                let k_before = k(&cp_pool);
+                let result = if staked_in {
+                    cp_pool.swap(StakedTokenAmount::from_lamports(amount)).map(|_| ())
+                } else {
+                    cp_pool.swap_tokens(TokenAmount::from_lamports(amount)).map(|_| ())
+                };
+                prop_assert!(result.is_ok());
+                prop_assert!(k(&cp_pool) >= k_before);
+            }
+        }
+    }
+}
@@ -1,22 +1,582 @@
-use derive_more::Display;
-use derive_more::From;
+use core::error::Error as _;
+use core::fmt::{Display, Write as _};
 
 use crate::lp_pool::error::Error as LpPoolError;
 
-#[derive(Debug, From)]
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
-    #[from]
-    LpPool(LpPoolError),
+    #[error("LpPool")]
+    LpPool(#[from] LpPoolError),
 
-    CalculationError,
+    #[error("CalculationError(op: {op}, a: {a}, b: {b}, c: {c})")]
+    CalculationError {
+        op: &'static str,
+        a: u64,
+        b: u64,
+        c: u64,
+    },
+
+    #[cfg(feature = "net")]
+    #[error("PriceFetch({0})")]
+    PriceFetch(String),
+
+    /// Saving or loading failed, `context` says what was being done, e.g. the file read.
+    #[cfg(feature = "persist")]
+    #[error("Persistence({context})")]
+    Persistence {
+        context: String,
+        #[source]
+        source: Box<dyn core::error::Error + Send + Sync>,
+    },
+
+    #[cfg(feature = "ledger-sqlite")]
+    #[error("Ledger({0})")]
+    Ledger(String),
+
+    #[cfg(feature = "ledger-sqlite")]
+    #[error("LedgerDivergence(sequence: {sequence})")]
+    LedgerDivergence { sequence: u64 },
+
+    /// Error of a pool set to `LpPool::redact_amounts_in_logs(true)`, displayed as
+    /// `Error::display_redacted` of the error it wraps, see `Error::unredacted`.
+    #[cfg(feature = "std")]
+    #[error("{}", .0.display_redacted())]
+    Redacted(Box<Error>),
+}
+
+/// Broad class of an `Error`, the hundreds digit of its `Error::code`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ErrorCategory {
+    /// `1xx`, rejected arguments or configuration.
+    Validation,
+    /// `2xx`, the pool cannot cover the operation.
+    Liquidity,
+    /// `3xx`, overflow or rounding failures.
+    Arithmetic,
+    /// `4xx`, malformed stored state.
+    Persistence,
+    /// `5xx`, failures of services outside the crate.
+    External,
+}
+
+impl Error {
+    /// Renders the error followed by every `source()` in the chain,
+    /// e.g. `LpPool: BasisPointsOverflow(10001)`.
+    #[must_use]
+    pub fn display_chain(&self) -> DisplayChain<'_> {
+        DisplayChain(self)
+    }
+
+    /// The error itself, without the `Redacted` wrapper of a pool that redacts its logs.
+    /// Match on this instead of the error to see the same variants whatever the setting.
+    #[must_use]
+    pub fn unredacted(&self) -> &Error {
+        match self {
+            #[cfg(feature = "std")]
+            Error::Redacted(error) => error.unredacted(),
+            error => error,
+        }
+    }
+
+    /// Stable numeric code of the variant. Codes are frozen once released: new variants take
+    /// the next free code in their category and removed variants leave a gap.
+    #[must_use]
+    pub fn code(&self) -> u32 {
+        match self {
+            Error::LpPool(error) => match error {
+                LpPoolError::LiquidityTargetIncorrect(_) => 101,
+                LpPoolError::PriceIncorrect(_) => 102,
+                LpPoolError::MaxSwapSizeIncorrect(_) => 103,
+                LpPoolError::TokenCapIncorrect(_) => 104,
+                LpPoolError::PriceConversionFailure { .. } => 105,
+                LpPoolError::FeeConversionFailure { .. } => 106,
+                LpPoolError::AmountConversionFailure { .. } => 107,
+                LpPoolError::BasisPointsOverflow(_) => 108,
+                LpPoolError::MinFeeGreaterThanMaxFee { .. } => 109,
+                LpPoolError::InitialLiquidityIncorrect { .. } => 110,
+                LpPoolError::SwapTooLarge { .. } => 111,
+                LpPoolError::ReferralFeeTooHigh { .. } => 112,
+                LpPoolError::FeeTierThresholdsUnsorted { .. } => 113,
+                LpPoolError::TicketNotFound(_) => 114,
+                LpPoolError::TicketNotClaimable { .. } => 115,
+                LpPoolError::TicketAlreadyClaimed(_) => 116,
+                LpPoolError::StaleQuote { .. } => 117,
+                LpPoolError::DustNotCollectable => 118,
+                LpPoolError::StateInvalid(_) => 119,
+                LpPoolError::IdempotencyKeyReused { .. } => 120,
+                LpPoolError::ZeroAmount => 121,
+                LpPoolError::ReserveFloorIncorrect { .. } => 122,
+                LpPoolError::PriceTimestampOutOfOrder { .. } => 123,
+                LpPoolError::OutputBelowMinimum { .. } => 124,
+                LpPoolError::ReconfigurePriceMismatch { .. } => 125,
+                LpPoolError::HoldingPeriodNotMet { .. } => 126,
+                LpPoolError::FeeBucketsUnsorted { .. } => 127,
+                LpPoolError::InsufficientTokenLiquidity { .. } => 201,
+                LpPoolError::InsufficientStakedLiquidity { .. } => 202,
+                LpPoolError::InsufficientLpTokens { .. } => 203,
+                LpPoolError::EmptyPool => 204,
+                LpPoolError::PoolCapExceeded { .. } => 205,
+                LpPoolError::NoRoute => 206,
+                LpPoolError::PoolNotEmpty(_) => 207,
+                LpPoolError::SlippageExceeded { .. } => 208,
+                LpPoolError::ReserveFloorBreached { .. } => 209,
+                LpPoolError::ArithmeticOverflow { .. } => 302,
+                LpPoolError::PriceRatioIncorrect { .. } => 303,
+                LpPoolError::AmountOutOfRange(_) => 304,
+                LpPoolError::AccountDataInvalid { .. } => 401,
+                LpPoolError::UnsupportedStateVersion { .. } => 405,
+                LpPoolError::OperationEncodingInvalid { .. } => 406,
+            },
+            Error::CalculationError { .. } => 301,
+            #[cfg(feature = "persist")]
+            Error::Persistence { .. } => 402,
+            #[cfg(feature = "ledger-sqlite")]
+            Error::Ledger(_) => 403,
+            #[cfg(feature = "ledger-sqlite")]
+            Error::LedgerDivergence { .. } => 404,
+            #[cfg(feature = "net")]
+            Error::PriceFetch(_) => 501,
+            #[cfg(feature = "std")]
+            Error::Redacted(error) => error.code(),
+        }
+    }
+
+    #[must_use]
+    pub fn category(&self) -> ErrorCategory {
+        match self.code() / 100 {
+            1 => ErrorCategory::Validation,
+            2 => ErrorCategory::Liquidity,
+            3 => ErrorCategory::Arithmetic,
+            4 => ErrorCategory::Persistence,
+            _ => ErrorCategory::External,
+        }
+    }
+
+    /// Whether the operation could succeed with a smaller amount. The liquidity errors that
+    /// allow it carry the largest amount that would have succeeded, e.g.
+    /// `InsufficientTokenLiquidity::max_fillable_input`.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.unredacted(),
+            Error::LpPool(
+                LpPoolError::InsufficientTokenLiquidity { .. }
+                    | LpPoolError::InsufficientStakedLiquidity { .. }
+                    | LpPoolError::InsufficientLpTokens { .. }
+                    | LpPoolError::SwapTooLarge { .. }
+                    | LpPoolError::PoolCapExceeded { .. }
+                    | LpPoolError::ReserveFloorBreached { .. }
+            )
+        )
+    }
+
+    /// Renders the innermost error prefixed with the code,
+    /// e.g. `[201] InsufficientTokenLiquidity(requested: 5000, available: 300, max_fillable_input: 200)`.
+    #[must_use]
+    pub fn display_with_code(&self) -> DisplayWithCode<'_> {
+        DisplayWithCode(self)
+    }
+
+    /// Renders `display_chain` with every number replaced by its order of magnitude, e.g.
+    /// `LpPool: SwapTooLarge(requested: ~1e9, max: ~1e8)`, for logs that must not hold
+    /// exact balances.
+    #[must_use]
+    pub fn display_redacted(&self) -> DisplayRedacted<'_> {
+        DisplayRedacted(self)
+    }
 }
 
-impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{self:?}")
+pub struct DisplayChain<'a>(&'a Error);
+
+impl Display for DisplayChain<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)?;
+        let mut source = self.0.source();
+        while let Some(error) = source {
+            write!(f, ": {error}")?;
+            source = error.source();
+        }
+        Ok(())
     }
 }
 
-impl std::error::Error for Error {}
+pub struct DisplayRedacted<'a>(&'a Error);
+
+impl Display for DisplayRedacted<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut writer = crate::format::MagnitudeWriter::new(f);
+        write!(writer, "{}", self.0.unredacted().display_chain())?;
+        writer.finish()
+    }
+}
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub struct DisplayWithCode<'a>(&'a Error);
+
+impl Display for DisplayWithCode<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            Error::LpPool(error) => write!(f, "[{}] {error}", self.0.code()),
+            error => write!(f, "[{}] {error}", error.code()),
+        }
+    }
+}
+
+/// Persistence errors compare by context and the message of their source, which has no
+/// equality of its own.
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Error::LpPool(a), Error::LpPool(b)) => a == b,
+            (
+                Error::CalculationError { op, a, b, c },
+                Error::CalculationError {
+                    op: other_op,
+                    a: other_a,
+                    b: other_b,
+                    c: other_c,
+                },
+            ) => (op, a, b, c) == (other_op, other_a, other_b, other_c),
+            #[cfg(feature = "net")]
+            (Error::PriceFetch(a), Error::PriceFetch(b)) => a == b,
+            #[cfg(feature = "persist")]
+            (
+                Error::Persistence { context, source },
+                Error::Persistence {
+                    context: other_context,
+                    source: other_source,
+                },
+            ) => context == other_context && source.to_string() == other_source.to_string(),
+            #[cfg(feature = "ledger-sqlite")]
+            (Error::Ledger(a), Error::Ledger(b)) => a == b,
+            #[cfg(feature = "ledger-sqlite")]
+            (
+                Error::LedgerDivergence { sequence },
+                Error::LedgerDivergence {
+                    sequence: other_sequence,
+                },
+            ) => sequence == other_sequence,
+            #[cfg(feature = "std")]
+            (Error::Redacted(a), Error::Redacted(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "persist")]
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Persistence {
+            context: "I/O".to_string(),
+            source: Box::new(error),
+        }
+    }
+}
+
+#[cfg(feature = "persist")]
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Persistence {
+            context: "JSON".to_string(),
+            source: Box::new(error),
+        }
+    }
+}
+
+/// Describes what the persistence layer was doing when an error occurred, e.g.
+/// `std::fs::read(path).with_context(|| format!("reading {}", path.display()))`.
+#[cfg(feature = "persist")]
+pub trait PersistenceContext<T> {
+    /// Prefixes the context of a persistence error. Other errors, e.g. a stored state
+    /// failing validation, keep their variant and code.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `self`, with `context` added to a `Persistence` error.
+    fn with_context<C: Display>(self, context: impl FnOnce() -> C) -> Result<T>;
+}
+
+#[cfg(feature = "persist")]
+impl<T, E: Into<Error>> PersistenceContext<T> for core::result::Result<T, E> {
+    fn with_context<C: Display>(self, context: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|error| match error.into() {
+            Error::Persistence {
+                context: inner,
+                source,
+            } => Error::Persistence {
+                context: format!("{}: {inner}", context()),
+                source,
+            },
+            error => error,
+        })
+    }
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_chains_lp_pool_error_as_source() {
+        let error = Error::from(LpPoolError::BasisPointsOverflow(
+            crate::lp_pool::data::fee::Fee::from_basis_points(10_001),
+        ));
+
+        assert_eq!(
+            error.source().map(ToString::to_string),
+            Some("BasisPointsOverflow(100.01%)".to_string())
+        );
+        assert_eq!(
+            error.display_chain().to_string(),
+            "LpPool: BasisPointsOverflow(100.01%)"
+        );
+    }
+
+    #[test]
+    fn it_redacts_numbers_in_chain() {
+        let error = Error::from(LpPoolError::InsufficientTokenLiquidity {
+            requested: crate::lp_pool::data::token::TokenAmount::from_lamports(5_000),
+            available: crate::lp_pool::data::token::TokenAmount::from_lamports(300),
+            max_fillable_input: crate::lp_pool::data::token::StakedTokenAmount::from_lamports(0),
+        });
+
+        assert_eq!(
+            error.display_redacted().to_string(),
+            "LpPool: InsufficientTokenLiquidity(requested: ~1e3, available: ~1e2, max_fillable_input: 0)"
+        );
+    }
+
+    #[test]
+    fn it_displays_redacted_error_like_display_redacted() {
+        let error = || {
+            Error::from(LpPoolError::SwapTooLarge {
+                requested: crate::lp_pool::data::token::StakedTokenAmount::from_lamports(5_000),
+                max: crate::lp_pool::data::token::StakedTokenAmount::from_lamports(300),
+            })
+        };
+        let redacted = Error::Redacted(Box::new(error()));
+        let error = error();
+
+        assert_eq!(redacted.to_string(), error.display_redacted().to_string());
+        assert_eq!(
+            redacted.display_chain().to_string(),
+            redacted.display_redacted().to_string()
+        );
+        assert_eq!(redacted.unredacted(), &error);
+        assert_eq!(redacted.code(), error.code());
+        assert!(redacted.is_retryable());
+    }
+
+    #[test]
+    fn it_displays_calculation_error_operands() {
+        let error = Error::CalculationError {
+            op: "proportional",
+            a: 1,
+            b: 2,
+            c: 3,
+        };
+
+        assert!(error.source().is_none());
+        assert_eq!(
+            error.to_string(),
+            "CalculationError(op: proportional, a: 1, b: 2, c: 3)"
+        );
+    }
+
+    fn samples() -> Vec<Error> {
+        use crate::lp_pool::data::{
+            fee::Fee,
+            price::Price,
+            token::{LpTokenAmount, SignedTokenAmount, StakedTokenAmount, TokenAmount},
+        };
+        use crate::lp_pool::error::{ConvertedFrom, InvariantViolation};
+
+        let tokens = TokenAmount::from_lamports(1);
+        let staked = StakedTokenAmount::from_lamports(1);
+        let lp_tokens = LpTokenAmount::from_lamports(1);
+        let fee = Fee::ZERO;
+        let converted_from = ConvertedFrom::new("x");
+        #[allow(unused_mut)]
+        let mut samples = vec![
+            Error::CalculationError {
+                op: "op",
+                a: 0,
+                b: 0,
+                c: 0,
+            },
+            LpPoolError::LiquidityTargetIncorrect(tokens).into(),
+            LpPoolError::PriceIncorrect(Price::from_raw_points(0)).into(),
+            LpPoolError::MaxSwapSizeIncorrect(staked).into(),
+            LpPoolError::TokenCapIncorrect(tokens).into(),
+            LpPoolError::PriceConversionFailure { converted_from }.into(),
+            LpPoolError::FeeConversionFailure { converted_from }.into(),
+            LpPoolError::AmountConversionFailure { converted_from }.into(),
+            LpPoolError::BasisPointsOverflow(fee).into(),
+            LpPoolError::PriceRatioIncorrect {
+                total_staked: 0,
+                supply: 0,
+            }
+            .into(),
+            LpPoolError::AccountDataInvalid { len: 0 }.into(),
+            LpPoolError::MinFeeGreaterThanMaxFee { min: fee, max: fee }.into(),
+            LpPoolError::InitialLiquidityIncorrect { tokens, staked }.into(),
+            LpPoolError::EmptyPool.into(),
+            LpPoolError::InsufficientTokenLiquidity {
+                requested: tokens,
+                available: tokens,
+                max_fillable_input: staked,
+            }
+            .into(),
+            LpPoolError::InsufficientStakedLiquidity {
+                requested: staked,
+                available: staked,
+            }
+            .into(),
+            LpPoolError::InsufficientLpTokens {
+                requested: lp_tokens,
+                available: lp_tokens,
+            }
+            .into(),
+            LpPoolError::ArithmeticOverflow {
+                op: "op",
+                lhs: 0,
+                rhs: 0,
+            }
+            .into(),
+            LpPoolError::SwapTooLarge {
+                requested: staked,
+                max: staked,
+            }
+            .into(),
+            LpPoolError::PoolCapExceeded {
+                amount_after: tokens,
+                cap: tokens,
+                max_fillable_input: tokens,
+            }
+            .into(),
+            LpPoolError::ReferralFeeTooHigh {
+                requested: fee,
+                max: fee,
+            }
+            .into(),
+            LpPoolError::FeeTierThresholdsUnsorted { index: 0 }.into(),
+            LpPoolError::TicketNotFound(0).into(),
+            LpPoolError::TicketNotClaimable {
+                ticket: 0,
+                claimable_at: 0,
+                current_epoch: 0,
+            }
+            .into(),
+            LpPoolError::TicketAlreadyClaimed(0).into(),
+            LpPoolError::NoRoute.into(),
+            LpPoolError::StaleQuote {
+                quoted_at: 0,
+                current: 0,
+            }
+            .into(),
+            LpPoolError::DustNotCollectable.into(),
+            LpPoolError::PoolNotEmpty(lp_tokens).into(),
+            LpPoolError::SlippageExceeded {
+                min_out: tokens,
+                quoted: tokens,
+            }
+            .into(),
+            LpPoolError::AmountOutOfRange(SignedTokenAmount::ZERO).into(),
+            LpPoolError::StateInvalid(InvariantViolation::FeesExceedBalance {
+                fees_collected: tokens,
+                token_amount: tokens,
+            })
+            .into(),
+            LpPoolError::UnsupportedStateVersion {
+                found: 0,
+                supported: 0,
+            }
+            .into(),
+            LpPoolError::OperationEncodingInvalid { offset: 0, len: 0 }.into(),
+            LpPoolError::HoldingPeriodNotMet { unlock_epoch: 0 }.into(),
+            LpPoolError::FeeBucketsUnsorted { index: 0 }.into(),
+            LpPoolError::IdempotencyKeyReused { sequence: 0 }.into(),
+            LpPoolError::ZeroAmount.into(),
+            LpPoolError::ReserveFloorIncorrect {
+                floor: tokens,
+                liquidity_target: tokens,
+            }
+            .into(),
+            LpPoolError::ReserveFloorBreached {
+                floor: tokens,
+                would_be: tokens,
+                max_outflow: tokens,
+            }
+            .into(),
+            LpPoolError::PriceTimestampOutOfOrder { last: 0, given: 0 }.into(),
+            LpPoolError::OutputBelowMinimum {
+                computed: TokenAmount::from_lamports(0),
+                minimum: TokenAmount::from_lamports(1),
+            }
+            .into(),
+            LpPoolError::ReconfigurePriceMismatch {
+                current: Price::from_raw_points(100),
+                requested: Price::from_raw_points(101),
+            }
+            .into(),
+        ];
+        #[cfg(feature = "persist")]
+        samples.push(Error::from(std::io::Error::other("")));
+        #[cfg(feature = "ledger-sqlite")]
+        samples.extend([
+            Error::Ledger(String::new()),
+            Error::LedgerDivergence { sequence: 0 },
+        ]);
+        #[cfg(feature = "net")]
+        samples.push(Error::PriceFetch(String::new()));
+        samples
+    }
+
+    #[test]
+    fn it_assigns_unique_codes_matching_category() {
+        let samples = samples();
+        let mut codes: Vec<u32> = samples.iter().map(Error::code).collect();
+        codes.sort_unstable();
+        codes.dedup();
+
+        assert_eq!(codes.len(), samples.len());
+        for error in &samples {
+            let expected = match error.category() {
+                ErrorCategory::Validation => 1,
+                ErrorCategory::Liquidity => 2,
+                ErrorCategory::Arithmetic => 3,
+                ErrorCategory::Persistence => 4,
+                ErrorCategory::External => 5,
+            };
+            assert_eq!(error.code() / 100, expected, "{error:?}");
+        }
+    }
+
+    #[test]
+    fn it_displays_innermost_error_with_code() {
+        let error = Error::from(LpPoolError::InsufficientTokenLiquidity {
+            requested: crate::lp_pool::data::token::TokenAmount::from_lamports(5000),
+            available: crate::lp_pool::data::token::TokenAmount::from_lamports(300),
+            max_fillable_input: crate::lp_pool::data::token::StakedTokenAmount::from_lamports(200),
+        });
+
+        assert_eq!(error.category(), ErrorCategory::Liquidity);
+        assert_eq!(
+            error.display_with_code().to_string(),
+            "[201] InsufficientTokenLiquidity(requested: 5000, available: 300, max_fillable_input: 200)"
+        );
+        assert_eq!(
+            Error::CalculationError {
+                op: "proportional",
+                a: 1,
+                b: 2,
+                c: 3,
+            }
+            .display_with_code()
+            .to_string(),
+            "[301] CalculationError(op: proportional, a: 1, b: 2, c: 3)"
+        );
+    }
+}
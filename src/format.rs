@@ -0,0 +1,370 @@
+//! Human readable amounts for CLI output and logs. Separators are fixed characters, the
+//! output never depends on the system locale.
+
+use core::fmt::{Result, Write};
+
+use crate::lp_pool::consts::DEFAULT_DECIMALS;
+
+/// Character between groups of three digits.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Separator {
+    /// `100_000_000`, like a Rust literal.
+    #[default]
+    Underscore,
+    /// `100,000,000`
+    Comma,
+}
+
+impl Separator {
+    fn as_char(self) -> char {
+        match self {
+            Separator::Underscore => '_',
+            Separator::Comma => ',',
+        }
+    }
+}
+
+/// Writes `lamports` in groups of three digits.
+///
+/// # Errors
+///
+/// Fails only when writing to `out` does.
+#[allow(
+    clippy::missing_panics_doc,
+    reason = "a remainder of division by 10 always fits a `u8`"
+)]
+pub fn write_lamports_grouped(out: &mut impl Write, lamports: u64, separator: Separator) -> Result {
+    let mut digits = [0u8; 20];
+    let mut len = 0;
+    let mut rest = lamports;
+    loop {
+        // PANIC SAFETY: a remainder of division by 10 is a single digit.
+        digits[len] = b'0' + u8::try_from(rest % 10).expect("a digit fits u8");
+        len += 1;
+        rest /= 10;
+        if rest == 0 {
+            break;
+        }
+    }
+
+    for position in (0..len).rev() {
+        out.write_char(char::from(digits[position]))?;
+        if position > 0 && position.is_multiple_of(3) {
+            out.write_char(separator.as_char())?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `lamports` in whole tokens, without trailing zeros in the fraction.
+///
+/// # Errors
+///
+/// Fails only when writing to `out` does.
+pub fn write_sol(out: &mut impl Write, lamports: u64) -> Result {
+    write_tokens(out, lamports, DEFAULT_DECIMALS)
+}
+
+/// Writes `base_units` of a token with `decimals` decimal places in whole tokens, without
+/// trailing zeros in the fraction.
+///
+/// # Errors
+///
+/// Fails only when writing to `out` does.
+pub fn write_tokens(out: &mut impl Write, base_units: u64, decimals: u32) -> Result {
+    let per_token = 10u64.pow(decimals);
+    write!(out, "{}", base_units / per_token)?;
+    let mut fraction = base_units % per_token;
+    if fraction == 0 {
+        return Ok(());
+    }
+    let mut width = decimals as usize;
+    while fraction.is_multiple_of(10) {
+        fraction /= 10;
+        width -= 1;
+    }
+    write!(out, ".{fraction:0width$}")
+}
+
+/// Writes `base_units` of a token with `decimals` decimal places in whole tokens with exactly
+/// `precision` digits in the fraction, truncating the rest.
+///
+/// # Errors
+///
+/// Fails only when writing to `out` does.
+pub fn write_tokens_fixed(
+    out: &mut impl Write,
+    base_units: u64,
+    decimals: u32,
+    precision: u32,
+) -> Result {
+    let per_token = 10u64.pow(decimals);
+    write!(out, "{}", base_units / per_token)?;
+    if precision == 0 {
+        return Ok(());
+    }
+    let shown = precision.min(decimals);
+    let fraction = base_units % per_token / 10u64.pow(decimals - shown);
+    let width = shown as usize;
+    let padding = (precision - shown) as usize;
+    write!(out, ".{fraction:0width$}{:0<padding$}", "")
+}
+
+/// Writes the order of magnitude of `lamports` instead of its value, `~1e8` from
+/// `100_000_000` up to `999_999_999` and `0` for zero.
+///
+/// # Errors
+///
+/// Fails only when writing to `out` does.
+pub fn write_magnitude(out: &mut impl Write, lamports: u64) -> Result {
+    match lamports.checked_ilog10() {
+        Some(exponent) => write!(out, "~1e{exponent}"),
+        None => out.write_char('0'),
+    }
+}
+
+/// Writer replacing each number written through it with its order of magnitude, like
+/// `write_magnitude` does for its integer part. Digits right after a letter or `_` belong to
+/// a name and are kept. Call `finish` after the last write, a number can still be pending.
+pub(crate) struct MagnitudeWriter<W> {
+    out: W,
+    number: Option<Number>,
+    in_name: bool,
+}
+
+/// Number being read by `MagnitudeWriter`.
+struct Number {
+    /// Digits of the integer part without leading zeros.
+    digits: u32,
+    fraction: bool,
+    /// A `.` was read that is part of the number only if a digit follows.
+    dot: bool,
+}
+
+impl<W: Write> MagnitudeWriter<W> {
+    pub(crate) fn new(out: W) -> Self {
+        Self {
+            out,
+            number: None,
+            in_name: false,
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> Result {
+        self.end_number()
+    }
+
+    fn end_number(&mut self) -> Result {
+        let Some(number) = self.number.take() else {
+            return Ok(());
+        };
+        match number.digits.checked_sub(1) {
+            Some(exponent) => write!(self.out, "~1e{exponent}")?,
+            None => self.out.write_char('0')?,
+        }
+        if number.dot {
+            self.out.write_char('.')?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for MagnitudeWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        for c in s.chars() {
+            if let Some(number) = self.number.as_mut() {
+                match c {
+                    '0'..='9' if number.dot || number.fraction => {
+                        number.dot = false;
+                        number.fraction = true;
+                        continue;
+                    }
+                    '0'..='9' => {
+                        if number.digits > 0 || c != '0' {
+                            number.digits += 1;
+                        }
+                        continue;
+                    }
+                    '_' if !number.dot && !number.fraction => continue,
+                    '.' if !number.dot && !number.fraction => {
+                        number.dot = true;
+                        continue;
+                    }
+                    _ => self.end_number()?,
+                }
+            }
+            if c.is_ascii_digit() && !self.in_name {
+                self.number = Some(Number {
+                    digits: u32::from(c != '0'),
+                    fraction: false,
+                    dot: false,
+                });
+                continue;
+            }
+            self.in_name = c.is_alphanumeric() || c == '_';
+            self.out.write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
+/// `lamports` grouped with the default separator.
+///
+/// ```
+/// use liquidity_pool::format::format_lamports_grouped;
+///
+/// assert_eq!(format_lamports_grouped(100_000_000), "100_000_000");
+/// ```
+#[cfg(feature = "std")]
+#[must_use]
+pub fn format_lamports_grouped(lamports: u64) -> String {
+    format_lamports_grouped_with(lamports, Separator::default())
+}
+
+/// ```
+/// use liquidity_pool::format::{format_lamports_grouped_with, Separator};
+///
+/// assert_eq!(format_lamports_grouped_with(1_234_567, Separator::Comma), "1,234,567");
+/// ```
+#[cfg(feature = "std")]
+#[must_use]
+#[allow(
+    clippy::missing_panics_doc,
+    reason = "writing to a `String` never fails"
+)]
+pub fn format_lamports_grouped_with(lamports: u64, separator: Separator) -> String {
+    let mut out = String::new();
+    // PANIC SAFETY: `fmt::Write` for `String` never returns an error.
+    write_lamports_grouped(&mut out, lamports, separator).expect("writing to a String succeeds");
+    out
+}
+
+/// ```
+/// use liquidity_pool::format::format_sol;
+///
+/// assert_eq!(format_sol(1_500_000_000), "1.5");
+/// ```
+#[cfg(feature = "std")]
+#[must_use]
+#[allow(
+    clippy::missing_panics_doc,
+    reason = "writing to a `String` never fails"
+)]
+pub fn format_sol(lamports: u64) -> String {
+    let mut out = String::new();
+    // PANIC SAFETY: `fmt::Write` for `String` never returns an error.
+    write_sol(&mut out, lamports).expect("writing to a String succeeds");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lp_pool::data::token::{
+        LpTokenAmount, StakedTokenAmount, TokenAmount, LAMPORTS_PER_SOL,
+    };
+
+    #[test]
+    fn it_groups_lamports_in_thousands() {
+        for (lamports, grouped) in [
+            (0, "0"),
+            (7, "7"),
+            (999, "999"),
+            (1_000, "1_000"),
+            (100_000, "100_000"),
+            (1_000_000, "1_000_000"),
+            (u64::MAX, "18_446_744_073_709_551_615"),
+        ] {
+            assert_eq!(format_lamports_grouped(lamports), grouped);
+            assert_eq!(
+                format_lamports_grouped_with(lamports, Separator::Comma),
+                grouped.replace('_', ",")
+            );
+        }
+    }
+
+    #[test]
+    fn it_formats_sol_without_trailing_zeros() {
+        for (lamports, sol) in [
+            (0, "0"),
+            (1, "0.000000001"),
+            (999, "0.000000999"),
+            (1_000, "0.000001"),
+            (LAMPORTS_PER_SOL, "1"),
+            (1_500_000_000, "1.5"),
+            (u64::MAX, "18446744073.709551615"),
+        ] {
+            assert_eq!(format_sol(lamports), sol);
+            assert_eq!(
+                format!("{sol}sol").parse::<TokenAmount>(),
+                Ok(TokenAmount::from_lamports(lamports))
+            );
+        }
+    }
+
+    #[test]
+    fn it_writes_fixed_precision_by_truncating() {
+        let fixed = |base_units, decimals, precision| {
+            let mut out = String::new();
+            write_tokens_fixed(&mut out, base_units, decimals, precision).unwrap();
+            out
+        };
+
+        assert_eq!(fixed(100 * LAMPORTS_PER_SOL, 9, 3), "100.000");
+        assert_eq!(fixed(3_996_999_999, 9, 3), "3.996");
+        assert_eq!(fixed(1_999_999_999, 9, 0), "1");
+        assert_eq!(fixed(1_500, 2, 4), "15.0000");
+        assert_eq!(fixed(u64::MAX, 9, 9), "18446744073.709551615");
+    }
+
+    #[test]
+    fn it_groups_amounts_in_alternate_display() {
+        assert_eq!(
+            format!("{:#}", TokenAmount::from_lamports(100_000_000)),
+            "100_000_000"
+        );
+        assert_eq!(
+            format!("{:#}", StakedTokenAmount::from_lamports(1_000)),
+            "1_000"
+        );
+        assert_eq!(format!("{:#}", LpTokenAmount::from_lamports(999)), "999");
+        assert_eq!(
+            format!("{}", TokenAmount::from_lamports(100_000_000)),
+            "100000000"
+        );
+    }
+
+    #[test]
+    fn it_writes_order_of_magnitude() {
+        for (lamports, magnitude) in [
+            (0, "0"),
+            (1, "~1e0"),
+            (9, "~1e0"),
+            (10, "~1e1"),
+            (123_456_789, "~1e8"),
+            (u64::MAX, "~1e19"),
+        ] {
+            let mut out = String::new();
+            write_magnitude(&mut out, lamports).unwrap();
+            assert_eq!(out, magnitude);
+        }
+    }
+
+    #[test]
+    fn it_replaces_numbers_but_not_names() {
+        let mut out = String::new();
+        let mut writer = MagnitudeWriter::new(&mut out);
+        write!(
+            writer,
+            "V2(requested: 12_345, price: 1.5, fee: 0.10%, delta: -{}.) at 9",
+            u64::MAX
+        )
+        .unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(
+            out,
+            "V2(requested: ~1e4, price: ~1e0, fee: 0%, delta: -~1e19.) at ~1e0"
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Operation history kept in a sqlite database, replayable into the pool it describes.
+
+use rusqlite::{params, Connection, Row};
+
+use crate::error::{Error, Result};
+use crate::lp_pool::{
+    data::{
+        fee::Fee,
+        price::Price,
+        token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    },
+    error::Error as LpPoolError,
+    LpPool,
+};
+
+pub use crate::lp_pool::operation::{Balances, Operation, PoolOperation};
+
+/// Schema changes in order, `PRAGMA user_version` counts the ones applied. Never edit an
+/// entry once released, append a new one instead.
+const MIGRATIONS: &[&str] = &["CREATE TABLE operations (
+        sequence INTEGER PRIMARY KEY,
+        kind TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        min_fee INTEGER,
+        max_fee INTEGER,
+        liquidity_target INTEGER,
+        fee INTEGER NOT NULL,
+        price INTEGER NOT NULL,
+        token_amount INTEGER NOT NULL,
+        staked_token_amount INTEGER NOT NULL,
+        lp_token_amount INTEGER NOT NULL,
+        fees_collected INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL
+    )"];
+
+impl Operation {
+    fn kind(&self) -> &'static str {
+        match self {
+            Operation::Init { .. } => "init",
+            Operation::AddLiquidity(_) => "add_liquidity",
+            Operation::RemoveLiquidity(_) => "remove_liquidity",
+            Operation::Swap(_) => "swap",
+            Operation::UpdatePrice(_) => "update_price",
+        }
+    }
+
+    fn amount(&self) -> u64 {
+        match *self {
+            Operation::Init { price, .. } | Operation::UpdatePrice(price) => price.into(),
+            Operation::AddLiquidity(tokens) => tokens.into(),
+            Operation::RemoveLiquidity(lp_tokens) => lp_tokens.into(),
+            Operation::Swap(staked_tokens) => staked_tokens.into(),
+        }
+    }
+}
+
+pub struct SqliteLedger {
+    connection: Connection,
+}
+
+impl SqliteLedger {
+    /// Opens or creates the ledger at `path`, `":memory:"` keeps it in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Ledger` when the database cannot be opened or migrated.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let connection = Connection::open(path).map_err(ledger)?;
+        Self::migrate(connection)
+    }
+
+    fn migrate(mut connection: Connection) -> Result<Self> {
+        let version: usize = connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(ledger)?;
+        let transaction = connection.transaction().map_err(ledger)?;
+        for migration in MIGRATIONS.iter().skip(version) {
+            transaction.execute_batch(migration).map_err(ledger)?;
+        }
+        transaction
+            .pragma_update(None, "user_version", MIGRATIONS.len().max(version))
+            .map_err(ledger)?;
+        transaction.commit().map_err(ledger)?;
+        Ok(Self { connection })
+    }
+
+    /// Appends `operation` to the ledger.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Ledger` when the row cannot be written.
+    pub fn record(&mut self, operation: &PoolOperation) -> Result<()> {
+        let (min_fee, max_fee, liquidity_target) = match operation.operation {
+            Operation::Init {
+                min_fee,
+                max_fee,
+                liquidity_target,
+                ..
+            } => (
+                Some(min_fee.centibps),
+                Some(max_fee.centibps),
+                Some(u64::from(liquidity_target)),
+            ),
+            _ => (None, None, None),
+        };
+        let balances = operation.post_balances;
+        self.connection
+            .execute(
+                "INSERT INTO operations (sequence, kind, amount, min_fee, max_fee,
+                    liquidity_target, fee, price, token_amount, staked_token_amount,
+                    lp_token_amount, fees_collected, timestamp)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                params![
+                    operation.sequence,
+                    operation.operation.kind(),
+                    operation.operation.amount(),
+                    min_fee,
+                    max_fee,
+                    liquidity_target,
+                    u64::from(operation.fee),
+                    u64::from(balances.price),
+                    u64::from(balances.token_amount),
+                    u64::from(balances.staked_token_amount),
+                    u64::from(balances.lp_token_amount),
+                    u64::from(balances.fees_collected),
+                    operation.timestamp,
+                ],
+            )
+            .map_err(ledger)?;
+        Ok(())
+    }
+
+    /// Replays every recorded operation into a fresh pool, failing with `LedgerDivergence` at
+    /// the first one that cannot be replayed or ends in different balances than recorded.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LedgerDivergence` as above and `Error::Ledger` when the rows cannot be read.
+    pub fn rebuild_pool(&self) -> Result<LpPool> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT * FROM operations ORDER BY sequence")
+            .map_err(ledger)?;
+        let mut rows = statement.query([]).map_err(ledger)?;
+
+        let mut lp_pool = None;
+        while let Some(row) = rows.next().map_err(ledger)? {
+            let sequence: u64 = row.get("sequence").map_err(ledger)?;
+            let divergence = || Error::LedgerDivergence { sequence };
+            let recorded = read_operation(row)?.ok_or_else(divergence)?;
+            let replayed =
+                PoolOperation::execute(&mut lp_pool, recorded.operation, recorded.timestamp)
+                    .map_err(|_| divergence())?;
+            if replayed != recorded {
+                return Err(divergence());
+            }
+        }
+        lp_pool.ok_or(Error::LpPool(LpPoolError::EmptyPool))
+    }
+
+    #[cfg(test)]
+    fn connection(&self) -> &Connection {
+        &self.connection
+    }
+}
+
+/// `None` when the row does not describe a known operation.
+fn read_operation(row: &Row) -> Result<Option<PoolOperation>> {
+    let get = |column: &str| -> Result<u64> { row.get(column).map_err(ledger) };
+    let get_fee = |column: &str| -> Result<Option<Fee>> {
+        row.get::<_, Option<u32>>(column)
+            .map(|centibps| centibps.map(Fee::from_centibps))
+            .map_err(ledger)
+    };
+
+    let kind: String = row.get("kind").map_err(ledger)?;
+    let amount = get("amount")?;
+    let operation = match kind.as_str() {
+        "init" => {
+            let (Some(min_fee), Some(max_fee), Some(liquidity_target)) = (
+                get_fee("min_fee")?,
+                get_fee("max_fee")?,
+                row.get::<_, Option<u64>>("liquidity_target")
+                    .map_err(ledger)?,
+            ) else {
+                return Ok(None);
+            };
+            Operation::Init {
+                price: Price::from_raw_points(amount),
+                min_fee,
+                max_fee,
+                liquidity_target: TokenAmount::from_lamports(liquidity_target),
+            }
+        }
+        "add_liquidity" => Operation::AddLiquidity(TokenAmount::from_lamports(amount)),
+        "remove_liquidity" => Operation::RemoveLiquidity(LpTokenAmount::from_lamports(amount)),
+        "swap" => Operation::Swap(StakedTokenAmount::from_lamports(amount)),
+        "update_price" => Operation::UpdatePrice(Price::from_raw_points(amount)),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(PoolOperation {
+        sequence: get("sequence")?,
+        operation,
+        fee: TokenAmount::from_lamports(get("fee")?),
+        post_balances: Balances {
+            price: Price::from_raw_points(get("price")?),
+            token_amount: TokenAmount::from_lamports(get("token_amount")?),
+            staked_token_amount: StakedTokenAmount::from_lamports(get("staked_token_amount")?),
+            lp_token_amount: LpTokenAmount::from_lamports(get("lp_token_amount")?),
+            fees_collected: TokenAmount::from_lamports(get("fees_collected")?),
+        },
+        timestamp: get("timestamp")?,
+        recorded_at: None,
+    }))
+}
+
+#[allow(
+    clippy::needless_pass_by_value,
+    reason = "passed to `map_err`, which hands over the error"
+)]
+fn ledger(error: rusqlite::Error) -> Error {
+    Error::Ledger(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded_ledger() -> (SqliteLedger, LpPool) {
+        let mut ledger = SqliteLedger::open(":memory:").unwrap();
+        let mut lp_pool = None;
+        let operations = [
+            Operation::Init {
+                price: Price::from_raw_points(100),
+                min_fee: Fee::from_basis_points(10),
+                max_fee: Fee::from_basis_points(900),
+                liquidity_target: TokenAmount::from_lamports(90_000),
+            },
+            Operation::AddLiquidity(TokenAmount::from_lamports(100_000)),
+            Operation::Swap(StakedTokenAmount::from_lamports(6_000)),
+            Operation::UpdatePrice(Price::from_raw_points(200)),
+            Operation::RemoveLiquidity(LpTokenAmount::from_lamports(40_000)),
+            Operation::Swap(StakedTokenAmount::from_lamports(3_000)),
+        ];
+        for (timestamp, operation) in (1_700_000_000..).zip(operations) {
+            let executed = PoolOperation::execute(&mut lp_pool, operation, timestamp).unwrap();
+            ledger.record(&executed).unwrap();
+        }
+        (ledger, lp_pool.unwrap())
+    }
+
+    #[test]
+    fn it_rebuilds_pool_from_recorded_operations() {
+        let (ledger, lp_pool) = recorded_ledger();
+
+        let rebuilt = ledger.rebuild_pool().unwrap();
+
+        assert_eq!(rebuilt.state(), lp_pool.state());
+        assert_eq!(rebuilt.sequence(), lp_pool.sequence());
+    }
+
+    #[test]
+    fn it_names_sequence_of_tampered_row() {
+        let (ledger, _) = recorded_ledger();
+        ledger
+            .connection()
+            .execute(
+                "UPDATE operations SET token_amount = token_amount + 1 WHERE sequence = 2",
+                [],
+            )
+            .unwrap();
+
+        assert_eq!(
+            ledger.rebuild_pool().map(|lp_pool| lp_pool.state()),
+            Err(Error::LedgerDivergence { sequence: 2 })
+        );
+    }
+
+    #[test]
+    fn it_reports_unreplayable_row_as_divergence() {
+        let (ledger, _) = recorded_ledger();
+        ledger
+            .connection()
+            .execute(
+                "UPDATE operations SET amount = 1000000000 WHERE kind = 'remove_liquidity'",
+                [],
+            )
+            .unwrap();
+
+        assert_eq!(
+            ledger.rebuild_pool().map(|lp_pool| lp_pool.state()),
+            Err(Error::LedgerDivergence { sequence: 4 })
+        );
+    }
+
+    #[test]
+    fn it_versions_schema_with_user_version() {
+        let (ledger, _) = recorded_ledger();
+        let version: usize = ledger
+            .connection()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+
+        assert_eq!(version, MIGRATIONS.len());
+        let reopened = SqliteLedger::migrate(ledger.connection).unwrap();
+        assert_eq!(
+            reopened.rebuild_pool().map(|lp_pool| lp_pool.sequence()),
+            Ok(5)
+        );
+    }
+}
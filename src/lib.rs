@@ -0,0 +1,53 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+pub mod calc;
+pub mod cp_pool;
+pub mod error;
+pub mod format;
+pub mod lp_pool;
+pub mod prelude;
+
+pub use lp_pool::consts::{
+    DEFAULT_DECIMALS, LAMPORTS_PER_SOL, MAX_BASIS_POINTS, PRICE_DECIMALS, PRICE_SCALE,
+};
+
+#[cfg(feature = "std")]
+pub mod router;
+
+#[cfg(feature = "std")]
+pub mod sim;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "python")]
+pub mod python;
+
+#[cfg(feature = "net")]
+pub mod net;
+
+#[cfg(feature = "ledger-sqlite")]
+pub mod ledger;
+
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+
+#[cfg(not(feature = "std"))]
+#[allow(dead_code)]
+mod no_std_check {
+    use crate::prelude::*;
+
+    fn pool_lifecycle_compiles_without_std() -> Result<()> {
+        let mut lp_pool = LpPool::init(PoolParams {
+            price: "1.5".parse::<Price>()?,
+            fee_range: FeeRange::new("0.1".parse::<Fee>()?, Fee::from_basis_points(900))?,
+            liquidity_target: TokenAmount::from_lamports(90_000),
+        })?;
+        lp_pool.add_liquidity(TokenAmount::from_lamports(100_000))?;
+        lp_pool.quote_swap(StakedTokenAmount::from_lamports(10))?;
+        lp_pool.swap(StakedTokenAmount::from_lamports(10))?;
+        lp_pool.remove_liquidity(LpTokenAmount::from_lamports(1_000))?;
+        let _ = lp_pool.state();
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+//! Value moved by rounding, per kind of result, for checking that rounding never leaks
+//! value out of the pool.
+
+use crate::lp_pool::{
+    consts::PRICE_SCALE,
+    data::{
+        fee::Fee,
+        token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    },
+    LpPool, Withdrawal,
+};
+
+/// Rounding of one kind of result, in billionths of a base unit of the result: lamports for
+/// tokens and staked tokens, LP lamports for minted shares.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct RoundingTally {
+    /// Users received less, or the pool kept more, than the exact result.
+    pub toward_pool: u128,
+    /// Users received more, or the pool kept less, than the exact result.
+    pub toward_user: u128,
+}
+
+impl RoundingTally {
+    pub const UNITS_PER_LAMPORT: u128 = 1_000_000_000;
+
+    /// `paid` to the user where the exact result is `numerator / denominator`.
+    fn paid(&mut self, numerator: u128, denominator: u128, paid: u64) {
+        let paid = u128::from(paid).saturating_mul(denominator);
+        if paid < numerator {
+            self.toward_pool += Self::units(numerator - paid, denominator);
+        } else {
+            self.toward_user += Self::units(paid - numerator, denominator);
+        }
+    }
+
+    /// `kept` by the pool where the exact result is `numerator / denominator`.
+    fn kept(&mut self, numerator: u128, denominator: u128, kept: u64) {
+        let kept = u128::from(kept).saturating_mul(denominator);
+        if kept < numerator {
+            self.toward_user += Self::units(numerator - kept, denominator);
+        } else {
+            self.toward_pool += Self::units(kept - numerator, denominator);
+        }
+    }
+
+    fn units(difference: u128, denominator: u128) -> u128 {
+        difference.saturating_mul(Self::UNITS_PER_LAMPORT) / denominator.max(1)
+    }
+}
+
+/// Rounding accumulated by every executed operation since `init`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct AuditReport {
+    /// Staked tokens converted to tokens at the price, before the fee.
+    pub swap_rounding: RoundingTally,
+    /// LP tokens minted for deposits.
+    pub add_rounding: RoundingTally,
+    /// Tokens and staked tokens paid for burned LP tokens, before the fee.
+    pub remove_rounding: RoundingTally,
+    /// Swap and withdrawal fees, the pool's and referrers' shares together.
+    pub fee_rounding: RoundingTally,
+}
+
+impl AuditReport {
+    /// Sum of the rounding in favor of users, zero unless some path leaks value.
+    #[must_use]
+    pub fn toward_user(&self) -> u128 {
+        self.swap_rounding.toward_user
+            + self.add_rounding.toward_user
+            + self.remove_rounding.toward_user
+            + self.fee_rounding.toward_user
+    }
+}
+
+impl LpPool {
+    #[must_use]
+    pub fn audit_report(&self) -> AuditReport {
+        self.audit
+    }
+
+    /// The fee and the conversion before and after it, as `swap_amounts` computes them.
+    pub(super) fn audit_swap(
+        &mut self,
+        staked_in: StakedTokenAmount,
+        (fee, tokens_without_fee, tokens_with_fee): (Fee, TokenAmount, TokenAmount),
+    ) {
+        self.audit.swap_rounding.paid(
+            u128::from(u64::from(staked_in)) * u128::from(u64::from(self.price)),
+            u128::from(PRICE_SCALE),
+            tokens_without_fee.into(),
+        );
+        self.audit_fee(fee, tokens_without_fee, tokens_with_fee);
+    }
+
+    /// Before the deposit changes the balances.
+    pub(super) fn audit_deposit(&mut self, tokens_in: TokenAmount, lp_tokens: LpTokenAmount) {
+        if self.lp_token_amount == LpTokenAmount::from_lamports(0) {
+            return;
+        }
+        let Ok(lp_value) = self.lp_attributable_value() else {
+            return;
+        };
+        self.audit.add_rounding.paid(
+            u128::from(u64::from(tokens_in)) * u128::from(u64::from(self.lp_token_amount)),
+            u128::from(u64::from(lp_value)),
+            lp_tokens.into(),
+        );
+    }
+
+    /// Before the withdrawal changes the balances.
+    pub(super) fn audit_withdrawal(&mut self, lp_tokens: LpTokenAmount, withdrawal: &Withdrawal) {
+        let lp_tokens = u128::from(u64::from(lp_tokens));
+        let supply = u128::from(u64::from(self.lp_token_amount));
+        self.audit.remove_rounding.paid(
+            lp_tokens * u128::from(u64::from(self.available_tokens())),
+            supply,
+            withdrawal.tokens_without_fee.into(),
+        );
+        self.audit.remove_rounding.paid(
+            lp_tokens * u128::from(u64::from(self.staked_token_amount)),
+            supply,
+            withdrawal.unstaked_tokens.into(),
+        );
+        self.audit_fee(
+            withdrawal.fee,
+            withdrawal.tokens_without_fee,
+            withdrawal.tokens_with_fee,
+        );
+    }
+
+    pub(super) fn audit_fee(
+        &mut self,
+        fee: Fee,
+        tokens_without_fee: TokenAmount,
+        tokens_with_fee: TokenAmount,
+    ) {
+        self.audit.fee_rounding.kept(
+            u128::from(u64::from(tokens_without_fee)) * u128::from(fee.centibps),
+            u128::from(Fee::MAX_CENTIBPS),
+            (tokens_without_fee - tokens_with_fee).into(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PoolFixture;
+
+    #[test]
+    fn it_tallies_rounding_per_operation() {
+        let mut lp_pool = PoolFixture::new()
+            .price("1.37")
+            .fees(10, 900)
+            .target_lamports(1_000_000)
+            .seeded_with_lamports(1_000_000)
+            .build()
+            .pool;
+        assert_eq!(lp_pool.audit_report(), AuditReport::default());
+
+        // 1_001 * 1.37 = 1_371.37, paid 1_371
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(1_001))
+            .unwrap();
+        let report = lp_pool.audit_report();
+        assert_eq!(report.swap_rounding.toward_pool, 370_000_000);
+        assert_eq!(report.swap_rounding.toward_user, 0);
+
+        lp_pool
+            .remove_liquidity(LpTokenAmount::from_lamports(333))
+            .unwrap();
+        lp_pool
+            .add_liquidity(TokenAmount::from_lamports(777))
+            .unwrap();
+        let report = lp_pool.audit_report();
+        assert!(report.add_rounding.toward_pool > 0);
+        assert!(report.remove_rounding.toward_pool > 0);
+        assert_eq!(
+            report.swap_rounding.toward_user
+                + report.add_rounding.toward_user
+                + report.remove_rounding.toward_user,
+            0
+        );
+    }
+
+    #[test]
+    fn it_reports_fee_rounded_down_toward_user() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(10, 10)
+            .target_lamports(1_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool;
+
+        // 0.1% of 1_234 is 1.234, the fee takes 1
+        assert_eq!(
+            lp_pool.swap(StakedTokenAmount::from_lamports(1_234)),
+            Ok(TokenAmount::from_lamports(1_233))
+        );
+        let report = lp_pool.audit_report();
+        assert_eq!(report.fee_rounding.toward_user, 234_000_000);
+        assert_eq!(report.toward_user(), 234_000_000);
+    }
+}
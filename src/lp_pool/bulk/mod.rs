@@ -0,0 +1,113 @@
+//! Streaming many operations through a pool, for simulations and benchmarks.
+
+use crate::error::Result;
+use crate::lp_pool::{
+    data::token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    quote::RemovedLiquidity,
+    LpPool,
+};
+
+/// Operation `LpPool::run_ops` executes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PoolOp {
+    AddLiquidity(TokenAmount),
+    RemoveLiquidity(LpTokenAmount),
+    Swap(StakedTokenAmount),
+}
+
+/// What a successful `PoolOp` paid out.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OpOutput {
+    LpTokens(LpTokenAmount),
+    Removed(RemovedLiquidity),
+    Tokens(TokenAmount),
+}
+
+impl LpPool {
+    /// Executes `ops` in order and passes each result with its index to `on_result`. A
+    /// failed operation leaves the pool unchanged and the next one still runs.
+    ///
+    /// Successful operations allocate nothing unless a history policy or price history
+    /// keeps them, errors may allocate.
+    pub fn run_ops(
+        &mut self,
+        ops: impl IntoIterator<Item = PoolOp>,
+        mut on_result: impl FnMut(usize, &Result<OpOutput>),
+    ) {
+        for (index, op) in ops.into_iter().enumerate() {
+            let result = self.run_op(op);
+            on_result(index, &result);
+        }
+    }
+
+    /// Runs a single `op` through the pool method it names.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the pool method `op` runs.
+    pub fn run_op(&mut self, op: PoolOp) -> Result<OpOutput> {
+        match op {
+            PoolOp::AddLiquidity(tokens) => self.add_liquidity(tokens).map(OpOutput::LpTokens),
+            PoolOp::RemoveLiquidity(lp_tokens) => {
+                self.remove_liquidity(lp_tokens)
+                    .map(|(tokens, staked_tokens)| {
+                        OpOutput::Removed(RemovedLiquidity {
+                            tokens,
+                            staked_tokens,
+                        })
+                    })
+            }
+            PoolOp::Swap(staked_tokens) => self.swap(staked_tokens).map(OpOutput::Tokens),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PoolFixture;
+
+    fn ops() -> impl Iterator<Item = PoolOp> {
+        (0..1_000u64).map(|index| match index % 4 {
+            0 => PoolOp::AddLiquidity(TokenAmount::from_lamports(1_000 + index * 7)),
+            1 | 2 => PoolOp::Swap(StakedTokenAmount::from_lamports(500 + index * 13)),
+            _ => PoolOp::RemoveLiquidity(LpTokenAmount::from_lamports(900 + index * 11)),
+        })
+    }
+
+    #[test]
+    fn it_runs_ops_like_individual_calls() {
+        let fixture = PoolFixture::new()
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(200_000);
+        let mut streamed = fixture.clone().build().pool;
+        let mut called = fixture.build().pool;
+        let mut failures = 0;
+
+        streamed.run_ops(ops(), |index, result| {
+            let expected = match ops().nth(index).unwrap() {
+                PoolOp::AddLiquidity(tokens) => {
+                    called.add_liquidity(tokens).map(OpOutput::LpTokens)
+                }
+                PoolOp::RemoveLiquidity(lp_tokens) => {
+                    called
+                        .remove_liquidity(lp_tokens)
+                        .map(|(tokens, staked_tokens)| {
+                            OpOutput::Removed(RemovedLiquidity {
+                                tokens,
+                                staked_tokens,
+                            })
+                        })
+                }
+                PoolOp::Swap(staked_tokens) => called.swap(staked_tokens).map(OpOutput::Tokens),
+            };
+            assert_eq!(*result, expected, "op {index}");
+            failures += usize::from(result.is_err());
+        });
+
+        assert_eq!(streamed.state(), called.state());
+        assert_eq!(streamed.sequence(), called.sequence());
+        assert!(failures > 0);
+    }
+}
@@ -0,0 +1,189 @@
+//! Source of the time a pool stamps its records with. The pool never reads the system time
+//! itself, so pools without a clock, or with a `ManualClock`, stay deterministic.
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::lp_pool::LpPool;
+
+/// Current time in units chosen by the clock, seconds for `SystemClock`.
+pub trait Clock: core::fmt::Debug + Send + Sync {
+    fn now(&self) -> u64;
+}
+
+/// Clock reading whatever it was last set to. Clones share the time, so a test keeps one
+/// to move the clock of the pool it handed the other to.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock(Arc<AtomicU64>);
+
+impl ManualClock {
+    #[must_use]
+    pub fn new(now: u64) -> Self {
+        Self(Arc::new(AtomicU64::new(now)))
+    }
+
+    pub fn set(&self, now: u64) {
+        self.0.store(now, Ordering::Relaxed);
+    }
+
+    /// Moves the clock `by` forward, saturating at `u64::MAX`.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "the update closure always returns `Some`"
+    )]
+    pub fn advance(&self, by: u64) {
+        // PANIC SAFETY: the closure always returns `Some`.
+        self.0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |now| {
+                Some(now.saturating_add(by))
+            })
+            .expect("the update never fails");
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Seconds since the Unix epoch, zero for a system time before it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs())
+    }
+}
+
+impl LpPool {
+    /// Stamps history entries, rejections and events with `clock`'s time in `recorded_at`,
+    /// and makes `update_price` an `update_price_at` the clock's time. Clones of the pool
+    /// share the clock.
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Time of the pool's clock, `None` without one.
+    #[must_use]
+    pub fn now(&self) -> Option<u64> {
+        self.clock.as_ref().map(|clock| clock.now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::lp_pool::{
+        data::token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+        events::{EventFilter, PoolEvent},
+        history::HistoryPolicy,
+        operation::Operation,
+    };
+    use crate::test_utils::PoolFixture;
+
+    #[test]
+    fn it_stamps_records_with_manual_clock() {
+        let clock = ManualClock::new(100);
+        let mut lp_pool = PoolFixture::new()
+            .price("1.0")
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool
+            .with_history_policy(HistoryPolicy::Unbounded)
+            .with_price_history(4)
+            .with_clock(clock.clone());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        lp_pool.subscribe(EventFilter::ALL, move |event| {
+            sink.lock().unwrap().push(event);
+        });
+
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(1_000))
+            .unwrap();
+        clock.set(250);
+        lp_pool.update_price("1.05".parse().unwrap()).unwrap();
+        clock.advance(50);
+        lp_pool
+            .add_liquidity(TokenAmount::from_lamports(5_000))
+            .unwrap();
+        lp_pool
+            .remove_liquidity(LpTokenAmount::from_lamports(2_000))
+            .unwrap();
+
+        let recorded: Vec<_> = lp_pool
+            .history()
+            .map(|entry| (entry.operation, entry.recorded_at))
+            .collect();
+        assert_eq!(
+            recorded,
+            [
+                (
+                    Operation::Swap(StakedTokenAmount::from_lamports(1_000)),
+                    Some(100)
+                ),
+                (Operation::UpdatePrice("1.05".parse().unwrap()), Some(250)),
+                (
+                    Operation::AddLiquidity(TokenAmount::from_lamports(5_000)),
+                    Some(300)
+                ),
+                (
+                    Operation::RemoveLiquidity(LpTokenAmount::from_lamports(2_000)),
+                    Some(300)
+                ),
+            ]
+        );
+        assert_eq!(lp_pool.now(), Some(300));
+        assert_eq!(lp_pool.checkpoint().timestamp, 250);
+        assert_eq!(
+            lp_pool.price_history().unwrap().latest().unwrap().timestamp,
+            250
+        );
+        let stamps: Vec<_> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| match event {
+                PoolEvent::Executed(entry) => entry.recorded_at,
+                PoolEvent::PriceUpdated { recorded_at, .. } => *recorded_at,
+            })
+            .collect();
+        assert_eq!(stamps, [Some(100), Some(250), Some(300), Some(300)]);
+    }
+
+    #[test]
+    fn it_records_no_time_without_clock() {
+        let mut lp_pool = PoolFixture::new()
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool
+            .with_history_policy(HistoryPolicy::Unbounded);
+
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(1_000))
+            .unwrap();
+        lp_pool.update_price("1.05".parse().unwrap()).unwrap();
+
+        assert_eq!(lp_pool.now(), None);
+        assert!(lp_pool.history().all(|entry| entry.recorded_at.is_none()));
+        assert_eq!(lp_pool.checkpoint().timestamp, 0);
+    }
+
+    #[test]
+    fn it_reads_system_clock_after_unix_epoch() {
+        assert!(SystemClock.now() > 0);
+    }
+}
@@ -0,0 +1,66 @@
+//! Protocol parameters the amount, fee and price types are built on, defined once so they
+//! cannot drift apart.
+
+/// 100%, in basis points.
+pub const MAX_BASIS_POINTS: u32 = 10_000;
+
+/// Decimal places of a `Price`.
+pub const PRICE_DECIMALS: u32 = 2;
+
+/// Points per unit of price, `Price::try_from_points(PRICE_SCALE)` is a price of 1.
+pub const PRICE_SCALE: u64 = 10u64.pow(PRICE_DECIMALS);
+
+/// Decimal places of SOL, mSOL and LP tokens.
+pub const DEFAULT_DECIMALS: u32 = 9;
+
+pub const LAMPORTS_PER_SOL: u64 = 10u64.pow(DEFAULT_DECIMALS);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lp_pool::data::{
+        fee::Fee,
+        price::Price,
+        token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    };
+
+    #[test]
+    fn it_derives_types_from_same_parameters() {
+        assert_eq!(
+            "1sol".parse::<TokenAmount>(),
+            Ok(TokenAmount::from_lamports(LAMPORTS_PER_SOL))
+        );
+        assert_eq!(
+            "1msol".parse::<StakedTokenAmount>(),
+            Ok(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL))
+        );
+        assert_eq!(
+            "1lp".parse::<LpTokenAmount>(),
+            Ok(LpTokenAmount::from_lamports(LAMPORTS_PER_SOL))
+        );
+        assert_eq!(
+            TokenAmount::from_lamports(LAMPORTS_PER_SOL)
+                .whole_tokens()
+                .to_string(),
+            "1 sol"
+        );
+
+        assert_eq!(Fee::MAX_BASIS_POINTS, MAX_BASIS_POINTS);
+        assert_eq!(Fee::from_basis_points(MAX_BASIS_POINTS), Fee::MAX);
+        assert_eq!(
+            Fee::from_basis_points(MAX_BASIS_POINTS).apply(12_345),
+            Ok(0)
+        );
+        assert_eq!("100".parse::<Fee>(), Ok(Fee::MAX));
+
+        assert_eq!(Price::SCALE, PRICE_SCALE);
+        assert_eq!("1".parse::<Price>(), Price::try_from_points(PRICE_SCALE));
+        assert_eq!(Price::try_from(1u64), Price::try_from_points(PRICE_SCALE));
+        assert_eq!(
+            Price::try_from_points(PRICE_SCALE)
+                .unwrap()
+                .mul_by_price(LAMPORTS_PER_SOL),
+            Ok(LAMPORTS_PER_SOL)
+        );
+    }
+}
@@ -1,65 +1,619 @@
-use std::fmt::Display;
+use core::fmt::Display;
+use core::str::FromStr;
 
+use crate::calc::parse_decimal;
 use crate::error::{Error, Result};
-use crate::lp_pool::error::Error as LpPoolError;
+use crate::lp_pool::consts;
+use crate::lp_pool::error::{ConvertedFrom, Error as LpPoolError};
 
-#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+/// Fee in hundredths of a basis point, `Fee::from_centibps(25)` is 0.25 bps.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct Fee {
-    pub basis_points: u32,
+    pub centibps: u32,
 }
 
 impl Display for Fee {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}.{:0>2}%",
-            self.basis_points / 100,
-            self.basis_points % 100
-        )
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let whole = self.centibps / Self::CENTIBPS_PER_PERCENT;
+        let fraction = self.centibps % Self::CENTIBPS_PER_PERCENT;
+        // at least two decimals, more only when the fee has sub basis point precision
+        let (fraction, width) = match (fraction % 100, fraction % 10) {
+            (0, _) => (fraction / 100, 2),
+            (_, 0) => (fraction / 10, 3),
+            _ => (fraction, 4),
+        };
+        write!(f, "{whole}.{fraction:0>width$}%")
+    }
+}
+
+impl FromStr for Fee {
+    type Err = Error;
+    fn from_str(percent: &str) -> Result<Self> {
+        let conversion_failure = || {
+            Error::LpPool(LpPoolError::FeeConversionFailure {
+                converted_from: ConvertedFrom::new(percent),
+            })
+        };
+
+        let centibps = parse_decimal(percent.trim().trim_end_matches('%'), 4)
+            .and_then(|centibps| u32::try_from(centibps).ok())
+            .ok_or_else(conversion_failure)?;
+        let fee = Self::from_centibps(centibps);
+        fee.check()?;
+        Ok(fee)
     }
 }
 
 impl Fee {
-    pub const MAX_BASIS_POINTS: u32 = 10_000; //100%
+    pub const MAX_BASIS_POINTS: u32 = consts::MAX_BASIS_POINTS;
+    pub const CENTIBPS_PER_BASIS_POINT: u32 = 100;
+    pub const MAX_CENTIBPS: u32 = Self::MAX_BASIS_POINTS * Self::CENTIBPS_PER_BASIS_POINT;
+    const CENTIBPS_PER_PERCENT: u32 = Self::MAX_CENTIBPS / 100;
+    pub const ZERO: Self = Self::from_centibps(0);
+    pub const MAX: Self = Self::from_centibps(Self::MAX_CENTIBPS);
 
+    #[must_use]
     pub const fn from_basis_points(basis_points: u32) -> Self {
-        Self { basis_points }
+        Self::from_centibps(basis_points.saturating_mul(Self::CENTIBPS_PER_BASIS_POINT))
+    }
+
+    #[must_use]
+    pub const fn from_centibps(centibps: u32) -> Self {
+        Self { centibps }
+    }
+
+    /// `Fee::from_percent(0.3)` is 30 bps, rounded to the nearest hundredth of a basis point.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FeeConversionFailure` for a negative or non-finite `percent` and
+    /// `BasisPointsOverflow` above 100%.
+    pub fn from_percent(percent: f64) -> Result<Self> {
+        if !percent.is_finite() || percent < 0.0 {
+            return Err(Error::LpPool(LpPoolError::FeeConversionFailure {
+                converted_from: ConvertedFrom::new(percent),
+            }));
+        }
+        // `f64::round` needs std, the value is non-negative so adding a half and truncating
+        // rounds to nearest, the cast saturates for huge inputs which `check` then rejects
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let centibps = (percent * f64::from(Self::CENTIBPS_PER_PERCENT) + 0.5) as u32;
+        let fee = Self::from_centibps(centibps);
+        fee.check()?;
+        Ok(fee)
+    }
+
+    #[must_use]
+    pub fn as_percent(self) -> f64 {
+        f64::from(self.centibps) / f64::from(Self::CENTIBPS_PER_PERCENT)
+    }
+
+    /// Whole basis points, sub basis point precision is truncated.
+    #[must_use]
+    pub fn basis_points(self) -> u32 {
+        self.centibps / Self::CENTIBPS_PER_BASIS_POINT
     }
 
-    pub fn check(self) -> Result<()> {
-        if self.basis_points > Self::MAX_BASIS_POINTS {
-            return Err(Error::LpPool(LpPoolError::BasisPointsOverflow(
-                self.basis_points,
-            )));
+    /// `check` as a `bool`, for `const` contexts.
+    #[must_use]
+    pub const fn is_valid(self) -> bool {
+        self.centibps <= Self::MAX_CENTIBPS
+    }
+
+    /// Rejects fees above 100%.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` above 100%.
+    pub const fn check(self) -> Result<()> {
+        if !self.is_valid() {
+            return Err(Error::LpPool(LpPoolError::BasisPointsOverflow(self)));
         }
         Ok(())
     }
 
+    /// Fee of charging `self` and then `other` on the remainder, `1 - (1 - a)(1 - b)`,
+    /// rounded to the nearest hundredth of a basis point.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` when either fee is above 100%.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "the composed fee is clamped to `MAX_CENTIBPS`"
+    )]
+    pub fn compose(self, other: Fee) -> Result<Fee> {
+        self.check()?;
+        other.check()?;
+
+        let (a, b) = (u64::from(self.centibps), u64::from(other.centibps));
+        let max = u64::from(Self::MAX_CENTIBPS);
+        let overlap = (a * b + max / 2) / max;
+        let centibps = (a + b - overlap).min(max);
+        // PANIC SAFETY: clamped to `MAX_CENTIBPS` just above, which is a `u32`.
+        Ok(Self::from_centibps(
+            u32::try_from(centibps).expect("composed fee is at most MAX_CENTIBPS"),
+        ))
+    }
+
+    pub fn checked_add(self, other: Fee) -> Option<Fee> {
+        self.centibps
+            .checked_add(other.centibps)
+            .map(Self::from_centibps)
+            .filter(|fee| *fee <= Self::MAX)
+    }
+
+    #[must_use]
+    pub fn saturating_add(self, other: Fee) -> Fee {
+        self.checked_add(other).unwrap_or(Self::MAX)
+    }
+
+    /// `FeeRange::interpolate` for bounds that have not been validated yet.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `FeeRange::new`.
+    pub fn interpolate(
+        min: Fee,
+        max: Fee,
+        amount: u64,
+        target: u64,
+        rounding: Rounding,
+    ) -> Result<Fee> {
+        Ok(FeeRange::new(min, max)?.interpolate(amount, target, rounding))
+    }
+
+    /// Part of `lamports` taken by the fee, rounded down. At 100% that is all of it, fees
+    /// above 100% are rejected with `BasisPointsOverflow` before any math.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` above 100%.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "a checked fee is at most `lamports`"
+    )]
+    pub fn fee_amount(self, lamports: u64) -> Result<u64> {
+        self.check()?;
+        let fee = u128::from(lamports) * u128::from(self.centibps) / u128::from(Self::MAX_CENTIBPS);
+        // PANIC SAFETY: `centibps <= MAX_CENTIBPS` after `check`, so the fee is at most
+        // `lamports`, a `u64`.
+        Ok(u64::try_from(fee).expect("fee is at most lamports"))
+    }
+
+    /// `lamports` less `fee_amount`, zero at 100%.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` above 100%.
     pub fn apply(self, lamports: u64) -> Result<u64> {
-        let fee = u64::try_from(
-            u128::from(lamports) * u128::from(self.basis_points)
-                / u128::from(Self::MAX_BASIS_POINTS),
-        )
-        .map_err(|_| Error::CalculationError)?;
-        Ok(lamports - fee)
+        // `fee_amount` is at most `lamports`
+        Ok(lamports - self.fee_amount(lamports)?)
     }
 }
 
+/// Bounds of a fee curve, `min <= max <= Fee::MAX` holds for every value.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FeeRange {
+    min: Fee,
+    max: Fee,
+}
+
+impl FeeRange {
+    /// Validated bounds, `min` charged at and above the target and `max` at an empty pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` when either bound is above 100% and
+    /// `MinFeeGreaterThanMaxFee` when `min > max`.
+    pub fn new(min: Fee, max: Fee) -> Result<Self> {
+        min.check()?;
+        max.check()?;
+        if min > max {
+            return Err(Error::LpPool(LpPoolError::MinFeeGreaterThanMaxFee {
+                min,
+                max,
+            }));
+        }
+        Ok(Self { min, max })
+    }
+
+    /// `new` returning `None` instead of the error, for `const` contexts:
+    ///
+    /// ```
+    /// use liquidity_pool::lp_pool::data::fee::{Fee, FeeRange};
+    ///
+    /// const RANGE: FeeRange =
+    ///     FeeRange::checked_new(Fee::from_basis_points(10), Fee::from_basis_points(900)).unwrap();
+    /// assert_eq!(RANGE.max(), Fee::from_basis_points(900));
+    /// ```
+    #[must_use]
+    pub const fn checked_new(min: Fee, max: Fee) -> Option<Self> {
+        if !min.is_valid() || !max.is_valid() || min.centibps > max.centibps {
+            return None;
+        }
+        Some(Self { min, max })
+    }
+
+    /// Range without any validation, to test fees `new` rejects.
+    #[cfg(any(test, feature = "test-utils"))]
+    #[doc(hidden)]
+    #[must_use]
+    pub fn new_unchecked(min: Fee, max: Fee) -> Self {
+        Self { min, max }
+    }
+
+    #[must_use]
+    pub const fn min(self) -> Fee {
+        self.min
+    }
+
+    #[must_use]
+    pub const fn max(self) -> Fee {
+        self.max
+    }
+
+    /// `max - min` in whole basis points, sub basis point precision is truncated.
+    #[must_use]
+    pub const fn delta_bps(self) -> u32 {
+        (self.max.centibps - self.min.centibps) / Fee::CENTIBPS_PER_BASIS_POINT
+    }
+
+    /// Fee on the curve from `max` at an empty pool down to `min` at `target` and above.
+    #[must_use]
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "the drop is at most `max - min` below the target"
+    )]
+    pub fn interpolate(self, amount: u64, target: u64, rounding: Rounding) -> Fee {
+        if amount >= target {
+            return self.min;
+        }
+
+        let scaled_delta = u128::from(self.max.centibps - self.min.centibps) * u128::from(amount);
+        let target = u128::from(target);
+        // a smaller drop from `max` rounds the fee up
+        let drop = match rounding {
+            Rounding::Up => scaled_delta / target,
+            Rounding::Down => scaled_delta.div_ceil(target),
+        };
+        // PANIC SAFETY: `amount < target`, so `scaled_delta / target` rounded either way is
+        // at most `max - min`, a `u32`.
+        let drop = u32::try_from(drop).expect("drop is below max - min while amount < target");
+        Fee::from_centibps(self.max.centibps - drop)
+    }
+
+    /// Smallest amount at which `interpolate` rounding up charges at most `fee`, `None` for
+    /// a fee below `min`.
+    #[must_use]
+    #[allow(clippy::missing_panics_doc, reason = "the amount is at most `target`")]
+    pub fn amount_for_fee(self, fee: Fee, target: u64) -> Option<u64> {
+        if fee < self.min {
+            return None;
+        }
+        if fee >= self.max {
+            return Some(0);
+        }
+        // rounding up charges `max - floor(delta * amount / target)`, at most `fee` once
+        // `delta * amount >= (max - fee) * target`
+        let delta = u128::from(self.max.centibps - self.min.centibps);
+        let drop = u128::from(self.max.centibps - fee.centibps);
+        let amount = (drop * u128::from(target)).div_ceil(delta);
+        // PANIC SAFETY: `drop <= delta`, so `amount` is at most `target`.
+        Some(u64::try_from(amount).expect("amount is at most target"))
+    }
+}
+
+/// Direction to round a fee that falls between two representable values.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Rounding {
+    Up,
+    Down,
+}
+
+/// How a configured withdrawal fee combines with the fee curve in `remove_liquidity`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum WithdrawalFeeMode {
+    #[default]
+    UseCurve,
+    Replace,
+    Additional,
+}
+
 #[cfg(test)]
 mod fee_tests {
     use super::*;
+    use proptest::prelude::*;
     #[test]
     fn it_creates_fee_with_valid_basis_points() {
-        let fee = Fee { basis_points: 10 };
+        let fee = Fee::from_basis_points(10);
         assert!(fee.check().is_ok());
         assert_eq!(fee.apply(10000).unwrap(), 9990);
     }
 
+    #[test]
+    fn it_takes_everything_at_max_fee_and_rejects_more() {
+        assert_eq!(Fee::MAX.apply(12_345), Ok(0));
+        assert_eq!(Fee::MAX.fee_amount(u64::MAX), Ok(u64::MAX));
+        assert_eq!(Fee::from_basis_points(9_999).apply(10_000), Ok(1));
+        assert_eq!(Fee::from_basis_points(9_999).fee_amount(10_000), Ok(9_999));
+
+        let over = Fee::from_centibps(Fee::MAX_CENTIBPS + 1);
+        assert_eq!(
+            over.apply(10_000),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(over)))
+        );
+        assert_eq!(
+            Fee::interpolate(Fee::ZERO, over, 0, 100, Rounding::Up),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(over)))
+        );
+    }
+
+    #[test]
+    fn it_rejects_misordered_or_out_of_range_fee_range() {
+        let (low, high) = (Fee::from_basis_points(10), Fee::from_centibps(90_050));
+        let range = FeeRange::new(low, high).unwrap();
+        assert_eq!((range.min(), range.max()), (low, high));
+        assert_eq!(range.delta_bps(), 890);
+        assert_eq!(FeeRange::new(low, low).map(FeeRange::delta_bps), Ok(0));
+
+        assert_eq!(
+            FeeRange::new(high, low),
+            Err(Error::LpPool(LpPoolError::MinFeeGreaterThanMaxFee {
+                min: high,
+                max: low,
+            }))
+        );
+        let over = Fee::from_basis_points(10_001);
+        assert_eq!(
+            FeeRange::new(low, over),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(over)))
+        );
+        assert_eq!(
+            FeeRange::new(over, over),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(over)))
+        );
+    }
+
     #[test]
     fn it_returns_err_if_basis_points_overflow() {
-        let fee = Fee {
-            basis_points: 10001,
-        };
-        assert!(fee.check().is_err());
+        let fee = Fee::from_basis_points(10001);
+        assert_eq!(
+            fee.check(),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(fee)))
+        );
+    }
+
+    #[test]
+    fn it_validates_fee_before_taking_it() {
+        let over = Fee::from_basis_points(10_001);
+        assert_eq!(
+            over.apply(0),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(over)))
+        );
+        assert_eq!(
+            over.fee_amount(u64::MAX),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(over)))
+        );
+
+        let max = Fee::from_basis_points(10_000);
+        assert_eq!(max.fee_amount(u64::MAX), Ok(u64::MAX));
+        assert_eq!(max.apply(u64::MAX), Ok(0));
+    }
+
+    #[test]
+    fn it_parses_fee_from_percent_string() {
+        assert_eq!("0.1".parse::<Fee>().unwrap(), Fee::from_basis_points(10));
+        assert_eq!("9%".parse::<Fee>().unwrap(), Fee::from_basis_points(900));
+        assert_eq!(
+            "100%".parse::<Fee>().unwrap(),
+            Fee::from_basis_points(10_000)
+        );
+    }
+
+    #[test]
+    fn it_returns_err_if_fee_string_is_invalid() {
+        assert_eq!(
+            "0.00005".parse::<Fee>(),
+            Err(Error::LpPool(LpPoolError::FeeConversionFailure {
+                converted_from: ConvertedFrom::new("0.00005")
+            }))
+        );
+        assert_eq!(
+            "100.0001".parse::<Fee>(),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(
+                Fee::from_centibps(1_000_001)
+            )))
+        );
+        assert!("fee".parse::<Fee>().is_err());
+    }
+
+    #[test]
+    fn it_composes_fees_multiplicatively() {
+        let one_percent = Fee::from_basis_points(100);
+
+        assert_eq!(
+            one_percent.compose(one_percent),
+            Ok(Fee::from_basis_points(199))
+        );
+        assert_eq!(one_percent.compose(Fee::ZERO), Ok(one_percent));
+        assert_eq!(Fee::ZERO.compose(one_percent), Ok(one_percent));
+        assert_eq!(
+            Fee::from_basis_points(15).compose(Fee::from_basis_points(30)),
+            Ok(Fee::from_centibps(4_495))
+        );
+    }
+
+    #[test]
+    fn it_clamps_composed_fee_near_max() {
+        assert_eq!(
+            Fee::from_basis_points(9_999).compose(Fee::from_basis_points(9_999)),
+            Ok(Fee::MAX)
+        );
+        assert_eq!(
+            Fee::MAX.compose(Fee::from_basis_points(5_000)),
+            Ok(Fee::MAX)
+        );
+        assert_eq!(
+            Fee::from_basis_points(9_000).compose(Fee::from_basis_points(9_000)),
+            Ok(Fee::from_basis_points(9_900))
+        );
+    }
+
+    #[test]
+    fn it_returns_err_if_composed_fee_is_invalid() {
+        assert_eq!(
+            Fee::from_basis_points(10_001).compose(Fee::ZERO),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(
+                Fee::from_basis_points(10_001)
+            )))
+        );
+        assert_eq!(
+            Fee::ZERO.compose(Fee::from_basis_points(10_001)),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(
+                Fee::from_basis_points(10_001)
+            )))
+        );
+    }
+
+    #[test]
+    fn it_adds_fees_up_to_max() {
+        let fee = Fee::from_basis_points(6_000);
+
+        assert_eq!(
+            fee.checked_add(Fee::from_basis_points(4_000)),
+            Some(Fee::MAX)
+        );
+        assert_eq!(fee.checked_add(fee), None);
+        assert_eq!(fee.saturating_add(fee), Fee::MAX);
+        assert!(Fee::ZERO < fee && fee < Fee::MAX);
+    }
+
+    #[test]
+    fn it_keeps_hundredths_of_a_basis_point() {
+        let fee = Fee::from_centibps(1);
+
+        assert_eq!("0.0001%".parse::<Fee>(), Ok(fee));
+        assert_eq!(fee.to_string(), "0.0001%");
+        assert_eq!(fee.apply(1_000_000).unwrap(), 999_999);
+        assert_eq!(fee.apply(999_999).unwrap(), 999_999);
+        assert_eq!(fee.basis_points(), 0);
+        assert_eq!(Fee::from_basis_points(3), Fee::from_centibps(300));
+        assert_eq!(Fee::from_centibps(25).to_string(), "0.0025%");
+        assert_eq!(Fee::from_centibps(2_530).to_string(), "0.253%");
+        assert_eq!(Fee::from_basis_points(30).to_string(), "0.30%");
+    }
+
+    #[test]
+    fn it_converts_fee_to_and_from_percent() {
+        assert_eq!(Fee::from_percent(0.3), Ok(Fee::from_basis_points(30)));
+        assert_eq!(Fee::from_percent(0.0025), Ok(Fee::from_centibps(25)));
+        assert_eq!(Fee::from_percent(0.000_04), Ok(Fee::ZERO));
+        assert_eq!(Fee::from_percent(0.000_05), Ok(Fee::from_centibps(1)));
+        assert!((Fee::from_centibps(25).as_percent() - 0.0025).abs() < f64::EPSILON);
+        assert_eq!(
+            Fee::from_percent(100.0001),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(
+                Fee::from_centibps(1_000_001)
+            )))
+        );
+        assert!(Fee::from_percent(-0.1).is_err());
+        assert!(Fee::from_percent(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn it_interpolates_between_max_and_min() {
+        let (min, max) = (Fee::from_basis_points(10), Fee::from_basis_points(900));
+        let interpolate = |amount, rounding| Fee::interpolate(min, max, amount, 1000, rounding);
+
+        assert_eq!(interpolate(0, Rounding::Up), Ok(max));
+        assert_eq!(interpolate(0, Rounding::Down), Ok(max));
+        assert_eq!(interpolate(1000, Rounding::Up), Ok(min));
+        assert_eq!(interpolate(1001, Rounding::Down), Ok(min));
+        assert_eq!(
+            interpolate(500, Rounding::Up),
+            Ok(Fee::from_basis_points(455))
+        );
+        assert_eq!(
+            interpolate(500, Rounding::Down),
+            Ok(Fee::from_basis_points(455))
+        );
+    }
+
+    #[test]
+    fn it_rounds_interpolated_fee_in_requested_direction() {
+        let (min, max) = (Fee::from_centibps(0), Fee::from_centibps(3));
+
+        assert_eq!(
+            Fee::interpolate(min, max, 1, 2, Rounding::Up),
+            Ok(Fee::from_centibps(2))
+        );
+        assert_eq!(
+            Fee::interpolate(min, max, 1, 2, Rounding::Down),
+            Ok(Fee::from_centibps(1))
+        );
+    }
+
+    #[test]
+    fn it_returns_err_if_interpolating_with_min_above_max() {
+        let (min, max) = (Fee::from_basis_points(11), Fee::from_basis_points(10));
+
+        assert_eq!(
+            Fee::interpolate(min, max, 0, 1, Rounding::Up),
+            Err(Error::LpPool(LpPoolError::MinFeeGreaterThanMaxFee {
+                min,
+                max
+            }))
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn it_never_raises_fee_as_amount_grows(
+            min in 0..=Fee::MAX_CENTIBPS,
+            max in 0..=Fee::MAX_CENTIBPS,
+            amount in any::<u64>(),
+            more in any::<u64>(),
+            target in 1..=u64::MAX,
+            up in any::<bool>(),
+        ) {
+            let (min, max) = (Fee::from_centibps(min.min(max)), Fee::from_centibps(min.max(max)));
+            let rounding = if up { Rounding::Up } else { Rounding::Down };
+            let fee = Fee::interpolate(min, max, amount, target, rounding).unwrap();
+            let fee_at_more =
+                Fee::interpolate(min, max, amount.saturating_add(more), target, rounding).unwrap();
+
+            prop_assert!(fee_at_more <= fee);
+            prop_assert!(min <= fee && fee <= max);
+        }
+
+        #[test]
+        fn it_inverts_fee_curve_exactly(
+            min in 0..=Fee::MAX_CENTIBPS,
+            max in 0..=Fee::MAX_CENTIBPS,
+            fee in 0..=Fee::MAX_CENTIBPS,
+            target in 1..=u64::MAX,
+        ) {
+            let range = FeeRange::new(
+                Fee::from_centibps(min.min(max)),
+                Fee::from_centibps(min.max(max)),
+            )
+            .unwrap();
+            let fee = Fee::from_centibps(fee);
+            let Some(amount) = range.amount_for_fee(fee, target) else {
+                prop_assert!(fee < range.min());
+                return Ok(());
+            };
+
+            prop_assert!(range.interpolate(amount, target, Rounding::Up) <= fee);
+            if amount > 0 {
+                prop_assert!(range.interpolate(amount - 1, target, Rounding::Up) > fee);
+            }
+        }
     }
 }
@@ -0,0 +1,172 @@
+//! Minimal Borsh layout of the Marinade `State` account, read up to the
+//! fields needed to price mSOL.
+
+use borsh::BorshDeserialize;
+
+use super::Price;
+use crate::error::{Error, Result};
+use crate::lp_pool::error::Error as LpPoolError;
+
+/// Anchor account discriminator, `sha256("account:State")[..8]`.
+const STATE_DISCRIMINATOR: [u8; 8] = [216, 146, 107, 94, 104, 75, 182, 177];
+
+type Pubkey = [u8; 32];
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+struct Fee {
+    basis_points: u32,
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+struct List {
+    account: Pubkey,
+    item_size: u32,
+    count: u32,
+    new_account: Pubkey,
+    copied_count: u32,
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+struct StakeSystem {
+    stake_list: List,
+    delayed_unstake_cooling_down: u64,
+    stake_deposit_bump_seed: u8,
+    stake_withdraw_bump_seed: u8,
+    slots_for_stake_delta: u64,
+    last_stake_delta_epoch: u64,
+    min_stake: u64,
+    extra_stake_delta_runs: u32,
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+struct ValidatorSystem {
+    validator_list: List,
+    manager_authority: Pubkey,
+    total_validator_score: u32,
+    total_active_balance: u64,
+    auto_add_validator_enabled: u8,
+}
+
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+struct LiqPool {
+    lp_mint: Pubkey,
+    lp_mint_authority_bump_seed: u8,
+    sol_leg_bump_seed: u8,
+    msol_leg_authority_bump_seed: u8,
+    msol_leg: Pubkey,
+    lp_liquidity_target: u64,
+    lp_max_fee: Fee,
+    lp_min_fee: Fee,
+    treasury_cut: Fee,
+    lp_supply: u64,
+    lent_from_sol_leg: u64,
+    liquidity_sol_cap: u64,
+}
+
+/// Prefix of the account, fields after `emergency_cooling_down` are ignored.
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+struct State {
+    msol_mint: Pubkey,
+    admin_authority: Pubkey,
+    operational_sol_account: Pubkey,
+    treasury_msol_account: Pubkey,
+    reserve_bump_seed: u8,
+    msol_mint_authority_bump_seed: u8,
+    rent_exempt_for_token_acc: u64,
+    reward_fee: Fee,
+    stake_system: StakeSystem,
+    validator_system: ValidatorSystem,
+    liq_pool: LiqPool,
+    available_reserve_balance: u64,
+    msol_supply: u64,
+    msol_price: u64,
+    circulating_ticket_count: u64,
+    circulating_ticket_balance: u64,
+    lent_from_reserve: u64,
+    min_deposit: u64,
+    min_withdraw: u64,
+    staking_sol_cap: u64,
+    emergency_cooling_down: u64,
+}
+
+impl State {
+    /// Same as Marinade's `total_virtual_staked_lamports`.
+    fn total_staked_lamports(&self) -> Option<u64> {
+        self.validator_system
+            .total_active_balance
+            .checked_add(self.stake_system.delayed_unstake_cooling_down)?
+            .checked_add(self.emergency_cooling_down)?
+            .checked_add(self.available_reserve_balance)?
+            .checked_sub(self.circulating_ticket_balance)
+    }
+}
+
+impl Price {
+    /// Price of mSOL from the raw data of the Marinade `State` account.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AccountDataInvalid` for data that is not a `State` account, and
+    /// `PriceRatioIncorrect` for a state without mSOL supply.
+    pub fn from_marinade_state_bytes(data: &[u8]) -> Result<Self> {
+        let invalid = || Error::LpPool(LpPoolError::AccountDataInvalid { len: data.len() });
+
+        let Some(body) = data.strip_prefix(&STATE_DISCRIMINATOR) else {
+            return Err(invalid());
+        };
+        let state = State::deserialize(&mut &body[..]).map_err(|_| invalid())?;
+        let total_staked = state.total_staked_lamports().ok_or_else(invalid)?;
+
+        Price::from_ratio(total_staked, state.msol_supply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> Vec<u8> {
+        let hex = include_str!("state_fixture.hex").trim();
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn it_parses_price_from_state_account() {
+        assert_eq!(
+            Price::from_marinade_state_bytes(&fixture()).unwrap(),
+            Price::from_raw_points(126)
+        );
+    }
+
+    #[test]
+    fn it_returns_err_if_discriminator_does_not_match() {
+        let mut data = fixture();
+        data[0] ^= 0xff;
+
+        assert_eq!(
+            Price::from_marinade_state_bytes(&data),
+            Err(Error::LpPool(LpPoolError::AccountDataInvalid {
+                len: data.len()
+            }))
+        );
+    }
+
+    #[test]
+    fn it_returns_err_if_data_is_truncated() {
+        let data = fixture();
+
+        assert_eq!(
+            Price::from_marinade_state_bytes(&data[..100]),
+            Err(Error::LpPool(LpPoolError::AccountDataInvalid { len: 100 }))
+        );
+    }
+}
@@ -1,29 +1,58 @@
+use core::str::FromStr;
+
+use crate::calc::parse_decimal;
 use crate::error::{Error, Result};
-use crate::lp_pool::error::Error as LpPoolError;
+use crate::lp_pool::{
+    consts,
+    error::{ConvertedFrom, Error as LpPoolError},
+    overflow,
+};
+
+#[cfg(feature = "marinade")]
+mod marinade;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+/// Price of a staked token in tokens, counted in points of `1 / Price::SCALE`. Every
+/// conversion takes the price in whole units and scales it by `Price::SCALE`, so `"1.5"`,
+/// `1.5f32` and `Price::try_from_points(150)` are the same price, and `Price::try_from(2u64)`
+/// is `Price::try_from_points(200)`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct Price(u64);
 
 impl TryFrom<f32> for Price {
     type Error = Error;
     fn try_from(price_without_scale: f32) -> Result<Self> {
-        #[allow(clippy::cast_possible_truncation)]
-        let price_i = (price_without_scale * 100.0).floor() as i64;
-        let price = u32::try_from(price_i).map_err(|_| {
+        #[allow(clippy::cast_precision_loss)]
+        let scaled = price_without_scale * Price::SCALE as f32;
+        // `f32::floor` needs std, round toward negative infinity by hand
+        #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+        let price_i = {
+            let truncated = scaled as i64;
+            if (truncated as f32) > scaled {
+                truncated - 1
+            } else {
+                truncated
+            }
+        };
+        let price = u64::try_from(price_i).map_err(|_| {
             Error::LpPool(LpPoolError::PriceConversionFailure {
-                converted_from: price_without_scale.to_string(),
+                converted_from: ConvertedFrom::new(price_without_scale),
             })
         })?;
-        Ok(Self(price.into()))
+        Ok(Self(price))
     }
 }
 
 impl TryFrom<u64> for Price {
     type Error = Error;
     fn try_from(price_without_scale: u64) -> Result<Self> {
-        let Some(price) = price_without_scale.checked_mul(100) else {
+        let Some(price) = price_without_scale.checked_mul(Price::SCALE) else {
             return Err(Error::LpPool(LpPoolError::PriceConversionFailure {
-                converted_from: price_without_scale.to_string(),
+                converted_from: ConvertedFrom::new(price_without_scale),
             }));
         };
 
@@ -31,31 +60,199 @@ impl TryFrom<u64> for Price {
     }
 }
 
-impl std::fmt::Display for Price {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}.{:0>2}%", self.0 / 100, self.0 % 100)
+impl FromStr for Price {
+    type Err = Error;
+    fn from_str(price_without_scale: &str) -> Result<Self> {
+        let price = parse_decimal(price_without_scale, Price::DECIMALS).ok_or_else(|| {
+            Error::LpPool(LpPoolError::PriceConversionFailure {
+                converted_from: ConvertedFrom::new(price_without_scale),
+            })
+        })?;
+        Ok(Self(price))
+    }
+}
+
+impl core::fmt::Display for Price {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}.{:0>width$}%",
+            self.0 / Self::SCALE,
+            self.0 % Self::SCALE,
+            width = Self::DECIMALS as usize
+        )
+    }
+}
+
+impl From<Price> for u64 {
+    fn from(val: Price) -> Self {
+        val.0
     }
 }
 
 impl Price {
-    pub fn from_points(points: u64) -> Self {
+    pub const DECIMALS: u32 = consts::PRICE_DECIMALS;
+    /// Points per unit of price, `Price::try_from_points(150)` is a price of 1.5.
+    pub const SCALE: u64 = consts::PRICE_SCALE;
+
+    /// Price of `points / Price::SCALE`, rejecting zero like `LpPool::init` does.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PriceIncorrect` for zero points.
+    pub fn try_from_points(points: u64) -> Result<Self> {
+        Self::checked_from_points(points).ok_or(Error::LpPool(LpPoolError::PriceIncorrect(Self(0))))
+    }
+
+    /// `try_from_points` returning `None` for zero, for `const` contexts.
+    #[must_use]
+    pub const fn checked_from_points(points: u64) -> Option<Self> {
+        if points == 0 {
+            return None;
+        }
+        Some(Self(points))
+    }
+
+    /// `points` without the zero check, for prices restored from state or recorded
+    /// operations. A zero price is accepted here and rejected wherever it reaches the pool:
+    ///
+    /// ```
+    /// use liquidity_pool::lp_pool::data::price::Price;
+    ///
+    /// const PRICE: Price = Price::from_raw_points(150);
+    /// assert_eq!(u64::from(PRICE), 150);
+    /// assert_eq!(Price::try_from_points(150), Ok(PRICE));
+    /// ```
+    #[must_use]
+    pub const fn from_raw_points(points: u64) -> Self {
         Self(points)
     }
 
-    pub fn mul_by_price(self, lamports: u64) -> u64 {
-        u64::try_from(u128::from(lamports) * u128::from(self.0))
-            .expect("Overflow in Price::mul_by_price")
+    /// Price of one staked token given the lamports backing the staked token
+    /// supply, rounded down to the price resolution.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PriceRatioIncorrect` for a zero supply and `ArithmeticOverflow` when the
+    /// price does not fit `u64` points.
+    pub fn from_ratio(total_staked_lamports: u64, msol_supply: u64) -> Result<Self> {
+        if msol_supply == 0 {
+            return Err(Error::LpPool(LpPoolError::PriceRatioIncorrect {
+                total_staked: total_staked_lamports,
+                supply: msol_supply,
+            }));
+        }
+
+        let points =
+            u128::from(total_staked_lamports) * u128::from(Self::SCALE) / u128::from(msol_supply);
+        let points = u64::try_from(points).map_err(|_| {
+            Error::LpPool(LpPoolError::ArithmeticOverflow {
+                op: "total_staked * SCALE / supply",
+                lhs: total_staked_lamports,
+                rhs: msol_supply,
+            })
+        })?;
+        Ok(Self(points))
+    }
+
+    /// Value of `lamports` staked tokens, rounded down.
+    #[must_use]
+    pub fn checked_mul_by_price(self, lamports: u64) -> Option<u64> {
+        u64::try_from(u128::from(lamports) * u128::from(self.0) / u128::from(Self::SCALE)).ok()
+    }
+
+    /// `checked_mul_by_price`, failing with `ArithmeticOverflow` if the value does not fit
+    /// `u64`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArithmeticOverflow` when the value does not fit `u64`.
+    pub fn mul_by_price(self, lamports: u64) -> Result<u64> {
+        self.checked_mul_by_price(lamports)
+            .ok_or_else(|| overflow("lamports * price / SCALE", lamports, self))
+    }
+
+    /// Value of `lamports` staked tokens, rounded up.
+    #[must_use]
+    pub fn checked_mul_by_price_ceil(self, lamports: u64) -> Option<u64> {
+        u64::try_from((u128::from(lamports) * u128::from(self.0)).div_ceil(u128::from(Self::SCALE)))
+            .ok()
+    }
+
+    /// `checked_mul_by_price_ceil`, failing like `mul_by_price`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArithmeticOverflow` when the value does not fit `u64`.
+    pub fn mul_by_price_ceil(self, lamports: u64) -> Result<u64> {
+        self.checked_mul_by_price_ceil(lamports)
+            .ok_or_else(|| overflow("lamports * price / SCALE", lamports, self))
+    }
+
+    /// Staked tokens worth `lamports`, rounded down. A zero price is rejected with
+    /// `PriceIncorrect`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PriceIncorrect` for a zero price and `ArithmeticOverflow` when the result
+    /// does not fit `u64`.
+    pub fn div_by_price(self, lamports: u64) -> Result<u64> {
+        self.ensure_not_zero()?;
+        u64::try_from(u128::from(lamports) * u128::from(Self::SCALE) / u128::from(self.0))
+            .map_err(|_| overflow("lamports * SCALE / price", lamports, self))
+    }
+
+    /// Staked tokens worth `lamports`, rounded up. A zero price is rejected with
+    /// `PriceIncorrect`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PriceIncorrect` for a zero price and `ArithmeticOverflow` when the result
+    /// does not fit `u64`.
+    pub fn div_by_price_ceil(self, lamports: u64) -> Result<u64> {
+        self.ensure_not_zero()?;
+        u64::try_from((u128::from(lamports) * u128::from(Self::SCALE)).div_ceil(u128::from(self.0)))
+            .map_err(|_| overflow("lamports * SCALE / price", lamports, self))
     }
 
-    pub fn div_by_price(self, lamports: u64) -> u64 {
-        u64::try_from(u128::from(lamports) / u128::from(self.0))
-            .expect("Overflow in Price::div_by_price")
+    fn ensure_not_zero(self) -> Result<()> {
+        if self.0 == 0 {
+            return Err(Error::LpPool(LpPoolError::PriceIncorrect(self)));
+        }
+        Ok(())
+    }
+
+    /// How far `other` is from this price, in basis points of this price, rounded up. Either
+    /// price being zero is rejected.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PriceIncorrect` when either price is zero and `ArithmeticOverflow` when the
+    /// deviation does not fit `u32`.
+    pub fn deviation_bps(&self, other: &Price) -> Result<u32> {
+        if let Some(zero) = [*self, *other].into_iter().find(|price| price.0 == 0) {
+            return Err(Error::LpPool(LpPoolError::PriceIncorrect(zero)));
+        }
+        let difference = u128::from(self.0.abs_diff(other.0));
+        let deviation =
+            (difference * u128::from(consts::MAX_BASIS_POINTS)).div_ceil(u128::from(self.0));
+        u32::try_from(deviation)
+            .map_err(|_| overflow("|other - price| * 10_000 / price", *other, *self))
+    }
+
+    /// `deviation_bps` is at most `max_bps`, false for a zero price.
+    #[must_use]
+    pub fn is_within_bps(&self, other: &Price, max_bps: u32) -> bool {
+        self.deviation_bps(other)
+            .is_ok_and(|deviation| deviation <= max_bps)
     }
 }
 
+#[cfg(test)]
 mod tests {
     #[allow(unused_imports)]
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn it_converts_price_in_float() {
@@ -68,4 +265,184 @@ mod tests {
         let price = Price::try_from(100).unwrap();
         assert_eq!(price.0, 10000);
     }
+
+    #[test]
+    fn it_parses_price_from_decimal_string() {
+        assert_eq!("1.5".parse::<Price>().unwrap().0, 150);
+        assert_eq!("1.52".parse::<Price>().unwrap().0, 152);
+        assert_eq!("2".parse::<Price>().unwrap().0, 200);
+        assert_eq!("0.010".parse::<Price>().unwrap().0, 1);
+    }
+
+    #[test]
+    fn it_returns_err_if_price_string_is_invalid() {
+        assert!("".parse::<Price>().is_err());
+        assert!("-1.5".parse::<Price>().is_err());
+        assert_eq!(
+            "1.525".parse::<Price>(),
+            Err(Error::LpPool(LpPoolError::PriceConversionFailure {
+                converted_from: ConvertedFrom::new("1.525")
+            }))
+        );
+        assert!("1.5sol".parse::<Price>().is_err());
+        assert!("184467440737095517".parse::<Price>().is_err());
+    }
+
+    #[test]
+    fn it_builds_same_price_from_every_constructor() {
+        let expected = Price::try_from_points(150).unwrap();
+        assert_eq!("1.5".parse::<Price>(), Ok(expected));
+        assert_eq!("1.50".parse::<Price>(), Ok(expected));
+        assert_eq!(Price::try_from(1.5f32), Ok(expected));
+        assert_eq!(Price::from_ratio(3, 2), Ok(expected));
+
+        let expected = Price::try_from_points(2 * Price::SCALE).unwrap();
+        assert_eq!(Price::try_from(2u64), Ok(expected));
+        assert_eq!("2".parse::<Price>(), Ok(expected));
+        assert_eq!(Price::try_from(2.0f32), Ok(expected));
+        assert_eq!(Price::from_raw_points(200), expected);
+
+        let large = u64::from(u32::MAX) + 1;
+        assert_eq!(
+            Price::try_from(large as f32),
+            Price::try_from(large),
+            "floats past u32 points convert like integers"
+        );
+    }
+
+    #[test]
+    fn it_rejects_zero_points() {
+        assert_eq!(
+            Price::try_from_points(0),
+            Err(Error::LpPool(LpPoolError::PriceIncorrect(
+                Price::from_raw_points(0)
+            )))
+        );
+        assert_eq!(u64::from(Price::try_from_points(1).unwrap()), 1);
+    }
+
+    #[test]
+    fn it_creates_price_from_staked_ratio() {
+        assert_eq!(
+            Price::from_ratio(6_950_000, 5_500_000).unwrap(),
+            Price::from_raw_points(126)
+        );
+        assert_eq!(Price::from_ratio(3, 2).unwrap(), "1.5".parse().unwrap());
+    }
+
+    #[test]
+    fn it_returns_err_if_ratio_supply_is_zero_or_price_overflows() {
+        assert_eq!(
+            Price::from_ratio(100, 0),
+            Err(Error::LpPool(LpPoolError::PriceRatioIncorrect {
+                total_staked: 100,
+                supply: 0
+            }))
+        );
+        assert!(matches!(
+            Price::from_ratio(u64::MAX, 1),
+            Err(Error::LpPool(LpPoolError::ArithmeticOverflow { .. }))
+        ));
+    }
+
+    #[test]
+    fn it_measures_deviation_against_own_price() {
+        let price = Price::from_raw_points(10_000);
+
+        assert_eq!(price.deviation_bps(&price), Ok(0));
+        assert_eq!(
+            price.deviation_bps(&Price::from_raw_points(10_100)),
+            Ok(100)
+        );
+        assert_eq!(price.deviation_bps(&Price::from_raw_points(9_900)), Ok(100));
+        assert_eq!(
+            Price::from_raw_points(9_900).deviation_bps(&price),
+            Ok(102),
+            "1.01% of the lower price, rounded up"
+        );
+        assert!(price.is_within_bps(&Price::from_raw_points(10_100), 100));
+        assert!(!price.is_within_bps(&Price::from_raw_points(10_101), 100));
+        assert!(Price::from_raw_points(100) < Price::from_raw_points(101));
+    }
+
+    #[test]
+    fn it_rejects_zero_price_in_deviation() {
+        let zero = Price::from_raw_points(0);
+        let price = Price::from_raw_points(100);
+
+        assert_eq!(
+            price.deviation_bps(&zero),
+            Err(Error::LpPool(LpPoolError::PriceIncorrect(zero)))
+        );
+        assert_eq!(
+            zero.deviation_bps(&price),
+            Err(Error::LpPool(LpPoolError::PriceIncorrect(zero)))
+        );
+        assert!(!zero.is_within_bps(&price, u32::MAX));
+    }
+
+    #[test]
+    fn it_returns_err_from_conversions_instead_of_panicking() {
+        let zero = Price::from_raw_points(0);
+        let price = Price::from_raw_points(150);
+
+        assert_eq!(price.mul_by_price(1_001), Ok(1_501));
+        assert_eq!(price.mul_by_price_ceil(1_001), Ok(1_502));
+        assert_eq!(price.div_by_price(1_001), Ok(667));
+        assert_eq!(price.div_by_price_ceil(1_001), Ok(668));
+        assert_eq!(
+            zero.div_by_price(1_001),
+            Err(Error::LpPool(LpPoolError::PriceIncorrect(zero)))
+        );
+        assert_eq!(
+            zero.div_by_price_ceil(1_001),
+            Err(Error::LpPool(LpPoolError::PriceIncorrect(zero)))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "strict-math")]
+    fn it_returns_overflow_from_conversions() {
+        let overflow = |op, lhs, rhs| {
+            Err(Error::LpPool(LpPoolError::ArithmeticOverflow {
+                op,
+                lhs,
+                rhs,
+            }))
+        };
+
+        assert_eq!(
+            Price::from_raw_points(101).mul_by_price(u64::MAX),
+            overflow("lamports * price / SCALE", u64::MAX, 101)
+        );
+        assert_eq!(
+            Price::from_raw_points(101).mul_by_price_ceil(u64::MAX),
+            overflow("lamports * price / SCALE", u64::MAX, 101)
+        );
+        assert_eq!(
+            Price::from_raw_points(99).div_by_price(u64::MAX),
+            overflow("lamports * SCALE / price", u64::MAX, 99)
+        );
+        assert_eq!(
+            Price::from_raw_points(99).div_by_price_ceil(u64::MAX),
+            overflow("lamports * SCALE / price", u64::MAX, 99)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn it_recovers_total_staked_from_ratio_within_rounding(
+            total_staked in any::<u64>(),
+            supply in 1..=u64::MAX,
+        ) {
+            let Ok(price) = Price::from_ratio(total_staked, supply) else {
+                return Err(TestCaseError::reject("price does not fit u64"));
+            };
+            let recovered = u128::from(price.0) * u128::from(supply) / u128::from(Price::SCALE);
+            let total_staked = u128::from(total_staked);
+
+            prop_assert!(recovered <= total_staked);
+            prop_assert!(total_staked - recovered <= u128::from(supply) / u128::from(Price::SCALE) + 1);
+        }
+    }
 }
@@ -1,30 +1,68 @@
-use std::fmt::Display;
-use std::ops::{Add, AddAssign, Div, Sub, SubAssign};
-use std::u64;
+use core::fmt::Display;
+use core::ops::{Add, AddAssign, Div, Neg, Sub, SubAssign};
+use core::str::FromStr;
 
-use super::fee::Fee;
+use super::fee::{Fee, Rounding};
 use super::price::Price;
-use crate::error::Result;
+use crate::calc::{parse_decimal, proportional, proportional_ceil};
+use crate::error::{Error, Result};
+use crate::format::{write_lamports_grouped, write_sol, Separator};
+use crate::lp_pool::consts::DEFAULT_DECIMALS;
+use crate::lp_pool::error::{ConvertedFrom, Error as LpPoolError};
+
+pub use crate::lp_pool::consts::LAMPORTS_PER_SOL;
+
+/// Parses `<amount><unit>` as a decimal number of whole tokens, anything
+/// without the unit suffix is read as a plain lamport count.
+fn parse_lamports(amount: &str, unit: &str) -> Result<u64> {
+    let trimmed = amount.trim();
+    let lamports = match trimmed
+        .len()
+        .checked_sub(unit.len())
+        .filter(|&split| trimmed.is_char_boundary(split))
+        .map(|split| trimmed.split_at(split))
+    {
+        Some((whole_tokens, suffix)) if suffix.eq_ignore_ascii_case(unit) => {
+            parse_decimal(whole_tokens, DEFAULT_DECIMALS)
+        }
+        _ => trimmed.parse().ok(),
+    };
+
+    lamports.ok_or_else(|| {
+        Error::LpPool(LpPoolError::AmountConversionFailure {
+            converted_from: ConvertedFrom::new(amount),
+        })
+    })
+}
 
+/// Lamports of SOL. Arithmetic operators panic on overflow, the `checked_` methods return
+/// `None` instead.
 #[allow(clippy::module_name_repetitions)]
-#[derive(PartialEq, PartialOrd, Debug, Copy, Clone)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct TokenAmount(u64);
+
 impl Add for TokenAmount {
     type Output = TokenAmount;
 
     fn add(self, rhs: Self) -> Self::Output {
-        let Some(resultult) = self.0.checked_add(rhs.0) else {
+        let Some(result) = self.checked_add(rhs) else {
             panic!("Overflow in TokenAmount::add");
         };
-        TokenAmount(resultult)
+        result
     }
 }
+
 impl AddAssign for TokenAmount {
     fn add_assign(&mut self, rhs: Self) {
-        let Some(result) = self.0.checked_add(rhs.0) else {
+        let Some(result) = self.checked_add(rhs) else {
             panic!("Overflow in TokenAmount::add_assign");
         };
-        self.0 = result;
+        *self = result;
     }
 }
 
@@ -32,19 +70,19 @@ impl Sub for TokenAmount {
     type Output = TokenAmount;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        let Some(result) = self.0.checked_sub(rhs.0) else {
+        let Some(result) = self.checked_sub(rhs) else {
             panic!("Overflow in TokenAmount::sub");
         };
-        TokenAmount(result)
+        result
     }
 }
 
 impl SubAssign for TokenAmount {
     fn sub_assign(&mut self, rhs: Self) {
-        let Some(result) = self.0.checked_sub(rhs.0) else {
+        let Some(result) = self.checked_sub(rhs) else {
             panic!("Overflow in TokenAmount::sub_assign");
         };
-        self.0 = result;
+        *self = result;
     }
 }
 
@@ -59,12 +97,35 @@ impl Div for TokenAmount {
     }
 }
 
+/// Splits the amount into `rhs` equal parts, rounded down.
+impl Div<u64> for TokenAmount {
+    type Output = TokenAmount;
+
+    fn div(self, rhs: u64) -> Self::Output {
+        let Some(result) = self.checked_div(rhs) else {
+            panic!("Overflow in TokenAmount::div");
+        };
+        result
+    }
+}
+
+/// `{:#}` groups the lamports in thousands.
 impl Display for TokenAmount {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if f.alternate() {
+            return write_lamports_grouped(f, self.0, Separator::Underscore);
+        }
         write!(f, "{}", self.0)
     }
 }
 
+impl FromStr for TokenAmount {
+    type Err = Error;
+    fn from_str(amount: &str) -> Result<Self> {
+        parse_lamports(amount, "sol").map(TokenAmount)
+    }
+}
+
 impl From<TokenAmount> for u64 {
     fn from(val: TokenAmount) -> Self {
         val.0
@@ -72,42 +133,148 @@ impl From<TokenAmount> for u64 {
 }
 
 impl TokenAmount {
-    pub fn from_lamports(amount: u64) -> Self {
-        TokenAmount(amount)
+    #[must_use]
+    pub const fn from_lamports(lamports: u64) -> Self {
+        TokenAmount(lamports)
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(TokenAmount)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(TokenAmount)
+    }
+
+    pub fn checked_mul(self, rhs: u64) -> Option<Self> {
+        self.0.checked_mul(rhs).map(TokenAmount)
+    }
+
+    /// `None` when `rhs` is zero.
+    pub fn checked_div(self, rhs: u64) -> Option<Self> {
+        self.0.checked_div(rhs).map(TokenAmount)
+    }
+
+    /// Displays the amount in whole tokens followed by the unit, like `1.5 sol`.
+    #[must_use]
+    pub fn whole_tokens(self) -> WholeTokens {
+        WholeTokens {
+            lamports: self.0,
+            unit: "sol",
+        }
+    }
+
+    /// Value of `staked_tokens` rounded down, for amounts the user receives.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArithmeticOverflow` when the value does not fit `u64`.
+    pub fn from_staked_tokens(staked_tokens: StakedTokenAmount, price: Price) -> Result<Self> {
+        price.mul_by_price(staked_tokens.into()).map(Self)
     }
 
-    pub fn from_staked_tokens(staked_tokens: StakedTokenAmount, price: Price) -> Self {
-        TokenAmount(price.mul_by_price(staked_tokens.into()))
+    /// Value of `staked_tokens` rounded up, for amounts the user must provide.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArithmeticOverflow` when the value does not fit `u64`.
+    pub fn from_staked_tokens_ceil(staked_tokens: StakedTokenAmount, price: Price) -> Result<Self> {
+        price.mul_by_price_ceil(staked_tokens.into()).map(Self)
+    }
+
+    /// `a - b`, negative when `b` is larger.
+    #[must_use]
+    pub fn signed_diff(a: Self, b: Self) -> SignedTokenAmount {
+        SignedTokenAmount(i128::from(a.0) - i128::from(b.0))
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct LpTokenAmount(u64);
+
 impl Add for LpTokenAmount {
     type Output = LpTokenAmount;
 
     fn add(self, rhs: Self) -> Self::Output {
-        let Some(result) = self.0.checked_add(rhs.0) else {
+        let Some(result) = self.checked_add(rhs) else {
             panic!("Overflow in LpTokenAmount::add");
         };
-        LpTokenAmount(result)
+        result
     }
 }
+
 impl AddAssign for LpTokenAmount {
     fn add_assign(&mut self, rhs: Self) {
-        let Some(result) = self.0.checked_add(rhs.0) else {
+        let Some(result) = self.checked_add(rhs) else {
             panic!("Overflow in LpTokenAmount::add_assign");
         };
-        self.0 = result;
+        *self = result;
+    }
+}
+
+impl Sub for LpTokenAmount {
+    type Output = LpTokenAmount;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        let Some(result) = self.checked_sub(rhs) else {
+            panic!("Overflow in LpTokenAmount::sub");
+        };
+        result
     }
 }
 
 impl SubAssign for LpTokenAmount {
     fn sub_assign(&mut self, rhs: Self) {
-        let Some(result) = self.0.checked_sub(rhs.0) else {
+        let Some(result) = self.checked_sub(rhs) else {
             panic!("Overflow in LpTokenAmount::sub_assign");
         };
-        self.0 = result;
+        *self = result;
+    }
+}
+
+impl Div for LpTokenAmount {
+    type Output = LpTokenAmount;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        let Some(result) = self.0.checked_div(rhs.0) else {
+            panic!("Overflow in LpTokenAmount::div");
+        };
+        LpTokenAmount(result)
+    }
+}
+
+/// Splits the amount into `rhs` equal parts, rounded down.
+impl Div<u64> for LpTokenAmount {
+    type Output = LpTokenAmount;
+
+    fn div(self, rhs: u64) -> Self::Output {
+        let Some(result) = self.checked_div(rhs) else {
+            panic!("Overflow in LpTokenAmount::div");
+        };
+        result
+    }
+}
+
+/// `{:#}` groups the lamports in thousands.
+impl Display for LpTokenAmount {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if f.alternate() {
+            return write_lamports_grouped(f, self.0, Separator::Underscore);
+        }
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for LpTokenAmount {
+    type Err = Error;
+    fn from_str(amount: &str) -> Result<Self> {
+        parse_lamports(amount, "lp").map(LpTokenAmount)
     }
 }
 
@@ -118,62 +285,150 @@ impl From<LpTokenAmount> for u64 {
 }
 
 impl LpTokenAmount {
-    pub fn from_tokens_with_fee(amount: TokenAmount, fee: Fee) -> Result<Self> {
-        Ok(LpTokenAmount(fee.apply(amount.into())?))
+    #[must_use]
+    pub const fn from_lamports(lamports: u64) -> Self {
+        LpTokenAmount(lamports)
     }
 
-    pub fn from_tokens(amount: TokenAmount) -> Self {
-        LpTokenAmount(amount.into())
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(LpTokenAmount)
     }
 
-    pub fn from_lamports(lamports: u64) -> Self {
-        LpTokenAmount(lamports)
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(LpTokenAmount)
+    }
+
+    pub fn checked_mul(self, rhs: u64) -> Option<Self> {
+        self.0.checked_mul(rhs).map(LpTokenAmount)
+    }
+
+    /// `None` when `rhs` is zero.
+    pub fn checked_div(self, rhs: u64) -> Option<Self> {
+        self.0.checked_div(rhs).map(LpTokenAmount)
+    }
+
+    /// Displays the amount in whole tokens followed by the unit, like `1.5 lp`.
+    #[must_use]
+    pub fn whole_tokens(self) -> WholeTokens {
+        WholeTokens {
+            lamports: self.0,
+            unit: "lp",
+        }
+    }
+
+    /// LP tokens minted 1:1 for `amount` less `fee`, as on a pool's first deposit.
+    /// `Rounding::Up` takes the fee rounded down, as `Fee::apply` does, `Rounding::Down`
+    /// takes it rounded up.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` for a fee above 100%.
+    pub fn from_tokens_with_fee(amount: TokenAmount, fee: Fee, rounding: Rounding) -> Result<Self> {
+        fee.check()?;
+        let kept = u64::from(Fee::MAX_CENTIBPS - fee.centibps);
+        let lamports = match rounding {
+            Rounding::Up => proportional_ceil(amount.into(), kept, Fee::MAX_CENTIBPS.into())?,
+            Rounding::Down => proportional(amount.into(), kept, Fee::MAX_CENTIBPS.into())?,
+        };
+        Ok(LpTokenAmount(lamports))
+    }
+
+    /// `from_tokens_with_fee` without a fee, which cannot fail.
+    #[must_use]
+    pub fn from_tokens(amount: TokenAmount) -> Self {
+        LpTokenAmount(amount.into())
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(clippy::module_name_repetitions)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct StakedTokenAmount(u64);
 
 impl Add for StakedTokenAmount {
     type Output = StakedTokenAmount;
 
     fn add(self, rhs: Self) -> Self::Output {
-        let Some(result) = self.0.checked_add(rhs.0) else {
+        let Some(result) = self.checked_add(rhs) else {
             panic!("Overflow in StakedTokenAmount::add");
         };
-        StakedTokenAmount(result)
+        result
     }
 }
 
 impl AddAssign for StakedTokenAmount {
     fn add_assign(&mut self, rhs: Self) {
-        let Some(result) = self.0.checked_add(rhs.0) else {
+        let Some(result) = self.checked_add(rhs) else {
             panic!("Overflow in StakedTokenAmount::add_assign");
         };
-        self.0 = result;
+        *self = result;
+    }
+}
+
+impl Sub for StakedTokenAmount {
+    type Output = StakedTokenAmount;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        let Some(result) = self.checked_sub(rhs) else {
+            panic!("Overflow in StakedTokenAmount::sub");
+        };
+        result
     }
 }
 
 impl SubAssign for StakedTokenAmount {
     fn sub_assign(&mut self, rhs: Self) {
-        let Some(result) = self.0.checked_sub(rhs.0) else {
+        let Some(result) = self.checked_sub(rhs) else {
             panic!("Overflow in StakedTokenAmount::sub_assign");
         };
-        self.0 = result;
+        *self = result;
     }
 }
 
-impl Sub for StakedTokenAmount {
+impl Div for StakedTokenAmount {
     type Output = StakedTokenAmount;
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        let Some(result) = self.0.checked_sub(rhs.0) else {
-            panic!("Overflow in StakedTokenAmount::sub");
+    fn div(self, rhs: Self) -> Self::Output {
+        let Some(result) = self.0.checked_div(rhs.0) else {
+            panic!("Overflow in StakedTokenAmount::div");
         };
         StakedTokenAmount(result)
     }
 }
 
+/// Splits the amount into `rhs` equal parts, rounded down.
+impl Div<u64> for StakedTokenAmount {
+    type Output = StakedTokenAmount;
+
+    fn div(self, rhs: u64) -> Self::Output {
+        let Some(result) = self.checked_div(rhs) else {
+            panic!("Overflow in StakedTokenAmount::div");
+        };
+        result
+    }
+}
+
+/// `{:#}` groups the lamports in thousands.
+impl Display for StakedTokenAmount {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if f.alternate() {
+            return write_lamports_grouped(f, self.0, Separator::Underscore);
+        }
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for StakedTokenAmount {
+    type Err = Error;
+    fn from_str(amount: &str) -> Result<Self> {
+        parse_lamports(amount, "msol").map(StakedTokenAmount)
+    }
+}
+
 impl From<StakedTokenAmount> for u64 {
     fn from(val: StakedTokenAmount) -> Self {
         val.0
@@ -181,18 +436,171 @@ impl From<StakedTokenAmount> for u64 {
 }
 
 impl StakedTokenAmount {
-    pub fn from_tokens(amount: TokenAmount, price: Price) -> Self {
-        StakedTokenAmount(price.div_by_price(amount.into()))
+    #[must_use]
+    pub const fn from_lamports(lamports: u64) -> Self {
+        StakedTokenAmount(lamports)
     }
 
-    pub fn from_lamports(lamports: u64) -> Self {
-        StakedTokenAmount(lamports)
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(StakedTokenAmount)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(StakedTokenAmount)
+    }
+
+    pub fn checked_mul(self, rhs: u64) -> Option<Self> {
+        self.0.checked_mul(rhs).map(StakedTokenAmount)
+    }
+
+    /// `None` when `rhs` is zero.
+    pub fn checked_div(self, rhs: u64) -> Option<Self> {
+        self.0.checked_div(rhs).map(StakedTokenAmount)
+    }
+
+    /// Displays the amount in whole tokens followed by the unit, like `1.5 msol`.
+    #[must_use]
+    pub fn whole_tokens(self) -> WholeTokens {
+        WholeTokens {
+            lamports: self.0,
+            unit: "msol",
+        }
+    }
+
+    /// Staked tokens worth `amount` rounded down, for amounts the user receives.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `Price::div_by_price`.
+    pub fn from_tokens(amount: TokenAmount, price: Price) -> Result<Self> {
+        price.div_by_price(amount.into()).map(Self)
+    }
+
+    /// Staked tokens worth `amount` rounded up, for amounts the user must provide.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `Price::div_by_price_ceil`.
+    pub fn from_tokens_ceil(amount: TokenAmount, price: Price) -> Result<Self> {
+        price.div_by_price_ceil(amount.into()).map(Self)
+    }
+}
+
+/// `whole_tokens` of an amount.
+#[derive(Debug, Clone, Copy)]
+pub struct WholeTokens {
+    lamports: u64,
+    unit: &'static str,
+}
+
+impl Display for WholeTokens {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write_sol(f, self.lamports)?;
+        write!(f, " {}", self.unit)
+    }
+}
+
+/// Signed difference of token amounts, for profit and loss and reconciliation.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct SignedTokenAmount(i128);
+
+impl SignedTokenAmount {
+    pub const ZERO: Self = SignedTokenAmount(0);
+
+    #[must_use]
+    pub const fn from_lamports(lamports: i128) -> Self {
+        SignedTokenAmount(lamports)
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(SignedTokenAmount)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(SignedTokenAmount)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        self.0.checked_neg().map(SignedTokenAmount)
+    }
+
+    #[must_use]
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+}
+
+impl Add for SignedTokenAmount {
+    type Output = SignedTokenAmount;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        let Some(result) = self.checked_add(rhs) else {
+            panic!("Overflow in SignedTokenAmount::add");
+        };
+        result
     }
 }
 
+impl Sub for SignedTokenAmount {
+    type Output = SignedTokenAmount;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        let Some(result) = self.checked_sub(rhs) else {
+            panic!("Overflow in SignedTokenAmount::sub");
+        };
+        result
+    }
+}
+
+impl Neg for SignedTokenAmount {
+    type Output = SignedTokenAmount;
+
+    fn neg(self) -> Self::Output {
+        let Some(result) = self.checked_neg() else {
+            panic!("Overflow in SignedTokenAmount::neg");
+        };
+        result
+    }
+}
+
+impl Display for SignedTokenAmount {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:+}", self.0)
+    }
+}
+
+impl From<TokenAmount> for SignedTokenAmount {
+    fn from(val: TokenAmount) -> Self {
+        SignedTokenAmount(i128::from(val.0))
+    }
+}
+
+impl From<SignedTokenAmount> for i128 {
+    fn from(val: SignedTokenAmount) -> Self {
+        val.0
+    }
+}
+
+impl TryFrom<SignedTokenAmount> for TokenAmount {
+    type Error = Error;
+
+    fn try_from(amount: SignedTokenAmount) -> Result<Self> {
+        u64::try_from(amount.0)
+            .map(TokenAmount)
+            .map_err(|_| Error::LpPool(LpPoolError::AmountOutOfRange(amount)))
+    }
+}
+
+#[cfg(test)]
 mod tests {
     #[allow(unused_imports)]
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn it_creates_token_amount_from_lamports() {
@@ -202,35 +610,277 @@ mod tests {
 
     #[test]
     fn it_creates_token_from_staked_tokens() {
-        let token_amount =
-            TokenAmount::from_staked_tokens(StakedTokenAmount(10000), Price::from_points(2));
+        let token_amount = TokenAmount::from_staked_tokens(
+            StakedTokenAmount::from_lamports(10000),
+            Price::from_raw_points(200),
+        )
+        .unwrap();
         assert_eq!(token_amount.0, 20000);
     }
 
     #[test]
     fn it_creates_lp_token_from_tokens() {
-        let lp_token_amount = LpTokenAmount::from_tokens(TokenAmount(10000));
+        let lp_token_amount = LpTokenAmount::from_tokens(TokenAmount::from_lamports(10000));
         assert_eq!(lp_token_amount.0, 10000);
     }
 
     #[test]
     fn it_creates_lp_token_from_tokens_with_fee() {
-        let lp_token_amount =
-            LpTokenAmount::from_tokens_with_fee(TokenAmount(10000), Fee::from_basis_points(100))
-                .unwrap();
+        let lp_token_amount = LpTokenAmount::from_tokens_with_fee(
+            TokenAmount::from_lamports(10000),
+            Fee::from_basis_points(100),
+            Rounding::Down,
+        )
+        .unwrap();
         assert_eq!(lp_token_amount.0, 9900);
     }
 
+    #[test]
+    fn it_rounds_fee_on_lp_tokens_as_asked() {
+        let amount = TokenAmount::from_lamports(10_001);
+        let fee = Fee::from_basis_points(100);
+        let mint = |rounding| LpTokenAmount::from_tokens_with_fee(amount, fee, rounding);
+
+        // the exact mint is 9_900.99
+        assert_eq!(
+            mint(Rounding::Down),
+            Ok(LpTokenAmount::from_lamports(9_900))
+        );
+        assert_eq!(mint(Rounding::Up), Ok(LpTokenAmount::from_lamports(9_901)));
+        assert_eq!(mint(Rounding::Up).map(u64::from), fee.apply(amount.into()));
+        assert_eq!(
+            LpTokenAmount::from_tokens_with_fee(amount, Fee::ZERO, Rounding::Down),
+            Ok(LpTokenAmount::from_tokens(amount))
+        );
+
+        let over = Fee::from_centibps(Fee::MAX_CENTIBPS + 1);
+        assert_eq!(
+            LpTokenAmount::from_tokens_with_fee(amount, over, Rounding::Down),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(over)))
+        );
+    }
+
     #[test]
     fn it_creates_staked_token_from_tokens() {
-        let staked_token_amount =
-            StakedTokenAmount::from_tokens(TokenAmount(10000), Price::from_points(2));
+        let staked_token_amount = StakedTokenAmount::from_tokens(
+            TokenAmount::from_lamports(10000),
+            Price::from_raw_points(200),
+        )
+        .unwrap();
         assert_eq!(staked_token_amount.0, 5000);
     }
 
+    #[test]
+    fn it_returns_none_on_checked_overflow() {
+        assert_eq!(
+            TokenAmount::from_lamports(u64::MAX).checked_add(TokenAmount::from_lamports(1)),
+            None
+        );
+        assert_eq!(
+            TokenAmount::from_lamports(1).checked_sub(TokenAmount::from_lamports(2)),
+            None
+        );
+        assert_eq!(
+            LpTokenAmount::from_lamports(1).checked_sub(LpTokenAmount::from_lamports(2)),
+            None
+        );
+        assert_eq!(
+            StakedTokenAmount::from_lamports(u64::MAX)
+                .checked_add(StakedTokenAmount::from_lamports(1)),
+            None
+        );
+        assert_eq!(
+            StakedTokenAmount::from_lamports(3).checked_sub(StakedTokenAmount::from_lamports(1)),
+            Some(StakedTokenAmount::from_lamports(2))
+        );
+    }
+
+    #[test]
+    fn it_computes_remaining_lp_and_staked_tokens() {
+        let supply = LpTokenAmount::from_lamports(1_000);
+        let withdrawn = LpTokenAmount::from_lamports(400);
+
+        assert_eq!(supply - withdrawn, LpTokenAmount::from_lamports(600));
+        assert!(withdrawn < supply / 2);
+        assert!(supply - withdrawn > supply / 2);
+        assert_eq!(supply / 3, LpTokenAmount::from_lamports(333));
+        assert_eq!(supply / withdrawn, LpTokenAmount::from_lamports(2));
+        assert_eq!(
+            supply.checked_mul(2),
+            Some(LpTokenAmount::from_lamports(2_000))
+        );
+        assert_eq!(supply.checked_div(0), None);
+        assert_eq!(withdrawn.max(supply), supply);
+
+        let staked = StakedTokenAmount::from_lamports(7);
+        assert_eq!(staked / 2, StakedTokenAmount::from_lamports(3));
+        assert!(StakedTokenAmount::from_lamports(3) < staked);
+        assert_eq!(
+            StakedTokenAmount::from_lamports(u64::MAX).checked_mul(2),
+            None
+        );
+        assert_eq!(staked.to_string(), "7");
+        assert_eq!(format!("{:#}", supply), "1_000");
+    }
+
+    #[test]
+    #[should_panic(expected = "Overflow in LpTokenAmount::sub")]
+    fn it_panics_on_lp_token_underflow() {
+        let _ = LpTokenAmount::from_lamports(1) - LpTokenAmount::from_lamports(2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Overflow in StakedTokenAmount::div")]
+    fn it_panics_on_staked_token_division_by_zero() {
+        let _ = StakedTokenAmount::from_lamports(1) / 0;
+    }
+
+    #[test]
+    fn it_parses_amounts_with_unit_suffix() {
+        assert_eq!(
+            "100sol".parse::<TokenAmount>().unwrap(),
+            TokenAmount::from_lamports(100 * LAMPORTS_PER_SOL)
+        );
+        assert_eq!(
+            "0.5SOL".parse::<TokenAmount>().unwrap(),
+            TokenAmount::from_lamports(LAMPORTS_PER_SOL / 2)
+        );
+        assert_eq!(
+            "6msol".parse::<StakedTokenAmount>().unwrap(),
+            StakedTokenAmount::from_lamports(6 * LAMPORTS_PER_SOL)
+        );
+        assert_eq!(
+            "1.000000001lp".parse::<LpTokenAmount>().unwrap(),
+            LpTokenAmount::from_lamports(LAMPORTS_PER_SOL + 1)
+        );
+        assert_eq!(
+            "2000".parse::<LpTokenAmount>().unwrap(),
+            LpTokenAmount::from_lamports(2000)
+        );
+    }
+
+    #[test]
+    fn it_returns_err_if_amount_string_is_invalid() {
+        assert_eq!(
+            "6msol".parse::<TokenAmount>(),
+            Err(Error::LpPool(LpPoolError::AmountConversionFailure {
+                converted_from: ConvertedFrom::new("6msol")
+            }))
+        );
+        assert!("0.0000000001sol".parse::<TokenAmount>().is_err());
+        assert!("1.5".parse::<TokenAmount>().is_err());
+        assert!("sol".parse::<TokenAmount>().is_err());
+        assert!("ł".parse::<StakedTokenAmount>().is_err());
+    }
+
     #[test]
     fn it_creates_staked_token_from_lamports() {
         let staked_token_amount = StakedTokenAmount::from_lamports(10000);
         assert_eq!(staked_token_amount.0, 10000);
     }
+
+    #[test]
+    fn it_round_trips_signed_token_amount() {
+        let amount = TokenAmount::from_lamports(u64::MAX);
+
+        assert_eq!(
+            TokenAmount::try_from(SignedTokenAmount::from(amount)),
+            Ok(amount)
+        );
+        assert_eq!(
+            TokenAmount::signed_diff(
+                TokenAmount::from_lamports(3),
+                TokenAmount::from_lamports(10)
+            ),
+            SignedTokenAmount(-7)
+        );
+        assert_eq!(
+            TokenAmount::try_from(SignedTokenAmount(-7)),
+            Err(Error::LpPool(LpPoolError::AmountOutOfRange(
+                SignedTokenAmount(-7)
+            )))
+        );
+        let too_large = SignedTokenAmount(i128::from(u64::MAX) + 1);
+        assert_eq!(
+            TokenAmount::try_from(too_large),
+            Err(Error::LpPool(LpPoolError::AmountOutOfRange(too_large)))
+        );
+    }
+
+    #[test]
+    fn it_checks_signed_token_amount_overflow() {
+        let max = SignedTokenAmount(i128::MAX);
+        let min = SignedTokenAmount(i128::MIN);
+
+        assert_eq!(max.checked_add(SignedTokenAmount(1)), None);
+        assert_eq!(min.checked_sub(SignedTokenAmount(1)), None);
+        assert_eq!(min.checked_neg(), None);
+        assert_eq!(-SignedTokenAmount(5), SignedTokenAmount(-5));
+        assert_eq!(
+            SignedTokenAmount(5) - SignedTokenAmount(7),
+            SignedTokenAmount(-2)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Overflow in SignedTokenAmount::neg")]
+    fn it_panics_on_negating_min_signed_token_amount() {
+        let _ = -SignedTokenAmount(i128::MIN);
+    }
+
+    #[test]
+    fn it_displays_amounts_in_whole_tokens() {
+        assert_eq!(
+            TokenAmount::from_lamports(1_500_000_000)
+                .whole_tokens()
+                .to_string(),
+            "1.5 sol"
+        );
+        assert_eq!(
+            StakedTokenAmount::from_lamports(1)
+                .whole_tokens()
+                .to_string(),
+            "0.000000001 msol"
+        );
+        assert_eq!(
+            LpTokenAmount::from_lamports(2 * LAMPORTS_PER_SOL)
+                .whole_tokens()
+                .to_string(),
+            "2 lp"
+        );
+    }
+
+    #[test]
+    fn it_displays_signed_token_amount_with_sign() {
+        assert_eq!(SignedTokenAmount(5).to_string(), "+5");
+        assert_eq!(SignedTokenAmount(-5).to_string(), "-5");
+        assert_eq!(SignedTokenAmount(0).to_string(), "+0");
+    }
+
+    proptest! {
+        #[test]
+        fn it_rounds_conversions_down_and_up_within_one_lamport(
+            lamports in 0..=u64::MAX / 10_000,
+            points in 1..=10_000u64,
+        ) {
+            let price = Price::from_raw_points(points);
+            let exact = u128::from(lamports) * u128::from(points);
+            let scale = u128::from(Price::SCALE);
+
+            let staked = StakedTokenAmount::from_lamports(lamports);
+            let floor = u64::from(TokenAmount::from_staked_tokens(staked, price).unwrap());
+            let ceil = u64::from(TokenAmount::from_staked_tokens_ceil(staked, price).unwrap());
+            prop_assert!(floor <= ceil && ceil - floor <= 1);
+            prop_assert!(u128::from(floor) * scale <= exact);
+            prop_assert!(u128::from(ceil) * scale >= exact);
+
+            let tokens = TokenAmount::from_lamports(lamports);
+            let exact = u128::from(lamports) * scale;
+            let floor = u64::from(StakedTokenAmount::from_tokens(tokens, price).unwrap());
+            let ceil = u64::from(StakedTokenAmount::from_tokens_ceil(tokens, price).unwrap());
+            prop_assert!(floor <= ceil && ceil - floor <= 1);
+            prop_assert!(u128::from(floor) * u128::from(points) <= exact);
+            prop_assert!(u128::from(ceil) * u128::from(points) >= exact);
+        }
+    }
 }
@@ -1,30 +1,261 @@
-use super::data::{fee::Fee, price::Price, token::TokenAmount};
+use core::fmt::{Display, Write};
 
-#[derive(Debug)]
+use super::data::{
+    fee::Fee,
+    price::Price,
+    token::{LpTokenAmount, SignedTokenAmount, StakedTokenAmount, TokenAmount},
+};
+
+/// Input of a failed conversion, kept inline so errors stay `Copy` and
+/// allocation free. Inputs longer than the buffer are truncated.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ConvertedFrom {
+    bytes: [u8; ConvertedFrom::CAPACITY],
+    len: u8,
+}
+
+impl ConvertedFrom {
+    const CAPACITY: usize = 32;
+
+    pub fn new(value: impl Display) -> Self {
+        let mut converted_from = Self {
+            bytes: [0; Self::CAPACITY],
+            len: 0,
+        };
+        // Writing into the buffer never fails, overflowing input is truncated
+        let _ = write!(converted_from, "{value}");
+        converted_from
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..usize::from(self.len)]).unwrap_or_default()
+    }
+}
+
+impl Write for ConvertedFrom {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let len = usize::from(self.len);
+        let mut take = s.len().min(Self::CAPACITY - len);
+        while !s.is_char_boundary(take) {
+            take -= 1;
+        }
+        self.bytes[len..len + take].copy_from_slice(&s.as_bytes()[..take]);
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            self.len += take as u8;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ConvertedFrom {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl core::fmt::Debug for ConvertedFrom {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
+    #[error("IncorrectLiquidityTarget({0})")]
     LiquidityTargetIncorrect(TokenAmount),
+    #[error("IncorrectPrice({0})")]
     PriceIncorrect(Price),
-    PriceConversionFailure { converted_from: String },
-    BasisPointsOverflow(u32),
+    #[error("IncorrectMaxSwapSize({0})")]
+    MaxSwapSizeIncorrect(StakedTokenAmount),
+    #[error("IncorrectTokenCap({0})")]
+    TokenCapIncorrect(TokenAmount),
+    #[error("PriceConversionError(converted_from: {converted_from})")]
+    PriceConversionFailure { converted_from: ConvertedFrom },
+    #[error("FeeConversionError(converted_from: {converted_from})")]
+    FeeConversionFailure { converted_from: ConvertedFrom },
+    #[error("AmountConversionError(converted_from: {converted_from})")]
+    AmountConversionFailure { converted_from: ConvertedFrom },
+    #[error("BasisPointsOverflow({0})")]
+    BasisPointsOverflow(Fee),
+    #[error("PriceRatioIncorrect(total_staked: {total_staked}, supply: {supply})")]
+    PriceRatioIncorrect { total_staked: u64, supply: u64 },
+    #[error("AccountDataInvalid(len: {len})")]
+    AccountDataInvalid { len: usize },
+    #[error("MinFeeGreaterThanMaxFee(min: {min}, max: {max})")]
     MinFeeGreaterThanMaxFee { min: Fee, max: Fee },
+    #[error("InitialLiquidityIncorrect(tokens: {tokens}, staked: {staked})")]
+    InitialLiquidityIncorrect {
+        tokens: TokenAmount,
+        staked: StakedTokenAmount,
+    },
+    #[error("EmptyPool")]
+    EmptyPool,
+    #[error("InsufficientTokenLiquidity(requested: {requested}, available: {available}, max_fillable_input: {max_fillable_input})")]
+    InsufficientTokenLiquidity {
+        requested: TokenAmount,
+        available: TokenAmount,
+        /// Largest swap input the pool accepted when the error was raised, its `max_swap_in`.
+        max_fillable_input: StakedTokenAmount,
+    },
+    #[error("InsufficientStakedLiquidity(requested: {requested}, available: {available})")]
+    InsufficientStakedLiquidity {
+        requested: StakedTokenAmount,
+        available: StakedTokenAmount,
+    },
+    #[error("InsufficientLpTokens(requested: {requested}, available: {available})")]
+    InsufficientLpTokens {
+        requested: LpTokenAmount,
+        available: LpTokenAmount,
+    },
+    #[error("ArithmeticOverflow(op: {op}, lhs: {lhs}, rhs: {rhs})")]
+    ArithmeticOverflow {
+        op: &'static str,
+        lhs: u64,
+        rhs: u64,
+    },
+    #[error("SwapTooLarge(requested: {requested}, max: {max})")]
+    SwapTooLarge {
+        requested: StakedTokenAmount,
+        max: StakedTokenAmount,
+    },
+    #[error("PoolCapExceeded(amount_after: {amount_after}, cap: {cap}, max_fillable_input: {max_fillable_input})")]
+    PoolCapExceeded {
+        amount_after: TokenAmount,
+        cap: TokenAmount,
+        /// Largest deposit that stays within the cap.
+        max_fillable_input: TokenAmount,
+    },
+    #[error("ReferralFeeTooHigh(requested: {requested}, max: {max})")]
+    ReferralFeeTooHigh { requested: Fee, max: Fee },
+    #[error("FeeTierThresholdsUnsorted(index: {index})")]
+    FeeTierThresholdsUnsorted { index: usize },
+    #[error("TicketNotFound(ticket: {0})")]
+    TicketNotFound(u64),
+    #[error("TicketNotClaimable(ticket: {ticket}, claimable_at: {claimable_at}, current_epoch: {current_epoch})")]
+    TicketNotClaimable {
+        ticket: u64,
+        claimable_at: u64,
+        current_epoch: u64,
+    },
+    #[error("TicketAlreadyClaimed(ticket: {0})")]
+    TicketAlreadyClaimed(u64),
+    #[error("NoRoute")]
+    NoRoute,
+    #[error("StaleQuote(quoted_at: {quoted_at}, current: {current})")]
+    StaleQuote { quoted_at: u64, current: u64 },
+    #[error("DustNotCollectable")]
+    DustNotCollectable,
+    #[error("PoolNotEmpty(lp_token_amount: {0})")]
+    PoolNotEmpty(LpTokenAmount),
+    #[error("SlippageExceeded(min_out: {min_out}, quoted: {quoted})")]
+    SlippageExceeded {
+        min_out: TokenAmount,
+        quoted: TokenAmount,
+    },
+    #[error("AmountOutOfRange(amount: {0})")]
+    AmountOutOfRange(SignedTokenAmount),
+    #[error("StateInvalid")]
+    StateInvalid(#[source] InvariantViolation),
+    #[error("UnsupportedStateVersion(found: {found}, supported: {supported})")]
+    UnsupportedStateVersion { found: u32, supported: u32 },
+    #[error("HoldingPeriodNotMet(unlock_epoch: {unlock_epoch})")]
+    HoldingPeriodNotMet { unlock_epoch: u64 },
+    /// Unknown tag, truncated field or trailing bytes at `offset` of an encoded operation.
+    #[error("OperationEncodingInvalid(offset: {offset}, len: {len})")]
+    OperationEncodingInvalid { offset: usize, len: usize },
+    #[error("IdempotencyKeyReused(sequence: {sequence})")]
+    IdempotencyKeyReused { sequence: u64 },
+    #[error("ZeroAmount")]
+    ZeroAmount,
+    #[error("ReserveFloorIncorrect(floor: {floor}, liquidity_target: {liquidity_target})")]
+    ReserveFloorIncorrect {
+        floor: TokenAmount,
+        liquidity_target: TokenAmount,
+    },
+    #[error("PriceTimestampOutOfOrder(last: {last}, given: {given})")]
+    PriceTimestampOutOfOrder { last: u64, given: u64 },
+    #[error("OutputBelowMinimum(computed: {computed}, minimum: {minimum})")]
+    OutputBelowMinimum {
+        computed: TokenAmount,
+        minimum: TokenAmount,
+    },
+    #[error("ReconfigurePriceMismatch(current: {current}, requested: {requested})")]
+    ReconfigurePriceMismatch { current: Price, requested: Price },
+    #[error(
+        "ReserveFloorBreached(floor: {floor}, would_be: {would_be}, max_outflow: {max_outflow})"
+    )]
+    ReserveFloorBreached {
+        floor: TokenAmount,
+        would_be: TokenAmount,
+        /// Available tokens above the floor, the most a swap or withdrawal can pay out.
+        max_outflow: TokenAmount,
+    },
+    #[error("FeeBucketsUnsorted(index: {index})")]
+    FeeBucketsUnsorted { index: usize },
 }
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Error::BasisPointsOverflow(basis) => write!(f, "BasisPointsOverflow({basis})"),
-            Error::MinFeeGreaterThanMaxFee { min, max } => {
-                write!(f, "MinFeeGreaterThanMaxFee(min: {min}, max: {max})")
-            }
-            Error::LiquidityTargetIncorrect(liquidity_target) => {
-                write!(f, "IncorrectLiquidityTarget({liquidity_target})",)
-            }
-            Error::PriceIncorrect(price) => write!(f, "IncorrectPrice({price})"),
-            Error::PriceConversionFailure { converted_from } => {
-                write!(f, "PriceConversionError(converted_from: {converted_from})",)
-            }
-        }
-    }
+/// Broken pool accounting found by `LpPool::check_solvency`.
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[non_exhaustive]
+pub enum InvariantViolation {
+    #[error("SharesNotRedeemable(lp_token_amount: {lp_token_amount}, value: {value})")]
+    SharesNotRedeemable {
+        lp_token_amount: LpTokenAmount,
+        value: TokenAmount,
+    },
+    #[error("FeesExceedBalance(fees_collected: {fees_collected}, token_amount: {token_amount})")]
+    FeesExceedBalance {
+        fees_collected: TokenAmount,
+        token_amount: TokenAmount,
+    },
+    #[error(
+        "BalanceWithoutLpSupply(token_amount: {token_amount}, fees_collected: {fees_collected})"
+    )]
+    BalanceWithoutLpSupply {
+        token_amount: TokenAmount,
+        fees_collected: TokenAmount,
+    },
+    #[error("StakedWithoutLpSupply(staked_token_amount: {staked_token_amount})")]
+    StakedWithoutLpSupply {
+        staked_token_amount: StakedTokenAmount,
+    },
+    /// Only checked by `LpPool::from_state`, the LP tokens of an imported state must be
+    /// backed by some value.
+    #[error("LpSupplyWithoutValue(lp_token_amount: {lp_token_amount})")]
+    LpSupplyWithoutValue { lp_token_amount: LpTokenAmount },
+    /// `token_amount` is what remains once `referral_fees_owed` is set aside.
+    #[error("ProtocolFeesExceedBalance(protocol_fees_owed: {protocol_fees_owed}, token_amount: {token_amount})")]
+    ProtocolFeesExceedBalance {
+        protocol_fees_owed: TokenAmount,
+        token_amount: TokenAmount,
+    },
+    #[error("ReferralFeesExceedBalance(referral_fees_owed: {referral_fees_owed}, token_amount: {token_amount})")]
+    ReferralFeesExceedBalance {
+        referral_fees_owed: TokenAmount,
+        token_amount: TokenAmount,
+    },
 }
 
-impl std::error::Error for Error {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_converted_input_inline() {
+        let converted_from = ConvertedFrom::new("1.525");
+        assert_eq!(converted_from.as_str(), "1.525");
+        assert_eq!(
+            Error::PriceConversionFailure { converted_from }.to_string(),
+            "PriceConversionError(converted_from: 1.525)"
+        );
+    }
+
+    #[test]
+    fn it_truncates_long_input_on_char_boundary() {
+        let converted_from = ConvertedFrom::new("1234567890123456789012345678901ł");
+        assert_eq!(converted_from.as_str(), "1234567890123456789012345678901");
+    }
+}
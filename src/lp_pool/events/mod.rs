@@ -0,0 +1,278 @@
+//! Executed operations pushed to handlers as they happen, each handler only called for the
+//! kinds of event it subscribed to.
+
+use core::fmt;
+
+use crate::lp_pool::{
+    data::price::Price,
+    operation::{Operation, PoolOperation},
+    LpPool,
+};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PoolEvent {
+    /// A deposit, withdrawal or swap, as the history records it.
+    Executed(PoolOperation),
+    /// `change_bps` is `new`'s deviation from `old`, negative for a move down and saturating
+    /// at `i32::MAX`. `recorded_at` is the time of the pool's clock, like in history entries.
+    PriceUpdated {
+        old: Price,
+        new: Price,
+        change_bps: i32,
+        sequence: u64,
+        recorded_at: Option<u64>,
+    },
+}
+
+impl PoolEvent {
+    #[must_use]
+    pub fn kind(&self) -> EventKind {
+        match self {
+            PoolEvent::Executed(entry) => match entry.operation {
+                Operation::AddLiquidity(_) => EventKind::AddLiquidity,
+                Operation::RemoveLiquidity(_) => EventKind::RemoveLiquidity,
+                Operation::Swap(_) => EventKind::Swap,
+                Operation::Init { .. } | Operation::UpdatePrice(_) => EventKind::PriceUpdated,
+            },
+            PoolEvent::PriceUpdated { .. } => EventKind::PriceUpdated,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EventKind {
+    AddLiquidity,
+    RemoveLiquidity,
+    Swap,
+    PriceUpdated,
+}
+
+impl EventKind {
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Set of event kinds, `EventFilter::only(EventKind::PriceUpdated)` for a price-only
+/// subscriber.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct EventFilter(u8);
+
+impl EventFilter {
+    pub const NONE: Self = Self(0);
+    pub const ALL: Self = Self::NONE
+        .with(EventKind::AddLiquidity)
+        .with(EventKind::RemoveLiquidity)
+        .with(EventKind::Swap)
+        .with(EventKind::PriceUpdated);
+
+    #[must_use]
+    pub const fn only(kind: EventKind) -> Self {
+        Self::NONE.with(kind)
+    }
+
+    #[must_use]
+    pub const fn with(self, kind: EventKind) -> Self {
+        Self(self.0 | kind.bit())
+    }
+
+    #[must_use]
+    pub const fn contains(self, kind: EventKind) -> bool {
+        self.0 & kind.bit() != 0
+    }
+}
+
+pub(super) type Handler = Box<dyn FnMut(PoolEvent) + Send + Sync>;
+
+/// Handlers of a pool. A clone of the pool starts without any, so dry runs on clones, like
+/// `validate_batch`, notify nobody.
+#[derive(Default)]
+pub(super) struct Subscribers(Vec<(EventFilter, Handler)>);
+
+impl Subscribers {
+    fn wants(&self, kind: EventKind) -> bool {
+        self.0.iter().any(|(filter, _)| filter.contains(kind))
+    }
+
+    fn notify(&mut self, event: PoolEvent) {
+        notify(&mut self.0, event);
+    }
+}
+
+/// Calls the handlers of `handlers` whose filter contains the kind of `event`.
+pub(super) fn notify(handlers: &mut [(EventFilter, Handler)], event: PoolEvent) {
+    let kind = event.kind();
+    for (filter, handler) in handlers {
+        if filter.contains(kind) {
+            handler(event);
+        }
+    }
+}
+
+impl Clone for Subscribers {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Debug for Subscribers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} subscribers", self.0.len())
+    }
+}
+
+impl LpPool {
+    /// Calls `handler` after every executed operation of a kind in `filter`, in the order
+    /// they execute. Rejected operations and quotes are not events. The handler runs inside
+    /// the call that executed the operation, subscribe to a `SharedLpPool` instead for
+    /// handlers that act on the pool.
+    pub fn subscribe(
+        &mut self,
+        filter: EventFilter,
+        handler: impl FnMut(PoolEvent) + Send + Sync + 'static,
+    ) {
+        self.subscribers.0.push((filter, Box::new(handler)));
+    }
+
+    /// Called by `record_history` with the entry it records, or would under a policy
+    /// keeping it. Price updates go through `notify_price_update` instead.
+    pub(super) fn notify_executed(&mut self, entry: PoolOperation) {
+        let event = PoolEvent::Executed(entry);
+        if event.kind() != EventKind::PriceUpdated {
+            self.subscribers.notify(event);
+        }
+    }
+
+    /// Called after the price moved from `old` and bumped the sequence.
+    pub(super) fn notify_price_update(&mut self, old: Price) {
+        if !self.subscribers.wants(EventKind::PriceUpdated) {
+            return;
+        }
+        let new = self.price;
+        // neither price is zero, the pool rejects one, so only the size can saturate
+        let deviation = old.deviation_bps(&new).unwrap_or(u32::MAX);
+        let deviation = i32::try_from(deviation).unwrap_or(i32::MAX);
+        let change_bps = if new < old { -deviation } else { deviation };
+        self.subscribers.notify(PoolEvent::PriceUpdated {
+            old,
+            new,
+            change_bps,
+            sequence: self.sequence,
+            recorded_at: self.now(),
+        });
+    }
+
+    pub(super) fn has_subscribers(&self) -> bool {
+        !self.subscribers.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::lp_pool::data::token::{LpTokenAmount, StakedTokenAmount, TokenAmount};
+    use crate::test_utils::PoolFixture;
+
+    /// Events `lp_pool` delivers to a handler subscribed with `filter`.
+    fn collect(lp_pool: &mut LpPool, filter: EventFilter) -> Arc<Mutex<Vec<PoolEvent>>> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        lp_pool.subscribe(filter, move |event| sink.lock().unwrap().push(event));
+        events
+    }
+
+    #[test]
+    fn it_calls_price_subscriber_only_for_price_updates() {
+        let mut lp_pool = PoolFixture::new()
+            .price("1.0")
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool;
+        let prices = collect(&mut lp_pool, EventFilter::only(EventKind::PriceUpdated));
+        let all = collect(&mut lp_pool, EventFilter::ALL);
+
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(1_000))
+            .unwrap();
+        lp_pool.update_price("1.05".parse().unwrap()).unwrap();
+        lp_pool
+            .add_liquidity(TokenAmount::from_lamports(5_000))
+            .unwrap();
+        lp_pool.update_price("0.84".parse().unwrap()).unwrap();
+        lp_pool
+            .remove_liquidity(LpTokenAmount::from_lamports(2_000))
+            .unwrap();
+        assert!(lp_pool.update_price(Price::from_raw_points(0)).is_err());
+        lp_pool.update_price_at("0.84".parse().unwrap(), 7).unwrap();
+
+        let sequence = lp_pool.sequence();
+        assert_eq!(
+            *prices.lock().unwrap(),
+            vec![
+                PoolEvent::PriceUpdated {
+                    old: "1.0".parse().unwrap(),
+                    new: "1.05".parse().unwrap(),
+                    change_bps: 500,
+                    sequence: sequence - 4,
+                    recorded_at: None,
+                },
+                // 20% of 1.05
+                PoolEvent::PriceUpdated {
+                    old: "1.05".parse().unwrap(),
+                    new: "0.84".parse().unwrap(),
+                    change_bps: -2_000,
+                    sequence: sequence - 2,
+                    recorded_at: None,
+                },
+                PoolEvent::PriceUpdated {
+                    old: "0.84".parse().unwrap(),
+                    new: "0.84".parse().unwrap(),
+                    change_bps: 0,
+                    sequence,
+                    recorded_at: None,
+                },
+            ]
+        );
+        let kinds: Vec<_> = all.lock().unwrap().iter().map(PoolEvent::kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                EventKind::Swap,
+                EventKind::PriceUpdated,
+                EventKind::AddLiquidity,
+                EventKind::PriceUpdated,
+                EventKind::RemoveLiquidity,
+                EventKind::PriceUpdated,
+            ]
+        );
+    }
+
+    #[test]
+    fn it_notifies_nobody_from_clones() {
+        let mut lp_pool = PoolFixture::new()
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool;
+        let events = collect(&mut lp_pool, EventFilter::ALL);
+
+        let mut copy = lp_pool.clone();
+        copy.swap(StakedTokenAmount::from_lamports(1_000)).unwrap();
+        let _ = lp_pool.validate_batch(&[Operation::Swap(StakedTokenAmount::from_lamports(1_000))]);
+
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_combines_event_kinds_in_filter() {
+        let liquidity = EventFilter::only(EventKind::AddLiquidity).with(EventKind::RemoveLiquidity);
+
+        assert!(liquidity.contains(EventKind::RemoveLiquidity));
+        assert!(!liquidity.contains(EventKind::Swap));
+        assert!(!EventFilter::NONE.contains(EventKind::PriceUpdated));
+        assert!(EventFilter::ALL.contains(EventKind::PriceUpdated));
+    }
+}
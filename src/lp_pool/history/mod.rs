@@ -0,0 +1,839 @@
+//! Operations the pool keeps about itself, bounded by a retention policy.
+
+use core::fmt;
+use std::collections::VecDeque;
+
+use crate::error::{Error, Result};
+use crate::lp_pool::{
+    data::token::{LpTokenAmount, SignedTokenAmount, StakedTokenAmount, TokenAmount},
+    operation::{Balances, Operation, PoolOperation},
+    overflow, LpPool,
+};
+
+/// How many executed operations the pool keeps.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum HistoryPolicy {
+    /// Every operation, until drained.
+    Unbounded,
+    /// The newest `N`, older ones are dropped as new ones arrive.
+    LastN(usize),
+    /// Nothing is recorded.
+    #[default]
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct History {
+    policy: HistoryPolicy,
+    entries: VecDeque<PoolOperation>,
+    /// `token_amount` before the oldest entry.
+    opening_token_amount: TokenAmount,
+    /// `None` unless the rejection log is on, kept under `policy` like `entries`.
+    rejections: Option<Vec<RejectedOp>>,
+    /// Log error messages with `Error::display_redacted`.
+    redact_amounts: bool,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(HistoryPolicy::default(), TokenAmount::from_lamports(0))
+    }
+}
+
+impl History {
+    fn new(policy: HistoryPolicy, opening_token_amount: TokenAmount) -> Self {
+        Self {
+            policy,
+            entries: VecDeque::with_capacity(Self::capacity(policy)),
+            opening_token_amount,
+            rejections: None,
+            redact_amounts: false,
+        }
+    }
+
+    fn capacity(policy: HistoryPolicy) -> usize {
+        match policy {
+            HistoryPolicy::LastN(capacity) => capacity,
+            HistoryPolicy::Unbounded | HistoryPolicy::None => 0,
+        }
+    }
+
+    /// Under `LastN` the buffer is allocated once, a full buffer drops its oldest entry to
+    /// make room.
+    fn push(&mut self, entry: PoolOperation) {
+        match self.policy {
+            HistoryPolicy::None | HistoryPolicy::LastN(0) => {}
+            HistoryPolicy::Unbounded => self.entries.push_back(entry),
+            HistoryPolicy::LastN(capacity) => {
+                if self.entries.len() == capacity {
+                    if let Some(dropped) = self.entries.pop_front() {
+                        self.opening_token_amount = dropped.post_balances.token_amount;
+                    }
+                }
+                self.entries.push_back(entry);
+            }
+        }
+    }
+
+    /// Like `push`, dropping the oldest rejection of a full log.
+    fn push_rejection(&mut self, rejection: RejectedOp) {
+        let Some(rejections) = self.rejections.as_mut() else {
+            return;
+        };
+        match self.policy {
+            HistoryPolicy::None | HistoryPolicy::LastN(0) => {}
+            HistoryPolicy::Unbounded => rejections.push(rejection),
+            HistoryPolicy::LastN(capacity) => {
+                if rejections.len() == capacity {
+                    rejections.remove(0);
+                }
+                rejections.push(rejection);
+            }
+        }
+    }
+}
+
+/// An operation the pool rejected, see `LpPool::with_rejection_log`.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RejectedOp {
+    /// `LpPool::sequence` when the operation was rejected, a rejection leaves it as it was.
+    pub sequence_at_rejection: u64,
+    /// The operation without a fee, with the balances it left unchanged.
+    pub op: PoolOperation,
+    /// `Error::code` of the rejection.
+    pub error_code: u32,
+    /// The error with its sources, as `LpPool::describe_error` renders it.
+    pub error_message: String,
+}
+
+impl LpPool {
+    /// Keeps executed liquidity, swap and price operations under `policy`, replacing any
+    /// history and rejections recorded so far.
+    #[must_use]
+    pub fn with_history_policy(mut self, policy: HistoryPolicy) -> Self {
+        let rejections = self.history.rejections.is_some();
+        let redact_amounts = self.history.redact_amounts;
+        self.history = History::new(policy, self.token_amount);
+        self.history.redact_amounts = redact_amounts;
+        self.with_rejection_log(rejections)
+    }
+
+    /// Logs error messages with every number replaced by its order of magnitude, for
+    /// deployments that keep balances out of their logs. Applies to messages written from
+    /// now on, see `describe_error`, and to the errors deposits, withdrawals, swaps and price
+    /// updates return from now on, which come wrapped in `Error::Redacted` and so display
+    /// redacted wherever they are logged.
+    #[must_use]
+    pub fn redact_amounts_in_logs(mut self, redact: bool) -> Self {
+        self.history.redact_amounts = redact;
+        self
+    }
+
+    /// `error` as the pool logs it: `Error::display_redacted` after
+    /// `redact_amounts_in_logs(true)`, `Error::display_chain` otherwise.
+    #[must_use]
+    pub fn describe_error(&self, error: &Error) -> String {
+        if self.history.redact_amounts {
+            error.display_redacted().to_string()
+        } else {
+            error.display_chain().to_string()
+        }
+    }
+
+    /// Also keeps liquidity, swap and price operations the pool rejected, under the history
+    /// policy, replacing any rejections recorded so far. Rejections are logged by the public
+    /// methods that take an `Operation`'s amount: deposits, withdrawals, swaps and price
+    /// updates, quoted or not.
+    #[must_use]
+    pub fn with_rejection_log(mut self, enabled: bool) -> Self {
+        self.history.rejections =
+            enabled.then(|| Vec::with_capacity(History::capacity(self.history.policy)));
+        self
+    }
+
+    /// Logged rejections, oldest first, empty unless `with_rejection_log` turned the log on.
+    #[must_use]
+    pub fn rejections(&self) -> &[RejectedOp] {
+        self.history.rejections.as_deref().unwrap_or_default()
+    }
+
+    #[must_use]
+    pub fn history_policy(&self) -> HistoryPolicy {
+        self.history.policy
+    }
+
+    /// Recorded operations, oldest first. Entries are stamped with the pool epoch.
+    #[must_use]
+    pub fn history(&self) -> impl ExactSizeIterator<Item = &PoolOperation> {
+        self.history.entries.iter()
+    }
+
+    /// Removes and returns every recorded operation, oldest first. Operations executed after
+    /// the drain are recorded from the next sequence, so draining periodically loses nothing
+    /// the policy kept.
+    pub fn drain_history(&mut self) -> Vec<PoolOperation> {
+        if let Some(newest) = self.history.entries.back() {
+            self.history.opening_token_amount = newest.post_balances.token_amount;
+        }
+        self.history.entries.drain(..).collect()
+    }
+
+    /// Recorded operations folded into one `EpochStats` per epoch, from the epoch of the
+    /// oldest entry to the epoch of the newest, epochs without entries included. Amounts are
+    /// taken from the entries and the balance changes between them, so tokens moved by calls
+    /// the history does not record, `withdraw_referral_fees`, `claim_protocol_fees` and
+    /// `collect_dust`, are counted in the next withdrawal.
+    #[must_use]
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "the expects cover invariants of the recorded history"
+    )]
+    pub fn epoch_stats(&self) -> Vec<EpochStats> {
+        let mut stats: Vec<EpochStats> = Vec::new();
+        let mut token_amount_before = self.history.opening_token_amount;
+        for entry in &self.history.entries {
+            let epoch = entry.timestamp;
+            while stats.last().is_none_or(|last| last.epoch < epoch) {
+                let idle = match stats.last() {
+                    Some(last) => EpochStats::idle(last.epoch + 1, last.closing_balances),
+                    None => EpochStats::idle(epoch, entry.post_balances),
+                };
+                stats.push(idle);
+            }
+            // PANIC SAFETY: the loop above pushes until the last entry is this epoch.
+            let current = stats.last_mut().expect("pushed above");
+            let balances = entry.post_balances;
+            match entry.operation {
+                Operation::Swap(staked_tokens) => {
+                    current.swaps += 1;
+                    current.staked_in += staked_tokens;
+                    // PANIC SAFETY: swaps leave the price as it was, and the swap converted
+                    // these staked tokens at it before it was recorded.
+                    current.tokens_out +=
+                        TokenAmount::from_staked_tokens(staked_tokens, balances.price)
+                            .expect("an executed swap's value fits u64");
+                }
+                Operation::AddLiquidity(tokens) => {
+                    current.net_liquidity = current.net_liquidity + tokens.into();
+                }
+                Operation::RemoveLiquidity(_) => {
+                    current.net_liquidity = current.net_liquidity
+                        - TokenAmount::signed_diff(token_amount_before, balances.token_amount);
+                }
+                Operation::Init { .. } | Operation::UpdatePrice(_) => {}
+            }
+            current.fees += entry.fee;
+            current.closing_balances = balances;
+            token_amount_before = balances.token_amount;
+        }
+        stats
+    }
+
+    /// Recorded swaps, oldest first.
+    pub fn swaps(&self) -> impl Iterator<Item = SwapRecord<'_>> {
+        self.records(|operation| match operation {
+            Operation::Swap(staked_tokens) => Some(staked_tokens),
+            _ => None,
+        })
+    }
+
+    /// Recorded `add_liquidity` calls, oldest first.
+    pub fn deposits(&self) -> impl Iterator<Item = DepositRecord<'_>> {
+        self.records(|operation| match operation {
+            Operation::AddLiquidity(tokens) => Some(tokens),
+            _ => None,
+        })
+    }
+
+    /// Recorded `remove_liquidity` calls, oldest first.
+    pub fn withdrawals(&self) -> impl Iterator<Item = WithdrawalRecord<'_>> {
+        self.records(|operation| match operation {
+            Operation::RemoveLiquidity(lp_tokens) => Some(lp_tokens),
+            _ => None,
+        })
+    }
+
+    /// Recorded operations executed after the pool was at `sequence`, oldest first.
+    pub fn since_sequence(&self, sequence: u64) -> impl Iterator<Item = &PoolOperation> {
+        self.history()
+            .filter(move |entry| entry.sequence > sequence)
+    }
+
+    /// Tokens the recorded swaps were worth at their price before fees, like
+    /// `EpochStats::tokens_out`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArithmeticOverflow` when the volume does not fit `u64`.
+    pub fn total_swap_volume(&self) -> Result<TokenAmount> {
+        self.swaps()
+            .try_fold(TokenAmount::from_lamports(0), |total, swap| {
+                let value =
+                    TokenAmount::from_staked_tokens(swap.amount, swap.entry.post_balances.price)?;
+                total
+                    .checked_add(value)
+                    .ok_or_else(|| overflow("swap volume + swap value", total, value))
+            })
+    }
+
+    /// Swap and withdrawal fees the recorded operations added to `fees_collected`. It
+    /// matches `fees_collected` for a history kept since `init` while no deposit bonus was
+    /// paid from it and no outflow took `token_amount` below it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArithmeticOverflow` when the fees do not fit `u64`.
+    pub fn total_fees_from_history(&self) -> Result<TokenAmount> {
+        let swap_fees = self.swaps().map(|swap| swap.entry.fee);
+        let withdrawal_fees = self.withdrawals().map(|withdrawal| withdrawal.entry.fee);
+        swap_fees
+            .chain(withdrawal_fees)
+            .try_fold(TokenAmount::from_lamports(0), |total, fee| {
+                total
+                    .checked_add(fee)
+                    .ok_or_else(|| overflow("history fees + fee", total, fee))
+            })
+    }
+
+    fn records<T>(
+        &self,
+        amount: fn(Operation) -> Option<T>,
+    ) -> impl Iterator<Item = Record<'_, T>> {
+        self.history()
+            .filter_map(move |entry| amount(entry.operation).map(|amount| Record { amount, entry }))
+    }
+
+    /// Called after `operation` bumped the sequence, also hands the entry to subscribers.
+    pub(super) fn record_history(&mut self, operation: Operation, fee: TokenAmount) {
+        if self.history.policy == HistoryPolicy::None && !self.has_subscribers() {
+            return;
+        }
+        let entry = PoolOperation {
+            sequence: self.sequence,
+            operation,
+            fee,
+            post_balances: Balances::of(self),
+            timestamp: self.epoch,
+            recorded_at: self.now(),
+        };
+        self.history.push(entry);
+        self.notify_executed(entry);
+    }
+
+    /// `error` as the pool returns it, wrapped in `Error::Redacted` after
+    /// `redact_amounts_in_logs(true)`.
+    pub(super) fn redact(&self, error: Error) -> Error {
+        if self.history.redact_amounts && !matches!(error, Error::Redacted(_)) {
+            Error::Redacted(Box::new(error))
+        } else {
+            error
+        }
+    }
+
+    /// Called with the error `operation` was rejected with, the pool unchanged by it.
+    pub(super) fn record_rejection(&mut self, operation: Operation, error: &Error) {
+        if self.history.rejections.is_none() || self.history.policy == HistoryPolicy::None {
+            return;
+        }
+        let rejection = RejectedOp {
+            sequence_at_rejection: self.sequence,
+            op: PoolOperation {
+                sequence: self.sequence,
+                operation,
+                fee: TokenAmount::from_lamports(0),
+                post_balances: Balances::of(self),
+                timestamp: self.epoch,
+                recorded_at: self.now(),
+            },
+            error_code: error.code(),
+            error_message: self.describe_error(error),
+        };
+        self.history.push_rejection(rejection);
+    }
+}
+
+/// A recorded operation of one kind with its amount taken out of `Operation`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Record<'a, T> {
+    pub amount: T,
+    pub entry: &'a PoolOperation,
+}
+
+/// Staked tokens swapped in, see `LpPool::swaps`.
+pub type SwapRecord<'a> = Record<'a, StakedTokenAmount>;
+
+/// Tokens deposited, see `LpPool::deposits`.
+pub type DepositRecord<'a> = Record<'a, TokenAmount>;
+
+/// LP tokens burned, see `LpPool::withdrawals`.
+pub type WithdrawalRecord<'a> = Record<'a, LpTokenAmount>;
+
+/// Activity of one epoch, see `LpPool::epoch_stats`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EpochStats {
+    pub epoch: u64,
+    pub swaps: u64,
+    /// Staked tokens swapped in.
+    pub staked_in: StakedTokenAmount,
+    /// Tokens the swaps were worth at the price, before fees.
+    pub tokens_out: TokenAmount,
+    /// Swap and withdrawal fees added to `fees_collected`, management fees are charged by
+    /// `advance_epoch` outside the history.
+    pub fees: TokenAmount,
+    /// Tokens deposited less tokens paid out to withdrawing LPs.
+    pub net_liquidity: SignedTokenAmount,
+    /// Balances after the last operation of the epoch, or of the epoch before without any.
+    pub closing_balances: Balances,
+}
+
+impl EpochStats {
+    pub const CSV_HEADER: &'static str = "epoch,swaps,staked_in,tokens_out,fees,net_liquidity,\
+        price_points,token_amount,staked_token_amount,lp_token_amount,fees_collected";
+
+    fn idle(epoch: u64, closing_balances: Balances) -> Self {
+        Self {
+            epoch,
+            swaps: 0,
+            staked_in: StakedTokenAmount::from_lamports(0),
+            tokens_out: TokenAmount::from_lamports(0),
+            fees: TokenAmount::from_lamports(0),
+            net_liquidity: SignedTokenAmount::ZERO,
+            closing_balances,
+        }
+    }
+
+    /// `CSV_HEADER` and a row per epoch, amounts in lamports and the price in points.
+    ///
+    /// # Errors
+    ///
+    /// Fails only when writing to `out` does.
+    pub fn write_csv(stats: &[EpochStats], out: &mut impl fmt::Write) -> fmt::Result {
+        writeln!(out, "{}", Self::CSV_HEADER)?;
+        for epoch in stats {
+            let balances = epoch.closing_balances;
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                epoch.epoch,
+                epoch.swaps,
+                epoch.staked_in,
+                epoch.tokens_out,
+                epoch.fees,
+                i128::from(epoch.net_liquidity),
+                u64::from(balances.price),
+                balances.token_amount,
+                balances.staked_token_amount,
+                balances.lp_token_amount,
+                balances.fees_collected,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lp_pool::data::{fee::Fee, price::Price};
+    use crate::test_utils::PoolFixture;
+
+    fn pool(policy: HistoryPolicy) -> LpPool {
+        PoolFixture::new()
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool
+            .with_history_policy(policy)
+    }
+
+    fn swap_times(lp_pool: &mut LpPool, times: u64) {
+        for _ in 0..times {
+            lp_pool
+                .swap(StakedTokenAmount::from_lamports(1_000))
+                .unwrap();
+        }
+    }
+
+    fn sequences(lp_pool: &LpPool) -> Vec<u64> {
+        lp_pool.history().map(|entry| entry.sequence).collect()
+    }
+
+    #[test]
+    fn it_keeps_newest_entries_under_last_n() {
+        let mut lp_pool = pool(HistoryPolicy::LastN(3));
+        let start = lp_pool.sequence();
+
+        swap_times(&mut lp_pool, 2);
+        assert_eq!(sequences(&lp_pool), vec![start + 1, start + 2]);
+
+        swap_times(&mut lp_pool, 5);
+        assert_eq!(sequences(&lp_pool), vec![start + 5, start + 6, start + 7]);
+        assert_eq!(lp_pool.history.entries.capacity(), 3);
+
+        let newest = lp_pool.history().last().unwrap();
+        assert_eq!(
+            newest.operation,
+            Operation::Swap(StakedTokenAmount::from_lamports(1_000))
+        );
+        assert_eq!(newest.post_balances, Balances::of(&lp_pool));
+    }
+
+    #[test]
+    fn it_drains_history_and_continues_sequence() {
+        let mut lp_pool = pool(HistoryPolicy::Unbounded);
+        let start = lp_pool.sequence();
+        swap_times(&mut lp_pool, 2);
+        lp_pool
+            .add_liquidity(TokenAmount::from_lamports(10_000))
+            .unwrap();
+
+        let drained = lp_pool.drain_history();
+        assert_eq!(
+            drained
+                .iter()
+                .map(|entry| entry.sequence)
+                .collect::<Vec<_>>(),
+            vec![start + 1, start + 2, start + 3]
+        );
+        assert_eq!(lp_pool.history().len(), 0);
+
+        swap_times(&mut lp_pool, 2);
+        assert_eq!(sequences(&lp_pool), vec![start + 4, start + 5]);
+    }
+
+    #[test]
+    fn it_summarizes_epochs_from_history() {
+        let mut lp_pool = PoolFixture::new()
+            .fees(10, 10)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool
+            .with_history_policy(HistoryPolicy::Unbounded);
+
+        // epoch 0: 10_000 staked at 1.00, 0.1% fee of 10, then 19_998 LP tokens for 20_000
+        // tokens against a pool worth 90_010 + 10_000, the fee kept for the LPs
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(10_000))
+            .unwrap();
+        lp_pool
+            .add_liquidity(TokenAmount::from_lamports(20_000))
+            .unwrap();
+        let epoch_0 = Balances::of(&lp_pool);
+        // epoch 1: nothing
+        lp_pool.advance_epoch().unwrap();
+        lp_pool.advance_epoch().unwrap();
+        // epoch 2: 1_000 staked at 2.00 with a fee of 2, then 12_000 of 119_998 LP tokens
+        // withdraw 10% of 108_012 available tokens less a fee of 10
+        lp_pool.update_price(Price::from_raw_points(200)).unwrap();
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(1_000))
+            .unwrap();
+        lp_pool
+            .remove_liquidity(LpTokenAmount::from_lamports(12_000))
+            .unwrap();
+        let epoch_2 = Balances::of(&lp_pool);
+
+        let stats = lp_pool.epoch_stats();
+        assert_eq!(
+            stats,
+            vec![
+                EpochStats {
+                    epoch: 0,
+                    swaps: 1,
+                    staked_in: StakedTokenAmount::from_lamports(10_000),
+                    tokens_out: TokenAmount::from_lamports(10_000),
+                    fees: TokenAmount::from_lamports(10),
+                    net_liquidity: SignedTokenAmount::from_lamports(20_000),
+                    closing_balances: epoch_0,
+                },
+                EpochStats::idle(1, epoch_0),
+                EpochStats {
+                    epoch: 2,
+                    swaps: 1,
+                    staked_in: StakedTokenAmount::from_lamports(1_000),
+                    tokens_out: TokenAmount::from_lamports(2_000),
+                    fees: TokenAmount::from_lamports(12),
+                    net_liquidity: SignedTokenAmount::from_lamports(-10_791),
+                    closing_balances: epoch_2,
+                },
+            ]
+        );
+        assert_eq!(epoch_0.token_amount, TokenAmount::from_lamports(110_010));
+        assert_eq!(epoch_2.token_amount, TokenAmount::from_lamports(97_221));
+        assert_eq!(
+            epoch_2.lp_token_amount,
+            LpTokenAmount::from_lamports(107_998)
+        );
+
+        let mut csv = String::new();
+        EpochStats::write_csv(&stats, &mut csv).unwrap();
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            vec![
+                EpochStats::CSV_HEADER,
+                "0,1,10000,10000,10,20000,100,110010,10000,119998,10",
+                "1,0,0,0,0,0,100,110010,10000,119998,10",
+                "2,1,1000,2000,12,-10791,200,97221,9900,107998,22",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_summarizes_epochs_after_dropping_and_draining_entries() {
+        let mut lp_pool = pool(HistoryPolicy::LastN(1));
+        lp_pool
+            .add_liquidity(TokenAmount::from_lamports(10_000))
+            .unwrap();
+        lp_pool.advance_epoch().unwrap();
+        lp_pool
+            .remove_liquidity(LpTokenAmount::from_lamports(5_000))
+            .unwrap();
+        let paid_out = TokenAmount::signed_diff(
+            lp_pool.history().next().unwrap().post_balances.token_amount,
+            TokenAmount::from_lamports(110_000),
+        );
+
+        let stats = lp_pool.epoch_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].epoch, 1);
+        assert_eq!(stats[0].net_liquidity, paid_out);
+        assert!(paid_out < SignedTokenAmount::ZERO);
+
+        lp_pool.drain_history();
+        assert!(lp_pool.epoch_stats().is_empty());
+        lp_pool
+            .add_liquidity(TokenAmount::from_lamports(1))
+            .unwrap();
+        assert_eq!(
+            lp_pool.epoch_stats()[0].net_liquidity,
+            SignedTokenAmount::from_lamports(1)
+        );
+    }
+
+    #[test]
+    fn it_records_nothing_by_default() {
+        let mut lp_pool = pool(HistoryPolicy::default());
+        swap_times(&mut lp_pool, 2);
+
+        assert_eq!(lp_pool.history().len(), 0);
+        assert!(lp_pool.drain_history().is_empty());
+    }
+
+    #[test]
+    fn it_keeps_exact_amounts_out_of_redacted_logs() {
+        for redact in [false, true] {
+            let mut lp_pool = pool(HistoryPolicy::Unbounded)
+                .redact_amounts_in_logs(redact)
+                .with_rejection_log(true)
+                .with_history_policy(HistoryPolicy::LastN(4));
+            let error = lp_pool
+                .remove_liquidity(LpTokenAmount::from_lamports(123_456_789))
+                .unwrap_err();
+            let exact = error.unredacted().display_chain().to_string();
+            let amounts: Vec<_> = exact
+                .split(|c: char| !c.is_ascii_digit())
+                .filter(|digits| digits.len() > 2)
+                .collect();
+            let shared = crate::lp_pool::shared::SharedLpPool::new(lp_pool.clone());
+
+            assert!(amounts.contains(&"123456789"));
+            assert!(matches!(
+                error.unredacted(),
+                Error::LpPool(crate::lp_pool::error::Error::InsufficientLpTokens { .. })
+            ));
+            assert_eq!(error.code(), 203);
+            for message in [
+                lp_pool.describe_error(&error),
+                lp_pool.rejections()[0].error_message.clone(),
+                shared.describe_error(&error),
+                error.display_chain().to_string(),
+            ] {
+                assert_eq!(message == exact, !redact);
+                for amount in &amounts {
+                    assert_eq!(message.contains(amount), !redact, "{amount} in {message}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_logs_exactly_the_rejected_operations() {
+        let mut lp_pool = pool(HistoryPolicy::Unbounded)
+            .with_rejection_log(true)
+            .with_token_cap(Some(TokenAmount::from_lamports(200_000)))
+            .unwrap();
+        let staked = StakedTokenAmount::from_lamports(1_000);
+        let stale_quote = lp_pool.quote_swap(staked).unwrap();
+        swap_times(&mut lp_pool, 1);
+        let sequence = lp_pool.sequence();
+
+        let rejected = [
+            (
+                Operation::Swap(StakedTokenAmount::from_lamports(u64::MAX)),
+                lp_pool
+                    .swap(StakedTokenAmount::from_lamports(u64::MAX))
+                    .unwrap_err(),
+            ),
+            (
+                Operation::Swap(staked),
+                lp_pool.execute_quote(&stale_quote).unwrap_err(),
+            ),
+            (
+                Operation::AddLiquidity(TokenAmount::from_lamports(1_000_000)),
+                lp_pool
+                    .add_liquidity(TokenAmount::from_lamports(1_000_000))
+                    .unwrap_err(),
+            ),
+            (
+                Operation::RemoveLiquidity(LpTokenAmount::from_lamports(u64::MAX)),
+                lp_pool
+                    .remove_liquidity(LpTokenAmount::from_lamports(u64::MAX))
+                    .unwrap_err(),
+            ),
+            (
+                Operation::UpdatePrice(Price::from_raw_points(0)),
+                lp_pool.update_price(Price::from_raw_points(0)).unwrap_err(),
+            ),
+        ];
+        swap_times(&mut lp_pool, 1);
+        lp_pool
+            .add_liquidity(TokenAmount::from_lamports(1_000))
+            .unwrap();
+
+        let logged: Vec<_> = lp_pool
+            .rejections()
+            .iter()
+            .map(|rejection| (rejection.op.operation, rejection.error_code))
+            .collect();
+        let expected: Vec<_> = rejected
+            .iter()
+            .map(|(operation, error)| (*operation, error.code()))
+            .collect();
+        assert_eq!(logged, expected);
+        assert_eq!(
+            lp_pool.rejections()[0].error_message,
+            rejected[0].1.display_chain().to_string()
+        );
+        assert_eq!(lp_pool.rejections()[1].error_code, 117);
+        assert!(lp_pool
+            .rejections()
+            .iter()
+            .all(|rejection| rejection.sequence_at_rejection == sequence
+                && rejection.op.fee == TokenAmount::from_lamports(0)));
+        assert_eq!(lp_pool.history().len(), 3);
+    }
+
+    #[test]
+    fn it_keeps_rejections_under_history_policy() {
+        let reject = |lp_pool: &mut LpPool, lamports| {
+            let _ = lp_pool.remove_liquidity(LpTokenAmount::from_lamports(lamports));
+        };
+
+        let mut lp_pool = pool(HistoryPolicy::Unbounded);
+        reject(&mut lp_pool, u64::MAX);
+        assert!(lp_pool.rejections().is_empty());
+
+        let mut lp_pool = pool(HistoryPolicy::None).with_rejection_log(true);
+        reject(&mut lp_pool, u64::MAX);
+        assert!(lp_pool.rejections().is_empty());
+
+        let mut lp_pool = pool(HistoryPolicy::LastN(2))
+            .with_rejection_log(true)
+            .with_history_policy(HistoryPolicy::LastN(2));
+        for lamports in [u64::MAX - 2, u64::MAX - 1, u64::MAX] {
+            reject(&mut lp_pool, lamports);
+        }
+        let logged: Vec<_> = lp_pool
+            .rejections()
+            .iter()
+            .map(|rejection| rejection.op.operation)
+            .collect();
+        assert_eq!(
+            logged,
+            [u64::MAX - 1, u64::MAX]
+                .map(|lamports| Operation::RemoveLiquidity(LpTokenAmount::from_lamports(lamports)))
+        );
+    }
+
+    #[test]
+    fn it_filters_history_by_operation_kind() {
+        let mut lp_pool = pool(HistoryPolicy::Unbounded);
+        let start = lp_pool.sequence();
+        swap_times(&mut lp_pool, 2);
+        lp_pool
+            .add_liquidity(TokenAmount::from_lamports(20_000))
+            .unwrap();
+        lp_pool
+            .remove_liquidity(LpTokenAmount::from_lamports(30_000))
+            .unwrap();
+        lp_pool.update_price(Price::from_raw_points(150)).unwrap();
+        lp_pool
+            .swap_with_referral(StakedTokenAmount::from_lamports(4_000), Fee::ZERO)
+            .unwrap();
+        lp_pool
+            .remove_liquidity(LpTokenAmount::from_lamports(1_000))
+            .unwrap();
+
+        let swaps: Vec<_> = lp_pool
+            .swaps()
+            .map(|swap| (swap.entry.sequence, u64::from(swap.amount)))
+            .collect();
+        assert_eq!(
+            swaps,
+            vec![(start + 1, 1_000), (start + 2, 1_000), (start + 6, 4_000)]
+        );
+        assert_eq!(
+            lp_pool
+                .deposits()
+                .map(|deposit| deposit.amount)
+                .collect::<Vec<_>>(),
+            vec![TokenAmount::from_lamports(20_000)]
+        );
+        assert_eq!(
+            lp_pool
+                .withdrawals()
+                .map(|withdrawal| withdrawal.entry.sequence)
+                .collect::<Vec<_>>(),
+            vec![start + 4, start + 7]
+        );
+        assert_eq!(
+            lp_pool
+                .since_sequence(start + 4)
+                .map(|entry| entry.sequence)
+                .collect::<Vec<_>>(),
+            vec![start + 5, start + 6, start + 7]
+        );
+        assert_eq!(lp_pool.since_sequence(lp_pool.sequence()).count(), 0);
+
+        // 1_000 + 1_000 at 1.00 and 4_000 at 1.50
+        assert_eq!(
+            lp_pool.total_swap_volume(),
+            Ok(TokenAmount::from_lamports(8_000))
+        );
+        let fees = lp_pool.total_fees_from_history().unwrap();
+        assert!(fees > TokenAmount::from_lamports(0));
+        assert_eq!(fees, lp_pool.state().fees_collected);
+    }
+
+    #[test]
+    #[cfg(feature = "strict-math")]
+    fn it_returns_overflow_summing_swap_volume() {
+        let mut lp_pool = pool(HistoryPolicy::Unbounded);
+        swap_times(&mut lp_pool, 1);
+        let mut entry = *lp_pool.history().next().unwrap();
+        entry.operation = Operation::Swap(StakedTokenAmount::from_lamports(u64::MAX));
+        lp_pool.history.push(entry);
+        lp_pool.history.push(entry);
+
+        assert!(matches!(
+            lp_pool.total_swap_volume(),
+            Err(crate::error::Error::LpPool(
+                crate::lp_pool::error::Error::ArithmeticOverflow { .. }
+            ))
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+//! Idempotency keys, so a retried request returns the first result instead of executing twice.
+
+use crate::error::{Error, Result};
+use crate::lp_pool::{
+    data::token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    error::Error as LpPoolError,
+    LpPool,
+};
+
+/// Request and result of an operation run under an idempotency key.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Executed {
+    Swap {
+        staked_tokens: StakedTokenAmount,
+        tokens_out: TokenAmount,
+    },
+    AddLiquidity {
+        tokens: TokenAmount,
+        lp_tokens: LpTokenAmount,
+    },
+    RemoveLiquidity {
+        lp_tokens: LpTokenAmount,
+        tokens: TokenAmount,
+        staked_tokens: StakedTokenAmount,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ExecutedKey {
+    /// `LpPool::sequence` after the operation.
+    sequence: u64,
+    executed: Executed,
+}
+
+impl LpPool {
+    /// `swap` that runs once per `key`, repeating the key with the same request returns the
+    /// first result. Failed swaps are not recorded and can be retried.
+    ///
+    /// # Errors
+    ///
+    /// Returns `IdempotencyKeyReused` for a key used by a different request, otherwise fails
+    /// like `swap`.
+    pub fn swap_idempotent(
+        &mut self,
+        key: [u8; 16],
+        staked_tokens_to_swap: StakedTokenAmount,
+    ) -> Result<TokenAmount> {
+        match self.executed(key) {
+            Some(Executed::Swap {
+                staked_tokens,
+                tokens_out,
+            }) if staked_tokens == staked_tokens_to_swap => Ok(tokens_out),
+            Some(_) => Err(self.key_reused(key)),
+            None => {
+                let tokens_out = self.swap(staked_tokens_to_swap)?;
+                self.record(
+                    key,
+                    Executed::Swap {
+                        staked_tokens: staked_tokens_to_swap,
+                        tokens_out,
+                    },
+                );
+                Ok(tokens_out)
+            }
+        }
+    }
+
+    /// `add_liquidity` that runs once per `key`, see `swap_idempotent`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `IdempotencyKeyReused` for a key used by a different request, otherwise fails
+    /// like `add_liquidity`.
+    pub fn add_liquidity_idempotent(
+        &mut self,
+        key: [u8; 16],
+        tokens_to_add: TokenAmount,
+    ) -> Result<LpTokenAmount> {
+        match self.executed(key) {
+            Some(Executed::AddLiquidity { tokens, lp_tokens }) if tokens == tokens_to_add => {
+                Ok(lp_tokens)
+            }
+            Some(_) => Err(self.key_reused(key)),
+            None => {
+                let lp_tokens = self.add_liquidity(tokens_to_add)?;
+                self.record(
+                    key,
+                    Executed::AddLiquidity {
+                        tokens: tokens_to_add,
+                        lp_tokens,
+                    },
+                );
+                Ok(lp_tokens)
+            }
+        }
+    }
+
+    /// `remove_liquidity` that runs once per `key`, see `swap_idempotent`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `IdempotencyKeyReused` for a key used by a different request, otherwise fails
+    /// like `remove_liquidity`.
+    pub fn remove_liquidity_idempotent(
+        &mut self,
+        key: [u8; 16],
+        lp_tokens_to_remove: LpTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount)> {
+        match self.executed(key) {
+            Some(Executed::RemoveLiquidity {
+                lp_tokens,
+                tokens,
+                staked_tokens,
+            }) if lp_tokens == lp_tokens_to_remove => Ok((tokens, staked_tokens)),
+            Some(_) => Err(self.key_reused(key)),
+            None => {
+                let (tokens, staked_tokens) = self.remove_liquidity(lp_tokens_to_remove)?;
+                self.record(
+                    key,
+                    Executed::RemoveLiquidity {
+                        lp_tokens: lp_tokens_to_remove,
+                        tokens,
+                        staked_tokens,
+                    },
+                );
+                Ok((tokens, staked_tokens))
+            }
+        }
+    }
+
+    /// Forgets keys of operations executed before `before_sequence`, retrying them after
+    /// this executes them again.
+    pub fn prune_keys(&mut self, before_sequence: u64) {
+        self.executed_keys
+            .retain(|_, executed| executed.sequence >= before_sequence);
+    }
+
+    #[must_use]
+    pub fn idempotency_key_count(&self) -> usize {
+        self.executed_keys.len()
+    }
+
+    fn executed(&self, key: [u8; 16]) -> Option<Executed> {
+        self.executed_keys
+            .get(&key)
+            .map(|executed| executed.executed)
+    }
+
+    fn key_reused(&self, key: [u8; 16]) -> Error {
+        let sequence = self
+            .executed_keys
+            .get(&key)
+            .map_or(0, |executed| executed.sequence);
+        Error::LpPool(LpPoolError::IdempotencyKeyReused { sequence })
+    }
+
+    fn record(&mut self, key: [u8; 16], executed: Executed) {
+        let sequence = self.sequence;
+        self.executed_keys
+            .insert(key, ExecutedKey { sequence, executed });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PoolFixture;
+
+    fn pool() -> LpPool {
+        PoolFixture::new()
+            .price_points(100)
+            .fees(10, 900)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool
+    }
+
+    #[test]
+    fn it_returns_first_result_for_retried_operations() {
+        let mut lp_pool = pool();
+        let staked = StakedTokenAmount::from_lamports(5_000);
+
+        let tokens_out = lp_pool.swap_idempotent([1; 16], staked).unwrap();
+        let state = lp_pool.state();
+        assert_eq!(lp_pool.swap_idempotent([1; 16], staked), Ok(tokens_out));
+        assert_eq!(lp_pool.state(), state);
+
+        let tokens = TokenAmount::from_lamports(10_000);
+        let lp_tokens = lp_pool.add_liquidity_idempotent([2; 16], tokens).unwrap();
+        let state = lp_pool.state();
+        assert_eq!(
+            lp_pool.add_liquidity_idempotent([2; 16], tokens),
+            Ok(lp_tokens)
+        );
+        assert_eq!(lp_pool.state(), state);
+
+        let removed = lp_pool
+            .remove_liquidity_idempotent([3; 16], lp_tokens)
+            .unwrap();
+        let state = lp_pool.state();
+        assert_eq!(
+            lp_pool.remove_liquidity_idempotent([3; 16], lp_tokens),
+            Ok(removed)
+        );
+        assert_eq!(lp_pool.state(), state);
+        assert_eq!(lp_pool.idempotency_key_count(), 3);
+    }
+
+    #[test]
+    fn it_rejects_key_reused_for_different_request() {
+        let mut lp_pool = pool();
+        lp_pool
+            .swap_idempotent([1; 16], StakedTokenAmount::from_lamports(5_000))
+            .unwrap();
+        let state = lp_pool.state();
+
+        assert_eq!(
+            lp_pool.swap_idempotent([1; 16], StakedTokenAmount::from_lamports(6_000)),
+            Err(Error::LpPool(LpPoolError::IdempotencyKeyReused {
+                sequence: lp_pool.sequence()
+            }))
+        );
+        assert_eq!(
+            lp_pool.add_liquidity_idempotent([1; 16], TokenAmount::from_lamports(1)),
+            Err(Error::LpPool(LpPoolError::IdempotencyKeyReused {
+                sequence: lp_pool.sequence()
+            }))
+        );
+        assert_eq!(lp_pool.state(), state);
+    }
+
+    #[test]
+    fn it_executes_again_after_failure_or_prune() {
+        let mut lp_pool = pool();
+        let too_large = StakedTokenAmount::from_lamports(1_000_000);
+        assert!(lp_pool.swap_idempotent([1; 16], too_large).is_err());
+        assert_eq!(lp_pool.idempotency_key_count(), 0);
+
+        let staked = StakedTokenAmount::from_lamports(1_000);
+        lp_pool.swap_idempotent([1; 16], staked).unwrap();
+        lp_pool.swap_idempotent([2; 16], staked).unwrap();
+        let state = lp_pool.state();
+
+        lp_pool.prune_keys(lp_pool.sequence());
+        assert_eq!(lp_pool.idempotency_key_count(), 1);
+        lp_pool.swap_idempotent([1; 16], staked).unwrap();
+        assert_ne!(lp_pool.state(), state);
+    }
+}
@@ -0,0 +1,365 @@
+//! Cost basis of each provider's LP tokens, kept as one lot per deposit and consumed first
+//! in, first out by withdrawals.
+
+use crate::calc::proportional;
+use crate::error::{Error, Result};
+use crate::lp_pool::{
+    data::{
+        price::Price,
+        token::{LpTokenAmount, SignedTokenAmount, TokenAmount},
+    },
+    error::Error as LpPoolError,
+    overflow,
+    quote::RemovedLiquidity,
+    tiers::ProviderId,
+    LpPool,
+};
+
+/// LP tokens minted to a provider by one deposit, and what they cost.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Lot {
+    pub lp_tokens: LpTokenAmount,
+    /// Tokens deposited for `lp_tokens`.
+    pub cost_basis: TokenAmount,
+    /// Pool price at the deposit.
+    pub price: Price,
+    /// `LpPool::sequence` after the deposit.
+    pub sequence: u64,
+    /// `LpPool::epoch` of the deposit.
+    pub epoch: u64,
+}
+
+impl Lot {
+    /// First epoch the lot can be withdrawn in, `holding_period_epochs` after its deposit.
+    #[must_use]
+    pub fn unlock_epoch(&self, holding_period_epochs: u32) -> u64 {
+        self.epoch.saturating_add(holding_period_epochs.into())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LotConsumption {
+    /// The lot before the withdrawal.
+    pub lot: Lot,
+    pub lp_tokens: LpTokenAmount,
+    /// Share of the lot's cost basis for `lp_tokens`, rounded down. The rest stays with the
+    /// lot, so a lot split in two keeps every lamport of its cost basis.
+    pub cost_basis: TokenAmount,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RealizedReport {
+    pub removed: RemovedLiquidity,
+    /// `removed` valued at the pool price, the staked tokens rounded down.
+    pub proceeds: TokenAmount,
+    pub cost_basis: TokenAmount,
+    pub realized_pnl: SignedTokenAmount,
+    /// Oldest lot first.
+    pub lots_consumed: Vec<LotConsumption>,
+}
+
+impl LpPool {
+    /// Lots are only withdrawn by `remove_liquidity_for` from the epoch `epochs` after their
+    /// deposit, zero to withdraw right away. Anonymous `remove_liquidity` burns LP tokens
+    /// regardless of lots and is unaffected: a pool enforcing the period must only let
+    /// providers withdraw through `remove_liquidity_for`.
+    #[must_use]
+    pub fn with_min_lp_holding_period(mut self, epochs: u32) -> Self {
+        self.min_lp_holding_period_epochs = epochs;
+        self
+    }
+
+    /// `add_liquidity`, opening a lot for `provider` with the LP tokens minted.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `add_liquidity`.
+    pub fn add_liquidity_for(
+        &mut self,
+        provider: ProviderId,
+        tokens_to_add: TokenAmount,
+    ) -> Result<LpTokenAmount> {
+        let lp_tokens = self.add_liquidity(tokens_to_add)?;
+        if lp_tokens != LpTokenAmount::from_lamports(0) {
+            self.lots.entry(provider).or_default().push(Lot {
+                lp_tokens,
+                cost_basis: tokens_to_add,
+                price: self.price,
+                sequence: self.sequence,
+                epoch: self.epoch,
+            });
+        }
+        Ok(lp_tokens)
+    }
+
+    /// `remove_liquidity`, consuming `provider`'s open lots oldest first. More LP tokens than
+    /// the open lots hold fail with `InsufficientLpTokens`, reaching a lot still in its
+    /// holding period with `HoldingPeriodNotMet`. A failed withdrawal leaves the lots as they
+    /// were.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InsufficientLpTokens` or `HoldingPeriodNotMet` as above, otherwise fails like
+    /// `remove_liquidity`.
+    pub fn remove_liquidity_for(
+        &mut self,
+        provider: ProviderId,
+        lp_tokens_to_remove: LpTokenAmount,
+    ) -> Result<RealizedReport> {
+        self.ensure_not_empty()?;
+        let mut lots = self.lots.get(&provider).cloned().unwrap_or_default();
+        let mut remaining = lp_tokens_to_remove;
+        let mut lots_consumed = Vec::new();
+        let mut cost_basis = TokenAmount::from_lamports(0);
+        for lot in &mut lots {
+            if remaining == LpTokenAmount::from_lamports(0) {
+                break;
+            }
+            let unlock_epoch = lot.unlock_epoch(self.min_lp_holding_period_epochs);
+            if self.epoch < unlock_epoch {
+                return Err(Error::LpPool(LpPoolError::HoldingPeriodNotMet {
+                    unlock_epoch,
+                }));
+            }
+            let lp_tokens = remaining.min(lot.lp_tokens);
+            let lot_cost = if lp_tokens == lot.lp_tokens {
+                lot.cost_basis
+            } else {
+                TokenAmount::from_lamports(proportional(
+                    lot.cost_basis.into(),
+                    lp_tokens.into(),
+                    lot.lp_tokens.into(),
+                )?)
+            };
+            lots_consumed.push(LotConsumption {
+                lot: *lot,
+                lp_tokens,
+                cost_basis: lot_cost,
+            });
+            cost_basis = cost_basis
+                .checked_add(lot_cost)
+                .ok_or_else(|| overflow("cost_basis + lot cost", cost_basis, lot_cost))?;
+            lot.lp_tokens -= lp_tokens;
+            lot.cost_basis -= lot_cost;
+            remaining -= lp_tokens;
+        }
+        if remaining != LpTokenAmount::from_lamports(0) {
+            return Err(Error::LpPool(LpPoolError::InsufficientLpTokens {
+                requested: lp_tokens_to_remove,
+                available: lp_tokens_to_remove - remaining,
+            }));
+        }
+        lots.retain(|lot| lot.lp_tokens != LpTokenAmount::from_lamports(0));
+
+        let quote = self.quote_remove_liquidity(lp_tokens_to_remove)?;
+        let removed = quote.amount_out;
+        let staked_value = TokenAmount::from_staked_tokens(removed.staked_tokens, self.price)?;
+        let proceeds = removed
+            .tokens
+            .checked_add(staked_value)
+            .ok_or_else(|| overflow("tokens + staked value", removed.tokens, staked_value))?;
+        self.execute_remove_liquidity_quote(&quote)?;
+
+        if lots.is_empty() {
+            self.lots.remove(&provider);
+        } else {
+            self.lots.insert(provider, lots);
+        }
+        Ok(RealizedReport {
+            removed,
+            proceeds,
+            cost_basis,
+            realized_pnl: TokenAmount::signed_diff(proceeds, cost_basis),
+            lots_consumed,
+        })
+    }
+
+    /// `provider`'s lots not yet withdrawn, oldest first.
+    pub fn open_lots(&self, provider: ProviderId) -> &[Lot] {
+        self.lots.get(&provider).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lp_pool::data::token::StakedTokenAmount;
+    use crate::test_utils::PoolFixture;
+
+    const ALICE: ProviderId = ProviderId(1);
+
+    fn lamports(amount: u64) -> TokenAmount {
+        TokenAmount::from_lamports(amount)
+    }
+
+    fn lp(amount: u64) -> LpTokenAmount {
+        LpTokenAmount::from_lamports(amount)
+    }
+
+    #[test]
+    fn it_attributes_withdrawal_to_lots_first_in_first_out() {
+        let mut lp_pool = PoolFixture::new()
+            .price("1.0")
+            .fees(0, 0)
+            .target_lamports(1_000_000)
+            .build()
+            .pool;
+        assert_eq!(
+            lp_pool.add_liquidity_for(ALICE, lamports(100_000)),
+            Ok(lp(100_000))
+        );
+        let first = lp_pool.open_lots(ALICE)[0];
+        // 50_000 staked tokens repriced to 1.2 put the pool at 110_000, 1.1 per LP token
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(50_000))
+            .unwrap();
+        lp_pool.update_price("1.2".parse().unwrap()).unwrap();
+        assert_eq!(
+            lp_pool.add_liquidity_for(ALICE, lamports(55_000)),
+            Ok(lp(50_000))
+        );
+        let second = Lot {
+            lp_tokens: lp(50_000),
+            cost_basis: lamports(55_000),
+            price: "1.2".parse().unwrap(),
+            sequence: lp_pool.sequence(),
+            epoch: 0,
+        };
+        assert_eq!(lp_pool.open_lots(ALICE).get(1), Some(&second));
+
+        let report = lp_pool.remove_liquidity_for(ALICE, lp(120_000)).unwrap();
+
+        // 120_000 of 150_000 LP tokens: 4/5 of 105_000 tokens and of 50_000 staked tokens
+        assert_eq!(
+            report.removed,
+            RemovedLiquidity {
+                tokens: lamports(84_000),
+                staked_tokens: StakedTokenAmount::from_lamports(40_000),
+            }
+        );
+        assert_eq!(report.proceeds, lamports(84_000 + 48_000));
+        assert_eq!(
+            report.lots_consumed,
+            vec![
+                LotConsumption {
+                    lot: first,
+                    lp_tokens: lp(100_000),
+                    cost_basis: lamports(100_000),
+                },
+                // 20_000 of 50_000 LP tokens that cost 55_000
+                LotConsumption {
+                    lot: second,
+                    lp_tokens: lp(20_000),
+                    cost_basis: lamports(22_000),
+                },
+            ]
+        );
+        assert_eq!(report.cost_basis, lamports(122_000));
+        assert_eq!(
+            report.realized_pnl,
+            SignedTokenAmount::from_lamports(10_000)
+        );
+        assert_eq!(
+            lp_pool.open_lots(ALICE),
+            [Lot {
+                lp_tokens: lp(30_000),
+                cost_basis: lamports(33_000),
+                ..second
+            }]
+        );
+    }
+
+    #[test]
+    fn it_splits_lot_without_losing_lamports() {
+        let mut lp_pool = PoolFixture::new().price("1.0").build().pool;
+        lp_pool.add_liquidity_for(ALICE, lamports(1_000)).unwrap();
+        let mut withdrawn_cost = 0;
+
+        for lp_tokens in [333, 333, 334] {
+            let report = lp_pool.remove_liquidity_for(ALICE, lp(lp_tokens)).unwrap();
+            withdrawn_cost += u64::from(report.cost_basis);
+        }
+
+        assert_eq!(withdrawn_cost, 1_000);
+        assert_eq!(lp_pool.open_lots(ALICE), []);
+    }
+
+    #[test]
+    fn it_rejects_withdrawal_beyond_open_lots_leaving_them_open() {
+        let mut lp_pool = PoolFixture::new()
+            .price("1.0")
+            .seeded_with_lamports(10_000)
+            .build()
+            .pool;
+        lp_pool.add_liquidity_for(ALICE, lamports(1_000)).unwrap();
+        let lots = lp_pool.open_lots(ALICE).to_vec();
+        let state = lp_pool.state();
+
+        assert_eq!(
+            lp_pool.remove_liquidity_for(ALICE, lp(1_001)),
+            Err(Error::LpPool(LpPoolError::InsufficientLpTokens {
+                requested: lp(1_001),
+                available: lp(1_000),
+            }))
+        );
+        assert_eq!(
+            lp_pool.remove_liquidity_for(ProviderId(2), lp(1)),
+            Err(Error::LpPool(LpPoolError::InsufficientLpTokens {
+                requested: lp(1),
+                available: lp(0),
+            }))
+        );
+        assert_eq!(lp_pool.open_lots(ALICE), lots);
+        assert_eq!(lp_pool.state(), state);
+    }
+
+    #[test]
+    fn it_withdraws_lots_from_end_of_holding_period() {
+        let mut lp_pool = PoolFixture::new()
+            .price("1.0")
+            .fees(0, 0)
+            .seeded_with_lamports(10_000)
+            .build()
+            .pool
+            .with_min_lp_holding_period(2);
+        lp_pool.add_liquidity_for(ALICE, lamports(1_000)).unwrap();
+        lp_pool.advance_epoch().unwrap();
+        let locked = Err(Error::LpPool(LpPoolError::HoldingPeriodNotMet {
+            unlock_epoch: 2,
+        }));
+
+        assert_eq!(lp_pool.remove_liquidity_for(ALICE, lp(1)), locked);
+        lp_pool.add_liquidity_for(ALICE, lamports(500)).unwrap();
+        lp_pool.advance_epoch().unwrap();
+        // the first lot unlocks at epoch 2, the second one at epoch 3
+        assert_eq!(
+            lp_pool.remove_liquidity_for(ALICE, lp(1_001)),
+            Err(Error::LpPool(LpPoolError::HoldingPeriodNotMet {
+                unlock_epoch: 3,
+            }))
+        );
+        let report = lp_pool.remove_liquidity_for(ALICE, lp(1_000)).unwrap();
+        assert_eq!(report.lots_consumed.len(), 1);
+        assert_eq!(report.lots_consumed[0].lot.epoch, 0);
+        assert_eq!(lp_pool.open_lots(ALICE)[0].lp_tokens, lp(500));
+
+        // anonymous withdrawals ignore lots
+        assert!(lp_pool.remove_liquidity(lp(500)).is_ok());
+        lp_pool.advance_epoch().unwrap();
+        assert!(lp_pool.remove_liquidity_for(ALICE, lp(500)).is_ok());
+    }
+
+    #[test]
+    fn it_clears_lots_when_all_liquidity_is_removed() {
+        let mut lp_pool = PoolFixture::new()
+            .fees(0, 0)
+            .target_lamports(1_000_000)
+            .build()
+            .pool;
+        lp_pool.add_liquidity_for(ALICE, lamports(1_000)).unwrap();
+
+        lp_pool.remove_all_liquidity().unwrap();
+
+        assert_eq!(lp_pool.open_lots(ALICE), &[]);
+    }
+}
@@ -1,43 +1,292 @@
+#[cfg(feature = "audit")]
+pub mod audit;
+pub mod bulk;
+#[cfg(feature = "std")]
+pub mod clock;
+pub mod consts;
 pub mod data;
 pub mod error;
+#[cfg(feature = "std")]
+pub mod events;
+#[cfg(feature = "std")]
+pub mod history;
+#[cfg(feature = "std")]
+pub mod idempotency;
+#[cfg(feature = "std")]
+pub mod lots;
+pub mod operation;
+#[cfg(feature = "std")]
+pub mod price_history;
+pub mod quote;
+#[cfg(feature = "std")]
+pub mod schedule;
+#[cfg(feature = "std")]
+pub mod shared;
+pub mod state;
+#[cfg(feature = "std")]
+pub mod status;
+pub mod store;
+#[cfg(feature = "proptest")]
+pub mod strategies;
+#[cfg(feature = "std")]
+pub mod tiers;
+pub mod twap;
+#[cfg(feature = "std")]
+pub mod unstake;
+pub mod view;
 
 use crate::lp_pool::data::{
-    fee::Fee,
-    token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    fee::{Rounding, WithdrawalFeeMode},
+    token::SignedTokenAmount,
 };
 
-use crate::{
-    calc::proportional,
-    error::{Error, Result},
-};
-use crate::{calc::value_from_shares, lp_pool::error::Error as LpPoolError};
+use crate::calc::{shares_from_value, value_from_shares};
+use crate::error::{Error, Result};
+use crate::lp_pool::error::InvariantViolation;
 
-use self::data::price::Price;
+use self::consts::{LAMPORTS_PER_SOL, MAX_BASIS_POINTS};
+use self::quote::{Quote, RemovedLiquidity};
+
+// The types most callers need, also under their defining modules.
+pub use self::data::{
+    fee::{Fee, FeeRange},
+    price::Price,
+    token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+};
+pub use self::error::Error as LpPoolError;
+pub use self::state::PoolState;
 
+/// Pool of tokens that LPs deposit and swappers buy with staked tokens.
+///
+/// A pool with no LP supply, like one fresh from `init`, only accepts deposits, which mint
+/// LP tokens 1:1. Every other operation or quote on LP liquidity returns `EmptyPool`.
+/// Configuration, price and epoch updates, read-only metrics and paying out balances that
+/// do not belong to LPs (referral fees, dust, unstake tickets) work regardless. New
+/// methods are added to `empty_pool_tests` with their behavior.
+#[derive(Clone, Debug)]
 pub struct LpPool {
     price: Price,
     token_amount: TokenAmount,
     staked_token_amount: StakedTokenAmount,
     lp_token_amount: LpTokenAmount,
     liquidity_target: TokenAmount,
-    min_fee: Fee,
-    max_fee: Fee,
+    fee_range: FeeRange,
+    max_swap_size: Option<StakedTokenAmount>,
+    token_cap: Option<TokenAmount>,
+    min_reserve: Option<TokenAmount>,
+    surplus_threshold: Option<TokenAmount>,
+    withdrawal_fee: Option<Fee>,
+    withdrawal_fee_mode: WithdrawalFeeMode,
+    /// Swap and withdrawal fees kept in `token_amount` for the LPs, counted until outflows
+    /// take `token_amount` below them.
+    fees_collected: TokenAmount,
+    max_referral_fee: Fee,
+    referral_fees_owed: TokenAmount,
+    dust: TokenAmount,
+    /// Management fees charged by `advance_epoch` and not yet claimed, held in
+    /// `token_amount` like `referral_fees_owed`.
+    protocol_fees_owed: TokenAmount,
+    dust_policy: DustPolicy,
+    dust_mode: DustMode,
+    min_output: TokenAmount,
+    sequence: u64,
+    management_fee: Fee,
+    incentive_bps_at_empty: Option<u32>,
+    epoch: u64,
+    price_cumulative: u128,
+    price_timestamp: Option<u64>,
+    #[cfg(feature = "std")]
+    unstake_queue: unstake::UnstakeQueue,
+    #[cfg(feature = "std")]
+    fee_tiers: Option<tiers::FeeTiers>,
+    #[cfg(feature = "std")]
+    provider_volume: std::collections::HashMap<tiers::ProviderId, TokenAmount>,
+    #[cfg(feature = "std")]
+    lots: std::collections::HashMap<tiers::ProviderId, Vec<lots::Lot>>,
+    #[cfg(feature = "std")]
+    min_lp_holding_period_epochs: u32,
+    #[cfg(feature = "std")]
+    executed_keys: std::collections::HashMap<[u8; 16], idempotency::ExecutedKey>,
+    #[cfg(feature = "std")]
+    history: history::History,
+    #[cfg(feature = "std")]
+    price_history: Option<price_history::PriceHistory>,
+    #[cfg(feature = "std")]
+    subscribers: events::Subscribers,
+    #[cfg(feature = "std")]
+    clock: Option<std::sync::Arc<dyn clock::Clock>>,
+    #[cfg(feature = "audit")]
+    audit: audit::AuditReport,
 }
 
-impl LpPool {
-    pub fn init(
-        price: Price,
-        min_fee: Fee,
-        max_fee: Fee,
-        liquidity_target: TokenAmount,
-    ) -> Result<Self> {
-        if min_fee > max_fee {
-            return Err(Error::LpPool(LpPoolError::MinFeeGreaterThanMaxFee {
-                min: min_fee,
-                max: max_fee,
-            }));
-        }
+/// Operations shared by pools of any curve.
+pub trait Pool {
+    /// Deposits `tokens_to_add`, returning the LP tokens minted.
+    ///
+    /// # Errors
+    ///
+    /// Returns the pool's error for a deposit it does not accept, `EmptyPool` for a pool
+    /// that only takes its first deposit in both tokens.
+    fn add_liquidity(&mut self, tokens_to_add: TokenAmount) -> Result<LpTokenAmount>;
+    /// Burns `lp_tokens_to_remove`, returning the tokens and staked tokens paid out.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` without LP supply, `InsufficientLpTokens` for more than the
+    /// supply, or the pool's error for a withdrawal it cannot pay.
+    fn remove_liquidity(
+        &mut self,
+        lp_tokens_to_remove: LpTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount)>;
+    /// Swaps `staked_tokens_to_swap` for tokens.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `quote_swap`.
+    fn swap(&mut self, staked_tokens_to_swap: StakedTokenAmount) -> Result<TokenAmount>;
+    /// Tokens `swap` would pay out for `staked_tokens_to_swap`, without changing the pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` without LP supply, or the pool's error for a swap it cannot fill.
+    fn quote_swap(&self, staked_tokens_to_swap: StakedTokenAmount) -> Result<TokenAmount>;
+    fn state(&self) -> PoolState;
+}
+
+/// Who owns the lamports that rounding leaves unclaimed by any LP token.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DustPolicy {
+    /// Dust is counted but stays with the remaining LPs.
+    #[default]
+    AccrueToLps,
+    /// Dust is set aside from the LP balance and can be withdrawn with `collect_dust`.
+    Collectable,
+}
+
+/// Whether swaps and withdrawals may pay out nothing, unlike `DustPolicy` it concerns what
+/// the user receives, not what rounding leaves in the pool.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DustMode {
+    /// Outputs below `min_output`, and at least zero outputs, fail with `OutputBelowMinimum`.
+    #[default]
+    RejectZeroOutput,
+    /// Any output is paid, an input too small to be worth a lamport is absorbed by the pool.
+    AllowZeroOutput,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SwapWithReferralResult {
+    pub tokens_out: TokenAmount,
+    pub pool_fee: TokenAmount,
+    pub referral_fee: TokenAmount,
+}
+
+/// Value of one whole LP token, see `LpPool::lp_price`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LpPrice {
+    pub tokens: TokenAmount,
+    /// `tokens` in staked tokens at the current price.
+    pub staked_tokens: StakedTokenAmount,
+}
+
+/// LP token value and swap fee on either side of `LpPool::reconfigure`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ReconfigReport {
+    /// `None` for a pool without LP supply.
+    pub lp_price_before: Option<LpPrice>,
+    pub lp_price_after: Option<LpPrice>,
+    /// Fee of swapping `REFERENCE_SWAP` at the pool's current balance.
+    pub swap_fee_before: Fee,
+    pub swap_fee_after: Fee,
+}
+
+impl ReconfigReport {
+    /// One whole staked token.
+    pub const REFERENCE_SWAP: StakedTokenAmount =
+        StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL);
+}
+
+/// Result of `swap_partial`, `consumed_staked + unfilled_staked` is the requested input.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PartialSwap {
+    pub consumed_staked: StakedTokenAmount,
+    pub tokens_out: TokenAmount,
+    pub unfilled_staked: StakedTokenAmount,
+}
+
+/// What changed when the pool moved to a new epoch.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct EpochSummary {
+    pub epoch: u64,
+    /// LP-owned tokens moved to `protocol_fees_owed`.
+    pub management_fee: TokenAmount,
+    /// Value of the unstake tickets becoming claimable, always zero without `std`.
+    pub tickets_maturing: TokenAmount,
+}
 
+/// Settings `LpPool::init` creates a pool with. The fee bounds are validated by
+/// `FeeRange::new` before a pool is involved, or by `FeeRange::checked_new` at compile time:
+///
+/// ```
+/// use liquidity_pool::lp_pool::{
+///     consts::LAMPORTS_PER_SOL,
+///     data::{fee::{Fee, FeeRange}, price::Price, token::TokenAmount},
+///     LpPool, PoolParams,
+/// };
+///
+/// const fn params(points: u64, min_bps: u32, max_bps: u32, target_sol: u64) -> PoolParams {
+///     PoolParams {
+///         price: Price::checked_from_points(points).unwrap(),
+///         fee_range: FeeRange::checked_new(
+///             Fee::from_basis_points(min_bps),
+///             Fee::from_basis_points(max_bps),
+///         )
+///         .unwrap(),
+///         liquidity_target: TokenAmount::from_lamports(target_sol * LAMPORTS_PER_SOL),
+///     }
+/// }
+///
+/// static POOLS: [(&str, PoolParams); 2] = [
+///     ("main", params(150, 10, 900, 90)),
+///     ("small", params(100, 30, 300, 5)),
+/// ];
+///
+/// for (_, params) in POOLS {
+///     LpPool::init(params).unwrap();
+/// }
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PoolParams {
+    pub price: Price,
+    pub fee_range: FeeRange,
+    pub liquidity_target: TokenAmount,
+}
+
+/// Balances left in a closed pool.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PoolRemains {
+    pub fees_collected: TokenAmount,
+    /// Tokens beyond the referral and protocol fees owed that no LP token could claim.
+    pub token_dust: TokenAmount,
+    pub staked_token_amount: StakedTokenAmount,
+    pub referral_fees_owed: TokenAmount,
+    pub protocol_fees_owed: TokenAmount,
+}
+
+impl LpPool {
+    /// Empty pool with the validated `params`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LiquidityTargetIncorrect` for a zero liquidity target and `PriceIncorrect`
+    /// for a zero price.
+    pub fn init(params: PoolParams) -> Result<Self> {
+        let PoolParams {
+            price,
+            fee_range,
+            liquidity_target,
+        } = params;
         if liquidity_target == TokenAmount::from_lamports(0) {
             return Err(Error::LpPool(LpPoolError::LiquidityTargetIncorrect(
                 liquidity_target,
@@ -50,205 +299,4444 @@ impl LpPool {
 
         Ok(Self {
             price,
-            min_fee,
-            max_fee,
+            fee_range,
             liquidity_target,
             token_amount: TokenAmount::from_lamports(0),
             staked_token_amount: StakedTokenAmount::from_lamports(0),
             lp_token_amount: LpTokenAmount::from_lamports(0),
+            max_swap_size: None,
+            token_cap: None,
+            min_reserve: None,
+            surplus_threshold: None,
+            withdrawal_fee: None,
+            withdrawal_fee_mode: WithdrawalFeeMode::UseCurve,
+            fees_collected: TokenAmount::from_lamports(0),
+            max_referral_fee: Fee::ZERO,
+            referral_fees_owed: TokenAmount::from_lamports(0),
+            dust: TokenAmount::from_lamports(0),
+            protocol_fees_owed: TokenAmount::from_lamports(0),
+            dust_policy: DustPolicy::AccrueToLps,
+            dust_mode: DustMode::RejectZeroOutput,
+            min_output: TokenAmount::from_lamports(0),
+            sequence: 0,
+            management_fee: Fee::ZERO,
+            incentive_bps_at_empty: None,
+            epoch: 0,
+            price_cumulative: 0,
+            price_timestamp: None,
+            #[cfg(feature = "std")]
+            unstake_queue: unstake::UnstakeQueue::default(),
+            #[cfg(feature = "std")]
+            fee_tiers: None,
+            #[cfg(feature = "std")]
+            provider_volume: std::collections::HashMap::new(),
+            #[cfg(feature = "std")]
+            lots: std::collections::HashMap::new(),
+            #[cfg(feature = "std")]
+            min_lp_holding_period_epochs: 0,
+            #[cfg(feature = "std")]
+            executed_keys: std::collections::HashMap::new(),
+            #[cfg(feature = "std")]
+            history: history::History::default(),
+            #[cfg(feature = "std")]
+            price_history: None,
+            #[cfg(feature = "std")]
+            subscribers: events::Subscribers::default(),
+            #[cfg(feature = "std")]
+            clock: None,
+            #[cfg(feature = "audit")]
+            audit: audit::AuditReport::default(),
         })
     }
 
-    pub fn add_liquidity(&mut self, tokens_to_add: TokenAmount) -> Result<LpTokenAmount> {
-        let token_amount_after =
-            TokenAmount::from_lamports(u64::from(self.token_amount) + u64::from(tokens_to_add));
-        let fee: Fee = self.calculate_fee(token_amount_after);
-        let tokens_with_fee = TokenAmount::from_lamports(fee.apply(tokens_to_add.into())?);
+    /// `init` with unvalidated fee bounds.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `FeeRange::new` and `init`.
+    #[deprecated(note = "pass `PoolParams` to `LpPool::init`, it validates the fee order")]
+    pub fn init_legacy(
+        price: Price,
+        min_fee: Fee,
+        max_fee: Fee,
+        liquidity_target: TokenAmount,
+    ) -> Result<Self> {
+        Self::init(PoolParams {
+            price,
+            fee_range: FeeRange::new(min_fee, max_fee)?,
+            liquidity_target,
+        })
+    }
 
-        self.token_amount += tokens_with_fee;
-        self.staked_token_amount += StakedTokenAmount::from_tokens(tokens_with_fee, self.price);
-        self.lp_token_amount += LpTokenAmount::from_tokens(tokens_with_fee);
-        Ok(LpTokenAmount::from_tokens(tokens_with_fee))
+    /// Pool with the balances of `state`, for taking over a pool that is already live. The
+    /// configuration is checked like `init`, the balances with `check_solvency`, and an LP
+    /// supply has to be backed by some value. Settings outside `PoolState` start at their
+    /// `init` defaults.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `FeeRange::new` and `init` on the configuration, and with `StateInvalid` for
+    /// balances failing `check_solvency` or an LP supply backed by nothing.
+    pub fn from_state(state: PoolState) -> Result<Self> {
+        let lp_pool = LpPool {
+            token_amount: state.token_amount,
+            staked_token_amount: state.staked_token_amount,
+            lp_token_amount: state.lp_token_amount,
+            fees_collected: state.fees_collected,
+            referral_fees_owed: state.referral_fees_owed,
+            dust: state.dust,
+            protocol_fees_owed: state.protocol_fees_owed,
+            ..LpPool::init(PoolParams {
+                price: state.price,
+                fee_range: FeeRange::new(state.min_fee, state.max_fee)?,
+                liquidity_target: state.liquidity_target,
+            })?
+        };
+        let invalid = |violation| Error::LpPool(LpPoolError::StateInvalid(violation));
+        lp_pool.check_solvency().map_err(invalid)?;
+        if lp_pool.lp_token_amount != LpTokenAmount::from_lamports(0)
+            && lp_pool.lp_attributable_value()? == TokenAmount::from_lamports(0)
+        {
+            return Err(invalid(InvariantViolation::LpSupplyWithoutValue {
+                lp_token_amount: lp_pool.lp_token_amount,
+            }));
+        }
+        Ok(lp_pool)
     }
 
-    pub fn remove_liquidity(
-        &mut self,
-        lp_tokens_to_remove: LpTokenAmount,
-    ) -> Result<(TokenAmount, StakedTokenAmount)> {
-        let tokens_without_fee = TokenAmount::from_lamports(value_from_shares(
-            lp_tokens_to_remove.into(),
-            self.token_amount.into(),
-            self.lp_token_amount.into(),
-        )?);
+    /// `set_max_swap_size` on a pool being built.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `set_max_swap_size`.
+    pub fn with_max_swap_size(mut self, max_swap_size: Option<StakedTokenAmount>) -> Result<Self> {
+        self.set_max_swap_size(max_swap_size)?;
+        Ok(self)
+    }
 
-        let token_amount_after = TokenAmount::from_lamports(
-            u64::from(self.token_amount) - u64::from(tokens_without_fee),
-        );
-        let fee: Fee = self.calculate_fee(token_amount_after);
+    /// `set_token_cap` on a pool being built.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `set_token_cap`.
+    pub fn with_token_cap(mut self, token_cap: Option<TokenAmount>) -> Result<Self> {
+        self.set_token_cap(token_cap)?;
+        Ok(self)
+    }
 
-        let tokens_with_fee: TokenAmount =
-            TokenAmount::from_lamports(fee.apply(tokens_without_fee.into())?);
-        let unstaked_tokens: StakedTokenAmount =
-            StakedTokenAmount::from_tokens(tokens_with_fee, self.price);
+    /// `set_min_reserve` on a pool being built.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `set_min_reserve`.
+    pub fn with_min_reserve(mut self, min_reserve: Option<TokenAmount>) -> Result<Self> {
+        self.set_min_reserve(min_reserve)?;
+        Ok(self)
+    }
 
-        self.token_amount -= tokens_with_fee;
-        self.staked_token_amount -= unstaked_tokens;
-        self.lp_token_amount -= lp_tokens_to_remove;
+    #[must_use]
+    pub fn with_surplus_threshold(mut self, surplus_threshold: Option<TokenAmount>) -> Self {
+        self.set_surplus_threshold(surplus_threshold);
+        self
+    }
 
-        Ok((tokens_with_fee, unstaked_tokens))
+    /// `set_withdrawal_fee` on a pool being built.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `set_withdrawal_fee`.
+    pub fn with_withdrawal_fee(
+        mut self,
+        withdrawal_fee: Option<Fee>,
+        mode: WithdrawalFeeMode,
+    ) -> Result<Self> {
+        self.set_withdrawal_fee(withdrawal_fee, mode)?;
+        Ok(self)
     }
 
-    pub fn swap(&mut self, staked_tokens_to_swap: StakedTokenAmount) -> Result<TokenAmount> {
-        let token_amount_after = TokenAmount::from_lamports(
-            u64::from(self.token_amount)
-                - self.price.mul_by_price(u64::from(staked_tokens_to_swap)),
-        );
-        let fee: Fee = self.calculate_fee(token_amount_after);
+    /// `set_max_referral_fee` on a pool being built.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `set_max_referral_fee`.
+    pub fn with_max_referral_fee(mut self, max_referral_fee: Fee) -> Result<Self> {
+        self.set_max_referral_fee(max_referral_fee)?;
+        Ok(self)
+    }
 
-        let tokens_without_fee = TokenAmount::from_staked_tokens(staked_tokens_to_swap, self.price);
-        let tokens_with_fee = TokenAmount::from_lamports(fee.apply(tokens_without_fee.into())?);
+    /// Meant to be set before the first deposit, dust counted under one policy is not
+    /// moved when switching to the other.
+    #[must_use]
+    pub fn with_dust_policy(mut self, dust_policy: DustPolicy) -> Self {
+        self.dust_policy = dust_policy;
+        self
+    }
 
-        self.token_amount -= tokens_with_fee;
-        self.staked_token_amount += staked_tokens_to_swap;
+    #[must_use]
+    pub fn with_dust_mode(mut self, dust_mode: DustMode, min_output: TokenAmount) -> Self {
+        self.set_dust_mode(dust_mode, min_output);
+        self
+    }
+
+    /// Share of `lp_attributable_value` moved to `protocol_fees_owed` on every
+    /// `advance_epoch`. It is paid in tokens, so at most the available tokens are charged.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `set_management_fee`.
+    pub fn with_management_fee(mut self, management_fee_per_epoch: Fee) -> Result<Self> {
+        self.set_management_fee(management_fee_per_epoch)?;
+        Ok(self)
+    }
 
-        Ok(tokens_with_fee)
+    /// `set_incentive_bps_at_empty` on a pool being built.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `set_incentive_bps_at_empty`.
+    pub fn with_incentive_bps_at_empty(
+        mut self,
+        incentive_bps_at_empty: Option<u32>,
+    ) -> Result<Self> {
+        self.set_incentive_bps_at_empty(incentive_bps_at_empty)?;
+        Ok(self)
     }
 
-    pub fn calculate_fee(&self, amount_after: TokenAmount) -> Fee {
-        if amount_after >= self.liquidity_target {
-            return Fee::from_basis_points(self.min_fee.basis_points);
+    /// Largest input `swap` accepts, `None` for no limit.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MaxSwapSizeIncorrect` for `Some(0)`.
+    pub fn set_max_swap_size(&mut self, max_swap_size: Option<StakedTokenAmount>) -> Result<()> {
+        if let Some(max) = max_swap_size.filter(|max| *max == StakedTokenAmount::from_lamports(0)) {
+            return Err(Error::LpPool(LpPoolError::MaxSwapSizeIncorrect(max)));
         }
+        self.max_swap_size = max_swap_size;
+        self.bump_sequence();
+        Ok(())
+    }
 
-        let fee_delta: u64 = u64::from(
-            self.max_fee
-                .basis_points
-                .saturating_sub(self.min_fee.basis_points),
-        );
-        let lamports: u64 = amount_after.into();
-        let target: u64 = self.liquidity_target.into();
+    /// A cap below the current balance is accepted, it only blocks further deposits.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TokenCapIncorrect` for `Some(0)`.
+    pub fn set_token_cap(&mut self, token_cap: Option<TokenAmount>) -> Result<()> {
+        if let Some(cap) = token_cap.filter(|cap| *cap == TokenAmount::from_lamports(0)) {
+            return Err(Error::LpPool(LpPoolError::TokenCapIncorrect(cap)));
+        }
+        self.token_cap = token_cap;
+        self.bump_sequence();
+        Ok(())
+    }
 
-        #[allow(clippy::cast_possible_truncation)]
-        Fee::from_basis_points(
-            self.max_fee.basis_points - proportional(fee_delta, lamports, target).unwrap() as u32,
-        )
+    /// Available tokens `swap` and `remove_liquidity` may not take the pool below, at most the
+    /// liquidity target. A floor above the current balance only blocks further outflows, and
+    /// withdrawing the whole LP supply ignores it so the last LP can always leave.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ReserveFloorIncorrect` for a floor above the liquidity target.
+    pub fn set_min_reserve(&mut self, min_reserve: Option<TokenAmount>) -> Result<()> {
+        if let Some(floor) = min_reserve.filter(|floor| *floor > self.liquidity_target) {
+            return Err(Error::LpPool(LpPoolError::ReserveFloorIncorrect {
+                floor,
+                liquidity_target: self.liquidity_target,
+            }));
+        }
+        self.min_reserve = min_reserve;
+        self.bump_sequence();
+        Ok(())
     }
-}
 
-mod tests {
-    #[allow(unused_imports)]
-    use super::*;
+    #[must_use]
+    pub fn min_reserve(&self) -> Option<TokenAmount> {
+        self.min_reserve
+    }
 
-    #[test]
-    fn it_returns_err_if_min_fee_greater_than_max_fee() {
-        let lp_pool = LpPool::init(
-            Price::try_from(10).unwrap(),
-            Fee::from_basis_points(101),
-            Fee::from_basis_points(100),
-            TokenAmount::from_lamports(100),
-        );
-        assert!(lp_pool.is_err());
+    /// How far above the liquidity target available tokens must stay for the fee to drop to
+    /// zero, below `min_fee`, so arbitrageurs rebalancing a pool with a large surplus swap for
+    /// free. Like the rest of the curve it is taken at the balance after the operation, a
+    /// swap starting above the line and ending below it pays the curve fee. `Some(0)` makes
+    /// everything at or above the target free, `None` keeps `min_fee` there.
+    pub fn set_surplus_threshold(&mut self, surplus_threshold: Option<TokenAmount>) {
+        self.surplus_threshold = surplus_threshold;
+        self.bump_sequence();
     }
 
-    #[test]
-    fn it_returns_err_if_liquidity_target_is_zero() {
-        let lp_pool = LpPool::init(
-            Price::try_from(10).unwrap(),
-            Fee::from_basis_points(10),
-            Fee::from_basis_points(100),
+    #[must_use]
+    pub fn surplus_threshold(&self) -> Option<TokenAmount> {
+        self.surplus_threshold
+    }
+
+    /// Smallest output `swap` and `remove_liquidity` pay under `DustMode::RejectZeroOutput`,
+    /// a withdrawal counting its staked tokens at the current price. `min_output` is ignored
+    /// with `DustMode::AllowZeroOutput`, and the last LP out is paid whatever is left.
+    pub fn set_dust_mode(&mut self, dust_mode: DustMode, min_output: TokenAmount) {
+        self.dust_mode = dust_mode;
+        self.min_output = min_output;
+        self.bump_sequence();
+    }
+
+    #[must_use]
+    pub fn dust_mode(&self) -> (DustMode, TokenAmount) {
+        (self.dust_mode, self.min_output)
+    }
+
+    /// Flat fee charged by `remove_liquidity`, ignored with `WithdrawalFeeMode::UseCurve`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` for a fee above 100%.
+    pub fn set_withdrawal_fee(
+        &mut self,
+        withdrawal_fee: Option<Fee>,
+        mode: WithdrawalFeeMode,
+    ) -> Result<()> {
+        if let Some(fee) = withdrawal_fee {
+            fee.check()?;
+        }
+        self.withdrawal_fee = withdrawal_fee;
+        self.withdrawal_fee_mode = mode;
+        self.bump_sequence();
+        Ok(())
+    }
+
+    /// Share of the LP value `advance_epoch` charges, see `with_management_fee`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` for a fee above 100%.
+    pub fn set_management_fee(&mut self, management_fee_per_epoch: Fee) -> Result<()> {
+        management_fee_per_epoch.check()?;
+        self.management_fee = management_fee_per_epoch;
+        self.bump_sequence();
+        Ok(())
+    }
+
+    /// Bonus on deposits into a pool below its liquidity target, in basis points of the
+    /// deposit when no tokens are available, see `deposit_bonus`. `None` turns it off.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` for more than 10 000 basis points.
+    pub fn set_incentive_bps_at_empty(
+        &mut self,
+        incentive_bps_at_empty: Option<u32>,
+    ) -> Result<()> {
+        if let Some(bps) = incentive_bps_at_empty {
+            Fee::from_basis_points(bps).check()?;
+        }
+        self.incentive_bps_at_empty = incentive_bps_at_empty;
+        self.bump_sequence();
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn incentive_bps_at_empty(&self) -> Option<u32> {
+        self.incentive_bps_at_empty
+    }
+
+    /// Largest `referral_fee` `swap_with_referral` accepts.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` for a fee above 100%.
+    pub fn set_max_referral_fee(&mut self, max_referral_fee: Fee) -> Result<()> {
+        max_referral_fee.check()?;
+        self.max_referral_fee = max_referral_fee;
+        self.bump_sequence();
+        Ok(())
+    }
+
+    /// Moves the pool to new fee bounds and liquidity target at once, validated like
+    /// `init` and against the reserve floor before anything changes. Balances are left
+    /// alone, so LP tokens are worth the same before and after; the price moves only
+    /// through `update_price`, a different `new.price` fails with
+    /// `ReconfigurePriceMismatch`. Outstanding quotes go stale.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ReconfigurePriceMismatch`, `LiquidityTargetIncorrect`, the errors of
+    /// `FeeRange::new`, `ReserveFloorIncorrect` or the errors of `lp_price`, leaving the pool
+    /// unchanged.
+    pub fn reconfigure(&mut self, new: PoolParams) -> Result<ReconfigReport> {
+        let PoolParams {
+            price,
+            fee_range,
+            liquidity_target,
+        } = new;
+        if price != self.price {
+            return Err(Error::LpPool(LpPoolError::ReconfigurePriceMismatch {
+                current: self.price,
+                requested: price,
+            }));
+        }
+        if liquidity_target == TokenAmount::from_lamports(0) {
+            return Err(Error::LpPool(LpPoolError::LiquidityTargetIncorrect(
+                liquidity_target,
+            )));
+        }
+        let fee_range = FeeRange::new(fee_range.min(), fee_range.max())?;
+        if let Some(floor) = self.min_reserve.filter(|floor| *floor > liquidity_target) {
+            return Err(Error::LpPool(LpPoolError::ReserveFloorIncorrect {
+                floor,
+                liquidity_target,
+            }));
+        }
+        // the report is taken on a copy, so a failure leaves the pool untouched
+        let mut reconfigured = self.clone();
+        reconfigured.fee_range = fee_range;
+        reconfigured.liquidity_target = liquidity_target;
+        let report = ReconfigReport {
+            lp_price_before: self.lp_price_if_minted()?,
+            lp_price_after: reconfigured.lp_price_if_minted()?,
+            swap_fee_before: self.reference_swap_fee()?,
+            swap_fee_after: reconfigured.reference_swap_fee()?,
+        };
+
+        self.fee_range = fee_range;
+        self.liquidity_target = liquidity_target;
+        self.bump_sequence();
+        Ok(report)
+    }
+
+    fn lp_price_if_minted(&self) -> Result<Option<LpPrice>> {
+        if self.lp_token_amount == LpTokenAmount::from_lamports(0) {
+            return Ok(None);
+        }
+        self.lp_price().map(Some)
+    }
+
+    /// Fee of swapping `ReconfigReport::REFERENCE_SWAP`, whether or not the pool could pay
+    /// it out.
+    fn reference_swap_fee(&self) -> Result<Fee> {
+        let value = self
+            .price
+            .checked_mul_by_price(ReconfigReport::REFERENCE_SWAP.into())
+            .map_or(
+                TokenAmount::from_lamports(u64::MAX),
+                TokenAmount::from_lamports,
+            );
+        let amount_after = self
+            .available_tokens()
+            .checked_sub(value)
+            .unwrap_or(TokenAmount::from_lamports(0));
+        self.calculate_fee(amount_after)
+    }
+
+    /// Mints LP tokens worth `tokens_to_add` of the pool value, valuing staked tokens at the
+    /// current price. Deposits are free, like in Marinade's liquidity pool, wherever the pool
+    /// stands against its liquidity target: the fee curve only prices swaps and withdrawals,
+    /// so a deposit lifting the pool past the target mints the same LP tokens as the same
+    /// amount split into smaller deposits, up to one lamport of rounding per deposit. With
+    /// `incentive_bps_at_empty` set, LP tokens for the `deposit_bonus` are minted on top.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PoolCapExceeded` for a deposit over the token cap and `ArithmeticOverflow`
+    /// when a balance would overflow.
+    pub fn add_liquidity(&mut self, tokens_to_add: TokenAmount) -> Result<LpTokenAmount> {
+        let deposit = self.deposit(tokens_to_add);
+        let Deposit {
+            token_amount,
+            lp_token_amount,
+            fees_collected,
+            lp_tokens,
+        } = self.log_rejection(operation::Operation::AddLiquidity(tokens_to_add), deposit)?;
+
+        #[cfg(feature = "audit")]
+        self.audit_deposit(
+            tokens_to_add + (self.fees_collected - fees_collected),
+            lp_tokens,
+        );
+        self.token_amount = token_amount;
+        self.lp_token_amount = lp_token_amount;
+        self.fees_collected = fees_collected;
+        self.bump_sequence();
+        #[cfg(feature = "std")]
+        self.record_history(
+            operation::Operation::AddLiquidity(tokens_to_add),
             TokenAmount::from_lamports(0),
         );
-        assert!(lp_pool.is_err());
+        self.debug_check_solvency();
+        Ok(lp_tokens)
     }
 
-    #[test]
-    fn it_returns_err_if_price_is_zero() {
-        let lp_pool = LpPool::init(
-            Price::from_points(0),
-            Fee::from_basis_points(10),
-            Fee::from_basis_points(100),
-            TokenAmount::from_lamports(100),
-        );
-        assert!(lp_pool.is_err());
+    /// What `add_liquidity` of `tokens_to_add` mints, without changing the pool.
+    ///
+    /// # Errors
+    ///
+    /// Fails exactly when `add_liquidity` would.
+    pub fn quote_add_liquidity(
+        &self,
+        tokens_to_add: TokenAmount,
+    ) -> Result<Quote<TokenAmount, LpTokenAmount>> {
+        self.view().quote_add_liquidity(tokens_to_add)
     }
 
-    #[test]
-    fn it_adds_liquidity_to_pool_and_returns_lp_tokens() {
-        let mut lp_pool = LpPool::init(
-            Price::try_from(10).unwrap(),
-            Fee::from_basis_points(10),
-            Fee::from_basis_points(100),
-            TokenAmount::from_lamports(100),
-        )
-        .unwrap();
-        let predicted_tokens: LpTokenAmount =
-            LpTokenAmount::from_tokens(TokenAmount::from_lamports(100));
-        let tokens: LpTokenAmount = lp_pool
-            .add_liquidity(TokenAmount::from_lamports(100))
-            .unwrap();
+    /// `add_liquidity` of the quoted amount, failing if the pool changed since the quote.
+    ///
+    /// # Errors
+    ///
+    /// Returns `StaleQuote` for a quote the pool no longer honors, otherwise fails like
+    /// `add_liquidity`.
+    pub fn execute_add_liquidity_quote(
+        &mut self,
+        quote: &Quote<TokenAmount, LpTokenAmount>,
+    ) -> Result<LpTokenAmount> {
+        let checked = self.check_quote(quote, self.quote_add_liquidity(quote.amount_in));
+        self.log_rejection(operation::Operation::AddLiquidity(quote.amount_in), checked)?;
+        self.add_liquidity(quote.amount_in)
+    }
 
-        assert_eq!(tokens, predicted_tokens);
-        assert_eq!(lp_pool.token_amount, TokenAmount::from_lamports(100));
-        assert_eq!(lp_pool.lp_token_amount, predicted_tokens);
-        assert_eq!(
-            lp_pool.staked_token_amount,
-            StakedTokenAmount::from_tokens(TokenAmount::from_lamports(100), lp_pool.price)
+    fn deposit(&self, tokens_to_add: TokenAmount) -> Result<Deposit> {
+        let token_amount_after = self
+            .token_amount
+            .checked_add(tokens_to_add)
+            .ok_or_else(|| {
+                overflow(
+                    "token_amount + tokens_to_add",
+                    self.token_amount,
+                    tokens_to_add,
+                )
+            })?;
+        if let Some(cap) = self.token_cap.filter(|cap| token_amount_after > *cap) {
+            return Err(Error::LpPool(LpPoolError::PoolCapExceeded {
+                amount_after: token_amount_after,
+                cap,
+                max_fillable_input: cap
+                    .checked_sub(self.token_amount)
+                    .unwrap_or(TokenAmount::from_lamports(0)),
+            }));
+        }
+        let bonus = self.deposit_bonus(tokens_to_add)?;
+        let credited = tokens_to_add
+            .checked_add(bonus)
+            .ok_or_else(|| overflow("tokens_to_add + bonus", tokens_to_add, bonus))?;
+        let lp_tokens = if self.lp_token_amount == LpTokenAmount::from_lamports(0) {
+            // deposits are free, the user receives the mint rounded down
+            LpTokenAmount::from_tokens_with_fee(credited, Fee::ZERO, Rounding::Down)?
+        } else {
+            LpTokenAmount::from_lamports(shares_from_value(
+                credited.into(),
+                self.lp_attributable_value()?.into(),
+                self.lp_token_amount.into(),
+            )?)
+        };
+        let lp_token_amount = self.lp_token_amount.checked_add(lp_tokens).ok_or_else(|| {
+            overflow(
+                "lp_token_amount + lp_tokens",
+                self.lp_token_amount,
+                lp_tokens,
+            )
+        })?;
+
+        Ok(Deposit {
+            token_amount: token_amount_after,
+            lp_token_amount,
+            // PANIC SAFETY: `deposit_bonus` is capped by `fees_collected`.
+            fees_collected: self.fees_collected - bonus,
+            lp_tokens,
+        })
+    }
+
+    /// Value a deposit of `tokens_to_add` is credited with on top of itself, paid from the
+    /// fee income the LPs earned and at most `fees_collected`, which it is taken off. The
+    /// rate falls from `incentive_bps_at_empty` with no available tokens to
+    /// zero at the liquidity target, the shape of the fee curve, taken at the balance before
+    /// the deposit and rounded down.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` for an incentive above 10 000 basis points.
+    pub fn deposit_bonus(&self, tokens_to_add: TokenAmount) -> Result<TokenAmount> {
+        let Some(bps) = self.incentive_bps_at_empty else {
+            return Ok(TokenAmount::from_lamports(0));
+        };
+        let rate = FeeRange::new(Fee::ZERO, Fee::from_basis_points(bps))?.interpolate(
+            self.available_tokens().into(),
+            self.liquidity_target.into(),
+            Rounding::Down,
         );
+        let bonus = TokenAmount::from_lamports(rate.fee_amount(tokens_to_add.into())?);
+        Ok(bonus.min(self.fees_collected))
     }
 
-    #[test]
-    fn it_removes_liquidity_from_pool_above_liquidity_target() {
-        let mut lp_pool = LpPool::init(
-            Price::from_points(100),
-            Fee::from_basis_points(10),
-            Fee::from_basis_points(100),
-            TokenAmount::from_lamports(50),
+    /// Burns `lp_tokens_to_remove` for their share of the available and staked tokens, the
+    /// token leg charged the withdrawal fee. Burning the whole supply pays no fee and ignores
+    /// the reserve floor and the dust mode, so the last LP can always leave.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` without LP supply, `InsufficientLpTokens` for more than the
+    /// supply, `ReserveFloorBreached` below the reserve floor, `OutputBelowMinimum` under the
+    /// dust mode, `BasisPointsOverflow` when an additional withdrawal fee takes the fee above
+    /// 100%, and `ArithmeticOverflow` when a balance would overflow.
+    pub fn remove_liquidity(
+        &mut self,
+        lp_tokens_to_remove: LpTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount)> {
+        let removed = self.execute_withdrawal(lp_tokens_to_remove);
+        self.log_rejection(
+            operation::Operation::RemoveLiquidity(lp_tokens_to_remove),
+            removed,
         )
-        .unwrap();
-        lp_pool
-            .add_liquidity(TokenAmount::from_lamports(100))
-            .unwrap();
-        let predicted_tokens = TokenAmount::from_lamports(lp_pool.min_fee.apply(10).unwrap());
+    }
 
-        let (tokens, staked_tokens) = lp_pool
-            .remove_liquidity(LpTokenAmount::from_lamports(10))
-            .unwrap();
+    fn execute_withdrawal(
+        &mut self,
+        lp_tokens_to_remove: LpTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount)> {
+        let withdrawal = self.withdrawal(lp_tokens_to_remove)?;
 
-        assert_eq!(tokens, predicted_tokens);
-        assert_eq!(
-            staked_tokens,
-            StakedTokenAmount::from_tokens(predicted_tokens, lp_pool.price)
+        #[cfg(feature = "audit")]
+        self.audit_withdrawal(lp_tokens_to_remove, &withdrawal);
+        self.token_amount = withdrawal.token_amount;
+        self.staked_token_amount = withdrawal.staked_token_amount;
+        self.lp_token_amount = withdrawal.lp_token_amount;
+        self.fees_collected = withdrawal.fees_collected;
+        self.dust = withdrawal.dust;
+        // no lot can be redeemed once the whole supply is burned
+        #[cfg(feature = "std")]
+        if self.lp_token_amount == LpTokenAmount::from_lamports(0) {
+            self.lots.clear();
+        }
+        self.bump_sequence();
+        #[cfg(feature = "std")]
+        self.record_history(
+            operation::Operation::RemoveLiquidity(lp_tokens_to_remove),
+            withdrawal.tokens_without_fee - withdrawal.tokens_with_fee,
         );
-        assert_eq!(
-            lp_pool.token_amount,
-            (TokenAmount::from_lamports(100) - predicted_tokens)
+        self.debug_check_solvency();
+
+        Ok((withdrawal.tokens_with_fee, withdrawal.unstaked_tokens))
+    }
+
+    /// `remove_liquidity` of the whole LP supply, paying out every LP-owned token and all
+    /// staked tokens without a fee, no LP is left to earn it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` without LP supply and `ArithmeticOverflow` when a balance would
+    /// overflow.
+    pub fn remove_all_liquidity(&mut self) -> Result<(TokenAmount, StakedTokenAmount)> {
+        self.remove_liquidity(self.lp_token_amount)
+    }
+
+    /// Consumes a pool with no LP supply left, returning what it still holds.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PoolNotEmpty` while LP tokens are outstanding.
+    pub fn close(self) -> Result<PoolRemains> {
+        if self.lp_token_amount != LpTokenAmount::from_lamports(0) {
+            return Err(Error::LpPool(LpPoolError::PoolNotEmpty(
+                self.lp_token_amount,
+            )));
+        }
+
+        Ok(PoolRemains {
+            fees_collected: self.fees_collected,
+            token_dust: self.available_tokens() + self.collectable_dust(),
+            staked_token_amount: self.staked_token_amount,
+            referral_fees_owed: self.referral_fees_owed,
+            protocol_fees_owed: self.protocol_fees_owed,
+        })
+    }
+
+    /// What `remove_liquidity` of `lp_tokens_to_remove` pays out, without changing the pool.
+    ///
+    /// # Errors
+    ///
+    /// Fails exactly when `remove_liquidity` would.
+    pub fn quote_remove_liquidity(
+        &self,
+        lp_tokens_to_remove: LpTokenAmount,
+    ) -> Result<Quote<LpTokenAmount, RemovedLiquidity>> {
+        self.view().quote_remove_liquidity(lp_tokens_to_remove)
+    }
+
+    /// `remove_liquidity` of the quoted amount, failing if the pool changed since the quote.
+    ///
+    /// # Errors
+    ///
+    /// Returns `StaleQuote` for a quote the pool no longer honors, otherwise fails like
+    /// `remove_liquidity`.
+    pub fn execute_remove_liquidity_quote(
+        &mut self,
+        quote: &Quote<LpTokenAmount, RemovedLiquidity>,
+    ) -> Result<(TokenAmount, StakedTokenAmount)> {
+        let checked = self.check_quote(quote, self.quote_remove_liquidity(quote.amount_in));
+        self.log_rejection(
+            operation::Operation::RemoveLiquidity(quote.amount_in),
+            checked,
+        )?;
+        self.remove_liquidity(quote.amount_in)
+    }
+
+    /// Swaps `staked_tokens_to_swap` for tokens at the current price less the curve fee.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `swap_with_referral`.
+    pub fn swap(&mut self, staked_tokens_to_swap: StakedTokenAmount) -> Result<TokenAmount> {
+        let swapped = self.swap_with_referral(staked_tokens_to_swap, Fee::ZERO)?;
+        Ok(swapped.tokens_out)
+    }
+
+    /// Swap where `referral_fee` is the share of the swap fee owed to the referrer, the user
+    /// receives the same amount as with `swap`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ReferralFeeTooHigh` above the max referral fee, `EmptyPool` without LP
+    /// supply, `SwapTooLarge` above the max swap size, `InsufficientTokenLiquidity` when the
+    /// available tokens do not cover the output, `ReserveFloorBreached` below the reserve floor,
+    /// `OutputBelowMinimum` under the dust mode, and `ArithmeticOverflow` when a balance would
+    /// overflow.
+    pub fn swap_with_referral(
+        &mut self,
+        staked_tokens_to_swap: StakedTokenAmount,
+        referral_fee: Fee,
+    ) -> Result<SwapWithReferralResult> {
+        self.swap_discounted(staked_tokens_to_swap, referral_fee, FeeDiscount::NONE)
+    }
+
+    fn swap_discounted(
+        &mut self,
+        staked_tokens_to_swap: StakedTokenAmount,
+        referral_fee: Fee,
+        discount: FeeDiscount,
+    ) -> Result<SwapWithReferralResult> {
+        let swapped = self.execute_swap(staked_tokens_to_swap, referral_fee, discount);
+        self.log_rejection(operation::Operation::Swap(staked_tokens_to_swap), swapped)
+    }
+
+    fn execute_swap(
+        &mut self,
+        staked_tokens_to_swap: StakedTokenAmount,
+        referral_fee: Fee,
+        discount: FeeDiscount,
+    ) -> Result<SwapWithReferralResult> {
+        let swap = self.swap_amounts(staked_tokens_to_swap, referral_fee, discount)?;
+
+        #[cfg(feature = "audit")]
+        self.audit_swap(
+            staked_tokens_to_swap,
+            (swap.fee, swap.tokens_without_fee, swap.tokens_with_fee),
         );
-        assert_eq!(lp_pool.lp_token_amount, LpTokenAmount::from_lamports(90));
-        assert_eq!(
-            lp_pool.staked_token_amount,
-            (StakedTokenAmount::from_tokens(TokenAmount::from_lamports(100), lp_pool.price)
-                - StakedTokenAmount::from_tokens(predicted_tokens, lp_pool.price))
+        self.token_amount = swap.token_amount;
+        self.staked_token_amount = swap.staked_token_amount;
+        self.fees_collected = swap.fees_collected;
+        self.referral_fees_owed = swap.referral_fees_owed;
+        self.bump_sequence();
+        #[cfg(feature = "std")]
+        self.record_history(
+            operation::Operation::Swap(staked_tokens_to_swap),
+            swap.pool_fee,
         );
+        self.debug_check_solvency();
+
+        Ok(SwapWithReferralResult {
+            tokens_out: swap.tokens_with_fee,
+            pool_fee: swap.pool_fee,
+            referral_fee: swap.referral_fee,
+        })
     }
 
-    #[test]
-    fn it_swaps_tokens_based_on_price() {
-        let mut lp_pool = LpPool::init(
-            Price::from_points(2),
-            Fee::from_basis_points(100),
-            Fee::from_basis_points(1000),
-            TokenAmount::from_lamports(10),
-        )
-        .unwrap();
-        lp_pool
-            .add_liquidity(TokenAmount::from_lamports(200))
-            .unwrap();
+    /// Pays out the referral fees accumulated since the last withdrawal. They stay in
+    /// `token_amount` until then, but do not belong to the LPs.
+    pub fn withdraw_referral_fees(&mut self) -> TokenAmount {
+        let fees = core::mem::replace(&mut self.referral_fees_owed, TokenAmount::from_lamports(0));
+        self.token_amount -= fees;
+        self.fees_collected = self.fees_collected.min(self.token_amount);
+        self.bump_sequence();
+        self.debug_check_solvency();
+        fees
+    }
 
-        let tokens: TokenAmount = lp_pool.swap(StakedTokenAmount::from_lamports(50)).unwrap();
-        assert_eq!(tokens, TokenAmount::from_lamports(99));
-        assert_eq!(lp_pool.token_amount, TokenAmount::from_lamports(99));
+    /// Pays out the management fees charged since the last claim, see `with_management_fee`.
+    pub fn claim_protocol_fees(&mut self) -> TokenAmount {
+        let fees = core::mem::replace(&mut self.protocol_fees_owed, TokenAmount::from_lamports(0));
+        self.token_amount -= fees;
+        self.fees_collected = self.fees_collected.min(self.token_amount);
+        self.bump_sequence();
+        self.debug_check_solvency();
+        fees
+    }
+
+    /// Withdraws the dust set aside under `DustPolicy::Collectable`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DustNotCollectable` unless the pool uses `DustPolicy::Collectable`.
+    pub fn collect_dust(&mut self) -> Result<TokenAmount> {
+        if self.dust_policy != DustPolicy::Collectable {
+            return Err(Error::LpPool(LpPoolError::DustNotCollectable));
+        }
+        let dust = core::mem::replace(&mut self.dust, TokenAmount::from_lamports(0));
+        self.token_amount -= dust;
+        self.fees_collected = self.fees_collected.min(self.token_amount);
+        self.bump_sequence();
+        self.debug_check_solvency();
+        Ok(dust)
+    }
+
+    /// Tokens held by the pool, including referral and protocol fees owed and dust.
+    #[must_use]
+    pub fn total_value(&self) -> TokenAmount {
+        self.view().total_value()
+    }
+
+    /// `total_value` less the net external flows recorded for the pool. Swap payouts count as
+    /// withdrawals, a non-zero result means the recorded flows do not explain the balance.
+    #[must_use]
+    pub fn reconcile(
+        &self,
+        external_deposits: TokenAmount,
+        external_withdrawals: TokenAmount,
+    ) -> SignedTokenAmount {
+        SignedTokenAmount::from(self.total_value())
+            - TokenAmount::signed_diff(external_deposits, external_withdrawals)
+    }
+
+    /// Share of the LP supply `lp_tokens` stand for, in basis points rounded down.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` without LP supply and `InsufficientLpTokens` for more than the
+    /// supply.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "a share of the supply is at most 10 000 basis points"
+    )]
+    pub fn share_bps(&self, lp_tokens: LpTokenAmount) -> Result<u32> {
+        self.ensure_not_empty()?;
+        if lp_tokens > self.lp_token_amount {
+            return Err(Error::LpPool(LpPoolError::InsufficientLpTokens {
+                requested: lp_tokens,
+                available: self.lp_token_amount,
+            }));
+        }
+        let bps = value_from_shares(
+            lp_tokens.into(),
+            MAX_BASIS_POINTS.into(),
+            self.lp_token_amount.into(),
+        )?;
+        // PANIC SAFETY: `lp_tokens` is at most the supply, so the share is at most 10 000.
+        Ok(u32::try_from(bps).expect("at most 10 000 basis points"))
+    }
+
+    /// Value of one whole LP token, `10^decimals` LP lamports, as UIs chart LP appreciation
+    /// over time. It is the whole token's share of `lp_attributable_value` rounded down, and
+    /// that amount converted at the current price rounded down again. LP tokens appreciate
+    /// with the swap and withdrawal fees the pool keeps, the staked tokens it holds and
+    /// rounding in its favor. The pool mints the first deposit 1:1, so the price
+    /// starts at one token, but a pool without LP supply has no price and returns
+    /// `EmptyPool`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` without LP supply and `ArithmeticOverflow` when the price does not
+    /// fit `u64`.
+    pub fn lp_price(&self) -> Result<LpPrice> {
+        self.ensure_not_empty()?;
+        let value = self.lp_attributable_value()?;
+        let one = LAMPORTS_PER_SOL;
+        let tokens = u128::from(one) * u128::from(u64::from(value))
+            / u128::from(u64::from(self.lp_token_amount));
+        let tokens = u64::try_from(tokens).map_err(|_| {
+            overflow(
+                "lp_attributable_value * one LP token / lp_token_amount",
+                value,
+                self.lp_token_amount,
+            )
+        })?;
+        let staked_tokens =
+            u128::from(tokens) * u128::from(Price::SCALE) / u128::from(u64::from(self.price));
+        let staked_tokens = u64::try_from(staked_tokens)
+            .map_err(|_| overflow("tokens * SCALE / price", tokens, self.price))?;
+        Ok(LpPrice {
+            tokens: TokenAmount::from_lamports(tokens),
+            staked_tokens: StakedTokenAmount::from_lamports(staked_tokens),
+        })
+    }
+
+    /// Available and staked tokens a `bps` share of the pool claims, each rounded down like
+    /// in `remove_liquidity`, before the withdrawal fee.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` without LP supply and `BasisPointsOverflow` for more than 10 000
+    /// basis points.
+    pub fn amounts_for_share_bps(&self, bps: u32) -> Result<(TokenAmount, StakedTokenAmount)> {
+        self.ensure_not_empty()?;
+        Fee::from_basis_points(bps).check()?;
+        let tokens = value_from_shares(
+            bps.into(),
+            self.available_tokens().into(),
+            MAX_BASIS_POINTS.into(),
+        )?;
+        let staked_tokens = value_from_shares(
+            bps.into(),
+            self.staked_token_amount.into(),
+            MAX_BASIS_POINTS.into(),
+        )?;
+        Ok((
+            TokenAmount::from_lamports(tokens),
+            StakedTokenAmount::from_lamports(staked_tokens),
+        ))
+    }
+
+    /// Swaps as much of `staked_in` as the pool can fill, up to `max_swap_in`, and reports
+    /// the rest as unfilled instead of failing. When the dust mode would reject the output of
+    /// that part, the whole input is unfilled. Only a zero input is rejected.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` without LP supply and `ZeroAmount` for a zero input, otherwise
+    /// fails like `swap` on the filled part.
+    pub fn swap_partial(&mut self, staked_in: StakedTokenAmount) -> Result<PartialSwap> {
+        let checked = self.ensure_not_empty().and_then(|()| {
+            if staked_in == StakedTokenAmount::from_lamports(0) {
+                return Err(Error::LpPool(LpPoolError::ZeroAmount));
+            }
+            Ok(())
+        });
+        self.log_rejection(operation::Operation::Swap(staked_in), checked)?;
+        let mut consumed_staked = staked_in.min(self.max_swap_in());
+        if matches!(
+            self.swap_amounts(consumed_staked, Fee::ZERO, FeeDiscount::NONE),
+            Err(Error::LpPool(LpPoolError::OutputBelowMinimum { .. }))
+        ) {
+            consumed_staked = StakedTokenAmount::from_lamports(0);
+        }
+        let tokens_out = if consumed_staked == StakedTokenAmount::from_lamports(0) {
+            TokenAmount::from_lamports(0)
+        } else {
+            self.swap(consumed_staked)?
+        };
+
+        Ok(PartialSwap {
+            consumed_staked,
+            tokens_out,
+            unfilled_staked: staked_in - consumed_staked,
+        })
+    }
+
+    /// Largest input `swap` accepts, limited by the available tokens and the max swap size.
+    #[must_use]
+    pub fn max_swap_in(&self) -> StakedTokenAmount {
+        if self.lp_token_amount == LpTokenAmount::from_lamports(0) {
+            return StakedTokenAmount::from_lamports(0);
+        }
+        // largest input whose value, rounded down like in `swap_amounts`, fits above the floor
+        let floor = self.min_reserve.unwrap_or(TokenAmount::from_lamports(0));
+        let Some(available) = self.available_tokens().checked_sub(floor) else {
+            return StakedTokenAmount::from_lamports(0);
+        };
+        let available = u128::from(u64::from(available));
+        let by_liquidity =
+            ((available + 1) * u128::from(Price::SCALE) - 1) / u128::from(u64::from(self.price));
+        let by_liquidity =
+            StakedTokenAmount::from_lamports(u64::try_from(by_liquidity).unwrap_or(u64::MAX));
+        self.max_swap_size
+            .map_or(by_liquidity, |max| by_liquidity.min(max))
+    }
+
+    /// `swap`, rejected before any change if it would pay less than `min_tokens_out`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SlippageExceeded` when the swap would pay less than `min_tokens_out`,
+    /// otherwise fails like `swap`.
+    pub fn swap_with_min_out(
+        &mut self,
+        staked_tokens_to_swap: StakedTokenAmount,
+        min_tokens_out: TokenAmount,
+    ) -> Result<TokenAmount> {
+        let checked = self.quote_swap(staked_tokens_to_swap).and_then(|quote| {
+            if quote.amount_out < min_tokens_out {
+                return Err(Error::LpPool(LpPoolError::SlippageExceeded {
+                    min_out: min_tokens_out,
+                    quoted: quote.amount_out,
+                }));
+            }
+            Ok(())
+        });
+        self.log_rejection(operation::Operation::Swap(staked_tokens_to_swap), checked)?;
+
+        self.swap(staked_tokens_to_swap)
+    }
+
+    /// What `swap` of `staked_tokens_to_swap` pays out, without changing the pool.
+    ///
+    /// # Errors
+    ///
+    /// Fails exactly when `swap` would.
+    pub fn quote_swap(
+        &self,
+        staked_tokens_to_swap: StakedTokenAmount,
+    ) -> Result<Quote<StakedTokenAmount, TokenAmount>> {
+        self.view().quote_swap(staked_tokens_to_swap)
+    }
+
+    /// Swaps at the quoted output, failing if the pool changed since the quote.
+    ///
+    /// # Errors
+    ///
+    /// Returns `StaleQuote` for a quote the pool no longer honors, otherwise fails like
+    /// `swap`.
+    pub fn execute_quote(
+        &mut self,
+        quote: &Quote<StakedTokenAmount, TokenAmount>,
+    ) -> Result<TokenAmount> {
+        let checked = self.check_quote(quote, self.quote_swap(quote.amount_in));
+        self.log_rejection(operation::Operation::Swap(quote.amount_in), checked)?;
+        self.swap(quote.amount_in)
+    }
+
+    /// Fails with `StaleQuote` unless `quote` was taken at the current sequence and
+    /// `requoted`, the same quote taken now, still pays its `amount_out`. A failing requote
+    /// is left for the operation itself to report.
+    fn check_quote<TIn, TOut: PartialEq>(
+        &self,
+        quote: &Quote<TIn, TOut>,
+        requoted: Result<Quote<TIn, TOut>>,
+    ) -> Result<()> {
+        let stale = quote.pool_sequence != self.sequence
+            || requoted.is_ok_and(|current| current.amount_out != quote.amount_out);
+        if stale {
+            return Err(Error::LpPool(LpPoolError::StaleQuote {
+                quoted_at: quote.pool_sequence,
+                current: self.sequence,
+            }));
+        }
+        Ok(())
+    }
+
+    /// `result`, logged as a rejection of `operation` if it is an error, see
+    /// `with_rejection_log`, and redacted under `redact_amounts_in_logs`.
+    fn log_rejection<T>(
+        &mut self,
+        operation: operation::Operation,
+        result: Result<T>,
+    ) -> Result<T> {
+        #[cfg(feature = "std")]
+        let result = result.map_err(|error| {
+            self.record_rejection(operation, &error);
+            self.redact(error)
+        });
+        #[cfg(not(feature = "std"))]
+        let _ = operation;
+        result
+    }
+
+    /// Number of operations that could change a quote, advanced by every balance or price
+    /// change and by every setting that changes what an operation pays.
+    #[must_use]
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Everything a swap pays out and the balances it leaves, without changing the pool.
+    /// Quotes and `execute_swap` both go through it, so a quote fails exactly when the swap
+    /// would and otherwise promises exactly what the swap pays.
+    fn swap_amounts(
+        &self,
+        staked_tokens_to_swap: StakedTokenAmount,
+        referral_fee: Fee,
+        discount: FeeDiscount,
+    ) -> Result<Swap> {
+        if referral_fee > self.max_referral_fee {
+            return Err(Error::LpPool(LpPoolError::ReferralFeeTooHigh {
+                requested: referral_fee,
+                max: self.max_referral_fee,
+            }));
+        }
+        self.ensure_not_empty()?;
+        if let Some(max) = self
+            .max_swap_size
+            .filter(|max| staked_tokens_to_swap > *max)
+        {
+            return Err(Error::LpPool(LpPoolError::SwapTooLarge {
+                requested: staked_tokens_to_swap,
+                max,
+            }));
+        }
+        let (tokens_without_fee, token_amount_after) = self.conversion(staked_tokens_to_swap)?;
+        self.check_min_reserve(token_amount_after)?;
+        let fee: Fee = discount.apply(self.calculate_fee(token_amount_after)?);
+        let tokens_with_fee = TokenAmount::from_lamports(fee.apply(tokens_without_fee.into())?);
+        self.check_min_output(tokens_with_fee)?;
+
+        let total_fee = tokens_without_fee - tokens_with_fee;
+        let pool_fee = TokenAmount::from_lamports(referral_fee.apply(total_fee.into())?);
+        let referral_fee = total_fee - pool_fee;
+        let token_amount = self
+            .token_amount
+            .checked_sub(tokens_with_fee)
+            .ok_or_else(|| LpPoolError::InsufficientTokenLiquidity {
+                requested: tokens_with_fee,
+                available: self.token_amount,
+                max_fillable_input: self.max_swap_in(),
+            })?;
+        let staked_token_amount = self
+            .staked_token_amount
+            .checked_add(staked_tokens_to_swap)
+            .ok_or_else(|| {
+                overflow(
+                    "staked_token_amount + staked_tokens_to_swap",
+                    self.staked_token_amount,
+                    staked_tokens_to_swap,
+                )
+            })?;
+        let fees_collected = self.collect_fee(pool_fee, token_amount)?;
+        let referral_fees_owed = self
+            .referral_fees_owed
+            .checked_add(referral_fee)
+            .ok_or_else(|| {
+                overflow(
+                    "referral_fees_owed + referral_fee",
+                    self.referral_fees_owed,
+                    referral_fee,
+                )
+            })?;
+
+        Ok(Swap {
+            token_amount,
+            staked_token_amount,
+            fees_collected,
+            referral_fees_owed,
+            fee,
+            tokens_without_fee,
+            tokens_with_fee,
+            pool_fee,
+            referral_fee,
+        })
+    }
+
+    /// Rejects `computed` below the minimum output of the dust mode.
+    fn check_min_output(&self, computed: TokenAmount) -> Result<()> {
+        let minimum = match self.dust_mode {
+            DustMode::RejectZeroOutput => self.min_output.max(TokenAmount::from_lamports(1)),
+            DustMode::AllowZeroOutput => return Ok(()),
+        };
+        if computed < minimum {
+            return Err(Error::LpPool(LpPoolError::OutputBelowMinimum {
+                computed,
+                minimum,
+            }));
+        }
+        Ok(())
+    }
+
+    /// Available tokens once `staked_in` is swapped, the balance the swap fee is taken at.
+    /// The user receives the conversion less the fee, and the fee is left out of the balance
+    /// so it does not depend on itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` without LP supply and `InsufficientTokenLiquidity` when the
+    /// available tokens do not cover the conversion.
+    pub fn post_swap_token_amount(&self, staked_in: StakedTokenAmount) -> Result<TokenAmount> {
+        self.ensure_not_empty()?;
+        let (_, token_amount_after) = self.conversion(staked_in)?;
+        Ok(token_amount_after)
+    }
+
+    /// Outflows leaving `token_amount_after` available tokens must not cross the floor,
+    /// operations paying out no tokens pass even below it.
+    fn check_min_reserve(&self, token_amount_after: TokenAmount) -> Result<()> {
+        match self.min_reserve {
+            Some(floor)
+                if token_amount_after < floor && token_amount_after < self.available_tokens() =>
+            {
+                Err(Error::LpPool(LpPoolError::ReserveFloorBreached {
+                    floor,
+                    would_be: token_amount_after,
+                    max_outflow: self
+                        .available_tokens()
+                        .checked_sub(floor)
+                        .unwrap_or(TokenAmount::from_lamports(0)),
+                }))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// `staked_in` at the current price and the available tokens left after paying it out.
+    fn conversion(&self, staked_in: StakedTokenAmount) -> Result<(TokenAmount, TokenAmount)> {
+        // paid to the user, rounded down
+        let tokens_without_fee = TokenAmount::from_staked_tokens(staked_in, self.price)?;
+        let available = self.available_tokens();
+        let token_amount_after = available.checked_sub(tokens_without_fee).ok_or_else(|| {
+            LpPoolError::InsufficientTokenLiquidity {
+                requested: tokens_without_fee,
+                available,
+                max_fillable_input: self.max_swap_in(),
+            }
+        })?;
+        Ok((tokens_without_fee, token_amount_after))
+    }
+
+    /// Everything a withdrawal pays out and the balances it leaves, shared by the quote and
+    /// `execute_withdrawal` like `swap_amounts`.
+    fn withdrawal(&self, lp_tokens_to_remove: LpTokenAmount) -> Result<Withdrawal> {
+        self.ensure_not_empty()?;
+        let lp_token_amount = self
+            .lp_token_amount
+            .checked_sub(lp_tokens_to_remove)
+            .ok_or(LpPoolError::InsufficientLpTokens {
+                requested: lp_tokens_to_remove,
+                available: self.lp_token_amount,
+            })?;
+
+        let available = self.available_tokens();
+        let tokens_without_fee = TokenAmount::from_lamports(value_from_shares(
+            lp_tokens_to_remove.into(),
+            available.into(),
+            self.lp_token_amount.into(),
+        )?);
+
+        let token_amount_after = available.checked_sub(tokens_without_fee).ok_or_else(|| {
+            LpPoolError::InsufficientTokenLiquidity {
+                requested: tokens_without_fee,
+                available,
+                max_fillable_input: self.max_swap_in(),
+            }
+        })?;
+        // the fee is kept for the remaining LPs, the last one out pays none and can always
+        // leave
+        let last_out = lp_token_amount == LpTokenAmount::from_lamports(0);
+        if !last_out {
+            self.check_min_reserve(token_amount_after)?;
+        }
+        let fee: Fee = if last_out {
+            Fee::ZERO
+        } else {
+            self.withdrawal_fee(token_amount_after)?
+        };
+
+        let tokens_with_fee: TokenAmount =
+            TokenAmount::from_lamports(fee.apply(tokens_without_fee.into())?);
+        let unstaked_tokens = StakedTokenAmount::from_lamports(value_from_shares(
+            lp_tokens_to_remove.into(),
+            self.staked_token_amount.into(),
+            self.lp_token_amount.into(),
+        )?);
+        if !last_out {
+            let staked_value = TokenAmount::from_staked_tokens(unstaked_tokens, self.price)?;
+            self.check_min_output(
+                tokens_with_fee
+                    .checked_add(staked_value)
+                    .unwrap_or(TokenAmount::from_lamports(u64::MAX)),
+            )?;
+        }
+        // both shares of `available` round down, whatever neither of them claims is dust
+        let remaining_value = TokenAmount::from_lamports(value_from_shares(
+            lp_token_amount.into(),
+            available.into(),
+            self.lp_token_amount.into(),
+        )?);
+        let dust = token_amount_after - remaining_value;
+
+        let token_amount = self
+            .token_amount
+            .checked_sub(tokens_with_fee)
+            .ok_or_else(|| LpPoolError::InsufficientTokenLiquidity {
+                requested: tokens_with_fee,
+                available: self.token_amount,
+                max_fillable_input: self.max_swap_in(),
+            })?;
+        let staked_token_amount = self
+            .staked_token_amount
+            .checked_sub(unstaked_tokens)
+            .ok_or(LpPoolError::InsufficientStakedLiquidity {
+                requested: unstaked_tokens,
+                available: self.staked_token_amount,
+            })?;
+        let fees_collected =
+            self.collect_fee(tokens_without_fee - tokens_with_fee, token_amount)?;
+        let dust = self
+            .dust
+            .checked_add(dust)
+            .ok_or_else(|| overflow("dust + withdrawal dust", self.dust, dust))?;
+
+        Ok(Withdrawal {
+            lp_token_amount,
+            token_amount,
+            staked_token_amount,
+            fees_collected,
+            dust,
+            fee,
+            tokens_without_fee,
+            tokens_with_fee,
+            unstaked_tokens,
+        })
+    }
+
+    fn withdrawal_fee(&self, amount_after: TokenAmount) -> Result<Fee> {
+        match (self.withdrawal_fee, self.withdrawal_fee_mode) {
+            (None, _) | (_, WithdrawalFeeMode::UseCurve) => self.calculate_fee(amount_after),
+            (Some(fee), WithdrawalFeeMode::Replace) => Ok(fee),
+            (Some(fee), WithdrawalFeeMode::Additional) => {
+                let curve_fee = self.calculate_fee(amount_after)?;
+                let fee = Fee::from_centibps(curve_fee.centibps.saturating_add(fee.centibps));
+                fee.check()?;
+                Ok(fee)
+            }
+        }
+    }
+
+    /// `fees_collected` with `fee` added, once the pool holds `token_amount`. Fees stay in
+    /// `token_amount` for the LPs, so what LPs withdrew or swaps paid out of it is no longer
+    /// counted.
+    fn collect_fee(&self, fee: TokenAmount, token_amount: TokenAmount) -> Result<TokenAmount> {
+        let fees_collected = self
+            .fees_collected
+            .checked_add(fee)
+            .ok_or_else(|| overflow("fees_collected + fee", self.fees_collected, fee))?;
+        Ok(fees_collected.min(token_amount))
+    }
+
+    #[must_use]
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Moves to the next epoch, charging the management fee and maturing unstake tickets.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` for a management fee above 100% and
+    /// `ArithmeticOverflow` when the LP value or the fees owed would overflow.
+    pub fn advance_epoch(&mut self) -> Result<EpochSummary> {
+        let epoch = self.epoch + 1;
+        let value = self.lp_attributable_value()?;
+        let management_fee =
+            TokenAmount::from_lamports(self.management_fee.fee_amount(value.into())?)
+                .min(self.available_tokens());
+        let protocol_fees_owed = self
+            .protocol_fees_owed
+            .checked_add(management_fee)
+            .ok_or_else(|| {
+                overflow(
+                    "protocol_fees_owed + management_fee",
+                    self.protocol_fees_owed,
+                    management_fee,
+                )
+            })?;
+        #[cfg(feature = "std")]
+        let tickets_maturing = self.unstake_queue.value_claimable_at(epoch)?;
+        #[cfg(not(feature = "std"))]
+        let tickets_maturing = TokenAmount::from_lamports(0);
+
+        self.epoch = epoch;
+        self.protocol_fees_owed = protocol_fees_owed;
+        self.bump_sequence();
+        self.debug_check_solvency();
+
+        Ok(EpochSummary {
+            epoch,
+            management_fee,
+            tickets_maturing,
+        })
+    }
+
+    /// With a clock, `update_price_at` the clock's time.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PriceIncorrect` for a zero price, and with a clock fails like
+    /// `update_price_at`.
+    pub fn update_price(&mut self, price: Price) -> Result<()> {
+        #[cfg(feature = "std")]
+        if let Some(now) = self.now() {
+            return self.update_price_at(price, now);
+        }
+        self.set_price(price, None)
+    }
+
+    /// `timestamp` is `None` for updates without a time, `update_price_at` passes its own.
+    fn set_price(&mut self, price: Price, timestamp: Option<u64>) -> Result<()> {
+        if price == Price::from_raw_points(0) {
+            return self.log_rejection(
+                operation::Operation::UpdatePrice(price),
+                Err(Error::LpPool(LpPoolError::PriceIncorrect(price))),
+            );
+        }
+        #[cfg(feature = "std")]
+        let old = self.price;
+        self.price = price;
+        self.bump_sequence();
+        #[cfg(feature = "std")]
+        {
+            self.record_history(
+                operation::Operation::UpdatePrice(price),
+                TokenAmount::from_lamports(0),
+            );
+            self.notify_price_update(old);
+            let timestamp = timestamp.or(self.price_timestamp).unwrap_or(0);
+            self.observe_price(price, timestamp);
+        }
+        #[cfg(not(feature = "std"))]
+        let _ = timestamp;
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn state(&self) -> PoolState {
+        PoolState {
+            price: self.price,
+            min_fee: self.fee_range.min(),
+            max_fee: self.fee_range.max(),
+            liquidity_target: self.liquidity_target,
+            token_amount: self.token_amount,
+            staked_token_amount: self.staked_token_amount,
+            lp_token_amount: self.lp_token_amount,
+            fees_collected: self.fees_collected,
+            referral_fees_owed: self.referral_fees_owed,
+            dust: self.dust,
+            protocol_fees_owed: self.protocol_fees_owed,
+        }
+    }
+
+    /// `PoolState::fmt_redacted` of the current state.
+    #[must_use]
+    pub fn fmt_redacted(&self) -> state::RedactedState {
+        self.state().fmt_redacted()
+    }
+
+    /// Swap fee once the pool holds `amount_after` available tokens. For fixed pool settings
+    /// the fee never rises as `amount_after` grows, always lies within `[min_fee, max_fee]`,
+    /// is `max_fee` at zero and `min_fee` from `liquidity_target` up.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` for fee bounds above 100%, which `init` rejects.
+    pub fn calculate_fee(&self, amount_after: TokenAmount) -> Result<Fee> {
+        self.view().fee_at(amount_after)
+    }
+
+    /// Fee a swap or withdrawal of next to nothing pays at the current balances.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `calculate_fee`.
+    pub fn current_fee(&self) -> Result<Fee> {
+        self.calculate_fee(self.available_tokens())
+    }
+
+    /// Tokens to deposit for `current_fee` to fall to `desired` or below, `None` if it
+    /// already is or no deposit gets it there. Below `min_fee` only the surplus threshold
+    /// does, by dropping the fee to zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` for a `desired` fee above 100%, otherwise fails like
+    /// `current_fee`.
+    pub fn tokens_until_fee(&self, desired: Fee) -> Result<Option<TokenAmount>> {
+        desired.check()?;
+        if self.current_fee()? <= desired {
+            return Ok(None);
+        }
+        let Some(amount) = self
+            .fee_range
+            .amount_for_fee(desired, self.liquidity_target.into())
+            .or(self.surplus_line())
+        else {
+            return Ok(None);
+        };
+        // PANIC SAFETY: the fee at the available tokens is above `desired`, so they are
+        // below the smallest amount charging at most `desired`.
+        Ok(Some(
+            TokenAmount::from_lamports(amount) - self.available_tokens(),
+        ))
+    }
+
+    /// Available tokens from which swaps are free, see `set_surplus_threshold`.
+    fn surplus_line(&self) -> Option<u64> {
+        self.surplus_threshold
+            .map(|threshold| u64::from(self.liquidity_target).saturating_add(threshold.into()))
+    }
+
+    /// Verifies the pool can honor every LP claim. Balances are unsigned, so the only
+    /// subtraction that could have gone negative is the LP share of `token_amount`. Without LP
+    /// supply, `lp_attributable_value` must be zero: no tokens or staked tokens left that no
+    /// LP can claim.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first `InvariantViolation` found.
+    pub fn check_solvency(&self) -> core::result::Result<(), InvariantViolation> {
+        if self.fees_collected > self.token_amount {
+            return Err(InvariantViolation::FeesExceedBalance {
+                fees_collected: self.fees_collected,
+                token_amount: self.token_amount,
+            });
+        }
+        let Some(unreserved) = self.token_amount.checked_sub(self.referral_fees_owed) else {
+            return Err(InvariantViolation::ReferralFeesExceedBalance {
+                referral_fees_owed: self.referral_fees_owed,
+                token_amount: self.token_amount,
+            });
+        };
+        if self.protocol_fees_owed > unreserved {
+            return Err(InvariantViolation::ProtocolFeesExceedBalance {
+                protocol_fees_owed: self.protocol_fees_owed,
+                token_amount: unreserved,
+            });
+        }
+
+        let value = self.available_tokens();
+        if self.lp_token_amount == LpTokenAmount::from_lamports(0) {
+            if value != TokenAmount::from_lamports(0) {
+                return Err(InvariantViolation::BalanceWithoutLpSupply {
+                    token_amount: self.token_amount,
+                    fees_collected: self.fees_collected,
+                });
+            }
+            if self.lp_attributable_value() != Ok(TokenAmount::from_lamports(0)) {
+                return Err(InvariantViolation::StakedWithoutLpSupply {
+                    staked_token_amount: self.staked_token_amount,
+                });
+            }
+            return Ok(());
+        }
+
+        let redeemable = value_from_shares(
+            self.lp_token_amount.into(),
+            value.into(),
+            self.lp_token_amount.into(),
+        );
+        if redeemable != Ok(value.into()) {
+            return Err(InvariantViolation::SharesNotRedeemable {
+                lp_token_amount: self.lp_token_amount,
+                value,
+            });
+        }
+        Ok(())
+    }
+
+    /// Runs `check_solvency` after mutations in debug builds or with the `paranoid` feature.
+    #[track_caller]
+    fn debug_check_solvency(&self) {
+        #[cfg(any(debug_assertions, feature = "paranoid"))]
+        if let Err(violation) = self.check_solvency() {
+            panic!("pool invariant violated: {violation}");
+        }
+    }
+
+    /// Tokens owned by LPs, swap and withdrawal fees included. Referral and protocol fees
+    /// owed and collectable dust stay in `token_amount` but are not part of it.
+    fn available_tokens(&self) -> TokenAmount {
+        self.token_amount
+            .checked_sub(self.referral_fees_owed)
+            .and_then(|tokens| tokens.checked_sub(self.protocol_fees_owed))
+            .and_then(|tokens| tokens.checked_sub(self.collectable_dust()))
+            .unwrap_or(TokenAmount::from_lamports(0))
+    }
+
+    /// Value owned by LPs, available tokens plus staked tokens at the current price: what
+    /// remains of `token_amount` once referral and protocol fees owed and collectable dust
+    /// are set aside, and
+    /// what burning every LP token pays out before the withdrawal fee. Zero exactly when the
+    /// LP supply is, see `check_solvency`.
+    ///
+    /// Deposits buy shares of it, so the staked tokens are valued rounded up: the user
+    /// provides at least the exact value of the shares minted.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArithmeticOverflow` when the value does not fit `u64`.
+    pub fn lp_attributable_value(&self) -> Result<TokenAmount> {
+        let staked_value =
+            TokenAmount::from_staked_tokens_ceil(self.staked_token_amount, self.price)?;
+        let available = self.available_tokens();
+        available
+            .checked_add(staked_value)
+            .ok_or_else(|| overflow("available + staked value", available, staked_value))
+    }
+
+    fn collectable_dust(&self) -> TokenAmount {
+        match self.dust_policy {
+            DustPolicy::AccrueToLps => TokenAmount::from_lamports(0),
+            DustPolicy::Collectable => self.dust,
+        }
+    }
+
+    /// Overwrites the balances without any validation, to test invariant checks.
+    #[cfg(any(test, feature = "test-utils"))]
+    #[doc(hidden)]
+    #[must_use]
+    pub fn with_balances_unchecked(
+        self,
+        token_amount: TokenAmount,
+        staked_token_amount: StakedTokenAmount,
+        lp_token_amount: LpTokenAmount,
+        fees_collected: TokenAmount,
+    ) -> Self {
+        Self {
+            token_amount,
+            staked_token_amount,
+            lp_token_amount,
+            fees_collected,
+            ..self
+        }
+    }
+
+    /// Overwrites the fee bounds without any validation, to test fees `init` rejects.
+    #[cfg(any(test, feature = "test-utils"))]
+    #[doc(hidden)]
+    #[must_use]
+    pub fn with_fees_unchecked(self, min_fee: Fee, max_fee: Fee) -> Self {
+        Self {
+            fee_range: FeeRange::new_unchecked(min_fee, max_fee),
+            ..self
+        }
+    }
+
+    fn bump_sequence(&mut self) {
+        self.sequence = self.sequence.wrapping_add(1);
+    }
+
+    fn ensure_not_empty(&self) -> Result<()> {
+        if self.lp_token_amount == LpTokenAmount::from_lamports(0) {
+            return Err(Error::LpPool(LpPoolError::EmptyPool));
+        }
+        Ok(())
+    }
+}
+
+impl Pool for LpPool {
+    fn add_liquidity(&mut self, tokens_to_add: TokenAmount) -> Result<LpTokenAmount> {
+        LpPool::add_liquidity(self, tokens_to_add)
+    }
+
+    fn remove_liquidity(
+        &mut self,
+        lp_tokens_to_remove: LpTokenAmount,
+    ) -> Result<(TokenAmount, StakedTokenAmount)> {
+        LpPool::remove_liquidity(self, lp_tokens_to_remove)
+    }
+
+    fn swap(&mut self, staked_tokens_to_swap: StakedTokenAmount) -> Result<TokenAmount> {
+        LpPool::swap(self, staked_tokens_to_swap)
+    }
+
+    fn quote_swap(&self, staked_tokens_to_swap: StakedTokenAmount) -> Result<TokenAmount> {
+        LpPool::quote_swap(self, staked_tokens_to_swap).map(|quote| quote.amount_out)
+    }
+
+    fn state(&self) -> PoolState {
+        LpPool::state(self)
+    }
+}
+
+impl core::fmt::Display for LpPool {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.state().fmt(f)
+    }
+}
+
+/// Reduction of the curve fee, never taking it below `floor` unless it already was.
+#[derive(Clone, Copy)]
+struct FeeDiscount {
+    discount: Fee,
+    floor: Fee,
+}
+
+impl FeeDiscount {
+    const NONE: Self = Self {
+        discount: Fee::ZERO,
+        floor: Fee::ZERO,
+    };
+
+    fn apply(self, fee: Fee) -> Fee {
+        let discounted = Fee::from_centibps(fee.centibps.saturating_sub(self.discount.centibps));
+        discounted.max(self.floor.min(fee))
+    }
+}
+
+struct Deposit {
+    token_amount: TokenAmount,
+    lp_token_amount: LpTokenAmount,
+    fees_collected: TokenAmount,
+    lp_tokens: LpTokenAmount,
+}
+
+/// Balances after the withdrawal, then what it pays out.
+struct Withdrawal {
+    lp_token_amount: LpTokenAmount,
+    token_amount: TokenAmount,
+    staked_token_amount: StakedTokenAmount,
+    fees_collected: TokenAmount,
+    dust: TokenAmount,
+    fee: Fee,
+    tokens_without_fee: TokenAmount,
+    tokens_with_fee: TokenAmount,
+    unstaked_tokens: StakedTokenAmount,
+}
+
+/// Balances after the swap, then what it pays out and how the fee is split.
+struct Swap {
+    token_amount: TokenAmount,
+    staked_token_amount: StakedTokenAmount,
+    fees_collected: TokenAmount,
+    referral_fees_owed: TokenAmount,
+    fee: Fee,
+    tokens_without_fee: TokenAmount,
+    tokens_with_fee: TokenAmount,
+    pool_fee: TokenAmount,
+    referral_fee: TokenAmount,
+}
+
+/// Overflow in the pool's checked arithmetic. With `strict-math` it is returned as
+/// `ArithmeticOverflow`, without it the pool panics like the arithmetic operators.
+#[cold]
+#[cfg_attr(not(feature = "strict-math"), track_caller)]
+pub(crate) fn overflow(op: &'static str, lhs: impl Into<u64>, rhs: impl Into<u64>) -> Error {
+    let error = LpPoolError::ArithmeticOverflow {
+        op,
+        lhs: lhs.into(),
+        rhs: rhs.into(),
+    };
+    #[cfg(not(feature = "strict-math"))]
+    panic!("{error}");
+    #[cfg(feature = "strict-math")]
+    Error::LpPool(error)
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    use crate::lp_pool::consts::LAMPORTS_PER_SOL;
+    use crate::test_utils::{assert_balances, assert_solvent, PoolFixture, Step, SCENARIOS};
+    use proptest::prelude::*;
+
+    #[test]
+    #[allow(deprecated)]
+    fn it_returns_err_if_min_fee_greater_than_max_fee() {
+        let lp_pool = LpPool::init_legacy(
+            Price::try_from(10).unwrap(),
+            Fee::from_basis_points(101),
+            Fee::from_basis_points(100),
+            TokenAmount::from_lamports(100),
+        );
+        assert_eq!(
+            lp_pool.err(),
+            Some(Error::LpPool(LpPoolError::MinFeeGreaterThanMaxFee {
+                min: Fee::from_basis_points(101),
+                max: Fee::from_basis_points(100),
+            }))
+        );
+    }
+
+    #[test]
+    fn it_returns_err_if_liquidity_target_is_zero() {
+        let lp_pool = LpPool::init(PoolParams {
+            price: Price::try_from(10).unwrap(),
+            fee_range: FeeRange::new(Fee::from_basis_points(10), Fee::from_basis_points(100))
+                .unwrap(),
+            liquidity_target: TokenAmount::from_lamports(0),
+        });
+        assert_eq!(
+            lp_pool.err(),
+            Some(Error::LpPool(LpPoolError::LiquidityTargetIncorrect(
+                TokenAmount::from_lamports(0)
+            )))
+        );
+    }
+
+    #[test]
+    fn it_returns_err_if_price_is_zero() {
+        let lp_pool = LpPool::init(PoolParams {
+            price: Price::from_raw_points(0),
+            fee_range: FeeRange::new(Fee::from_basis_points(10), Fee::from_basis_points(100))
+                .unwrap(),
+            liquidity_target: TokenAmount::from_lamports(100),
+        });
+        assert_eq!(
+            lp_pool.err(),
+            Some(Error::LpPool(LpPoolError::PriceIncorrect(
+                Price::from_raw_points(0)
+            )))
+        );
+    }
+
+    #[test]
+    fn it_adds_liquidity_to_pool_and_returns_lp_tokens() {
+        let mut lp_pool = PoolFixture::new()
+            .price("10")
+            .fees(10, 100)
+            .target_lamports(100)
+            .build()
+            .pool;
+        let predicted_tokens: LpTokenAmount =
+            LpTokenAmount::from_tokens(TokenAmount::from_lamports(100));
+        let tokens: LpTokenAmount = lp_pool
+            .add_liquidity(TokenAmount::from_lamports(100))
+            .unwrap();
+
+        assert_eq!(tokens, predicted_tokens);
+        assert_balances(&lp_pool, 100, 0, 100);
+        assert_solvent(&lp_pool);
+    }
+
+    #[test]
+    fn it_mints_first_deposit_as_lp_token_constructor() {
+        for lamports in [1, 999, 10_001, LAMPORTS_PER_SOL + 7] {
+            let tokens = TokenAmount::from_lamports(lamports);
+            let expected = LpTokenAmount::from_tokens_with_fee(tokens, Fee::ZERO, Rounding::Down);
+            let mut lp_pool = PoolFixture::new().price("1.37").build().pool;
+
+            assert_eq!(
+                lp_pool
+                    .quote_add_liquidity(tokens)
+                    .map(|quote| quote.amount_out),
+                expected
+            );
+            assert_eq!(lp_pool.add_liquidity(tokens), expected);
+        }
+    }
+
+    #[test]
+    fn it_mints_same_lp_tokens_for_split_deposit_across_target() {
+        let below_target = || {
+            let mut lp_pool = PoolFixture::new()
+                .price("1.37")
+                .fees(10, 900)
+                .target_lamports(1_000_000)
+                .seeded_with_lamports(400_000)
+                .build()
+                .pool;
+            lp_pool
+                .swap(StakedTokenAmount::from_lamports(150_000))
+                .unwrap();
+            lp_pool
+        };
+        let deposit = 2_345_677;
+
+        let mut single = below_target();
+        let minted = u64::from(
+            single
+                .add_liquidity(TokenAmount::from_lamports(deposit))
+                .unwrap(),
+        );
+        for parts in [2, 7, 100] {
+            let mut split = below_target();
+            let mut split_minted = 0;
+            for part in 0..parts {
+                let tokens = deposit / parts + u64::from(part < deposit % parts);
+                split_minted += u64::from(
+                    split
+                        .add_liquidity(TokenAmount::from_lamports(tokens))
+                        .unwrap(),
+                );
+            }
+
+            assert!(split_minted <= minted);
+            assert!(minted - split_minted <= parts, "{parts} deposits");
+            assert_eq!(split.state().fees_collected, single.state().fees_collected);
+        }
+    }
+
+    #[test]
+    fn it_removes_liquidity_from_pool_above_liquidity_target() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(10, 100)
+            .target_lamports(50)
+            .seeded_with_lamports(100)
+            .build()
+            .pool;
+        let predicted_tokens =
+            TokenAmount::from_lamports(lp_pool.fee_range.min().apply(10).unwrap());
+
+        let (tokens, staked_tokens) = lp_pool
+            .remove_liquidity(LpTokenAmount::from_lamports(10))
+            .unwrap();
+
+        assert_eq!(tokens, predicted_tokens);
+        assert_eq!(staked_tokens, StakedTokenAmount::from_lamports(0));
+        assert_eq!(
+            lp_pool.token_amount,
+            (TokenAmount::from_lamports(100) - predicted_tokens)
+        );
+        assert_eq!(lp_pool.lp_token_amount, LpTokenAmount::from_lamports(90));
+    }
+
+    #[test]
+    fn it_swaps_tokens_based_on_price() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(200)
+            .fees(100, 1000)
+            .target_lamports(10)
+            .seeded_with_lamports(200)
+            .build()
+            .pool;
+
+        let tokens: TokenAmount = lp_pool.swap(StakedTokenAmount::from_lamports(50)).unwrap();
+        assert_eq!(tokens, TokenAmount::from_lamports(99));
+        assert_balances(&lp_pool, 101, 50, 200);
+        assert_solvent(&lp_pool);
+    }
+
+    #[test]
+    fn it_takes_swap_fee_at_post_swap_balance_near_target() {
+        let mut lp_pool = PoolFixture::new()
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(102_000)
+            .build()
+            .pool
+            .with_max_referral_fee(Fee::from_basis_points(5_000))
+            .unwrap();
+        let staked = StakedTokenAmount::from_lamports(4_000);
+
+        let post_swap = lp_pool.post_swap_token_amount(staked).unwrap();
+        assert_eq!(post_swap, TokenAmount::from_lamports(98_000));
+        let quote = lp_pool.quote_swap(staked).unwrap();
+        assert_eq!(quote.fee, lp_pool.calculate_fee(post_swap).unwrap());
+        // counting the retained fee as LP liquidity would undercharge
+        let paid_out_only = post_swap + quote.fee_amount;
+        assert!(paid_out_only < TokenAmount::from_lamports(100_000));
+        assert!(lp_pool.calculate_fee(paid_out_only).unwrap() < quote.fee);
+
+        let swapped = lp_pool
+            .swap_with_referral(staked, Fee::from_basis_points(5_000))
+            .unwrap();
+        assert_eq!(swapped.tokens_out, quote.amount_out);
+        assert_eq!(swapped.pool_fee + swapped.referral_fee, quote.fee_amount);
+        assert_eq!(lp_pool.available_tokens(), post_swap + swapped.pool_fee);
+    }
+
+    #[test]
+    fn it_splits_pool_between_complementary_shares() {
+        let mut lp_pool = PoolFixture::new()
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_003)
+            .build()
+            .pool;
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(33_333))
+            .unwrap();
+        let available = lp_pool.available_tokens();
+        let staked = lp_pool.state().staked_token_amount;
+
+        for bps in [0, 1, 2_500, 3_333, 5_000, 9_999, 10_000] {
+            let (tokens, staked_tokens) = lp_pool.amounts_for_share_bps(bps).unwrap();
+            let (rest, staked_rest) = lp_pool.amounts_for_share_bps(10_000 - bps).unwrap();
+            assert!(available - (tokens + rest) <= TokenAmount::from_lamports(1));
+            assert!(staked - (staked_tokens + staked_rest) <= StakedTokenAmount::from_lamports(1));
+        }
+        assert_eq!(
+            lp_pool.amounts_for_share_bps(10_001),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(
+                Fee::from_basis_points(10_001)
+            )))
+        );
+    }
+
+    #[test]
+    fn it_reports_share_of_lp_supply() {
+        let lp_pool = PoolFixture::new().seeded_with_lamports(30_000).build().pool;
+
+        assert_eq!(
+            lp_pool.share_bps(LpTokenAmount::from_lamports(10_000)),
+            Ok(3_333)
+        );
+        assert_eq!(
+            lp_pool.share_bps(LpTokenAmount::from_lamports(30_000)),
+            Ok(10_000)
+        );
+        assert_eq!(lp_pool.share_bps(LpTokenAmount::from_lamports(2)), Ok(0));
+        assert_eq!(
+            lp_pool.share_bps(LpTokenAmount::from_lamports(30_001)),
+            Err(Error::LpPool(LpPoolError::InsufficientLpTokens {
+                requested: LpTokenAmount::from_lamports(30_001),
+                available: LpTokenAmount::from_lamports(30_000),
+            }))
+        );
+        assert_eq!(
+            PoolFixture::new()
+                .build()
+                .pool
+                .share_bps(LpTokenAmount::from_lamports(0)),
+            Err(Error::LpPool(LpPoolError::EmptyPool))
+        );
+    }
+
+    fn surplus_pool(surplus_threshold: Option<u64>) -> LpPool {
+        PoolFixture::new()
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(300_000)
+            .build()
+            .pool
+            .with_surplus_threshold(surplus_threshold.map(TokenAmount::from_lamports))
+    }
+
+    #[test]
+    fn it_swaps_fee_free_above_surplus_threshold() {
+        let swap = |staked| {
+            let mut lp_pool = surplus_pool(Some(50_000));
+            let quote = lp_pool
+                .quote_swap(StakedTokenAmount::from_lamports(staked))
+                .unwrap();
+            assert_eq!(lp_pool.execute_quote(&quote), Ok(quote.amount_out));
+            (quote.fee, u64::from(quote.amount_out))
+        };
+
+        assert_eq!(swap(100), (Fee::ZERO, 100));
+        // ends exactly on the surplus line
+        assert_eq!(swap(150_000), (Fee::ZERO, 150_000));
+        // start above the line and end below it, at min_fee like anywhere above the target
+        assert_eq!(swap(150_001), (Fee::from_basis_points(10), 149_851));
+        assert_eq!(swap(200_000), (Fee::from_basis_points(10), 199_800));
+    }
+
+    #[test]
+    fn it_swaps_fee_free_from_target_with_zero_surplus_threshold() {
+        let lp_pool = surplus_pool(Some(0));
+        let fee_at = |lamports| lp_pool.calculate_fee(TokenAmount::from_lamports(lamports));
+
+        assert_eq!(fee_at(100_000), Ok(Fee::ZERO));
+        assert_eq!(fee_at(u64::MAX), Ok(Fee::ZERO));
+        assert!(fee_at(99_999).unwrap() > Fee::from_basis_points(10));
+        assert_eq!(fee_at(0), Ok(Fee::from_basis_points(900)));
+    }
+
+    #[test]
+    fn it_keeps_min_fee_above_target_without_surplus_threshold() {
+        let mut lp_pool = surplus_pool(None);
+
+        assert_eq!(lp_pool.surplus_threshold(), None);
+        assert_eq!(
+            lp_pool.calculate_fee(TokenAmount::from_lamports(u64::MAX)),
+            Ok(Fee::from_basis_points(10))
+        );
+        lp_pool.set_surplus_threshold(Some(TokenAmount::from_lamports(u64::MAX)));
+        assert_eq!(
+            lp_pool.calculate_fee(TokenAmount::from_lamports(u64::MAX - 1)),
+            Ok(Fee::from_basis_points(10))
+        );
+        assert_eq!(
+            lp_pool.calculate_fee(TokenAmount::from_lamports(u64::MAX)),
+            Ok(Fee::ZERO)
+        );
+    }
+
+    #[test]
+    fn it_reports_deposit_bringing_fee_down_to_desired() {
+        let lp_pool = PoolFixture::new()
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(30_000)
+            .build()
+            .pool;
+        let current = lp_pool.current_fee().unwrap();
+        assert_eq!(
+            current,
+            lp_pool
+                .calculate_fee(TokenAmount::from_lamports(30_000))
+                .unwrap()
+        );
+
+        let after_deposit = |lamports| {
+            let mut lp_pool = lp_pool.clone();
+            lp_pool
+                .add_liquidity(TokenAmount::from_lamports(lamports))
+                .unwrap();
+            lp_pool.current_fee().unwrap()
+        };
+        for desired in [
+            Fee::from_basis_points(500),
+            Fee::from_centibps(12_345),
+            Fee::from_basis_points(10),
+        ] {
+            let tokens = u64::from(lp_pool.tokens_until_fee(desired).unwrap().unwrap());
+            assert!(after_deposit(tokens) <= desired);
+            assert!(after_deposit(tokens - 1) > desired);
+        }
+        // 500 bps lies on the curve at 44_944 available tokens
+        assert_eq!(
+            lp_pool.tokens_until_fee(Fee::from_basis_points(500)),
+            Ok(Some(TokenAmount::from_lamports(14_944)))
+        );
+        assert_eq!(after_deposit(14_944), Fee::from_basis_points(500));
+
+        assert_eq!(lp_pool.tokens_until_fee(current), Ok(None));
+        assert_eq!(lp_pool.tokens_until_fee(Fee::MAX), Ok(None));
+        assert_eq!(
+            lp_pool.tokens_until_fee(Fee::from_basis_points(9)),
+            Ok(None)
+        );
+        let over = Fee::from_basis_points(10_001);
+        assert_eq!(
+            lp_pool.tokens_until_fee(over),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(over)))
+        );
+
+        let free_above_target = lp_pool.with_surplus_threshold(Some(TokenAmount::from_lamports(0)));
+        assert_eq!(
+            free_above_target.tokens_until_fee(Fee::ZERO),
+            Ok(Some(TokenAmount::from_lamports(70_000)))
+        );
+    }
+
+    fn floored_pool() -> LpPool {
+        PoolFixture::new()
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool
+            .with_min_reserve(Some(TokenAmount::from_lamports(60_000)))
+            .unwrap()
+    }
+
+    #[test]
+    fn it_rejects_outflows_below_reserve_floor() {
+        let breached = |would_be| {
+            Error::LpPool(LpPoolError::ReserveFloorBreached {
+                floor: TokenAmount::from_lamports(60_000),
+                would_be: TokenAmount::from_lamports(would_be),
+                max_outflow: TokenAmount::from_lamports(40_000),
+            })
+        };
+        let lp_pool = floored_pool();
+        assert_eq!(
+            lp_pool.max_swap_in(),
+            StakedTokenAmount::from_lamports(40_000)
+        );
+
+        let over = StakedTokenAmount::from_lamports(40_001);
+        assert_eq!(lp_pool.quote_swap(over), Err(breached(59_999)));
+        assert_eq!(lp_pool.clone().swap(over), Err(breached(59_999)));
+        let at_floor = StakedTokenAmount::from_lamports(40_000);
+        let quote = lp_pool.quote_swap(at_floor).unwrap();
+        assert_eq!(lp_pool.clone().swap(at_floor), Ok(quote.amount_out));
+        let partial = lp_pool
+            .clone()
+            .swap_partial(StakedTokenAmount::from_lamports(50_000))
+            .unwrap();
+        assert_eq!(partial.consumed_staked, at_floor);
+
+        let over = LpTokenAmount::from_lamports(40_001);
+        assert_eq!(
+            lp_pool
+                .quote_remove_liquidity(over)
+                .map(|quote| quote.amount_out),
+            Err(breached(59_999))
+        );
+        assert_eq!(
+            lp_pool.clone().remove_liquidity(over),
+            Err(breached(59_999))
+        );
+        let at_floor = LpTokenAmount::from_lamports(40_000);
+        let quote = lp_pool.quote_remove_liquidity(at_floor).unwrap();
+        assert_eq!(
+            lp_pool.clone().remove_liquidity(at_floor),
+            Ok(quote.amount_out.into())
+        );
+    }
+
+    #[test]
+    fn it_returns_err_if_reserve_floor_above_target() {
+        let mut lp_pool = floored_pool();
+
+        assert_eq!(
+            lp_pool.set_min_reserve(Some(TokenAmount::from_lamports(100_001))),
+            Err(Error::LpPool(LpPoolError::ReserveFloorIncorrect {
+                floor: TokenAmount::from_lamports(100_001),
+                liquidity_target: TokenAmount::from_lamports(100_000),
+            }))
+        );
+        assert_eq!(
+            lp_pool.min_reserve(),
+            Some(TokenAmount::from_lamports(60_000))
+        );
+        lp_pool
+            .set_min_reserve(Some(TokenAmount::from_lamports(100_000)))
+            .unwrap();
+        assert_eq!(lp_pool.max_swap_in(), StakedTokenAmount::from_lamports(0));
+        lp_pool.set_min_reserve(None).unwrap();
+        assert!(lp_pool
+            .swap(StakedTokenAmount::from_lamports(40_001))
+            .is_ok());
+    }
+
+    #[test]
+    fn it_quotes_swap_without_changing_pool() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(200)
+            .fees(100, 1000)
+            .target_lamports(10)
+            .seeded_with_lamports(200)
+            .build()
+            .pool;
+        let state_before = lp_pool.state();
+
+        let quoted: TokenAmount = lp_pool
+            .quote_swap(StakedTokenAmount::from_lamports(50))
+            .unwrap()
+            .amount_out;
+        assert_eq!(lp_pool.state(), state_before);
+
+        let swapped: TokenAmount = lp_pool.swap(StakedTokenAmount::from_lamports(50)).unwrap();
+        assert_eq!(quoted, swapped);
+    }
+
+    #[test]
+    fn it_returns_empty_pool_when_swapping_against_fresh_pool() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(200)
+            .fees(100, 1000)
+            .target_lamports(10)
+            .build()
+            .pool;
+
+        assert_eq!(
+            lp_pool.swap(StakedTokenAmount::from_lamports(1)),
+            Err(Error::LpPool(LpPoolError::EmptyPool))
+        );
+        assert_eq!(
+            lp_pool.remove_liquidity(LpTokenAmount::from_lamports(1)),
+            Err(Error::LpPool(LpPoolError::EmptyPool))
+        );
+    }
+
+    #[test]
+    fn it_returns_insufficient_token_liquidity_when_swap_exceeds_pool() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(200)
+            .fees(100, 1000)
+            .target_lamports(10)
+            .seeded_with_lamports(200)
+            .build()
+            .pool;
+
+        let error = lp_pool
+            .swap(StakedTokenAmount::from_lamports(101))
+            .unwrap_err();
+        assert_eq!(
+            error,
+            Error::LpPool(LpPoolError::InsufficientTokenLiquidity {
+                requested: TokenAmount::from_lamports(202),
+                available: TokenAmount::from_lamports(200),
+                max_fillable_input: StakedTokenAmount::from_lamports(100),
+            })
+        );
+        assert_eq!(
+            error.display_chain().to_string(),
+            "LpPool: InsufficientTokenLiquidity(requested: 202, available: 200, max_fillable_input: 100)"
+        );
+        assert_eq!(lp_pool.token_amount, TokenAmount::from_lamports(200));
+    }
+
+    fn dust_pool(dust_mode: DustMode, min_output: u64) -> LpPool {
+        PoolFixture::new()
+            .price("0.5")
+            .fees(900, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool
+            .with_dust_mode(dust_mode, TokenAmount::from_lamports(min_output))
+    }
+
+    #[test]
+    fn it_rejects_zero_output_swap_by_default() {
+        let mut lp_pool = PoolFixture::new()
+            .price("0.5")
+            .fees(900, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool;
+        let state = lp_pool.state();
+        let rejected = Err(Error::LpPool(LpPoolError::OutputBelowMinimum {
+            computed: TokenAmount::from_lamports(0),
+            minimum: TokenAmount::from_lamports(1),
+        }));
+        // 1 lamport at 0.5 is worth nothing
+        let staked = StakedTokenAmount::from_lamports(1);
+
+        assert_eq!(
+            lp_pool.dust_mode(),
+            (DustMode::RejectZeroOutput, TokenAmount::from_lamports(0))
+        );
+        assert_eq!(
+            lp_pool.quote_swap(staked).map(|quote| quote.amount_out),
+            rejected
+        );
+        assert_eq!(lp_pool.swap(staked), rejected);
+        assert_eq!(lp_pool.state(), state);
+        assert_eq!(
+            lp_pool.swap(StakedTokenAmount::from_lamports(2)),
+            Ok(TokenAmount::from_lamports(1))
+        );
+
+        let mut lp_pool = dust_pool(DustMode::AllowZeroOutput, 0);
+        assert_eq!(
+            lp_pool.quote_swap(staked).map(|quote| quote.amount_out),
+            Ok(TokenAmount::from_lamports(0))
+        );
+        assert_eq!(lp_pool.swap(staked), Ok(TokenAmount::from_lamports(0)));
+        assert_eq!(
+            lp_pool.state().staked_token_amount,
+            StakedTokenAmount::from_lamports(1)
+        );
+    }
+
+    #[test]
+    fn it_rejects_outputs_below_min_output() {
+        let mut lp_pool = dust_pool(DustMode::RejectZeroOutput, 100);
+        let state = lp_pool.state();
+
+        // 216 at 0.5 is 108, less a 9% fee of 9
+        assert_eq!(
+            lp_pool.swap(StakedTokenAmount::from_lamports(216)),
+            Err(Error::LpPool(LpPoolError::OutputBelowMinimum {
+                computed: TokenAmount::from_lamports(99),
+                minimum: TokenAmount::from_lamports(100),
+            }))
+        );
+        assert_eq!(
+            lp_pool.quote_remove_liquidity(LpTokenAmount::from_lamports(100)),
+            Err(Error::LpPool(LpPoolError::OutputBelowMinimum {
+                computed: TokenAmount::from_lamports(91),
+                minimum: TokenAmount::from_lamports(100),
+            }))
+        );
+        assert_eq!(
+            lp_pool.remove_liquidity(LpTokenAmount::from_lamports(100)),
+            Err(Error::LpPool(LpPoolError::OutputBelowMinimum {
+                computed: TokenAmount::from_lamports(91),
+                minimum: TokenAmount::from_lamports(100),
+            }))
+        );
+        assert_eq!(lp_pool.state(), state);
+        assert_eq!(
+            lp_pool.swap(StakedTokenAmount::from_lamports(218)),
+            Ok(TokenAmount::from_lamports(100))
+        );
+
+        lp_pool.set_dust_mode(DustMode::AllowZeroOutput, TokenAmount::from_lamports(100));
+        assert_eq!(
+            lp_pool.remove_liquidity(LpTokenAmount::from_lamports(1)),
+            Ok((
+                TokenAmount::from_lamports(0),
+                StakedTokenAmount::from_lamports(0)
+            ))
+        );
+    }
+
+    #[test]
+    fn it_prices_lp_token_from_attributable_value() {
+        let mut lp_pool = PoolFixture::new()
+            .fees(10, 900)
+            .target_lamports(10 * LAMPORTS_PER_SOL)
+            .build()
+            .pool;
+        lp_pool
+            .add_liquidity(TokenAmount::from_lamports(7 * LAMPORTS_PER_SOL + 3))
+            .unwrap();
+        assert_eq!(
+            lp_pool.lp_price(),
+            Ok(LpPrice {
+                tokens: TokenAmount::from_lamports(LAMPORTS_PER_SOL),
+                staked_tokens: StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL),
+            })
+        );
+
+        // every fee-bearing swap at a held price lifts it, repricing never lowers it
+        let mut previous = lp_pool.lp_price().unwrap();
+        for points in [100, 137, 151] {
+            lp_pool
+                .update_price(Price::from_raw_points(points))
+                .unwrap();
+            let repriced = lp_pool.lp_price().unwrap();
+            assert!(repriced.tokens >= previous.tokens);
+            previous = repriced;
+            for _ in 0..2 {
+                let fees_before = lp_pool.fees_collected;
+                lp_pool
+                    .swap(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL / 2))
+                    .unwrap();
+                let lp_price = lp_pool.lp_price().unwrap();
+
+                assert!(lp_pool.fees_collected > fees_before);
+                assert!(lp_price.tokens > previous.tokens);
+                assert!(lp_price.staked_tokens > previous.staked_tokens);
+                assert_eq!(
+                    lp_price.staked_tokens,
+                    StakedTokenAmount::from_tokens(lp_price.tokens, lp_pool.price).unwrap()
+                );
+                previous = lp_price;
+            }
+        }
+        assert!(previous.tokens > TokenAmount::from_lamports(LAMPORTS_PER_SOL));
+
+        let supply = u128::from(u64::from(lp_pool.lp_token_amount));
+        let value = u128::from(u64::from(lp_pool.lp_attributable_value().unwrap()));
+        let priced = u128::from(u64::from(previous.tokens)) * supply / u128::from(LAMPORTS_PER_SOL);
+        assert!(priced <= value);
+        assert!(value - priced <= supply / u128::from(LAMPORTS_PER_SOL) + 1);
+    }
+
+    #[test]
+    fn it_prices_lp_token_of_extreme_supplies() {
+        let lp_pool = PoolFixture::new().build().pool;
+        let with_supply = |tokens, lp_tokens| {
+            lp_pool
+                .clone()
+                .with_balances_unchecked(
+                    TokenAmount::from_lamports(tokens),
+                    StakedTokenAmount::from_lamports(0),
+                    LpTokenAmount::from_lamports(lp_tokens),
+                    TokenAmount::from_lamports(0),
+                )
+                .lp_price()
+                .map(|lp_price| u64::from(lp_price.tokens))
+        };
+
+        assert_eq!(with_supply(u64::MAX, u64::MAX), Ok(LAMPORTS_PER_SOL));
+        assert_eq!(with_supply(1, u64::MAX), Ok(0));
+        assert_eq!(
+            with_supply(u64::MAX / LAMPORTS_PER_SOL, 1),
+            Ok(u64::MAX / LAMPORTS_PER_SOL * LAMPORTS_PER_SOL)
+        );
+        #[cfg(feature = "strict-math")]
+        assert!(matches!(
+            with_supply(u64::MAX, 1),
+            Err(Error::LpPool(LpPoolError::ArithmeticOverflow { .. }))
+        ));
+    }
+
+    fn reconfigured_pool() -> LpPool {
+        let mut lp_pool = PoolFixture::new()
+            .price("1.37")
+            .fees(10, 900)
+            .target_lamports(10 * LAMPORTS_PER_SOL)
+            .seeded_with_lamports(10 * LAMPORTS_PER_SOL)
+            .build()
+            .pool
+            .with_min_reserve(Some(TokenAmount::from_lamports(LAMPORTS_PER_SOL)))
+            .unwrap();
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(3 * LAMPORTS_PER_SOL))
+            .unwrap();
+        lp_pool
+    }
+
+    fn params(lp_pool: &LpPool, min_bps: u32, max_bps: u32, target: u64) -> PoolParams {
+        PoolParams {
+            price: lp_pool.price,
+            fee_range: FeeRange::new_unchecked(
+                Fee::from_basis_points(min_bps),
+                Fee::from_basis_points(max_bps),
+            ),
+            liquidity_target: TokenAmount::from_lamports(target),
+        }
+    }
+
+    #[test]
+    fn it_keeps_lp_value_across_reconfigure() {
+        let mut lp_pool = reconfigured_pool();
+        let state_before = lp_pool.state();
+        let lp_price = lp_pool.lp_price().unwrap();
+        let fee_before = lp_pool
+            .quote_swap(ReconfigReport::REFERENCE_SWAP)
+            .unwrap()
+            .fee;
+
+        let report = lp_pool
+            .reconfigure(params(&lp_pool, 50, 500, 20 * LAMPORTS_PER_SOL))
+            .unwrap();
+        let state_after = lp_pool.state();
+        let fee_after = lp_pool
+            .quote_swap(ReconfigReport::REFERENCE_SWAP)
+            .unwrap()
+            .fee;
+
+        assert_eq!(report.lp_price_before, Some(lp_price));
+        assert_eq!(report.lp_price_after, Some(lp_price));
+        assert_eq!(lp_pool.lp_price(), Ok(lp_price));
+        assert_eq!(report.swap_fee_before, fee_before);
+        assert_eq!(report.swap_fee_after, fee_after);
+        assert_ne!(fee_before, fee_after);
+        assert_eq!(
+            (
+                state_after.min_fee,
+                state_after.max_fee,
+                state_after.liquidity_target
+            ),
+            (
+                Fee::from_basis_points(50),
+                Fee::from_basis_points(500),
+                TokenAmount::from_lamports(20 * LAMPORTS_PER_SOL)
+            )
+        );
+        assert_eq!(
+            PoolState {
+                min_fee: state_before.min_fee,
+                max_fee: state_before.max_fee,
+                liquidity_target: state_before.liquidity_target,
+                ..state_after
+            },
+            state_before
+        );
+        assert_eq!(lp_pool.check_solvency(), Ok(()));
+
+        let mut empty = PoolFixture::new().build().pool;
+        let report = empty
+            .reconfigure(params(&empty, 0, 100, LAMPORTS_PER_SOL))
+            .unwrap();
+        assert_eq!(
+            (report.lp_price_before, report.lp_price_after),
+            (None, None)
+        );
+        assert_eq!(report.swap_fee_after, Fee::from_basis_points(100));
+    }
+
+    #[test]
+    fn it_leaves_params_in_place_on_invalid_reconfigure() {
+        let mut lp_pool = reconfigured_pool();
+        let state = lp_pool.state();
+        let sequence = lp_pool.sequence();
+        let target = 10 * LAMPORTS_PER_SOL;
+
+        let moved_price = PoolParams {
+            price: Price::from_raw_points(200),
+            ..params(&lp_pool, 50, 500, target)
+        };
+        assert_eq!(
+            lp_pool.reconfigure(moved_price),
+            Err(Error::LpPool(LpPoolError::ReconfigurePriceMismatch {
+                current: lp_pool.price,
+                requested: Price::from_raw_points(200),
+            }))
+        );
+        assert_eq!(
+            lp_pool.reconfigure(params(&lp_pool, 50, 500, 0)),
+            Err(Error::LpPool(LpPoolError::LiquidityTargetIncorrect(
+                TokenAmount::from_lamports(0)
+            )))
+        );
+        assert_eq!(
+            lp_pool.reconfigure(params(&lp_pool, 500, 50, target)),
+            Err(Error::LpPool(LpPoolError::MinFeeGreaterThanMaxFee {
+                min: Fee::from_basis_points(500),
+                max: Fee::from_basis_points(50),
+            }))
+        );
+        assert!(matches!(
+            lp_pool.reconfigure(params(&lp_pool, 50, 10_001, target)),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(_)))
+        ));
+        assert_eq!(
+            lp_pool.reconfigure(params(&lp_pool, 50, 500, LAMPORTS_PER_SOL - 1)),
+            Err(Error::LpPool(LpPoolError::ReserveFloorIncorrect {
+                floor: TokenAmount::from_lamports(LAMPORTS_PER_SOL),
+                liquidity_target: TokenAmount::from_lamports(LAMPORTS_PER_SOL - 1),
+            }))
+        );
+
+        assert_eq!(lp_pool.state(), state);
+        assert_eq!(lp_pool.sequence(), sequence);
+    }
+
+    #[cfg(feature = "strict-math")]
+    #[test]
+    fn it_leaves_params_in_place_when_reconfigure_report_fails() {
+        let mut lp_pool = reconfigured_pool().with_balances_unchecked(
+            TokenAmount::from_lamports(u64::MAX),
+            StakedTokenAmount::from_lamports(0),
+            LpTokenAmount::from_lamports(1),
+            TokenAmount::from_lamports(0),
+        );
+        let state = lp_pool.state();
+        let sequence = lp_pool.sequence();
+
+        assert!(matches!(
+            lp_pool.reconfigure(params(&lp_pool, 50, 500, 20 * LAMPORTS_PER_SOL)),
+            Err(Error::LpPool(LpPoolError::ArithmeticOverflow { .. }))
+        ));
+        assert_eq!(lp_pool.state(), state);
+        assert_eq!(lp_pool.sequence(), sequence);
+    }
+
+    #[test]
+    fn it_caps_swap_fee_at_one_hundred_percent() {
+        let pool = |basis_points| {
+            PoolFixture::new()
+                .fees(basis_points, basis_points)
+                .target_lamports(100_000)
+                .seeded_with_lamports(100_000)
+                .build()
+                .pool
+        };
+        let staked = StakedTokenAmount::from_lamports(10_000);
+
+        let mut lp_pool = pool(9_999);
+        assert_eq!(lp_pool.swap(staked), Ok(TokenAmount::from_lamports(1)));
+        assert_eq!(lp_pool.fees_collected, TokenAmount::from_lamports(9_999));
+
+        let mut lp_pool =
+            pool(10_000).with_dust_mode(DustMode::AllowZeroOutput, TokenAmount::from_lamports(0));
+        let quote = lp_pool.quote_swap(staked).unwrap();
+        assert_eq!(quote.amount_out, TokenAmount::from_lamports(0));
+        assert_eq!(quote.fee_amount, TokenAmount::from_lamports(10_000));
+        assert_eq!(lp_pool.swap(staked), Ok(TokenAmount::from_lamports(0)));
+        assert_eq!(lp_pool.fees_collected, TokenAmount::from_lamports(10_000));
+
+        let over = Fee::from_basis_points(10_001);
+        let overflow = Err(Error::LpPool(LpPoolError::BasisPointsOverflow(over)));
+        let mut lp_pool = pool(0).with_fees_unchecked(over, over);
+        let state = lp_pool.state();
+        assert_eq!(
+            lp_pool.quote_swap(staked).map(|quote| quote.amount_out),
+            overflow
+        );
+        assert_eq!(lp_pool.swap(staked), overflow);
+        assert_eq!(lp_pool.state(), state);
+        assert_eq!(
+            FeeRange::new(Fee::ZERO, over),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(over)))
+        );
+    }
+
+    #[test]
+    fn it_retries_failed_swap_with_hinted_input() {
+        let mut lp_pool = floored_pool()
+            .with_max_swap_size(Some(StakedTokenAmount::from_lamports(90_000)))
+            .unwrap();
+        lp_pool.update_price(Price::from_raw_points(130)).unwrap();
+        let too_large = StakedTokenAmount::from_lamports(80_000);
+
+        let quoted = lp_pool.quote_swap(too_large).unwrap_err();
+        let error = lp_pool.swap(too_large).unwrap_err();
+        assert_eq!(quoted, error);
+        assert!(error.is_retryable());
+        let Error::LpPool(LpPoolError::InsufficientTokenLiquidity {
+            max_fillable_input, ..
+        }) = error
+        else {
+            panic!("unexpected error {error:?}");
+        };
+        assert_eq!(max_fillable_input, lp_pool.max_swap_in());
+
+        assert!(lp_pool.swap(max_fillable_input).is_ok());
+        assert!(!Error::LpPool(LpPoolError::EmptyPool).is_retryable());
+        assert!(!Error::LpPool(LpPoolError::ZeroAmount).is_retryable());
+    }
+
+    #[test]
+    fn it_pays_and_mints_lp_tokens_in_proportion_to_pool_value() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(0, 0)
+            .target_lamports(10)
+            .seeded_with_lamports(150)
+            .build()
+            .pool;
+        lp_pool.swap(StakedTokenAmount::from_lamports(100)).unwrap();
+
+        assert_eq!(
+            lp_pool.remove_liquidity(LpTokenAmount::from_lamports(60)),
+            Ok((
+                TokenAmount::from_lamports(20),
+                StakedTokenAmount::from_lamports(40)
+            ))
+        );
+        assert_eq!(
+            lp_pool.add_liquidity(TokenAmount::from_lamports(90)),
+            Ok(LpTokenAmount::from_lamports(90))
+        );
+        assert_balances(&lp_pool, 120, 60, 180);
+    }
+
+    #[test]
+    fn it_returns_insufficient_lp_tokens_when_burning_more_than_supply() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(10, 100)
+            .target_lamports(50)
+            .seeded_with_lamports(100)
+            .build()
+            .pool;
+
+        assert_eq!(
+            lp_pool.remove_liquidity(LpTokenAmount::from_lamports(101)),
+            Err(Error::LpPool(LpPoolError::InsufficientLpTokens {
+                requested: LpTokenAmount::from_lamports(101),
+                available: LpTokenAmount::from_lamports(100),
+            }))
+        );
+    }
+
+    #[cfg(feature = "strict-math")]
+    #[test]
+    fn it_returns_arithmetic_overflow_when_balance_would_overflow() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(10, 100)
+            .target_lamports(50)
+            .seeded_with_lamports(100)
+            .build()
+            .pool;
+
+        assert_eq!(
+            lp_pool.add_liquidity(TokenAmount::from_lamports(u64::MAX)),
+            Err(Error::LpPool(LpPoolError::ArithmeticOverflow {
+                op: "token_amount + tokens_to_add",
+                lhs: 100,
+                rhs: u64::MAX,
+            }))
+        );
+        assert_eq!(lp_pool.token_amount, TokenAmount::from_lamports(100));
+    }
+
+    #[cfg(not(feature = "strict-math"))]
+    #[test]
+    #[should_panic(expected = "ArithmeticOverflow(op: token_amount + tokens_to_add")]
+    fn it_panics_on_overflow_without_strict_math() {
+        let mut lp_pool = PoolFixture::new().seeded_with_lamports(100).build().pool;
+
+        let _ = lp_pool.add_liquidity(TokenAmount::from_lamports(u64::MAX));
+    }
+
+    fn partial_swap_pool(price_points: u64) -> LpPool {
+        PoolFixture::new()
+            .price_points(price_points)
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool
+    }
+
+    #[test]
+    fn it_fills_partial_swap_fully_when_liquidity_suffices() {
+        let mut lp_pool = partial_swap_pool(100);
+        let staked = StakedTokenAmount::from_lamports(1_000);
+        let quoted = lp_pool.quote_swap(staked).unwrap().amount_out;
+
+        assert_eq!(
+            lp_pool.swap_partial(staked),
+            Ok(PartialSwap {
+                consumed_staked: staked,
+                tokens_out: quoted,
+                unfilled_staked: StakedTokenAmount::from_lamports(0),
+            })
+        );
+        assert_eq!(
+            lp_pool.swap_partial(StakedTokenAmount::from_lamports(0)),
+            Err(Error::LpPool(LpPoolError::ZeroAmount))
+        );
+    }
+
+    #[test]
+    fn it_fills_partial_swap_up_to_exact_boundary() {
+        let lp_pool = partial_swap_pool(150);
+        let max = lp_pool.max_swap_in();
+        assert_eq!(max, StakedTokenAmount::from_lamports(66_667));
+        assert!(lp_pool.quote_swap(max).is_ok());
+        assert!(lp_pool
+            .quote_swap(max + StakedTokenAmount::from_lamports(1))
+            .is_err());
+
+        let mut at_boundary = lp_pool.clone();
+        let swapped = at_boundary.swap_partial(max).unwrap();
+        assert_eq!(swapped.consumed_staked, max);
+        assert_eq!(swapped.unfilled_staked, StakedTokenAmount::from_lamports(0));
+
+        let mut past_boundary = lp_pool.clone();
+        let swapped = past_boundary
+            .swap_partial(max + StakedTokenAmount::from_lamports(1))
+            .unwrap();
+        assert_eq!(swapped.consumed_staked, max);
+        assert_eq!(swapped.unfilled_staked, StakedTokenAmount::from_lamports(1));
+        assert_eq!(past_boundary.state(), at_boundary.state());
+    }
+
+    #[test]
+    fn it_reports_remainder_of_partial_swap_draining_pool() {
+        let mut lp_pool = partial_swap_pool(100);
+
+        assert_eq!(
+            lp_pool.swap_partial(StakedTokenAmount::from_lamports(250_000)),
+            Ok(PartialSwap {
+                consumed_staked: StakedTokenAmount::from_lamports(100_000),
+                tokens_out: TokenAmount::from_lamports(91_000),
+                unfilled_staked: StakedTokenAmount::from_lamports(150_000),
+            })
+        );
+        assert_balances(&lp_pool, 9_000, 100_000, 100_000);
+        assert_eq!(lp_pool.fees_collected, TokenAmount::from_lamports(9_000));
+        assert_eq!(lp_pool.check_solvency(), Ok(()));
+        // the fees stay with the LPs as liquidity
+        assert_eq!(
+            lp_pool.max_swap_in(),
+            StakedTokenAmount::from_lamports(9_000)
+        );
+    }
+
+    #[test]
+    fn it_leaves_partial_swap_unfilled_below_dust_threshold() {
+        let lp_pool = dust_pool(DustMode::RejectZeroOutput, 100);
+        // 217 staked tokens are worth 108 lamports, 99 after the 9% fee, 218 pay out 100
+        let below = StakedTokenAmount::from_lamports(217);
+        let at = StakedTokenAmount::from_lamports(218);
+        assert_eq!(
+            lp_pool.quote_swap(below).map(|quote| quote.amount_out),
+            Err(Error::LpPool(LpPoolError::OutputBelowMinimum {
+                computed: TokenAmount::from_lamports(99),
+                minimum: TokenAmount::from_lamports(100),
+            }))
+        );
+
+        let mut filled = lp_pool.clone();
+        assert_eq!(
+            filled.swap_partial(at),
+            Ok(PartialSwap {
+                consumed_staked: at,
+                tokens_out: TokenAmount::from_lamports(100),
+                unfilled_staked: StakedTokenAmount::from_lamports(0),
+            })
+        );
+
+        let mut unfilled = lp_pool.clone();
+        assert_eq!(
+            unfilled.swap_partial(below),
+            Ok(PartialSwap {
+                consumed_staked: StakedTokenAmount::from_lamports(0),
+                tokens_out: TokenAmount::from_lamports(0),
+                unfilled_staked: below,
+            })
+        );
+        assert_eq!(unfilled.state(), lp_pool.state());
+    }
+
+    #[test]
+    fn it_leaves_clamped_partial_swap_unfilled_below_dust_threshold() {
+        let mut lp_pool = dust_pool(DustMode::RejectZeroOutput, 95_000);
+        let staked = StakedTokenAmount::from_lamports(250_000);
+        assert!(lp_pool.max_swap_in() < staked);
+        assert_eq!(
+            lp_pool
+                .quote_swap(lp_pool.max_swap_in())
+                .map(|quote| quote.amount_out),
+            Err(Error::LpPool(LpPoolError::OutputBelowMinimum {
+                computed: TokenAmount::from_lamports(91_000),
+                minimum: TokenAmount::from_lamports(95_000),
+            }))
+        );
+
+        assert_eq!(
+            lp_pool.swap_partial(staked),
+            Ok(PartialSwap {
+                consumed_staked: StakedTokenAmount::from_lamports(0),
+                tokens_out: TokenAmount::from_lamports(0),
+                unfilled_staked: staked,
+            })
+        );
+        assert_balances(&lp_pool, 100_000, 0, 100_000);
+    }
+
+    #[test]
+    fn it_returns_slippage_exceeded_when_quote_below_min_out() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(200)
+            .fees(100, 1000)
+            .target_lamports(10)
+            .seeded_with_lamports(200)
+            .build()
+            .pool;
+
+        assert_eq!(
+            lp_pool.swap_with_min_out(
+                StakedTokenAmount::from_lamports(50),
+                TokenAmount::from_lamports(100)
+            ),
+            Err(Error::LpPool(LpPoolError::SlippageExceeded {
+                min_out: TokenAmount::from_lamports(100),
+                quoted: TokenAmount::from_lamports(99),
+            }))
+        );
+        assert_eq!(
+            lp_pool.swap_with_min_out(
+                StakedTokenAmount::from_lamports(50),
+                TokenAmount::from_lamports(99)
+            ),
+            Ok(TokenAmount::from_lamports(99))
+        );
+    }
+
+    #[test]
+    fn it_updates_price_and_rejects_zero() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(10, 100)
+            .target_lamports(50)
+            .build()
+            .pool;
+
+        lp_pool.update_price(Price::from_raw_points(152)).unwrap();
+        assert_eq!(lp_pool.price, Price::from_raw_points(152));
+        assert_eq!(
+            lp_pool.update_price(Price::from_raw_points(0)),
+            Err(Error::LpPool(LpPoolError::PriceIncorrect(
+                Price::from_raw_points(0)
+            )))
+        );
+        assert_eq!(lp_pool.price, Price::from_raw_points(152));
+    }
+
+    #[test]
+    fn it_rejects_swaps_above_max_swap_size() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(200)
+            .fees(100, 1000)
+            .target_lamports(10)
+            .seeded_with_lamports(200)
+            .build()
+            .pool
+            .with_max_swap_size(Some(StakedTokenAmount::from_lamports(20)))
+            .unwrap();
+
+        assert_eq!(
+            lp_pool.swap(StakedTokenAmount::from_lamports(21)),
+            Err(Error::LpPool(LpPoolError::SwapTooLarge {
+                requested: StakedTokenAmount::from_lamports(21),
+                max: StakedTokenAmount::from_lamports(20),
+            }))
+        );
+        assert!(lp_pool.swap(StakedTokenAmount::from_lamports(20)).is_ok());
+
+        lp_pool.set_max_swap_size(None).unwrap();
+        assert!(lp_pool.swap(StakedTokenAmount::from_lamports(21)).is_ok());
+    }
+
+    #[test]
+    fn it_rejects_deposits_above_token_cap() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(10, 100)
+            .target_lamports(50)
+            .build()
+            .pool
+            .with_token_cap(Some(TokenAmount::from_lamports(100)))
+            .unwrap();
+
+        assert_eq!(
+            lp_pool.add_liquidity(TokenAmount::from_lamports(101)),
+            Err(Error::LpPool(LpPoolError::PoolCapExceeded {
+                amount_after: TokenAmount::from_lamports(101),
+                cap: TokenAmount::from_lamports(100),
+                max_fillable_input: TokenAmount::from_lamports(100),
+            }))
+        );
+        lp_pool
+            .add_liquidity(TokenAmount::from_lamports(100))
+            .unwrap();
+        assert_eq!(lp_pool.token_amount, TokenAmount::from_lamports(100));
+
+        lp_pool
+            .set_token_cap(Some(TokenAmount::from_lamports(50)))
+            .unwrap();
+        assert!(lp_pool
+            .add_liquidity(TokenAmount::from_lamports(1))
+            .is_err());
+        assert!(lp_pool
+            .remove_liquidity(LpTokenAmount::from_lamports(10))
+            .is_ok());
+
+        lp_pool.set_token_cap(None).unwrap();
+        assert!(lp_pool
+            .add_liquidity(TokenAmount::from_lamports(1000))
+            .is_ok());
+    }
+
+    #[test]
+    fn it_returns_err_if_cap_is_zero() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(10, 100)
+            .target_lamports(50)
+            .build()
+            .pool;
+
+        assert_eq!(
+            lp_pool.set_max_swap_size(Some(StakedTokenAmount::from_lamports(0))),
+            Err(Error::LpPool(LpPoolError::MaxSwapSizeIncorrect(
+                StakedTokenAmount::from_lamports(0)
+            )))
+        );
+        assert_eq!(
+            lp_pool.set_token_cap(Some(TokenAmount::from_lamports(0))),
+            Err(Error::LpPool(LpPoolError::TokenCapIncorrect(
+                TokenAmount::from_lamports(0)
+            )))
+        );
+    }
+
+    #[test]
+    fn it_charges_withdrawal_fee_according_to_mode() {
+        for (mode, tokens_out) in [
+            (WithdrawalFeeMode::UseCurve, 99_900),
+            (WithdrawalFeeMode::Replace, 95_000),
+            (WithdrawalFeeMode::Additional, 94_900),
+        ] {
+            let mut lp_pool = PoolFixture::new()
+                .price_points(100)
+                .fees(10, 100)
+                .target_lamports(50)
+                .seeded_with_lamports(1_000_000)
+                .build()
+                .pool
+                .with_withdrawal_fee(Some(Fee::from_basis_points(500)), mode)
+                .unwrap();
+            assert_eq!(lp_pool.fees_collected, TokenAmount::from_lamports(0));
+
+            let quoted = lp_pool
+                .quote_remove_liquidity(LpTokenAmount::from_lamports(100_000))
+                .unwrap();
+            let (tokens, staked_tokens) = lp_pool
+                .remove_liquidity(LpTokenAmount::from_lamports(100_000))
+                .unwrap();
+
+            assert_eq!(
+                (tokens, staked_tokens),
+                quoted.amount_out.into(),
+                "{mode:?}"
+            );
+            assert_eq!(tokens, TokenAmount::from_lamports(tokens_out), "{mode:?}");
+            assert_eq!(
+                lp_pool.fees_collected,
+                TokenAmount::from_lamports(100_000 - tokens_out),
+                "{mode:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn it_collects_swap_fees() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(200)
+            .fees(100, 1000)
+            .target_lamports(10)
+            .seeded_with_lamports(200)
+            .build()
+            .pool;
+        let fees_before = lp_pool.fees_collected;
+
+        lp_pool.swap(StakedTokenAmount::from_lamports(50)).unwrap();
+        assert_eq!(
+            lp_pool.fees_collected,
+            fees_before + TokenAmount::from_lamports(1)
+        );
+    }
+
+    #[test]
+    fn it_returns_err_if_withdrawal_fee_overflows_basis_points() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(10, 100)
+            .target_lamports(50)
+            .build()
+            .pool;
+
+        assert_eq!(
+            lp_pool.set_withdrawal_fee(
+                Some(Fee::from_basis_points(10_001)),
+                WithdrawalFeeMode::Replace
+            ),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(
+                Fee::from_basis_points(10_001)
+            )))
+        );
+    }
+
+    fn referral_pool() -> LpPool {
+        PoolFixture::new()
+            .price_points(100)
+            .fees(100, 1000)
+            .target_lamports(10)
+            .seeded_with_lamports(1_000_000)
+            .build()
+            .pool
+            .with_max_referral_fee(Fee::from_basis_points(3000))
+            .unwrap()
+    }
+
+    #[test]
+    fn it_carves_referral_fee_out_of_pool_fee() {
+        let mut without_referral = referral_pool();
+        let mut with_referral = referral_pool();
+
+        let tokens_out = without_referral
+            .swap(StakedTokenAmount::from_lamports(10_007))
+            .unwrap();
+        let swapped = with_referral
+            .swap_with_referral(
+                StakedTokenAmount::from_lamports(10_007),
+                Fee::from_basis_points(2500),
+            )
+            .unwrap();
+
+        assert_eq!(swapped.tokens_out, tokens_out);
+        assert_eq!(
+            swapped.pool_fee + swapped.referral_fee,
+            TokenAmount::from_lamports(10_007) - tokens_out
+        );
+        assert_eq!(swapped.referral_fee, TokenAmount::from_lamports(25));
+        assert_eq!(with_referral.token_amount, without_referral.token_amount);
+        assert_eq!(
+            with_referral.lp_attributable_value().unwrap() + swapped.referral_fee,
+            without_referral.lp_attributable_value().unwrap()
+        );
+
+        assert_eq!(with_referral.withdraw_referral_fees(), swapped.referral_fee);
+        assert_eq!(
+            with_referral.withdraw_referral_fees(),
+            TokenAmount::from_lamports(0)
+        );
+    }
+
+    #[test]
+    fn it_stays_solvent_from_referral_swap_to_fee_withdrawal() {
+        let mut lp_pool = referral_pool();
+        let before = lp_pool.state();
+
+        let swapped = lp_pool
+            .swap_with_referral(
+                StakedTokenAmount::from_lamports(10_007),
+                Fee::from_basis_points(2500),
+            )
+            .unwrap();
+        assert_eq!(lp_pool.check_solvency(), Ok(()));
+        assert_eq!(
+            lp_pool.state().token_amount,
+            before.token_amount - swapped.tokens_out
+        );
+        assert_eq!(lp_pool.state().referral_fees_owed, swapped.referral_fee);
+        let attributable = lp_pool.lp_attributable_value();
+        let total = lp_pool.total_value();
+
+        assert_eq!(lp_pool.withdraw_referral_fees(), swapped.referral_fee);
+        assert_eq!(lp_pool.check_solvency(), Ok(()));
+        assert_eq!(lp_pool.total_value(), total - swapped.referral_fee);
+        assert_eq!(lp_pool.lp_attributable_value(), attributable);
+        assert_eq!(
+            lp_pool.state().referral_fees_owed,
+            TokenAmount::from_lamports(0)
+        );
+    }
+
+    #[test]
+    fn it_returns_err_if_referral_fee_exceeds_max() {
+        let mut lp_pool = referral_pool();
+
+        assert_eq!(
+            lp_pool.swap_with_referral(
+                StakedTokenAmount::from_lamports(100),
+                Fee::from_basis_points(3001)
+            ),
+            Err(Error::LpPool(LpPoolError::ReferralFeeTooHigh {
+                requested: Fee::from_basis_points(3001),
+                max: Fee::from_basis_points(3000),
+            }))
+        );
+    }
+
+    #[test]
+    fn it_interpolates_fee_curve_below_basis_point() {
+        let lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(10, 100)
+            .target_lamports(1000)
+            .build()
+            .pool;
+
+        assert_eq!(
+            lp_pool.calculate_fee(TokenAmount::from_lamports(333)),
+            Ok(Fee::from_centibps(7_003))
+        );
+        assert_eq!(
+            lp_pool.calculate_fee(TokenAmount::from_lamports(1000)),
+            Ok(Fee::from_basis_points(10))
+        );
+    }
+
+    #[test]
+    fn it_passes_solvency_check_after_operations() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(200)
+            .fees(100, 1000)
+            .target_lamports(10)
+            .seeded_with_lamports(200)
+            .build()
+            .pool;
+        lp_pool.swap(StakedTokenAmount::from_lamports(50)).unwrap();
+        lp_pool
+            .remove_liquidity(LpTokenAmount::from_lamports(100))
+            .unwrap();
+        assert_eq!(lp_pool.check_solvency(), Ok(()));
+        assert!(lp_pool.fees_collected > TokenAmount::from_lamports(0));
+
+        // the last LP out takes the fees the pool kept
+        lp_pool
+            .remove_liquidity(LpTokenAmount::from_lamports(100))
+            .unwrap();
+        assert_eq!(lp_pool.check_solvency(), Ok(()));
+        assert_eq!(lp_pool.token_amount, TokenAmount::from_lamports(0));
+        assert_eq!(lp_pool.fees_collected, TokenAmount::from_lamports(0));
+    }
+
+    #[test]
+    fn it_reports_invariant_violations_of_corrupted_pool() {
+        let lp_pool = PoolFixture::new().build().pool;
+
+        assert_eq!(
+            lp_pool
+                .clone()
+                .with_balances_unchecked(
+                    TokenAmount::from_lamports(10),
+                    StakedTokenAmount::from_lamports(0),
+                    LpTokenAmount::from_lamports(10),
+                    TokenAmount::from_lamports(11),
+                )
+                .check_solvency(),
+            Err(InvariantViolation::FeesExceedBalance {
+                fees_collected: TokenAmount::from_lamports(11),
+                token_amount: TokenAmount::from_lamports(10),
+            })
+        );
+        let mut owing = lp_pool.clone().with_balances_unchecked(
+            TokenAmount::from_lamports(10),
+            StakedTokenAmount::from_lamports(0),
+            LpTokenAmount::from_lamports(10),
+            TokenAmount::from_lamports(0),
+        );
+        owing.referral_fees_owed = TokenAmount::from_lamports(4);
+        owing.protocol_fees_owed = TokenAmount::from_lamports(7);
+        assert_eq!(
+            owing.check_solvency(),
+            Err(InvariantViolation::ProtocolFeesExceedBalance {
+                protocol_fees_owed: TokenAmount::from_lamports(7),
+                token_amount: TokenAmount::from_lamports(6),
+            })
+        );
+        owing.referral_fees_owed = TokenAmount::from_lamports(11);
+        assert_eq!(
+            owing.check_solvency(),
+            Err(InvariantViolation::ReferralFeesExceedBalance {
+                referral_fees_owed: TokenAmount::from_lamports(11),
+                token_amount: TokenAmount::from_lamports(10),
+            })
+        );
+        assert_eq!(
+            lp_pool
+                .clone()
+                .with_balances_unchecked(
+                    TokenAmount::from_lamports(10),
+                    StakedTokenAmount::from_lamports(0),
+                    LpTokenAmount::from_lamports(0),
+                    TokenAmount::from_lamports(1),
+                )
+                .check_solvency(),
+            Err(InvariantViolation::BalanceWithoutLpSupply {
+                token_amount: TokenAmount::from_lamports(10),
+                fees_collected: TokenAmount::from_lamports(1),
+            })
+        );
+        assert_eq!(
+            lp_pool
+                .clone()
+                .with_balances_unchecked(
+                    TokenAmount::from_lamports(0),
+                    StakedTokenAmount::from_lamports(1),
+                    LpTokenAmount::from_lamports(0),
+                    TokenAmount::from_lamports(0),
+                )
+                .check_solvency(),
+            Err(InvariantViolation::StakedWithoutLpSupply {
+                staked_token_amount: StakedTokenAmount::from_lamports(1),
+            })
+        );
+        assert_eq!(
+            lp_pool
+                .with_balances_unchecked(
+                    TokenAmount::from_lamports(u64::MAX),
+                    StakedTokenAmount::from_lamports(0),
+                    LpTokenAmount::from_lamports(u64::MAX),
+                    TokenAmount::from_lamports(0),
+                )
+                .check_solvency(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn it_continues_pool_taken_over_from_state() {
+        let mut lp_pool = PoolFixture::new()
+            .price("1.37")
+            .fees(10, 900)
+            .target_lamports(1_000_000)
+            .seeded_with_lamports(1_000_000)
+            .build()
+            .pool;
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(300_000))
+            .unwrap();
+        lp_pool
+            .remove_liquidity(LpTokenAmount::from_lamports(200_000))
+            .unwrap();
+
+        let mut taken_over = LpPool::from_state(lp_pool.state()).unwrap();
+        assert_eq!(taken_over.state(), lp_pool.state());
+
+        let staked = StakedTokenAmount::from_lamports(100_000);
+        assert_eq!(taken_over.swap(staked), lp_pool.swap(staked));
+        let tokens = TokenAmount::from_lamports(50_000);
+        assert_eq!(
+            taken_over.add_liquidity(tokens),
+            lp_pool.add_liquidity(tokens)
+        );
+        assert_eq!(taken_over.state(), lp_pool.state());
+    }
+
+    #[test]
+    fn it_rejects_state_with_lp_supply_but_no_value() {
+        let mut state = PoolFixture::new().build().pool.state();
+        state.lp_token_amount = LpTokenAmount::from_lamports(1_000);
+        state.token_amount = TokenAmount::from_lamports(10);
+        state.referral_fees_owed = TokenAmount::from_lamports(10);
+
+        let error = LpPool::from_state(state).unwrap_err();
+        assert_eq!(
+            error,
+            Error::LpPool(LpPoolError::StateInvalid(
+                InvariantViolation::LpSupplyWithoutValue {
+                    lp_token_amount: LpTokenAmount::from_lamports(1_000),
+                }
+            ))
+        );
+        assert_eq!(
+            error.display_chain().to_string(),
+            "LpPool: StateInvalid: LpSupplyWithoutValue(lp_token_amount: 1000)"
+        );
+
+        state.lp_token_amount = LpTokenAmount::from_lamports(0);
+        assert!(LpPool::from_state(state).is_ok());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "pool invariant violated: FeesExceedBalance")]
+    fn it_panics_when_mutating_corrupted_pool_in_debug_builds() {
+        let mut lp_pool = PoolFixture::new().build().pool.with_balances_unchecked(
+            TokenAmount::from_lamports(0),
+            StakedTokenAmount::from_lamports(0),
+            LpTokenAmount::from_lamports(0),
+            TokenAmount::from_lamports(1_000),
+        );
+
+        let _ = lp_pool.add_liquidity(TokenAmount::from_lamports(10));
+    }
+
+    #[test]
+    fn it_removes_all_liquidity_leaving_no_dust() {
+        for (deposits, swap) in [
+            (&[200][..], 50),
+            (&[1_000_000, 333, 77_777][..], 1_234),
+            (&[1, 2, 3][..], 0),
+        ] {
+            let mut lp_pool = PoolFixture::new()
+                .price_points(200)
+                .fees(100, 1000)
+                .target_lamports(10)
+                .build()
+                .pool;
+            for deposit in deposits {
+                lp_pool
+                    .add_liquidity(TokenAmount::from_lamports(*deposit))
+                    .unwrap();
+            }
+            if swap > 0 {
+                lp_pool
+                    .swap(StakedTokenAmount::from_lamports(swap))
+                    .unwrap();
+            }
+            let staked_before = lp_pool.staked_token_amount;
+
+            let (_, staked_tokens) = lp_pool.remove_all_liquidity().unwrap();
+
+            assert_eq!(staked_tokens, staked_before);
+            assert_eq!(lp_pool.token_amount, TokenAmount::from_lamports(0));
+            let remains = lp_pool.close().unwrap();
+            // the whole LP supply claims every LP-owned lamport, so no rounding dust is left
+            assert_eq!(remains.token_dust, TokenAmount::from_lamports(0));
+            assert_eq!(
+                remains.staked_token_amount,
+                StakedTokenAmount::from_lamports(0)
+            );
+        }
+    }
+
+    #[test]
+    fn it_charges_no_fee_when_removing_all_liquidity() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(100, 1000)
+            .target_lamports(1_000_000)
+            .seeded_with_lamports(10_000)
+            .build()
+            .pool;
+
+        let (tokens, _) = lp_pool.remove_all_liquidity().unwrap();
+
+        assert_eq!(tokens, TokenAmount::from_lamports(10_000));
+        assert_eq!(lp_pool.token_amount, TokenAmount::from_lamports(0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn it_removes_all_liquidity_like_the_whole_supply_past_floor_and_dust() {
+        let mut lp_pool = floored_pool().with_history_policy(history::HistoryPolicy::Unbounded);
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(10_000))
+            .unwrap();
+        lp_pool.set_dust_mode(
+            DustMode::RejectZeroOutput,
+            TokenAmount::from_lamports(1_000_000),
+        );
+        let supply = lp_pool.lp_token_amount;
+        let mut removed = lp_pool.clone();
+
+        assert_eq!(
+            lp_pool.remove_all_liquidity(),
+            removed.remove_liquidity(supply)
+        );
+        assert_eq!(lp_pool.state(), removed.state());
+        assert_eq!(lp_pool.lp_token_amount, LpTokenAmount::from_lamports(0));
+        let last = lp_pool.history().last().unwrap();
+        assert_eq!(
+            last.operation,
+            operation::Operation::RemoveLiquidity(supply)
+        );
+        assert_eq!(last.fee, TokenAmount::from_lamports(0));
+    }
+
+    #[test]
+    fn it_returns_err_when_closing_pool_with_lp_supply() {
+        let lp_pool = PoolFixture::new().seeded_with_lamports(100).build().pool;
+
+        assert_eq!(
+            lp_pool.close(),
+            Err(Error::LpPool(LpPoolError::PoolNotEmpty(
+                LpTokenAmount::from_lamports(100)
+            )))
+        );
+    }
+
+    fn dusty_pool(dust_policy: DustPolicy) -> (LpPool, Vec<LpTokenAmount>) {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .build()
+            .pool
+            .with_dust_policy(dust_policy);
+        let positions = [100, 200]
+            .into_iter()
+            .map(|tokens| {
+                lp_pool
+                    .add_liquidity(TokenAmount::from_lamports(tokens))
+                    .unwrap()
+            })
+            .collect();
+        lp_pool.swap(StakedTokenAmount::from_lamports(1)).unwrap();
+        (lp_pool, positions)
+    }
+
+    #[test]
+    fn it_reconciles_payouts_and_dust_against_deposits() {
+        let (mut lp_pool, positions) = dusty_pool(DustPolicy::Collectable);
+        let deposited = TokenAmount::from_lamports(300);
+        let mut paid_out = TokenAmount::from_lamports(1);
+
+        for position in positions {
+            let (tokens, _) = lp_pool.remove_liquidity(position).unwrap();
+            paid_out += tokens;
+            assert_eq!(deposited, paid_out + lp_pool.total_value());
+        }
+
+        assert_eq!(paid_out, TokenAmount::from_lamports(299));
+        assert_eq!(lp_pool.state().dust, TokenAmount::from_lamports(1));
+        assert_eq!(
+            lp_pool.total_value(),
+            lp_pool.state().fees_collected + lp_pool.state().dust
+        );
+        assert_eq!(lp_pool.collect_dust(), Ok(TokenAmount::from_lamports(1)));
+        assert_eq!(lp_pool.total_value(), TokenAmount::from_lamports(0));
+    }
+
+    #[test]
+    fn it_accounts_for_every_lamport_once_lps_withdraw_everything() {
+        let mut lp_pool = PoolFixture::new()
+            .price("1.37")
+            .fees(10, 900)
+            .target_lamports(1_000_000)
+            .build()
+            .pool
+            .with_dust_policy(DustPolicy::Collectable)
+            .with_withdrawal_fee(Some(Fee::from_basis_points(30)), WithdrawalFeeMode::Replace)
+            .unwrap()
+            .with_max_referral_fee(Fee::MAX)
+            .unwrap()
+            .with_management_fee(Fee::from_basis_points(5))
+            .unwrap();
+        let positions: Vec<_> = [400_003, 250_007, 90_011]
+            .into_iter()
+            .map(|tokens| {
+                lp_pool
+                    .add_liquidity(TokenAmount::from_lamports(tokens))
+                    .unwrap()
+            })
+            .collect();
+        let deposited = TokenAmount::from_lamports(400_003 + 250_007 + 90_011);
+        let mut paid_out = TokenAmount::from_lamports(0);
+        let mut staked_paid_out = StakedTokenAmount::from_lamports(0);
+
+        for staked in [10_007, 123_457, 33_331] {
+            let swapped = lp_pool
+                .swap_with_referral(
+                    StakedTokenAmount::from_lamports(staked),
+                    Fee::from_basis_points(2_500),
+                )
+                .unwrap();
+            paid_out += swapped.tokens_out;
+        }
+        lp_pool.advance_epoch().unwrap();
+        paid_out += lp_pool.withdraw_referral_fees();
+        let (tokens, staked) = lp_pool.remove_liquidity(positions[1]).unwrap();
+        paid_out += tokens;
+        staked_paid_out += staked;
+        assert!(lp_pool.lp_attributable_value().unwrap() > TokenAmount::from_lamports(0));
+
+        let state = lp_pool.state();
+        let fees_collected = state.fees_collected;
+        let attributable = lp_pool.lp_attributable_value().unwrap();
+        let available =
+            state.token_amount - state.referral_fees_owed - state.protocol_fees_owed - state.dust;
+        let (tokens, staked) = lp_pool.remove_all_liquidity().unwrap();
+        assert_eq!(staked, state.staked_token_amount);
+        assert_eq!(tokens, available);
+        assert_eq!(
+            attributable,
+            available + TokenAmount::from_staked_tokens_ceil(staked, state.price).unwrap()
+        );
+        paid_out += tokens;
+        staked_paid_out += staked;
+        paid_out += lp_pool.collect_dust().unwrap();
+        let protocol_fees_owed = lp_pool.state().protocol_fees_owed;
+        assert_eq!(lp_pool.claim_protocol_fees(), protocol_fees_owed);
+        paid_out += protocol_fees_owed;
+
+        let state = lp_pool.state();
+        assert_eq!(state.lp_token_amount, LpTokenAmount::from_lamports(0));
+        assert_eq!(
+            lp_pool.lp_attributable_value(),
+            Ok(TokenAmount::from_lamports(0))
+        );
+        assert_eq!(lp_pool.check_solvency(), Ok(()));
+        assert_eq!(
+            state.staked_token_amount,
+            StakedTokenAmount::from_lamports(0)
+        );
+        assert_eq!(
+            staked_paid_out,
+            StakedTokenAmount::from_lamports(10_007 + 123_457 + 33_331)
+        );
+        assert!(fees_collected > TokenAmount::from_lamports(0));
+        assert!(protocol_fees_owed > TokenAmount::from_lamports(0));
+        assert_eq!(state.token_amount, TokenAmount::from_lamports(0));
+        assert_eq!(state.fees_collected, TokenAmount::from_lamports(0));
+        assert_eq!(state.protocol_fees_owed, TokenAmount::from_lamports(0));
+        assert_eq!(deposited, paid_out);
+    }
+
+    #[test]
+    fn it_reports_unrecorded_flows_on_reconcile() {
+        let (mut lp_pool, positions) = dusty_pool(DustPolicy::Collectable);
+        let deposited = TokenAmount::from_lamports(300);
+        let swapped_out = TokenAmount::from_lamports(1);
+
+        assert_eq!(
+            lp_pool.reconcile(deposited, swapped_out),
+            SignedTokenAmount::ZERO
+        );
+
+        let (tokens, _) = lp_pool.remove_liquidity(positions[0]).unwrap();
+        assert_eq!(
+            lp_pool.reconcile(deposited, swapped_out),
+            -SignedTokenAmount::from(tokens)
+        );
+        assert_eq!(
+            lp_pool.reconcile(deposited, swapped_out + tokens + tokens),
+            SignedTokenAmount::from(tokens)
+        );
+    }
+
+    #[test]
+    fn it_leaves_dust_to_remaining_lps_by_default() {
+        let (mut lp_pool, positions) = dusty_pool(DustPolicy::AccrueToLps);
+
+        let (first, _) = lp_pool.remove_liquidity(positions[0]).unwrap();
+        let (second, _) = lp_pool.remove_liquidity(positions[1]).unwrap();
+
+        assert_eq!(
+            (first, second),
+            (
+                TokenAmount::from_lamports(99),
+                TokenAmount::from_lamports(200)
+            )
+        );
+        assert_eq!(lp_pool.state().dust, TokenAmount::from_lamports(1));
+        assert_eq!(
+            lp_pool.collect_dust(),
+            Err(Error::LpPool(LpPoolError::DustNotCollectable))
+        );
+    }
+
+    #[test]
+    fn it_executes_quote_for_quoted_amount() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(200)
+            .fees(10, 900)
+            .target_lamports(1_000)
+            .seeded_with_lamports(1_000)
+            .build()
+            .pool;
+        let quote = lp_pool
+            .quote_swap(StakedTokenAmount::from_lamports(100))
+            .unwrap();
+
+        assert_eq!(lp_pool.execute_quote(&quote), Ok(quote.amount_out));
+        assert_eq!(lp_pool.sequence(), quote.pool_sequence + 1);
+    }
+
+    #[test]
+    fn it_returns_err_when_executing_stale_quote() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(200)
+            .fees(10, 900)
+            .target_lamports(1_000)
+            .seeded_with_lamports(1_000)
+            .build()
+            .pool;
+        let quote = lp_pool
+            .quote_swap(StakedTokenAmount::from_lamports(100))
+            .unwrap();
+        lp_pool
+            .add_liquidity(TokenAmount::from_lamports(10))
+            .unwrap();
+        let before = lp_pool.state();
+
+        assert_eq!(
+            lp_pool.execute_quote(&quote),
+            Err(Error::LpPool(LpPoolError::StaleQuote {
+                quoted_at: quote.pool_sequence,
+                current: quote.pool_sequence + 1,
+            }))
+        );
+        assert_eq!(lp_pool.state(), before);
+    }
+
+    #[test]
+    fn it_rejects_swap_quote_after_surplus_threshold_change() {
+        let mut lp_pool = surplus_pool(None);
+        let quote = lp_pool
+            .quote_swap(StakedTokenAmount::from_lamports(1_000))
+            .unwrap();
+        lp_pool.set_surplus_threshold(Some(TokenAmount::from_lamports(0)));
+        let before = lp_pool.state();
+
+        assert_eq!(
+            lp_pool.execute_quote(&quote),
+            Err(Error::LpPool(LpPoolError::StaleQuote {
+                quoted_at: quote.pool_sequence,
+                current: quote.pool_sequence + 1,
+            }))
+        );
+        assert_eq!(lp_pool.state(), before);
+    }
+
+    #[test]
+    fn it_rejects_quotes_whose_payout_changed_without_sequence_change() {
+        let mut lp_pool = PoolFixture::new()
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool;
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(50_000))
+            .unwrap();
+        let swap_quote = lp_pool
+            .quote_swap(StakedTokenAmount::from_lamports(1_000))
+            .unwrap();
+        let add_quote = lp_pool
+            .quote_add_liquidity(TokenAmount::from_lamports(1_000))
+            .unwrap();
+        let remove_quote = lp_pool
+            .quote_remove_liquidity(LpTokenAmount::from_lamports(1_000))
+            .unwrap();
+        lp_pool.price = Price::from_raw_points(200);
+        lp_pool.incentive_bps_at_empty = Some(1_000);
+        lp_pool.withdrawal_fee = Some(Fee::from_basis_points(500));
+        lp_pool.withdrawal_fee_mode = WithdrawalFeeMode::Replace;
+        let before = lp_pool.state();
+        let stale = || {
+            Error::LpPool(LpPoolError::StaleQuote {
+                quoted_at: swap_quote.pool_sequence,
+                current: swap_quote.pool_sequence,
+            })
+        };
+
+        assert_eq!(lp_pool.execute_quote(&swap_quote), Err(stale()));
+        assert_eq!(
+            lp_pool.execute_add_liquidity_quote(&add_quote),
+            Err(stale())
+        );
+        assert_eq!(
+            lp_pool.execute_remove_liquidity_quote(&remove_quote),
+            Err(stale())
+        );
+        assert_eq!(lp_pool.state(), before);
+    }
+
+    #[test]
+    fn it_rejects_quotes_after_settings_change() {
+        let settings: [fn(&mut LpPool); 7] = [
+            |lp_pool| {
+                lp_pool
+                    .set_withdrawal_fee(
+                        Some(Fee::from_basis_points(500)),
+                        WithdrawalFeeMode::Replace,
+                    )
+                    .unwrap();
+            },
+            |lp_pool| {
+                lp_pool
+                    .set_token_cap(Some(TokenAmount::from_lamports(1_000_000)))
+                    .unwrap();
+            },
+            |lp_pool| {
+                lp_pool
+                    .set_min_reserve(Some(TokenAmount::from_lamports(1_000)))
+                    .unwrap();
+            },
+            |lp_pool| {
+                lp_pool.set_dust_mode(DustMode::RejectZeroOutput, TokenAmount::from_lamports(1));
+            },
+            |lp_pool| {
+                lp_pool
+                    .set_management_fee(Fee::from_basis_points(1))
+                    .unwrap()
+            },
+            |lp_pool| {
+                lp_pool
+                    .set_max_referral_fee(Fee::from_basis_points(100))
+                    .unwrap()
+            },
+            |lp_pool| lp_pool.set_surplus_threshold(Some(TokenAmount::from_lamports(0))),
+        ];
+        for change in settings {
+            let mut lp_pool = PoolFixture::new()
+                .fees(10, 900)
+                .target_lamports(100_000)
+                .seeded_with_lamports(100_000)
+                .build()
+                .pool;
+            let swap_quote = lp_pool
+                .quote_swap(StakedTokenAmount::from_lamports(1_000))
+                .unwrap();
+            let remove_quote = lp_pool
+                .quote_remove_liquidity(LpTokenAmount::from_lamports(1_000))
+                .unwrap();
+            change(&mut lp_pool);
+            let current = lp_pool.sequence();
+            let stale = || {
+                Error::LpPool(LpPoolError::StaleQuote {
+                    quoted_at: swap_quote.pool_sequence,
+                    current,
+                })
+            };
+
+            assert_ne!(lp_pool.sequence(), swap_quote.pool_sequence);
+            assert_eq!(lp_pool.execute_quote(&swap_quote), Err(stale()));
+            assert_eq!(
+                lp_pool.execute_remove_liquidity_quote(&remove_quote),
+                Err(stale())
+            );
+        }
+    }
+
+    #[test]
+    fn it_runs_scenarios_through_pool_trait_unchanged() {
+        for (name, steps) in SCENARIOS {
+            let fixture = PoolFixture::new()
+                .price_points(200)
+                .fees(10, 900)
+                .target_lamports(10_000);
+            let mut direct = fixture.clone().build().pool;
+            let mut lp_pool = fixture.build().pool;
+            let dyn_pool: &mut dyn Pool = &mut lp_pool;
+
+            for step in *steps {
+                let expected = match *step {
+                    Step::AddLiquidity(tokens) => direct.add_liquidity(tokens).map(|lp| {
+                        (
+                            TokenAmount::from_lamports(u64::from(lp)),
+                            StakedTokenAmount::from_lamports(0),
+                        )
+                    }),
+                    Step::RemoveLiquidity(lp_tokens) => direct.remove_liquidity(lp_tokens),
+                    Step::Swap(staked_tokens) => direct
+                        .swap(staked_tokens)
+                        .map(|tokens| (tokens, StakedTokenAmount::from_lamports(0))),
+                };
+
+                assert_eq!(step.run(dyn_pool), expected, "{name}: {step:?}");
+                assert_eq!(dyn_pool.state(), direct.state(), "{name}: {step:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn it_compounds_management_fee_per_epoch() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .seeded_with_lamports(1_000_000_000)
+            .build()
+            .pool
+            .with_management_fee(Fee::from_basis_points(10))
+            .unwrap();
+        let lp_tokens = lp_pool.state().lp_token_amount;
+        let value_before = lp_pool
+            .quote_remove_liquidity(lp_tokens)
+            .unwrap()
+            .amount_out
+            .tokens;
+
+        let charged: u64 = (0..10)
+            .map(|_| u64::from(lp_pool.advance_epoch().unwrap().management_fee))
+            .sum();
+
+        let value_after = lp_pool
+            .quote_remove_liquidity(lp_tokens)
+            .unwrap()
+            .amount_out
+            .tokens;
+        // 1e9 * 0.999^10 = 990_044_880.209..., each epoch rounds the fee down
+        let compounded = 990_044_880;
+        assert!(u64::from(value_after).abs_diff(compounded) <= 10);
+        assert_eq!(u64::from(value_before - value_after), charged);
+        assert_eq!(
+            lp_pool.state().protocol_fees_owed,
+            TokenAmount::from_lamports(charged)
+        );
+        assert_eq!(
+            lp_pool.state().fees_collected,
+            TokenAmount::from_lamports(0)
+        );
+        assert_eq!(lp_pool.epoch(), 10);
+
+        let token_amount = lp_pool.state().token_amount;
+        assert_eq!(
+            lp_pool.claim_protocol_fees(),
+            TokenAmount::from_lamports(charged)
+        );
+        assert_eq!(
+            lp_pool.state().token_amount,
+            token_amount - TokenAmount::from_lamports(charged)
+        );
+        assert_eq!(
+            lp_pool.state().protocol_fees_owed,
+            TokenAmount::from_lamports(0)
+        );
+        assert_eq!(
+            lp_pool
+                .quote_remove_liquidity(lp_tokens)
+                .unwrap()
+                .amount_out
+                .tokens,
+            value_after
+        );
+    }
+
+    #[test]
+    fn it_charges_management_fee_on_staked_value() {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(1_000_000)
+            .build()
+            .pool;
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(900_000))
+            .unwrap();
+        let value = lp_pool.lp_attributable_value().unwrap();
+        let available = lp_pool.available_tokens();
+        assert!(value > TokenAmount::from_lamports(1_000_000));
+        assert!(available < TokenAmount::from_lamports(200_000));
+
+        let mut charged = lp_pool
+            .clone()
+            .with_management_fee(Fee::from_basis_points(10))
+            .unwrap();
+        assert_eq!(
+            charged.advance_epoch().unwrap().management_fee,
+            TokenAmount::from_lamports(u64::from(value) / 1_000)
+        );
+        assert_eq!(
+            charged.lp_attributable_value(),
+            Ok(value - TokenAmount::from_lamports(u64::from(value) / 1_000))
+        );
+        assert_eq!(charged.check_solvency(), Ok(()));
+
+        // a fee above the available tokens takes all of them and leaves the staked tokens
+        let mut drained = lp_pool
+            .with_management_fee(Fee::from_basis_points(5_000))
+            .unwrap();
+        assert_eq!(drained.advance_epoch().unwrap().management_fee, available);
+        assert_eq!(drained.state().protocol_fees_owed, available);
+        assert_eq!(drained.available_tokens(), TokenAmount::from_lamports(0));
+        assert_eq!(drained.check_solvency(), Ok(()));
+        assert_eq!(drained.claim_protocol_fees(), available);
+        assert_eq!(drained.check_solvency(), Ok(()));
+    }
+
+    #[test]
+    fn it_charges_nothing_on_empty_pool() {
+        let mut lp_pool = PoolFixture::new()
+            .build()
+            .pool
+            .with_management_fee(Fee::from_basis_points(10))
+            .unwrap();
+
+        let summary = lp_pool.advance_epoch().unwrap();
+
+        assert_eq!(summary.management_fee, TokenAmount::from_lamports(0));
+        assert_eq!(
+            lp_pool.state().protocol_fees_owed,
+            TokenAmount::from_lamports(0)
+        );
+    }
+
+    /// Pool holding `available` tokens, 5 000 of them collected fees, and staked tokens.
+    fn incentive_pool(available: u64, incentive_bps_at_empty: u32) -> LpPool {
+        LpPool::from_state(PoolState {
+            price: Price::from_raw_points(Price::SCALE),
+            min_fee: Fee::from_basis_points(10),
+            max_fee: Fee::from_basis_points(900),
+            liquidity_target: TokenAmount::from_lamports(100_000),
+            token_amount: TokenAmount::from_lamports(available),
+            staked_token_amount: StakedTokenAmount::from_lamports(100_000),
+            lp_token_amount: LpTokenAmount::from_lamports(100_000),
+            fees_collected: TokenAmount::from_lamports(5_000),
+            referral_fees_owed: TokenAmount::from_lamports(0),
+            dust: TokenAmount::from_lamports(0),
+            protocol_fees_owed: TokenAmount::from_lamports(0),
+        })
+        .unwrap()
+        .with_incentive_bps_at_empty(Some(incentive_bps_at_empty))
+        .unwrap()
+    }
+
+    #[test]
+    fn it_pays_no_deposit_bonus_at_or_above_target() {
+        let tokens = TokenAmount::from_lamports(10_000);
+        for available in [100_000, 250_000] {
+            let mut lp_pool = incentive_pool(available, 1_000);
+            let expected = lp_pool
+                .clone()
+                .with_incentive_bps_at_empty(None)
+                .unwrap()
+                .add_liquidity(tokens);
+
+            assert_eq!(
+                lp_pool.deposit_bonus(tokens),
+                Ok(TokenAmount::from_lamports(0))
+            );
+            assert_eq!(lp_pool.add_liquidity(tokens), expected);
+            assert_eq!(
+                lp_pool.state().fees_collected,
+                TokenAmount::from_lamports(5_000)
+            );
+        }
+    }
+
+    #[test]
+    fn it_scales_deposit_bonus_with_distance_below_target() {
+        let tokens = TokenAmount::from_lamports(10_000);
+
+        assert_eq!(
+            incentive_pool(5_000, 1_000).deposit_bonus(tokens),
+            Ok(TokenAmount::from_lamports(950))
+        );
+        assert_eq!(
+            incentive_pool(50_000, 1_000).deposit_bonus(tokens),
+            Ok(TokenAmount::from_lamports(500))
+        );
+        assert_eq!(
+            incentive_pool(5_000, 1_000).deposit_bonus(TokenAmount::from_lamports(1_000_000)),
+            Ok(TokenAmount::from_lamports(5_000))
+        );
+        assert_eq!(
+            incentive_pool(5_000, 1_000).set_incentive_bps_at_empty(Some(10_001)),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(
+                Fee::from_basis_points(10_001)
+            )))
+        );
+    }
+
+    #[test]
+    fn it_funds_deposit_bonus_from_collected_fees() {
+        for tokens in [10_000, 1_000_000] {
+            let mut lp_pool = incentive_pool(5_000, 1_000);
+            let before = lp_pool.state();
+            let value_before = lp_pool.lp_attributable_value().unwrap();
+            let tokens = TokenAmount::from_lamports(tokens);
+            let bonus = lp_pool.deposit_bonus(tokens).unwrap();
+
+            let minted = lp_pool.add_liquidity(tokens).unwrap();
+
+            let after = lp_pool.state();
+            assert_eq!(after.fees_collected, before.fees_collected - bonus);
+            assert_eq!(lp_pool.total_value(), before.token_amount + tokens);
+            // the fees were already LP value, the bonus only shifts it to the depositor
+            assert_eq!(lp_pool.lp_attributable_value(), Ok(value_before + tokens));
+            assert_eq!(
+                u64::from(minted),
+                u64::from(tokens + bonus) * u64::from(before.lp_token_amount)
+                    / u64::from(value_before)
+            );
+            assert_solvent(&lp_pool);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn it_keeps_fee_monotonic_and_within_bounds(
+            fees in (0..=Fee::MAX_CENTIBPS, 0..=Fee::MAX_CENTIBPS),
+            target in 1..=u64::MAX,
+            mut amounts in prop::collection::vec(any::<u64>(), 1..32),
+        ) {
+            let (min_fee, max_fee) = (
+                Fee::from_centibps(fees.0.min(fees.1)),
+                Fee::from_centibps(fees.0.max(fees.1)),
+            );
+            let lp_pool = LpPool::init(PoolParams {
+                price: Price::from_raw_points(Price::SCALE),
+                fee_range: FeeRange::new(min_fee, max_fee).unwrap(),
+                liquidity_target: TokenAmount::from_lamports(target),
+            })
+            .unwrap();
+            let fee_at = |lamports| lp_pool.calculate_fee(TokenAmount::from_lamports(lamports)).unwrap();
+
+            prop_assert_eq!(fee_at(0), max_fee);
+            prop_assert_eq!(fee_at(target), min_fee);
+            amounts.sort_unstable();
+            let fees: Vec<Fee> = amounts.iter().map(|amount| fee_at(*amount)).collect();
+            for fee in &fees {
+                prop_assert!(min_fee <= *fee && *fee <= max_fee);
+            }
+            for pair in fees.windows(2) {
+                prop_assert!(pair[1] <= pair[0]);
+            }
+        }
+
+        #[test]
+        fn it_dilutes_lps_by_at_most_deposit_bonus(
+            swaps in prop::collection::vec(1..=100_000_u64, 0..8),
+            incentive_bps_at_empty in 0..=10_000_u32,
+            tokens in 1..=1_000_000_u64,
+        ) {
+            let mut lp_pool = PoolFixture::new()
+                .fees(10, 900)
+                .target_lamports(100_000)
+                .seeded_with_lamports(100_000)
+                .build()
+                .pool
+                .with_incentive_bps_at_empty(Some(incentive_bps_at_empty))
+                .unwrap();
+            for staked in swaps {
+                let _ = lp_pool.swap(StakedTokenAmount::from_lamports(staked));
+            }
+            let before = lp_pool.state();
+            let value_before = lp_pool.lp_attributable_value().unwrap();
+            let tokens = TokenAmount::from_lamports(tokens);
+            let bonus = lp_pool.deposit_bonus(tokens).unwrap();
+
+            let minted = lp_pool.add_liquidity(tokens).unwrap();
+
+            let value_after = lp_pool.lp_attributable_value().unwrap();
+            prop_assert!(bonus <= before.fees_collected);
+            prop_assert_eq!(lp_pool.total_value(), before.token_amount + tokens);
+            prop_assert_eq!(value_after, value_before + tokens);
+            // the LPs before the deposit own at least the value they owned less the bonus
+            let supply_before = u128::from(u64::from(before.lp_token_amount));
+            let supply_after = supply_before + u128::from(u64::from(minted));
+            prop_assert!(
+                u128::from(u64::from(value_after)) * supply_before
+                    >= u128::from(u64::from(value_before - bonus)) * supply_after
+            );
+        }
+
+        #[test]
+        fn it_keeps_fee_monotonic_with_surplus_threshold(
+            target in 1..=u64::MAX,
+            surplus_threshold in any::<u64>(),
+            mut amounts in prop::collection::vec(any::<u64>(), 1..32),
+        ) {
+            let lp_pool = LpPool::init(PoolParams {
+                price: Price::from_raw_points(Price::SCALE),
+                fee_range: FeeRange::new(Fee::from_basis_points(10), Fee::from_basis_points(900))
+                    .unwrap(),
+                liquidity_target: TokenAmount::from_lamports(target),
+            })
+            .unwrap()
+            .with_surplus_threshold(Some(TokenAmount::from_lamports(surplus_threshold)));
+            let line = target.saturating_add(surplus_threshold);
+
+            amounts.sort_unstable();
+            let fees: Vec<Fee> = amounts
+                .iter()
+                .map(|amount| lp_pool.calculate_fee(TokenAmount::from_lamports(*amount)).unwrap())
+                .collect();
+            for (amount, fee) in amounts.iter().zip(&fees) {
+                prop_assert_eq!(*fee == Fee::ZERO, *amount >= line);
+            }
+            for pair in fees.windows(2) {
+                prop_assert!(pair[1] <= pair[0]);
+            }
+        }
+
+        #[test]
+        fn it_never_pays_out_more_than_exact_value(
+            points in 1..=10 * Price::SCALE,
+            staked in 0..=1_000_000_000u64,
+            tokens in 1..=1_000_000_000u64,
+        ) {
+            let mut lp_pool = PoolFixture::new()
+                .price_points(points)
+                .fees(10, 900)
+                .target_lamports(1_000_000_000)
+                .seeded_with_lamports(10_000_000_000)
+                .build()
+                .pool;
+            let scale = u128::from(Price::SCALE);
+
+            let quote = lp_pool.quote_swap(StakedTokenAmount::from_lamports(staked)).unwrap();
+            let paid = u128::from(u64::from(quote.amount_out + quote.fee_amount));
+            prop_assert!(paid * scale <= u128::from(staked) * u128::from(points));
+            lp_pool.execute_quote(&quote).unwrap();
+
+            // exact value of the pool in points, available tokens plus staked tokens at the price
+            let state = lp_pool.state();
+            let value = u128::from(u64::from(state.token_amount)) * scale
+                + u128::from(u64::from(state.staked_token_amount)) * u128::from(points);
+            let supply = u128::from(u64::from(state.lp_token_amount));
+            let minted = lp_pool.add_liquidity(TokenAmount::from_lamports(tokens)).unwrap();
+            prop_assert!(u128::from(u64::from(minted)) * value <= u128::from(tokens) * supply * scale);
+        }
+    }
+}
+
+/// Every public method against a pool fresh from `init`, see the contract on `LpPool`.
+#[cfg(test)]
+mod empty_pool_tests {
+    use super::*;
+    use crate::lp_pool::bulk::PoolOp;
+    use crate::test_utils::PoolFixture;
+
+    fn empty_pool() -> LpPool {
+        PoolFixture::new()
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .build()
+            .pool
+    }
+
+    fn empty() -> Error {
+        Error::LpPool(LpPoolError::EmptyPool)
+    }
+
+    #[test]
+    fn it_mints_first_deposit_one_to_one() {
+        let tokens = TokenAmount::from_lamports(12_345);
+        let minted = LpTokenAmount::from_lamports(12_345);
+
+        let mut lp_pool = empty_pool();
+        let quote = lp_pool.quote_add_liquidity(tokens).unwrap();
+        assert_eq!(quote.amount_out, minted);
+        assert_eq!(lp_pool.execute_add_liquidity_quote(&quote), Ok(minted));
+        assert_eq!(empty_pool().add_liquidity(tokens), Ok(minted));
+        assert_eq!(
+            empty_pool().run_op(PoolOp::AddLiquidity(tokens)),
+            Ok(bulk::OpOutput::LpTokens(minted))
+        );
+        #[cfg(feature = "std")]
+        {
+            assert_eq!(
+                empty_pool().add_liquidity_idempotent([1; 16], tokens),
+                Ok(minted)
+            );
+            assert_eq!(
+                empty_pool().add_liquidity_for(tiers::ProviderId(1), tokens),
+                Ok(minted)
+            );
+        }
+    }
+
+    #[test]
+    fn it_rejects_operations_on_lp_liquidity() {
+        let mut lp_pool = empty_pool();
+        let state = lp_pool.state();
+        let staked = StakedTokenAmount::from_lamports(1_000);
+        let lp_tokens = LpTokenAmount::from_lamports(1_000);
+        let swap_quote = Quote {
+            amount_in: staked,
+            amount_out: TokenAmount::from_lamports(1_000),
+            fee: Fee::ZERO,
+            fee_amount: TokenAmount::from_lamports(0),
+            pool_sequence: lp_pool.sequence(),
+        };
+        let remove_quote = Quote {
+            amount_in: lp_tokens,
+            amount_out: RemovedLiquidity {
+                tokens: TokenAmount::from_lamports(1_000),
+                staked_tokens: StakedTokenAmount::from_lamports(0),
+            },
+            fee: Fee::ZERO,
+            fee_amount: TokenAmount::from_lamports(0),
+            pool_sequence: lp_pool.sequence(),
+        };
+
+        assert_eq!(lp_pool.quote_swap(staked), Err(empty()));
+        assert_eq!(lp_pool.lp_price(), Err(empty()));
+        assert_eq!(lp_pool.view().quote_swap(staked), Err(empty()));
+        assert_eq!(lp_pool.post_swap_token_amount(staked), Err(empty()));
+        assert_eq!(lp_pool.swap(staked), Err(empty()));
+        assert_eq!(lp_pool.swap_with_referral(staked, Fee::ZERO), Err(empty()));
+        assert_eq!(
+            lp_pool.swap_with_min_out(staked, TokenAmount::from_lamports(0)),
+            Err(empty())
+        );
+        assert_eq!(lp_pool.swap_partial(staked), Err(empty()));
+        assert_eq!(lp_pool.execute_quote(&swap_quote), Err(empty()));
+        assert_eq!(lp_pool.run_op(PoolOp::Swap(staked)), Err(empty()));
+        assert_eq!(
+            lp_pool.apply_batch(&[operation::Operation::Swap(staked)]),
+            Err(operation::BatchFailure {
+                index: 0,
+                error: empty()
+            })
+        );
+
+        assert_eq!(lp_pool.quote_remove_liquidity(lp_tokens), Err(empty()));
+        assert_eq!(
+            lp_pool.view().quote_remove_liquidity(lp_tokens),
+            Err(empty())
+        );
+        assert_eq!(
+            lp_pool.remove_liquidity(LpTokenAmount::from_lamports(0)),
+            Err(empty())
+        );
+        assert_eq!(lp_pool.remove_liquidity(lp_tokens), Err(empty()));
+        assert_eq!(lp_pool.remove_all_liquidity(), Err(empty()));
+        assert_eq!(
+            lp_pool.execute_remove_liquidity_quote(&remove_quote),
+            Err(empty())
+        );
+        assert_eq!(
+            lp_pool.run_op(PoolOp::RemoveLiquidity(lp_tokens)),
+            Err(empty())
+        );
+        assert_eq!(lp_pool.share_bps(lp_tokens), Err(empty()));
+        assert_eq!(lp_pool.amounts_for_share_bps(5_000), Err(empty()));
+        assert_eq!(
+            lp_pool.apply(operation::Operation::Swap(staked)),
+            Err(empty())
+        );
+
+        #[cfg(feature = "std")]
+        {
+            assert_eq!(lp_pool.swap_idempotent([1; 16], staked), Err(empty()));
+            assert_eq!(
+                lp_pool.remove_liquidity_idempotent([2; 16], lp_tokens),
+                Err(empty())
+            );
+            assert_eq!(lp_pool.swap_for(tiers::ProviderId(1), staked), Err(empty()));
+            assert_eq!(
+                lp_pool.remove_liquidity_for(tiers::ProviderId(1), lp_tokens),
+                Err(empty())
+            );
+            assert_eq!(lp_pool.idempotency_key_count(), 0);
+            assert_eq!(
+                lp_pool.fee_schedule(&[TokenAmount::from_lamports(1_000)]),
+                Err(empty())
+            );
+        }
+        assert_eq!(lp_pool.state(), state);
+        assert_eq!(lp_pool.sequence(), 0);
+    }
+
+    #[test]
+    fn it_reports_metrics_of_empty_pool() {
+        let lp_pool = empty_pool();
+        let zero = TokenAmount::from_lamports(0);
+
+        assert_eq!(
+            lp_pool.state().lp_token_amount,
+            LpTokenAmount::from_lamports(0)
+        );
+        assert_eq!(lp_pool.total_value(), zero);
+        assert_eq!(lp_pool.lp_attributable_value(), Ok(zero));
+        assert_eq!(lp_pool.reconcile(zero, zero), SignedTokenAmount::from(zero));
+        assert_eq!(lp_pool.max_swap_in(), StakedTokenAmount::from_lamports(0));
+        assert_eq!(lp_pool.calculate_fee(zero), Ok(Fee::from_basis_points(900)));
+        assert_eq!(lp_pool.view().fee_at(zero), Ok(Fee::from_basis_points(900)));
+        assert_eq!(lp_pool.view().utilization_bps(), 10_000);
+        assert_eq!(lp_pool.check_solvency(), Ok(()));
+        assert_eq!(lp_pool.epoch(), 0);
+        assert_eq!(lp_pool.checkpoint().timestamp, 0);
+        assert_eq!(lp_pool.min_reserve(), None);
+        #[cfg(feature = "std")]
+        {
+            assert_eq!(lp_pool.history().len(), 0);
+            assert_eq!(
+                lp_pool.status_line(),
+                "tok=0.000 sol staked=0.000 msol lp=0.000 target=0.000 fee=9.00% util=0%"
+            );
+        }
+        assert_eq!(
+            lp_pool.close(),
+            Ok(PoolRemains {
+                fees_collected: zero,
+                token_dust: zero,
+                staked_token_amount: StakedTokenAmount::from_lamports(0),
+                referral_fees_owed: zero,
+                protocol_fees_owed: zero,
+            })
+        );
+    }
+
+    #[test]
+    fn it_accepts_configuration_and_non_lp_payouts() {
+        let mut lp_pool = empty_pool()
+            .with_dust_policy(DustPolicy::Collectable)
+            .with_max_referral_fee(Fee::from_basis_points(5_000))
+            .unwrap()
+            .with_management_fee(Fee::from_basis_points(1))
+            .unwrap()
+            .with_withdrawal_fee(Some(Fee::ZERO), WithdrawalFeeMode::Replace)
+            .unwrap();
+
+        assert_eq!(
+            lp_pool.set_max_swap_size(Some(StakedTokenAmount::from_lamports(10))),
+            Ok(())
+        );
+        assert_eq!(
+            lp_pool.set_token_cap(Some(TokenAmount::from_lamports(1_000_000))),
+            Ok(())
+        );
+        assert_eq!(
+            lp_pool.set_min_reserve(Some(TokenAmount::from_lamports(10))),
+            Ok(())
+        );
+        lp_pool.set_surplus_threshold(Some(TokenAmount::from_lamports(0)));
+        assert_eq!(lp_pool.set_incentive_bps_at_empty(Some(1_000)), Ok(()));
+        assert_eq!(
+            lp_pool.deposit_bonus(TokenAmount::from_lamports(1_000)),
+            Ok(TokenAmount::from_lamports(0))
+        );
+        let params = PoolParams {
+            price: lp_pool.price,
+            fee_range: FeeRange::new(Fee::from_basis_points(20), Fee::from_basis_points(800))
+                .unwrap(),
+            liquidity_target: TokenAmount::from_lamports(200_000),
+        };
+        assert_eq!(
+            lp_pool
+                .reconfigure(params)
+                .map(|report| report.lp_price_after),
+            Ok(None)
+        );
+        assert_eq!(
+            lp_pool.calculate_fee(TokenAmount::from_lamports(0)),
+            Ok(lp_pool.state().max_fee)
+        );
+        assert_eq!(lp_pool.current_fee(), Ok(lp_pool.state().max_fee));
+        assert_eq!(
+            lp_pool.tokens_until_fee(lp_pool.state().min_fee),
+            Ok(Some(TokenAmount::from_lamports(200_000)))
+        );
+        assert_eq!(lp_pool.update_price(Price::from_raw_points(120)), Ok(()));
+        assert_eq!(
+            lp_pool.update_price_at(Price::from_raw_points(130), 5),
+            Ok(())
+        );
+        assert_eq!(
+            lp_pool
+                .advance_epoch()
+                .map(|summary| summary.management_fee),
+            Ok(TokenAmount::from_lamports(0))
+        );
+        assert_eq!(
+            lp_pool.withdraw_referral_fees(),
+            TokenAmount::from_lamports(0)
+        );
+        assert_eq!(lp_pool.claim_protocol_fees(), TokenAmount::from_lamports(0));
+        assert_eq!(lp_pool.collect_dust(), Ok(TokenAmount::from_lamports(0)));
+        #[cfg(feature = "std")]
+        {
+            assert_eq!(
+                lp_pool
+                    .clone()
+                    .with_history_policy(history::HistoryPolicy::Unbounded)
+                    .with_rejection_log(true)
+                    .rejections(),
+                []
+            );
+            lp_pool.subscribe(events::EventFilter::ALL, |_| {});
+            assert_eq!(
+                lp_pool.apply_batch(&[operation::Operation::UpdatePrice(lp_pool.state().price)]),
+                Ok(())
+            );
+            let mut clocked = lp_pool.clone().with_clock(clock::ManualClock::new(5));
+            assert_eq!(clocked.update_price(clocked.state().price), Ok(()));
+            assert_eq!(clocked.checkpoint().timestamp, 5);
+            let ticket = lp_pool
+                .request_delayed_unstake(StakedTokenAmount::from_lamports(100), 0)
+                .unwrap();
+            assert_eq!(
+                lp_pool.claim_ticket(ticket, 1),
+                Ok(TokenAmount::from_lamports(130))
+            );
+        }
         assert_eq!(
-            lp_pool.staked_token_amount,
-            StakedTokenAmount::from_lamports(149)
+            lp_pool.state().lp_token_amount,
+            LpTokenAmount::from_lamports(0)
         );
     }
 }
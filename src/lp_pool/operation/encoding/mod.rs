@@ -0,0 +1,363 @@
+//! Canonical bytes of a `PoolOperation`, for hashing, signing and sending between services.
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::lp_pool::{
+    data::{
+        fee::Fee,
+        price::Price,
+        token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    },
+    error::Error as LpPoolError,
+    operation::{Balances, Operation, PoolOperation},
+};
+
+const INIT: u8 = 0;
+const ADD_LIQUIDITY: u8 = 1;
+const REMOVE_LIQUIDITY: u8 = 2;
+const SWAP: u8 = 3;
+const UPDATE_PRICE: u8 = 4;
+
+/// `PoolOperation` fields in declaration order, little-endian with no padding. Amounts are
+/// lamports, prices points and fees hundredths of a basis point:
+///
+/// | size | field                                 |
+/// |------|---------------------------------------|
+/// | 8    | `sequence`                            |
+/// | 1    | operation tag                         |
+/// | 0-24 | operation payload, see below          |
+/// | 8    | `fee`                                 |
+/// | 8    | `post_balances.price`                 |
+/// | 8    | `post_balances.token_amount`          |
+/// | 8    | `post_balances.staked_token_amount`   |
+/// | 8    | `post_balances.lp_token_amount`       |
+/// | 8    | `post_balances.fees_collected`        |
+/// | 8    | `timestamp`                           |
+/// | 1    | 1 if `recorded_at` is set, 0 if not   |
+/// | 0-8  | `recorded_at` if set                  |
+///
+/// | tag | operation         | payload                                                   |
+/// |-----|-------------------|-----------------------------------------------------------|
+/// | 0   | `Init`            | `price` 8, `min_fee` 4, `max_fee` 4, `liquidity_target` 8 |
+/// | 1   | `AddLiquidity`    | tokens 8                                                  |
+/// | 2   | `RemoveLiquidity` | LP tokens 8                                               |
+/// | 3   | `Swap`            | staked tokens 8                                           |
+/// | 4   | `UpdatePrice`     | price 8                                                   |
+impl PoolOperation {
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(90);
+        bytes.extend(self.sequence.to_le_bytes());
+        match self.operation {
+            Operation::Init {
+                price,
+                min_fee,
+                max_fee,
+                liquidity_target,
+            } => {
+                bytes.push(INIT);
+                bytes.extend(u64::from(price).to_le_bytes());
+                bytes.extend(min_fee.centibps.to_le_bytes());
+                bytes.extend(max_fee.centibps.to_le_bytes());
+                bytes.extend(u64::from(liquidity_target).to_le_bytes());
+            }
+            Operation::AddLiquidity(tokens) => {
+                bytes.push(ADD_LIQUIDITY);
+                bytes.extend(u64::from(tokens).to_le_bytes());
+            }
+            Operation::RemoveLiquidity(lp_tokens) => {
+                bytes.push(REMOVE_LIQUIDITY);
+                bytes.extend(u64::from(lp_tokens).to_le_bytes());
+            }
+            Operation::Swap(staked_tokens) => {
+                bytes.push(SWAP);
+                bytes.extend(u64::from(staked_tokens).to_le_bytes());
+            }
+            Operation::UpdatePrice(price) => {
+                bytes.push(UPDATE_PRICE);
+                bytes.extend(u64::from(price).to_le_bytes());
+            }
+        }
+        let balances = self.post_balances;
+        for lamports in [
+            u64::from(self.fee),
+            u64::from(balances.price),
+            u64::from(balances.token_amount),
+            u64::from(balances.staked_token_amount),
+            u64::from(balances.lp_token_amount),
+            u64::from(balances.fees_collected),
+            self.timestamp,
+        ] {
+            bytes.extend(lamports.to_le_bytes());
+        }
+        match self.recorded_at {
+            Some(recorded_at) => {
+                bytes.push(1);
+                bytes.extend(recorded_at.to_le_bytes());
+            }
+            None => bytes.push(0),
+        }
+        bytes
+    }
+
+    /// Inverse of `encode`, failing with `OperationEncodingInvalid` on an unknown tag or
+    /// `recorded_at` flag, a truncated buffer or bytes left over.
+    ///
+    /// # Errors
+    ///
+    /// Returns `OperationEncodingInvalid` for bytes `encode` did not produce.
+    pub fn decode(bytes: &[u8]) -> Result<PoolOperation> {
+        let mut reader = Reader { bytes, offset: 0 };
+        let sequence = reader.u64()?;
+        let tag_offset = reader.offset;
+        let operation = match reader.u8()? {
+            INIT => Operation::Init {
+                price: Price::from_raw_points(reader.u64()?),
+                min_fee: Fee::from_centibps(reader.u32()?),
+                max_fee: Fee::from_centibps(reader.u32()?),
+                liquidity_target: TokenAmount::from_lamports(reader.u64()?),
+            },
+            ADD_LIQUIDITY => Operation::AddLiquidity(TokenAmount::from_lamports(reader.u64()?)),
+            REMOVE_LIQUIDITY => {
+                Operation::RemoveLiquidity(LpTokenAmount::from_lamports(reader.u64()?))
+            }
+            SWAP => Operation::Swap(StakedTokenAmount::from_lamports(reader.u64()?)),
+            UPDATE_PRICE => Operation::UpdatePrice(Price::from_raw_points(reader.u64()?)),
+            _ => return Err(invalid(tag_offset, bytes)),
+        };
+        let pool_operation = PoolOperation {
+            sequence,
+            operation,
+            fee: TokenAmount::from_lamports(reader.u64()?),
+            post_balances: Balances {
+                price: Price::from_raw_points(reader.u64()?),
+                token_amount: TokenAmount::from_lamports(reader.u64()?),
+                staked_token_amount: StakedTokenAmount::from_lamports(reader.u64()?),
+                lp_token_amount: LpTokenAmount::from_lamports(reader.u64()?),
+                fees_collected: TokenAmount::from_lamports(reader.u64()?),
+            },
+            timestamp: reader.u64()?,
+            recorded_at: {
+                let flag_offset = reader.offset;
+                match reader.u8()? {
+                    0 => None,
+                    1 => Some(reader.u64()?),
+                    _ => return Err(invalid(flag_offset, bytes)),
+                }
+            },
+        };
+        if reader.offset != bytes.len() {
+            return Err(invalid(reader.offset, bytes));
+        }
+        Ok(pool_operation)
+    }
+
+    /// SHA-256 of `encode`.
+    #[must_use]
+    pub fn digest(&self) -> [u8; 32] {
+        Sha256::digest(self.encode()).into()
+    }
+}
+
+fn invalid(offset: usize, bytes: &[u8]) -> Error {
+    Error::LpPool(LpPoolError::OperationEncodingInvalid {
+        offset,
+        len: bytes.len(),
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let field = self
+            .bytes
+            .get(self.offset..)
+            .and_then(|rest| rest.first_chunk::<N>())
+            .ok_or_else(|| invalid(self.offset, self.bytes))?;
+        self.offset += N;
+        Ok(*field)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        self.take::<1>().map(|[byte]| byte)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn balances() -> Balances {
+        Balances {
+            price: Price::from_raw_points(0x0102),
+            token_amount: TokenAmount::from_lamports(0x0304),
+            staked_token_amount: StakedTokenAmount::from_lamports(0x0506),
+            lp_token_amount: LpTokenAmount::from_lamports(0x0708),
+            fees_collected: TokenAmount::from_lamports(0x090a),
+        }
+    }
+
+    fn pool_operation(operation: Operation) -> PoolOperation {
+        PoolOperation {
+            sequence: 7,
+            operation,
+            fee: TokenAmount::from_lamports(0xff),
+            post_balances: balances(),
+            timestamp: u64::MAX,
+            recorded_at: None,
+        }
+    }
+
+    fn le(value: u64) -> [u8; 8] {
+        value.to_le_bytes()
+    }
+
+    /// `payload` framed by the fields every operation shares.
+    fn golden(tag: u8, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![7, 0, 0, 0, 0, 0, 0, 0, tag];
+        bytes.extend(payload);
+        for value in [0xff, 0x0102, 0x0304, 0x0506, 0x0708, 0x090a, u64::MAX] {
+            bytes.extend(le(value));
+        }
+        bytes.push(0);
+        bytes
+    }
+
+    #[test]
+    fn it_encodes_every_operation_to_golden_bytes() {
+        let cases = [
+            (
+                Operation::Init {
+                    price: Price::from_raw_points(0x1122),
+                    min_fee: Fee::from_centibps(0x33),
+                    max_fee: Fee::from_centibps(0x4455),
+                    liquidity_target: TokenAmount::from_lamports(0x66),
+                },
+                golden(
+                    0,
+                    &[
+                        0x22, 0x11, 0, 0, 0, 0, 0, 0, 0x33, 0, 0, 0, 0x55, 0x44, 0, 0, 0x66, 0, 0,
+                        0, 0, 0, 0, 0,
+                    ],
+                ),
+            ),
+            (
+                Operation::AddLiquidity(TokenAmount::from_lamports(1)),
+                golden(1, &le(1)),
+            ),
+            (
+                Operation::RemoveLiquidity(LpTokenAmount::from_lamports(2)),
+                golden(2, &le(2)),
+            ),
+            (
+                Operation::Swap(StakedTokenAmount::from_lamports(3)),
+                golden(3, &le(3)),
+            ),
+            (
+                Operation::UpdatePrice(Price::from_raw_points(4)),
+                golden(4, &le(4)),
+            ),
+        ];
+
+        for (operation, bytes) in cases {
+            let pool_operation = pool_operation(operation);
+            assert_eq!(pool_operation.encode(), bytes);
+            assert_eq!(PoolOperation::decode(&bytes), Ok(pool_operation));
+        }
+        assert_eq!(
+            pool_operation(Operation::Swap(StakedTokenAmount::from_lamports(3))).encode()[..9],
+            [7, 0, 0, 0, 0, 0, 0, 0, SWAP]
+        );
+
+        let recorded = PoolOperation {
+            recorded_at: Some(0x0b0c),
+            ..pool_operation(Operation::UpdatePrice(Price::from_raw_points(4)))
+        };
+        let mut bytes = golden(4, &le(4));
+        bytes.pop();
+        bytes.push(1);
+        bytes.extend(le(0x0b0c));
+        assert_eq!(recorded.encode(), bytes);
+        assert_eq!(PoolOperation::decode(&bytes), Ok(recorded));
+    }
+
+    #[test]
+    fn it_digests_encoding() {
+        let swap = pool_operation(Operation::Swap(StakedTokenAmount::from_lamports(3)));
+        let other = pool_operation(Operation::Swap(StakedTokenAmount::from_lamports(4)));
+
+        assert_eq!(
+            swap.digest(),
+            <[u8; 32]>::from(Sha256::digest(swap.encode()))
+        );
+        assert_eq!(swap.digest(), swap.digest());
+        assert_ne!(swap.digest(), other.digest());
+    }
+
+    #[test]
+    fn it_rejects_unknown_tag_truncation_and_trailing_bytes() {
+        let bytes = pool_operation(Operation::AddLiquidity(TokenAmount::from_lamports(1))).encode();
+        let invalid = |offset, len| {
+            Err(Error::LpPool(LpPoolError::OperationEncodingInvalid {
+                offset,
+                len,
+            }))
+        };
+
+        let mut unknown = bytes.clone();
+        unknown[8] = 5;
+        assert_eq!(PoolOperation::decode(&unknown), invalid(8, bytes.len()));
+        for len in 0..bytes.len() {
+            let error = PoolOperation::decode(&bytes[..len]).unwrap_err();
+            assert!(matches!(
+                error,
+                Error::LpPool(LpPoolError::OperationEncodingInvalid { offset, len: got })
+                    if offset <= len && got == len
+            ));
+        }
+        assert_eq!(PoolOperation::decode(&bytes[..20]), invalid(17, 20));
+        let mut flag = bytes.clone();
+        *flag.last_mut().unwrap() = 2;
+        assert_eq!(
+            PoolOperation::decode(&flag),
+            invalid(bytes.len() - 1, bytes.len())
+        );
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            PoolOperation::decode(&trailing),
+            invalid(bytes.len(), bytes.len() + 1)
+        );
+    }
+
+    #[cfg(feature = "proptest")]
+    mod round_trip {
+        use proptest::prelude::*;
+
+        use super::super::*;
+        use crate::lp_pool::strategies::pool_operation;
+
+        proptest! {
+            #[test]
+            fn it_round_trips_generated_operations(pool_operation in pool_operation()) {
+                let bytes = pool_operation.encode();
+
+                prop_assert_eq!(PoolOperation::decode(&bytes), Ok(pool_operation));
+                prop_assert_eq!(PoolOperation::decode(&bytes).unwrap().encode(), bytes);
+            }
+        }
+    }
+}
@@ -0,0 +1,347 @@
+//! Pool operations as data, for replaying and validating them.
+
+#[cfg(feature = "std")]
+pub mod encoding;
+
+use crate::error::{Error, Result};
+use crate::lp_pool::{
+    data::{
+        fee::{Fee, FeeRange},
+        price::Price,
+        token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    },
+    error::Error as LpPoolError,
+    LpPool, PoolParams,
+};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operation {
+    Init {
+        price: Price,
+        min_fee: Fee,
+        max_fee: Fee,
+        liquidity_target: TokenAmount,
+    },
+    AddLiquidity(TokenAmount),
+    RemoveLiquidity(LpTokenAmount),
+    Swap(StakedTokenAmount),
+    UpdatePrice(Price),
+}
+
+/// Pool balances after an operation.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Balances {
+    pub price: Price,
+    pub token_amount: TokenAmount,
+    pub staked_token_amount: StakedTokenAmount,
+    pub lp_token_amount: LpTokenAmount,
+    pub fees_collected: TokenAmount,
+}
+
+impl Balances {
+    pub(crate) fn of(lp_pool: &LpPool) -> Self {
+        let state = lp_pool.state();
+        Self {
+            price: state.price,
+            token_amount: state.token_amount,
+            staked_token_amount: state.staked_token_amount,
+            lp_token_amount: state.lp_token_amount,
+            fees_collected: state.fees_collected,
+        }
+    }
+}
+
+/// An operation and its outcome, as recorded in a ledger or the pool history.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoolOperation {
+    /// `LpPool::sequence` after the operation.
+    pub sequence: u64,
+    pub operation: Operation,
+    /// Swap or withdrawal fee the operation added to `fees_collected`.
+    pub fee: TokenAmount,
+    pub post_balances: Balances,
+    /// Chosen by the caller of `execute`, entries of the pool's own history carry the pool
+    /// epoch.
+    pub timestamp: u64,
+    /// Time of the pool's clock when it recorded the entry, `None` without a clock and for
+    /// `execute`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub recorded_at: Option<u64>,
+}
+
+impl PoolOperation {
+    /// Applies `operation` to the pool and describes the outcome. `Operation::Init` replaces
+    /// the pool, every other operation needs one.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EmptyPool` for an operation other than `Operation::Init` without a pool, and
+    /// otherwise the error of the pool method it runs.
+    pub fn execute(
+        lp_pool: &mut Option<LpPool>,
+        operation: Operation,
+        timestamp: u64,
+    ) -> Result<Self> {
+        let fees_before = lp_pool
+            .as_ref()
+            .map_or(TokenAmount::from_lamports(0), |lp_pool| {
+                lp_pool.state().fees_collected
+            });
+        let pool = match (operation, lp_pool.as_mut()) {
+            (
+                Operation::Init {
+                    price,
+                    min_fee,
+                    max_fee,
+                    liquidity_target,
+                },
+                None,
+            ) => lp_pool.insert(LpPool::init(PoolParams {
+                price,
+                fee_range: FeeRange::new(min_fee, max_fee)?,
+                liquidity_target,
+            })?),
+            (_, None) => return Err(Error::LpPool(LpPoolError::EmptyPool)),
+            (operation, Some(pool)) => {
+                pool.apply(operation)?;
+                pool
+            }
+        };
+
+        let post_balances = Balances::of(pool);
+        Ok(Self {
+            sequence: pool.sequence(),
+            operation,
+            fee: post_balances
+                .fees_collected
+                .checked_sub(fees_before)
+                .unwrap_or(TokenAmount::from_lamports(0)),
+            post_balances,
+            timestamp,
+            recorded_at: None,
+        })
+    }
+}
+
+/// The operation of a batch that failed, by position, and its error. See
+/// `LpPool::apply_batch`.
+#[derive(Debug, PartialEq)]
+pub struct BatchFailure {
+    pub index: usize,
+    pub error: Error,
+}
+
+impl core::fmt::Display for BatchFailure {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "operation {} of the batch failed: {}",
+            self.index, self.error
+        )
+    }
+}
+
+impl core::error::Error for BatchFailure {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl LpPool {
+    /// Runs `operation` through the same method a caller would use. `Operation::Init`
+    /// replaces the pool, settings included. A failed operation leaves the pool unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the pool method `operation` runs.
+    pub fn apply(&mut self, operation: Operation) -> Result<()> {
+        match operation {
+            Operation::Init {
+                price,
+                min_fee,
+                max_fee,
+                liquidity_target,
+            } => {
+                *self = LpPool::init(PoolParams {
+                    price,
+                    fee_range: FeeRange::new(min_fee, max_fee)?,
+                    liquidity_target,
+                })?;
+            }
+            Operation::AddLiquidity(tokens) => {
+                self.add_liquidity(tokens)?;
+            }
+            Operation::RemoveLiquidity(lp_tokens) => {
+                self.remove_liquidity(lp_tokens)?;
+            }
+            Operation::Swap(staked_tokens) => {
+                self.swap(staked_tokens)?;
+            }
+            Operation::UpdatePrice(price) => self.update_price(price)?,
+        }
+        Ok(())
+    }
+
+    /// Applies every operation of `operations` or none. They run strictly in order, each
+    /// against the pool the ones before it left: a swap after an `UpdatePrice` converts at
+    /// the new price, and quotes taken before the batch are stale once it changed the pool.
+    /// On the first failure the pool is left as it was before the batch, price included.
+    ///
+    /// The batch is dry-run on a clone first, so subscribers and the history only see
+    /// batches that are applied.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first failing operation and its error as a `BatchFailure`.
+    pub fn apply_batch(
+        &mut self,
+        operations: &[Operation],
+    ) -> core::result::Result<(), BatchFailure> {
+        let mut dry_run = self.clone();
+        for (index, operation) in operations.iter().enumerate() {
+            dry_run
+                .apply(*operation)
+                .map_err(|error| BatchFailure { index, error })?;
+        }
+        for (index, operation) in operations.iter().enumerate() {
+            self.apply(*operation)
+                .map_err(|error| BatchFailure { index, error })?;
+        }
+        Ok(())
+    }
+
+    /// Dry-runs `operations` on a clone of the pool, returning what each would return from
+    /// `apply`. Operations run in the order `apply_batch` runs them, price updates included,
+    /// but every operation is evaluated: one that fails is skipped and the ones after it
+    /// run against the pool as if it had never been submitted.
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn validate_batch(&self, operations: &[Operation]) -> Vec<Result<()>> {
+        let mut lp_pool = self.clone();
+        operations
+            .iter()
+            .map(|operation| lp_pool.apply(*operation))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PoolFixture;
+
+    #[test]
+    fn it_reports_failures_in_batch_without_changing_pool() {
+        let lp_pool = PoolFixture::new()
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool;
+        let state = lp_pool.state();
+        let operations = [
+            Operation::AddLiquidity(TokenAmount::from_lamports(10_000)),
+            Operation::RemoveLiquidity(LpTokenAmount::from_lamports(1_000_000)),
+            Operation::Swap(StakedTokenAmount::from_lamports(1_000)),
+            Operation::UpdatePrice(Price::from_raw_points(0)),
+            Operation::Swap(StakedTokenAmount::from_lamports(109_000)),
+        ];
+
+        let results = lp_pool.validate_batch(&operations);
+
+        assert_eq!(
+            results,
+            vec![
+                Ok(()),
+                Err(Error::LpPool(LpPoolError::InsufficientLpTokens {
+                    requested: LpTokenAmount::from_lamports(1_000_000),
+                    available: LpTokenAmount::from_lamports(110_000),
+                })),
+                Ok(()),
+                Err(Error::LpPool(LpPoolError::PriceIncorrect(
+                    Price::from_raw_points(0)
+                ))),
+                Ok(()),
+            ]
+        );
+        assert_eq!(lp_pool.state(), state);
+
+        let mut executed = lp_pool.clone();
+        let executed_results: Vec<_> = operations
+            .iter()
+            .map(|operation| executed.apply(*operation))
+            .collect();
+        assert_eq!(executed_results, results);
+    }
+
+    #[test]
+    fn it_swaps_at_price_in_effect_at_position_in_batch() {
+        let mut lp_pool = PoolFixture::new()
+            .price("1.0")
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool;
+        let swap = Operation::Swap(StakedTokenAmount::from_lamports(1_000));
+        let operations = [swap, Operation::UpdatePrice("1.5".parse().unwrap()), swap];
+        let mut called = lp_pool.clone();
+        let first = called
+            .swap(StakedTokenAmount::from_lamports(1_000))
+            .unwrap();
+        called.update_price("1.5".parse().unwrap()).unwrap();
+        let second = called
+            .swap(StakedTokenAmount::from_lamports(1_000))
+            .unwrap();
+
+        assert_eq!(
+            lp_pool.validate_batch(&operations),
+            vec![Ok(()), Ok(()), Ok(())]
+        );
+        lp_pool.apply_batch(&operations).unwrap();
+
+        assert!(second > first);
+        assert_eq!(lp_pool.state(), called.state());
+        assert_eq!(lp_pool.sequence(), called.sequence());
+    }
+
+    #[test]
+    fn it_restores_pre_batch_price_when_final_operation_fails() {
+        let mut lp_pool = PoolFixture::new()
+            .price("1.0")
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool
+            .with_history_policy(crate::lp_pool::history::HistoryPolicy::Unbounded);
+        let (state, sequence) = (lp_pool.state(), lp_pool.sequence());
+        let operations = [
+            Operation::Swap(StakedTokenAmount::from_lamports(1_000)),
+            Operation::UpdatePrice("1.5".parse().unwrap()),
+            Operation::Swap(StakedTokenAmount::from_lamports(1_000)),
+            Operation::RemoveLiquidity(LpTokenAmount::from_lamports(1_000_000)),
+        ];
+
+        assert_eq!(
+            lp_pool.apply_batch(&operations),
+            Err(BatchFailure {
+                index: 3,
+                error: Error::LpPool(LpPoolError::InsufficientLpTokens {
+                    requested: LpTokenAmount::from_lamports(1_000_000),
+                    available: LpTokenAmount::from_lamports(100_000),
+                }),
+            })
+        );
+        assert_eq!(lp_pool.state(), state);
+        assert_eq!(lp_pool.state().price, "1.0".parse().unwrap());
+        assert_eq!(lp_pool.sequence(), sequence);
+        assert_eq!(lp_pool.history().len(), 0);
+    }
+}
@@ -0,0 +1,242 @@
+//! The last prices the pool was updated with, for monitoring.
+
+use std::collections::VecDeque;
+
+use crate::lp_pool::{data::price::Price, LpPool};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PriceObservation {
+    pub price: Price,
+    pub timestamp: u64,
+}
+
+/// Up to `capacity` observations, oldest first. The buffer is allocated once and a full one
+/// drops its oldest observation to make room.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PriceHistory {
+    capacity: usize,
+    observations: VecDeque<PriceObservation>,
+}
+
+impl PriceHistory {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            observations: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, observation: PriceObservation) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.observations.len() == self.capacity {
+            self.observations.pop_front();
+        }
+        self.observations.push_back(observation);
+    }
+
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.observations.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.observations.is_empty()
+    }
+
+    #[must_use]
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &PriceObservation> {
+        self.observations.iter()
+    }
+
+    #[must_use]
+    pub fn latest(&self) -> Option<PriceObservation> {
+        self.observations.back().copied()
+    }
+
+    #[must_use]
+    pub fn min(&self) -> Option<Price> {
+        self.prices().min()
+    }
+
+    #[must_use]
+    pub fn max(&self) -> Option<Price> {
+        self.prices().max()
+    }
+
+    /// Middle price, the mean of the two middle ones rounded down for an even count. Selects
+    /// on a copy, the history keeps its order.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "an even, non-empty history has a lower half"
+    )]
+    pub fn median(&self) -> Option<Price> {
+        let mut points: Vec<u64> = self.prices().map(u64::from).collect();
+        if points.is_empty() {
+            return None;
+        }
+        let len = points.len();
+        let odd = len % 2 == 1;
+        let (lower, upper, _) = points.select_nth_unstable(len / 2);
+        let upper = *upper;
+        let median = if odd {
+            upper
+        } else {
+            // PANIC SAFETY: `points` is not empty and its length is even, so the half below
+            // `len / 2` holds at least one point.
+            let below = lower
+                .iter()
+                .copied()
+                .max()
+                .expect("an even count has a lower half");
+            below + (upper - below) / 2
+        };
+        Some(Price::from_raw_points(median))
+    }
+
+    /// Observations at most `duration` older than the latest one, as a history of their own.
+    #[must_use]
+    pub fn window(&self, duration: u64) -> PriceHistory {
+        let since = self
+            .latest()
+            .map_or(0, |latest| latest.timestamp.saturating_sub(duration));
+        let observations: VecDeque<_> = self
+            .observations
+            .iter()
+            .filter(|observation| observation.timestamp >= since)
+            .copied()
+            .collect();
+        PriceHistory {
+            capacity: observations.len(),
+            observations,
+        }
+    }
+
+    fn prices(&self) -> impl Iterator<Item = Price> + '_ {
+        self.observations
+            .iter()
+            .map(|observation| observation.price)
+    }
+}
+
+impl LpPool {
+    /// Keeps the last `capacity` prices passed to `update_price` and `update_price_at`.
+    /// Updates without a time are stamped with the last timestamped one, like the TWAP
+    /// accumulator counts them.
+    #[must_use]
+    pub fn with_price_history(mut self, capacity: usize) -> Self {
+        self.price_history = Some(PriceHistory::new(capacity));
+        self
+    }
+
+    #[must_use]
+    pub fn price_history(&self) -> Option<&PriceHistory> {
+        self.price_history.as_ref()
+    }
+
+    pub(super) fn observe_price(&mut self, price: Price, timestamp: u64) {
+        if let Some(history) = &mut self.price_history {
+            history.push(PriceObservation { price, timestamp });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PoolFixture;
+
+    fn observation(points: u64, timestamp: u64) -> PriceObservation {
+        PriceObservation {
+            price: Price::from_raw_points(points),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn it_evicts_oldest_observations_past_capacity() {
+        let mut history = PriceHistory::new(4);
+        for (points, timestamp) in [(130, 1), (90, 2), (150, 3), (110, 4), (70, 5), (120, 6)] {
+            history.push(observation(points, timestamp));
+        }
+
+        assert_eq!(history.len(), 4);
+        assert_eq!(
+            history.iter().copied().collect::<Vec<_>>(),
+            [
+                observation(150, 3),
+                observation(110, 4),
+                observation(70, 5),
+                observation(120, 6)
+            ]
+        );
+        assert_eq!(history.latest(), Some(observation(120, 6)));
+        assert_eq!(history.min(), Some(Price::from_raw_points(70)));
+        assert_eq!(history.max(), Some(Price::from_raw_points(150)));
+        // 70, 110, 120, 150: (110 + 120) / 2
+        assert_eq!(history.median(), Some(Price::from_raw_points(115)));
+
+        history.push(observation(101, 7));
+        // 70, 101, 110, 120
+        assert_eq!(history.median(), Some(Price::from_raw_points(105)));
+    }
+
+    #[test]
+    fn it_computes_odd_median_and_window() {
+        let mut history = PriceHistory::new(8);
+        for (points, timestamp) in [(100, 10), (300, 20), (200, 30), (500, 40), (400, 50)] {
+            history.push(observation(points, timestamp));
+        }
+
+        assert_eq!(history.median(), Some(Price::from_raw_points(300)));
+        let window = history.window(20);
+        assert_eq!(
+            window.iter().copied().collect::<Vec<_>>(),
+            [
+                observation(200, 30),
+                observation(500, 40),
+                observation(400, 50)
+            ]
+        );
+        assert_eq!(window.median(), Some(Price::from_raw_points(400)));
+        assert_eq!(window.min(), Some(Price::from_raw_points(200)));
+    }
+
+    #[test]
+    fn it_returns_none_for_empty_history() {
+        let history = PriceHistory::new(3);
+
+        assert_eq!(history.latest(), None);
+        assert_eq!(history.min(), None);
+        assert_eq!(history.max(), None);
+        assert_eq!(history.median(), None);
+        assert!(history.window(100).is_empty());
+    }
+
+    #[test]
+    fn it_records_price_updates_when_attached() {
+        let mut lp_pool = PoolFixture::new().build().pool.with_price_history(2);
+        lp_pool
+            .update_price_at(Price::from_raw_points(110), 5)
+            .unwrap();
+        lp_pool.update_price(Price::from_raw_points(120)).unwrap();
+        lp_pool
+            .update_price_at(Price::from_raw_points(90), 9)
+            .unwrap();
+
+        let history = lp_pool.price_history().unwrap();
+        assert_eq!(
+            history.iter().copied().collect::<Vec<_>>(),
+            [observation(120, 5), observation(90, 9)]
+        );
+        assert!(PoolFixture::new().build().pool.price_history().is_none());
+    }
+}
@@ -0,0 +1,266 @@
+//! Previews of pool operations with their fee breakdown.
+
+use crate::lp_pool::data::{
+    fee::Fee,
+    token::{StakedTokenAmount, TokenAmount},
+};
+
+/// Result an operation would have at `pool_sequence`. `fee_amount` is the fee in tokens,
+/// taken out of the gross conversion of `amount_in` before it was paid as `amount_out`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quote<TIn, TOut> {
+    pub amount_in: TIn,
+    pub amount_out: TOut,
+    pub fee: Fee,
+    pub fee_amount: TokenAmount,
+    pub pool_sequence: u64,
+}
+
+/// Tokens and staked tokens paid out for burned LP tokens.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RemovedLiquidity {
+    pub tokens: TokenAmount,
+    pub staked_tokens: StakedTokenAmount,
+}
+
+impl core::fmt::Display for RemovedLiquidity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} + {} staked", self.tokens, self.staked_tokens)
+    }
+}
+
+impl From<RemovedLiquidity> for (TokenAmount, StakedTokenAmount) {
+    fn from(removed: RemovedLiquidity) -> Self {
+        (removed.tokens, removed.staked_tokens)
+    }
+}
+
+impl<TIn: core::fmt::Display, TOut: core::fmt::Display> core::fmt::Display for Quote<TIn, TOut> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} in, {} out, fee {} ({}) at sequence {}",
+            self.amount_in, self.amount_out, self.fee, self.fee_amount, self.pool_sequence
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::lp_pool::{data::token::LpTokenAmount, error::Error as LpPoolError, LpPool};
+    use crate::test_utils::PoolFixture;
+
+    fn pool() -> LpPool {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(150)
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool;
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(20_000))
+            .unwrap();
+        lp_pool
+    }
+
+    #[test]
+    fn it_reconciles_swap_quote_with_gross_conversion() {
+        let mut lp_pool = pool();
+        let staked = StakedTokenAmount::from_lamports(10_000);
+        let quote = lp_pool.quote_swap(staked).unwrap();
+
+        assert_eq!(
+            quote.amount_out + quote.fee_amount,
+            TokenAmount::from_staked_tokens(staked, lp_pool.state().price).unwrap()
+        );
+        assert!(quote.fee > Fee::from_basis_points(10));
+        assert_eq!(lp_pool.execute_quote(&quote), Ok(quote.amount_out));
+    }
+
+    #[test]
+    fn it_reconciles_add_liquidity_quote_with_gross_conversion() {
+        let mut lp_pool = pool();
+        let state = lp_pool.state();
+        let tokens = TokenAmount::from_lamports(15_000);
+        let quote = lp_pool.quote_add_liquidity(tokens).unwrap();
+        let value = u64::from(state.token_amount)
+            + state
+                .price
+                .mul_by_price_ceil(state.staked_token_amount.into())
+                .unwrap();
+
+        assert_eq!(quote.fee, Fee::ZERO);
+        assert_eq!(quote.fee_amount, TokenAmount::from_lamports(0));
+        assert_eq!(
+            u64::from(quote.amount_out),
+            u64::from(tokens) * u64::from(state.lp_token_amount) / value
+        );
+        assert_eq!(
+            lp_pool.execute_add_liquidity_quote(&quote),
+            Ok(quote.amount_out)
+        );
+    }
+
+    #[test]
+    fn it_reconciles_remove_liquidity_quote_with_gross_conversion() {
+        let mut lp_pool = pool();
+        let state = lp_pool.state();
+        let lp_tokens = LpTokenAmount::from_lamports(30_000);
+        let quote = lp_pool.quote_remove_liquidity(lp_tokens).unwrap();
+        let share = |amount: u64| amount * u64::from(lp_tokens) / u64::from(state.lp_token_amount);
+
+        assert_eq!(
+            quote.amount_out.tokens + quote.fee_amount,
+            TokenAmount::from_lamports(share(u64::from(state.token_amount)))
+        );
+        assert_eq!(
+            quote.amount_out.staked_tokens,
+            StakedTokenAmount::from_lamports(share(state.staked_token_amount.into()))
+        );
+        assert_eq!(
+            lp_pool.execute_remove_liquidity_quote(&quote),
+            Ok(quote.amount_out.into())
+        );
+    }
+
+    #[test]
+    fn it_rejects_stale_quotes_and_renders_summary() {
+        let mut lp_pool = pool();
+        let add = lp_pool
+            .quote_add_liquidity(TokenAmount::from_lamports(1_000))
+            .unwrap();
+        let remove = lp_pool
+            .quote_remove_liquidity(LpTokenAmount::from_lamports(1_000))
+            .unwrap();
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(1_000))
+            .unwrap();
+        let state = lp_pool.state();
+        let current = lp_pool.sequence();
+        let stale = || {
+            Error::LpPool(LpPoolError::StaleQuote {
+                quoted_at: add.pool_sequence,
+                current,
+            })
+        };
+
+        assert_eq!(lp_pool.execute_add_liquidity_quote(&add), Err(stale()));
+        assert_eq!(
+            lp_pool.execute_remove_liquidity_quote(&remove),
+            Err(stale())
+        );
+        assert_eq!(lp_pool.state(), state);
+        assert_eq!(
+            Quote {
+                amount_in: LpTokenAmount::from_lamports(1_000),
+                amount_out: RemovedLiquidity {
+                    tokens: TokenAmount::from_lamports(690),
+                    staked_tokens: StakedTokenAmount::from_lamports(200),
+                },
+                fee: Fee::from_basis_points(10),
+                fee_amount: TokenAmount::from_lamports(1),
+                pool_sequence: 3,
+            }
+            .to_string(),
+            "1000 in, 690 + 200 staked out, fee 0.10% (1) at sequence 3"
+        );
+    }
+
+    /// Quotes promise exactly what executing the same input on the same pool does, failing
+    /// with the same error when it fails. Proptest shrinks any divergence to a minimal pool
+    /// and input.
+    #[cfg(feature = "proptest")]
+    mod quote_matches_execution {
+        use proptest::prelude::*;
+
+        use super::*;
+        use crate::lp_pool::{
+            history::HistoryPolicy,
+            strategies::{lp_token_amount, staked_token_amount, token_amount, traded_pool},
+            DustMode,
+        };
+
+        /// Fee recorded by running `execute` on a copy of `lp_pool`, with its result.
+        fn execute<T>(
+            lp_pool: &LpPool,
+            execute: impl FnOnce(&mut LpPool) -> crate::error::Result<T>,
+        ) -> crate::error::Result<(T, TokenAmount)> {
+            let mut copy = lp_pool
+                .clone()
+                .with_history_policy(HistoryPolicy::Unbounded);
+            let result = execute(&mut copy)?;
+            let fee = copy
+                .history()
+                .last()
+                .map_or(TokenAmount::from_lamports(0), |entry| entry.fee);
+            Ok((result, fee))
+        }
+
+        fn limited_pool() -> impl Strategy<Value = (LpPool, Vec<LpTokenAmount>)> {
+            (
+                traded_pool(),
+                prop::option::of(token_amount()),
+                prop::option::of(token_amount()),
+            )
+                .prop_map(|((mut lp_pool, positions), min_reserve, min_output)| {
+                    let target = lp_pool.state().liquidity_target;
+                    lp_pool
+                        .set_min_reserve(min_reserve.map(|floor| floor.min(target)))
+                        .expect("floors up to the target are accepted");
+                    if let Some(min_output) = min_output {
+                        lp_pool.set_dust_mode(DustMode::RejectZeroOutput, min_output);
+                    }
+                    (lp_pool, positions)
+                })
+        }
+
+        proptest! {
+            #[test]
+            fn it_swaps_exactly_as_quoted(
+                (lp_pool, _) in limited_pool(),
+                staked_tokens in staked_token_amount(),
+            ) {
+                let quoted = lp_pool
+                    .quote_swap(staked_tokens)
+                    .map(|quote| (quote.amount_out, quote.fee_amount));
+
+                prop_assert_eq!(quoted, execute(&lp_pool, |copy| copy.swap(staked_tokens)));
+            }
+
+            #[test]
+            fn it_adds_liquidity_exactly_as_quoted(
+                (lp_pool, _) in limited_pool(),
+                tokens in token_amount(),
+            ) {
+                let quoted = lp_pool
+                    .quote_add_liquidity(tokens)
+                    .map(|quote| (quote.amount_out, quote.fee_amount));
+
+                prop_assert_eq!(quoted, execute(&lp_pool, |copy| copy.add_liquidity(tokens)));
+            }
+
+            #[test]
+            fn it_removes_liquidity_exactly_as_quoted(
+                (lp_pool, positions) in limited_pool(),
+                position in any::<prop::sample::Index>(),
+                other in lp_token_amount(),
+                use_position in any::<bool>(),
+            ) {
+                let lp_tokens = if use_position { *position.get(&positions) } else { other };
+                let quoted = lp_pool
+                    .quote_remove_liquidity(lp_tokens)
+                    .map(|quote| (quote.amount_out.into(), quote.fee_amount));
+
+                prop_assert_eq!(
+                    quoted,
+                    execute(&lp_pool, |copy| copy.remove_liquidity(lp_tokens))
+                );
+            }
+        }
+    }
+}
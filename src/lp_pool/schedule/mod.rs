@@ -0,0 +1,173 @@
+//! Swap fees by trade size, for callers caching fee estimates per size bucket.
+
+use crate::error::{Error, Result};
+use crate::lp_pool::{
+    data::{
+        fee::Fee,
+        price::Price,
+        token::{StakedTokenAmount, TokenAmount},
+    },
+    error::Error as LpPoolError,
+    LpPool,
+};
+
+/// Fee and tokens paid out per staked token for the smallest swap paying out `up_to`
+/// tokens before fees. `truncated` marks the last bucket of a schedule cut short at
+/// `max_swap_in`, its `up_to` is then what that swap pays out before fees.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FeeBucket {
+    pub up_to: TokenAmount,
+    pub fee: Fee,
+    pub output_per_unit: Price,
+    pub truncated: bool,
+}
+
+impl LpPool {
+    /// `quote_swap` at each bucket boundary, without changing the pool. `buckets` must be
+    /// non-zero and strictly increasing. Boundaries past `max_swap_in` are dropped and the
+    /// last bucket is flagged as truncated, so a pool accepting no swap returns no buckets.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FeeBucketsUnsorted` or `ZeroAmount` for invalid `buckets`, `EmptyPool`
+    /// without LP supply, and otherwise fails like `quote_swap`.
+    pub fn fee_schedule(&self, buckets: &[TokenAmount]) -> Result<Vec<FeeBucket>> {
+        if let Some(index) = buckets.windows(2).position(|pair| pair[0] >= pair[1]) {
+            return Err(Error::LpPool(LpPoolError::FeeBucketsUnsorted {
+                index: index + 1,
+            }));
+        }
+        if buckets.first() == Some(&TokenAmount::from_lamports(0)) {
+            return Err(Error::LpPool(LpPoolError::ZeroAmount));
+        }
+        self.ensure_not_empty()?;
+
+        let max_swap_in = self.max_swap_in();
+        let mut last_swap_in = StakedTokenAmount::from_lamports(0);
+        let mut schedule = Vec::with_capacity(buckets.len());
+        for &up_to in buckets {
+            let staked_in = StakedTokenAmount::from_tokens_ceil(up_to, self.price)?;
+            if staked_in > max_swap_in {
+                if max_swap_in > last_swap_in {
+                    let up_to = TokenAmount::from_staked_tokens(max_swap_in, self.price)?;
+                    schedule.push(self.fee_bucket(up_to, max_swap_in)?);
+                }
+                if let Some(last) = schedule.last_mut() {
+                    last.truncated = true;
+                }
+                break;
+            }
+            schedule.push(self.fee_bucket(up_to, staked_in)?);
+            last_swap_in = staked_in;
+        }
+        Ok(schedule)
+    }
+
+    fn fee_bucket(&self, up_to: TokenAmount, staked_in: StakedTokenAmount) -> Result<FeeBucket> {
+        let quote = self.quote_swap(staked_in)?;
+        Ok(FeeBucket {
+            up_to,
+            fee: quote.fee,
+            output_per_unit: Price::from_ratio(quote.amount_out.into(), staked_in.into())?,
+            truncated: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PoolFixture;
+
+    fn pool() -> LpPool {
+        PoolFixture::new()
+            .price("1.5")
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool
+    }
+
+    fn lamports(amounts: &[u64]) -> Vec<TokenAmount> {
+        amounts
+            .iter()
+            .copied()
+            .map(TokenAmount::from_lamports)
+            .collect()
+    }
+
+    #[test]
+    fn it_agrees_with_quotes_at_each_boundary() {
+        let lp_pool = pool();
+        let state = lp_pool.state();
+        let schedule = lp_pool
+            .fee_schedule(&lamports(&[1_000, 10_000, 50_000, 1_000_000]))
+            .unwrap();
+        let max_swap_in = lp_pool.max_swap_in();
+
+        assert_eq!(schedule.len(), 4);
+        for bucket in &schedule {
+            let staked_in = if bucket.truncated {
+                max_swap_in
+            } else {
+                StakedTokenAmount::from_tokens_ceil(bucket.up_to, state.price).unwrap()
+            };
+            let quote = lp_pool.quote_swap(staked_in).unwrap();
+
+            assert_eq!(bucket.fee, quote.fee);
+            assert_eq!(
+                bucket.output_per_unit,
+                Price::from_ratio(quote.amount_out.into(), staked_in.into()).unwrap()
+            );
+        }
+        assert!(schedule.windows(2).all(|pair| pair[0].fee <= pair[1].fee));
+        assert!(schedule[0].fee < schedule[3].fee);
+        assert_eq!(
+            schedule
+                .iter()
+                .map(|bucket| bucket.truncated)
+                .collect::<Vec<_>>(),
+            [false, false, false, true]
+        );
+        assert_eq!(
+            schedule[3].up_to,
+            TokenAmount::from_staked_tokens(max_swap_in, state.price).unwrap()
+        );
+        assert_eq!(lp_pool.state(), state);
+    }
+
+    #[test]
+    fn it_flags_last_bucket_when_max_swap_in_is_reached() {
+        let max_swap_size = StakedTokenAmount::from_tokens_ceil(
+            TokenAmount::from_lamports(10_000),
+            Price::from_raw_points(150),
+        )
+        .unwrap();
+        let lp_pool = pool().with_max_swap_size(Some(max_swap_size)).unwrap();
+
+        let schedule = lp_pool
+            .fee_schedule(&lamports(&[10_000, 20_000, 30_000]))
+            .unwrap();
+
+        assert_eq!(schedule.len(), 1);
+        assert_eq!(schedule[0].up_to, TokenAmount::from_lamports(10_000));
+        assert!(schedule[0].truncated);
+    }
+
+    #[test]
+    fn it_rejects_unsorted_or_zero_buckets() {
+        let lp_pool = pool();
+
+        assert_eq!(
+            lp_pool.fee_schedule(&lamports(&[1_000, 5_000, 5_000])),
+            Err(Error::LpPool(LpPoolError::FeeBucketsUnsorted { index: 2 }))
+        );
+        assert_eq!(
+            lp_pool.fee_schedule(&lamports(&[0, 5_000])),
+            Err(Error::LpPool(LpPoolError::ZeroAmount))
+        );
+        assert_eq!(lp_pool.fee_schedule(&[]), Ok(Vec::new()));
+    }
+}
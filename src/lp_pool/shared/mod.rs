@@ -0,0 +1,196 @@
+//! A pool shared between threads and event handlers that act on it.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::Error;
+use crate::lp_pool::{
+    events::{self, EventFilter, Handler, PoolEvent},
+    state::PoolState,
+    LpPool,
+};
+
+/// Handle to a pool behind a lock, clones share the pool. Events of an operation are
+/// delivered after the operation returned and the lock is released, so a handler sees the
+/// pool as the operation left it and may call into it through a clone of the handle. Events
+/// of calls made from a handler are delivered once it returns, in the order the operations
+/// executed.
+#[derive(Debug, Clone)]
+pub struct SharedLpPool {
+    pool: Arc<Mutex<LpPool>>,
+    /// Events executed operations left for delivery, oldest first.
+    outbox: Arc<Mutex<VecDeque<PoolEvent>>>,
+    dispatch: Arc<Mutex<Dispatch>>,
+}
+
+#[derive(Default)]
+struct Dispatch {
+    /// `None` while a call delivers events, calls made meanwhile only queue theirs.
+    handlers: Option<Vec<(EventFilter, Handler)>>,
+    /// Subscribed while `handlers` was out for delivery.
+    added: Vec<(EventFilter, Handler)>,
+}
+
+impl core::fmt::Debug for Dispatch {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let handlers = self.handlers.as_ref().map_or(0, Vec::len) + self.added.len();
+        write!(f, "{handlers} subscribers")
+    }
+}
+
+impl SharedLpPool {
+    /// Handlers subscribed on `lp_pool` itself keep running inside the operations.
+    #[must_use]
+    pub fn new(mut lp_pool: LpPool) -> Self {
+        let outbox = Arc::new(Mutex::new(VecDeque::new()));
+        let sink = Arc::clone(&outbox);
+        lp_pool.subscribe(EventFilter::ALL, move |event| lock(&sink).push_back(event));
+        Self {
+            pool: Arc::new(Mutex::new(lp_pool)),
+            outbox,
+            dispatch: Arc::new(Mutex::new(Dispatch {
+                handlers: Some(Vec::new()),
+                added: Vec::new(),
+            })),
+        }
+    }
+
+    /// `LpPool::subscribe` for handlers that may use the pool, see `SharedLpPool`.
+    pub fn subscribe(
+        &self,
+        filter: EventFilter,
+        handler: impl FnMut(PoolEvent) + Send + Sync + 'static,
+    ) {
+        let mut dispatch = lock(&self.dispatch);
+        let handler: Handler = Box::new(handler);
+        match dispatch.handlers.as_mut() {
+            Some(handlers) => handlers.push((filter, handler)),
+            None => dispatch.added.push((filter, handler)),
+        }
+    }
+
+    /// Runs `call` on the locked pool, then delivers the events of what it executed.
+    pub fn with<T>(&self, call: impl FnOnce(&mut LpPool) -> T) -> T {
+        let result = call(&mut lock(&self.pool));
+        self.deliver();
+        result
+    }
+
+    #[must_use]
+    pub fn state(&self) -> PoolState {
+        lock(&self.pool).state()
+    }
+
+    /// `LpPool::describe_error` under the pool's log settings.
+    #[must_use]
+    pub fn describe_error(&self, error: &Error) -> String {
+        lock(&self.pool).describe_error(error)
+    }
+
+    /// Delivers queued events unless another call is delivering, which then delivers them
+    /// too. The outbox is checked again before the handlers are put back, so an event queued
+    /// meanwhile is not left behind.
+    fn deliver(&self) {
+        let Some(mut handlers) = lock(&self.dispatch).handlers.take() else {
+            return;
+        };
+        loop {
+            loop {
+                // popped in its own statement, the guard must not outlive it into the handlers
+                let event = lock(&self.outbox).pop_front();
+                let Some(event) = event else {
+                    break;
+                };
+                events::notify(&mut handlers, event);
+            }
+            let mut dispatch = lock(&self.dispatch);
+            handlers.append(&mut dispatch.added);
+            if lock(&self.outbox).is_empty() {
+                dispatch.handlers = Some(handlers);
+                return;
+            }
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // PANIC SAFETY: the locks are only held by this module, a poisoned one means a pool
+    // operation or a handler already panicked.
+    mutex
+        .lock()
+        .expect("a thread panicked holding the shared pool")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lp_pool::{
+        data::token::{StakedTokenAmount, TokenAmount},
+        operation::Operation,
+    };
+    use crate::test_utils::PoolFixture;
+
+    #[test]
+    fn it_lets_handler_swap_through_shared_handle() {
+        let shared = SharedLpPool::new(
+            PoolFixture::new()
+                .fees(10, 900)
+                .target_lamports(100_000)
+                .seeded_with_lamports(100_000)
+                .build()
+                .pool,
+        );
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let handle = shared.clone();
+        let sink = Arc::clone(&delivered);
+        shared.subscribe(EventFilter::ALL, move |event| {
+            let PoolEvent::Executed(entry) = event else {
+                return;
+            };
+            // the pool is unlocked and already reflects the operation
+            assert_eq!(handle.with(|lp_pool| lp_pool.sequence()), entry.sequence);
+            sink.lock().unwrap().push(entry);
+            if entry.operation == Operation::Swap(StakedTokenAmount::from_lamports(1_000)) {
+                handle
+                    .with(|lp_pool| lp_pool.swap(StakedTokenAmount::from_lamports(2_000)))
+                    .unwrap();
+            }
+        });
+        let sequence = shared.with(|lp_pool| lp_pool.sequence());
+
+        shared
+            .with(|lp_pool| lp_pool.swap(StakedTokenAmount::from_lamports(1_000)))
+            .unwrap();
+        shared
+            .with(|lp_pool| lp_pool.add_liquidity(TokenAmount::from_lamports(500)))
+            .unwrap();
+
+        let delivered: Vec<_> = delivered
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.sequence, entry.operation))
+            .collect();
+        assert_eq!(
+            delivered,
+            [
+                (
+                    sequence + 1,
+                    Operation::Swap(StakedTokenAmount::from_lamports(1_000))
+                ),
+                (
+                    sequence + 2,
+                    Operation::Swap(StakedTokenAmount::from_lamports(2_000))
+                ),
+                (
+                    sequence + 3,
+                    Operation::AddLiquidity(TokenAmount::from_lamports(500))
+                ),
+            ]
+        );
+        assert_eq!(
+            shared.state().staked_token_amount,
+            StakedTokenAmount::from_lamports(3_000)
+        );
+    }
+}
@@ -0,0 +1,436 @@
+pub mod raw;
+
+use crate::format::{write_lamports_grouped, write_magnitude, write_sol, Separator};
+
+use super::data::{
+    fee::Fee,
+    price::Price,
+    token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+};
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoolState {
+    pub price: Price,
+    pub min_fee: Fee,
+    pub max_fee: Fee,
+    pub liquidity_target: TokenAmount,
+    pub token_amount: TokenAmount,
+    pub staked_token_amount: StakedTokenAmount,
+    pub lp_token_amount: LpTokenAmount,
+    pub fees_collected: TokenAmount,
+    pub referral_fees_owed: TokenAmount,
+    pub dust: TokenAmount,
+    pub protocol_fees_owed: TokenAmount,
+}
+
+impl PoolState {
+    /// The state of a pool restored from this one. `LpPool::state` captures everything
+    /// `LpPool::from_state` reads, so any state a pool accepts canonicalizes to itself and
+    /// the rest fail like `from_state`.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `LpPool::from_state`.
+    pub fn canonicalize(self) -> crate::error::Result<PoolState> {
+        super::LpPool::from_state(self).map(|lp_pool| lp_pool.state())
+    }
+
+    /// Displays the state with each balance as its order of magnitude,
+    /// `token_amount: ~1e8 lamports`, for logs that must not hold exact balances. Price and
+    /// fees are shown as they are.
+    #[must_use]
+    pub fn fmt_redacted(&self) -> RedactedState {
+        RedactedState(*self)
+    }
+}
+
+/// `PoolState` with balances redacted in both `Display` and `Debug`, see
+/// `PoolState::fmt_redacted`.
+#[derive(Clone, Copy)]
+pub struct RedactedState(PoolState);
+
+impl core::fmt::Display for RedactedState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let state = &self.0;
+        write!(
+            f,
+            "price: {}\nfees: {} - {}",
+            state.price, state.min_fee, state.max_fee
+        )?;
+        for (name, lamports) in [
+            ("liquidity_target", u64::from(state.liquidity_target)),
+            ("token_amount", u64::from(state.token_amount)),
+            ("staked_token_amount", u64::from(state.staked_token_amount)),
+            ("lp_token_amount", u64::from(state.lp_token_amount)),
+            ("fees_collected", u64::from(state.fees_collected)),
+            ("referral_fees_owed", u64::from(state.referral_fees_owed)),
+            ("dust", u64::from(state.dust)),
+            ("protocol_fees_owed", u64::from(state.protocol_fees_owed)),
+        ] {
+            write!(f, "\n{name}: ")?;
+            write_magnitude(f, lamports)?;
+            f.write_str(" lamports")?;
+        }
+        Ok(())
+    }
+}
+
+impl core::fmt::Debug for RedactedState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(self, f)
+    }
+}
+
+/// Version written by `VersionedPoolState::to_json`, the shape of the current `PoolState`.
+pub const STATE_VERSION: u32 = 3;
+
+/// `PoolState` as serialized before versioning, without fee or dust balances and with fees
+/// in whole basis points. Frozen, later shapes get their own struct and a migration.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoolStateV1 {
+    pub price: Price,
+    #[cfg_attr(feature = "serde", serde(rename = "min_fee"))]
+    pub min_fee_basis_points: u32,
+    #[cfg_attr(feature = "serde", serde(rename = "max_fee"))]
+    pub max_fee_basis_points: u32,
+    pub liquidity_target: TokenAmount,
+    pub token_amount: TokenAmount,
+    pub staked_token_amount: StakedTokenAmount,
+    pub lp_token_amount: LpTokenAmount,
+}
+
+/// `PoolState` as serialized before protocol fees were owed separately. Frozen like
+/// `PoolStateV1`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoolStateV2 {
+    pub price: Price,
+    pub min_fee: Fee,
+    pub max_fee: Fee,
+    pub liquidity_target: TokenAmount,
+    pub token_amount: TokenAmount,
+    pub staked_token_amount: StakedTokenAmount,
+    pub lp_token_amount: LpTokenAmount,
+    pub fees_collected: TokenAmount,
+    pub referral_fees_owed: TokenAmount,
+    pub dust: TokenAmount,
+}
+
+/// Stored state of any supported version. `V3` is the current `PoolState`, when it changes
+/// freeze its shape as `PoolStateV3` and add `V4`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum VersionedPoolState {
+    V1(PoolStateV1),
+    V2(PoolStateV2),
+    V3(PoolState),
+}
+
+impl VersionedPoolState {
+    #[must_use]
+    pub fn version(&self) -> u32 {
+        match self {
+            VersionedPoolState::V1(_) => 1,
+            VersionedPoolState::V2(_) => 2,
+            VersionedPoolState::V3(_) => 3,
+        }
+    }
+
+    /// Upgrades to the current `PoolState`, balances added after the stored version start
+    /// at zero.
+    #[must_use]
+    pub fn migrate(self) -> PoolState {
+        match self {
+            VersionedPoolState::V1(state) => VersionedPoolState::V2(PoolStateV2 {
+                price: state.price,
+                min_fee: Fee::from_basis_points(state.min_fee_basis_points),
+                max_fee: Fee::from_basis_points(state.max_fee_basis_points),
+                liquidity_target: state.liquidity_target,
+                token_amount: state.token_amount,
+                staked_token_amount: state.staked_token_amount,
+                lp_token_amount: state.lp_token_amount,
+                fees_collected: TokenAmount::from_lamports(0),
+                referral_fees_owed: TokenAmount::from_lamports(0),
+                dust: TokenAmount::from_lamports(0),
+            })
+            .migrate(),
+            VersionedPoolState::V2(state) => PoolState {
+                price: state.price,
+                min_fee: state.min_fee,
+                max_fee: state.max_fee,
+                liquidity_target: state.liquidity_target,
+                token_amount: state.token_amount,
+                staked_token_amount: state.staked_token_amount,
+                lp_token_amount: state.lp_token_amount,
+                fees_collected: state.fees_collected,
+                referral_fees_owed: state.referral_fees_owed,
+                dust: state.dust,
+                protocol_fees_owed: TokenAmount::from_lamports(0),
+            },
+            VersionedPoolState::V3(state) => state,
+        }
+    }
+}
+
+impl From<PoolState> for VersionedPoolState {
+    fn from(state: PoolState) -> Self {
+        VersionedPoolState::V3(state)
+    }
+}
+
+#[cfg(feature = "persist")]
+impl VersionedPoolState {
+    /// The state with a `version` field, files without one are read as version 1.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Persistence` when serialization fails.
+    pub fn to_json(&self) -> crate::error::Result<Vec<u8>> {
+        #[derive(serde::Serialize)]
+        struct Tagged<'a, T> {
+            version: u32,
+            #[serde(flatten)]
+            state: &'a T,
+        }
+
+        let version = self.version();
+        match self {
+            VersionedPoolState::V1(state) => serde_json::to_vec(&Tagged { version, state }),
+            VersionedPoolState::V2(state) => serde_json::to_vec(&Tagged { version, state }),
+            VersionedPoolState::V3(state) => serde_json::to_vec(&Tagged { version, state }),
+        }
+        .map_err(crate::error::Error::from)
+    }
+
+    /// Reads a state written by `to_json` at any supported version.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UnsupportedStateVersion` for a version newer than `STATE_VERSION`, and
+    /// `Error::Persistence` for malformed JSON.
+    pub fn from_json(bytes: &[u8]) -> crate::error::Result<Self> {
+        #[derive(serde::Deserialize)]
+        struct Version {
+            version: Option<u32>,
+        }
+
+        let Version { version } = serde_json::from_slice(bytes)?;
+        match version.unwrap_or(1) {
+            1 => serde_json::from_slice(bytes).map(VersionedPoolState::V1),
+            2 => serde_json::from_slice(bytes).map(VersionedPoolState::V2),
+            3 => serde_json::from_slice(bytes).map(VersionedPoolState::V3),
+            found => {
+                return Err(crate::error::Error::LpPool(
+                    super::error::Error::UnsupportedStateVersion {
+                        found,
+                        supported: STATE_VERSION,
+                    },
+                ))
+            }
+        }
+        .map_err(crate::error::Error::from)
+    }
+}
+
+/// `{:#}` groups amounts in thousands and follows each with its value in whole tokens.
+impl core::fmt::Display for PoolState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "price: {}", self.price)?;
+        writeln!(f, "fees: {} - {}", self.min_fee, self.max_fee)?;
+        write_amount(f, "liquidity_target", self.liquidity_target.into(), "sol")?;
+        writeln!(f)?;
+        write_amount(f, "token_amount", self.token_amount.into(), "sol")?;
+        writeln!(f)?;
+        write_amount(
+            f,
+            "staked_token_amount",
+            self.staked_token_amount.into(),
+            "msol",
+        )?;
+        writeln!(f)?;
+        write_amount(f, "lp_token_amount", self.lp_token_amount.into(), "lp")?;
+        writeln!(f)?;
+        write_amount(f, "fees_collected", self.fees_collected.into(), "sol")?;
+        writeln!(f)?;
+        write_amount(
+            f,
+            "referral_fees_owed",
+            self.referral_fees_owed.into(),
+            "sol",
+        )?;
+        writeln!(f)?;
+        write_amount(f, "dust", self.dust.into(), "sol")?;
+        writeln!(f)?;
+        write_amount(
+            f,
+            "protocol_fees_owed",
+            self.protocol_fees_owed.into(),
+            "sol",
+        )
+    }
+}
+
+fn write_amount(
+    f: &mut core::fmt::Formatter<'_>,
+    name: &str,
+    lamports: u64,
+    unit: &str,
+) -> core::fmt::Result {
+    if f.alternate() {
+        write!(f, "{name}: ")?;
+        write_lamports_grouped(f, lamports, Separator::Underscore)?;
+        write!(f, " (")?;
+        write_sol(f, lamports)?;
+        write!(f, " {unit})")
+    } else {
+        write!(f, "{name}: {lamports}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_serializes_state_as_flat_lamports() {
+        let state = PoolState {
+            price: Price::from_raw_points(150),
+            min_fee: Fee::from_basis_points(10),
+            max_fee: Fee::from_basis_points(900),
+            liquidity_target: TokenAmount::from_lamports(90_000),
+            token_amount: TokenAmount::from_lamports(100),
+            staked_token_amount: StakedTokenAmount::from_lamports(0),
+            lp_token_amount: LpTokenAmount::from_lamports(100),
+            fees_collected: TokenAmount::from_lamports(1),
+            referral_fees_owed: TokenAmount::from_lamports(0),
+            dust: TokenAmount::from_lamports(0),
+            protocol_fees_owed: TokenAmount::from_lamports(0),
+        };
+
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(
+            json,
+            r#"{"price":150,"min_fee":1000,"max_fee":90000,"liquidity_target":90000,"token_amount":100,"staked_token_amount":0,"lp_token_amount":100,"fees_collected":1,"referral_fees_owed":0,"dust":0,"protocol_fees_owed":0}"#
+        );
+        assert_eq!(serde_json::from_str::<PoolState>(&json).unwrap(), state);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_migrates_v1_state_with_zero_fee_balances() {
+        let json = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/pool_state_v1.json"
+        ));
+        let v1: PoolStateV1 = serde_json::from_str(json).unwrap();
+
+        let state = VersionedPoolState::V1(v1).migrate();
+        assert_eq!(
+            (state.min_fee, state.max_fee),
+            (Fee::from_basis_points(10), Fee::from_basis_points(900))
+        );
+        assert_eq!(
+            (
+                state.fees_collected,
+                state.referral_fees_owed,
+                state.dust,
+                state.protocol_fees_owed
+            ),
+            (
+                TokenAmount::from_lamports(0),
+                TokenAmount::from_lamports(0),
+                TokenAmount::from_lamports(0),
+                TokenAmount::from_lamports(0)
+            )
+        );
+        assert_eq!(VersionedPoolState::V3(state).migrate(), state);
+    }
+
+    #[test]
+    fn it_canonicalizes_only_states_a_pool_accepts() {
+        let state = crate::test_utils::PoolFixture::new()
+            .price("1.5")
+            .fees(10, 900)
+            .seeded_with_lamports(100)
+            .build()
+            .pool
+            .state();
+
+        assert_eq!(state.canonicalize(), Ok(state));
+        assert_eq!(
+            PoolState {
+                min_fee: state.max_fee,
+                max_fee: state.min_fee,
+                ..state
+            }
+            .canonicalize(),
+            Err(crate::error::Error::LpPool(
+                super::super::error::Error::MinFeeGreaterThanMaxFee {
+                    min: state.max_fee,
+                    max: state.min_fee,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn it_displays_extreme_balances_exactly_or_redacted() {
+        let state = PoolState {
+            price: Price::from_raw_points(u64::MAX),
+            min_fee: Fee::ZERO,
+            max_fee: Fee::MAX,
+            liquidity_target: TokenAmount::from_lamports(u64::MAX),
+            token_amount: TokenAmount::from_lamports(u64::MAX),
+            staked_token_amount: StakedTokenAmount::from_lamports(u64::MAX),
+            lp_token_amount: LpTokenAmount::from_lamports(123_456_789),
+            fees_collected: TokenAmount::from_lamports(1),
+            referral_fees_owed: TokenAmount::from_lamports(0),
+            dust: TokenAmount::from_lamports(u64::MAX),
+            protocol_fees_owed: TokenAmount::from_lamports(0),
+        };
+        let exact = format!("{state} {state:#} {state:?}");
+        let redacted = format!("{} {:?}", state.fmt_redacted(), state.fmt_redacted());
+
+        assert!(exact.contains("18446744073709551615") && exact.contains("123456789"));
+        assert!(redacted.contains("token_amount: ~1e19 lamports"));
+        assert!(redacted.contains("lp_token_amount: ~1e8 lamports"));
+        assert!(redacted.contains("referral_fees_owed: 0 lamports"));
+        assert!(!redacted.contains("18446744073709551615") && !redacted.contains("123456789"));
+    }
+
+    /// Every encoding of a state any pool accepts decodes to a state that restores the same
+    /// pool. A pool field missing from `PoolState` or dropped by an encoding shows up here.
+    #[cfg(feature = "proptest")]
+    mod canonical_round_trips {
+        use proptest::prelude::*;
+
+        use super::*;
+        use crate::lp_pool::{state::raw::RawPoolState, strategies::pool_state};
+
+        proptest! {
+            #[test]
+            fn it_is_a_fixed_point(state in pool_state()) {
+                prop_assert_eq!(state.canonicalize(), Ok(state));
+            }
+
+            #[cfg(feature = "serde")]
+            #[test]
+            fn it_round_trips_through_json(state in pool_state()) {
+                let json = serde_json::to_string(&state).unwrap();
+                let decoded: PoolState = serde_json::from_str(&json).unwrap();
+
+                prop_assert_eq!(decoded.canonicalize(), Ok(state));
+            }
+
+            #[test]
+            fn it_round_trips_through_raw_layout(state in pool_state()) {
+                let decoded = PoolState::try_from(RawPoolState::from(state));
+
+                prop_assert_eq!(decoded.and_then(PoolState::canonicalize), Ok(state));
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Fixed layout `PoolState` for sharing with other processes without serialization.
+
+use crate::error::{Error, Result};
+use crate::lp_pool::{
+    data::{
+        fee::Fee,
+        price::Price,
+        token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    },
+    error::Error as LpPoolError,
+    state::PoolState,
+};
+
+/// Value of `RawPoolState::layout_version` for the layout below.
+pub const RAW_LAYOUT_VERSION: u32 = 2;
+
+/// `PoolState` as 88 bytes, 8 byte aligned, native endian with no implicit padding:
+///
+/// | offset | size | field                 | unit                          |
+/// |--------|------|-----------------------|-------------------------------|
+/// | 0      | 8    | `price`               | price points                  |
+/// | 8      | 8    | `liquidity_target`    | lamports                      |
+/// | 16     | 8    | `token_amount`        | lamports                      |
+/// | 24     | 8    | `staked_token_amount` | lamports                      |
+/// | 32     | 8    | `lp_token_amount`     | lamports                      |
+/// | 40     | 8    | `fees_collected`      | lamports                      |
+/// | 48     | 8    | `referral_fees_owed`  | lamports                      |
+/// | 56     | 8    | `dust`                | lamports                      |
+/// | 64     | 8    | `protocol_fees_owed`  | lamports                      |
+/// | 72     | 4    | `min_fee`             | hundredths of a basis point   |
+/// | 76     | 4    | `max_fee`             | hundredths of a basis point   |
+/// | 80     | 4    | `layout_version`      | `RAW_LAYOUT_VERSION`          |
+/// | 84     | 4    | `padding`             | always zero                   |
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct RawPoolState {
+    pub price: u64,
+    pub liquidity_target: u64,
+    pub token_amount: u64,
+    pub staked_token_amount: u64,
+    pub lp_token_amount: u64,
+    pub fees_collected: u64,
+    pub referral_fees_owed: u64,
+    pub dust: u64,
+    pub protocol_fees_owed: u64,
+    pub min_fee: u32,
+    pub max_fee: u32,
+    pub layout_version: u32,
+    pub padding: u32,
+}
+
+const _: () = assert!(core::mem::size_of::<RawPoolState>() == 88);
+const _: () = assert!(core::mem::align_of::<RawPoolState>() == 8);
+
+impl From<PoolState> for RawPoolState {
+    fn from(state: PoolState) -> Self {
+        Self {
+            price: state.price.into(),
+            liquidity_target: state.liquidity_target.into(),
+            token_amount: state.token_amount.into(),
+            staked_token_amount: state.staked_token_amount.into(),
+            lp_token_amount: state.lp_token_amount.into(),
+            fees_collected: state.fees_collected.into(),
+            referral_fees_owed: state.referral_fees_owed.into(),
+            dust: state.dust.into(),
+            protocol_fees_owed: state.protocol_fees_owed.into(),
+            min_fee: state.min_fee.centibps,
+            max_fee: state.max_fee.centibps,
+            layout_version: RAW_LAYOUT_VERSION,
+            padding: 0,
+        }
+    }
+}
+
+impl TryFrom<RawPoolState> for PoolState {
+    type Error = Error;
+
+    /// Rejects other layout versions and fees no pool could have been created with.
+    fn try_from(raw: RawPoolState) -> Result<Self> {
+        if raw.layout_version != RAW_LAYOUT_VERSION || raw.padding != 0 {
+            return Err(Error::LpPool(LpPoolError::UnsupportedStateVersion {
+                found: raw.layout_version,
+                supported: RAW_LAYOUT_VERSION,
+            }));
+        }
+        let min_fee = Fee::from_centibps(raw.min_fee);
+        let max_fee = Fee::from_centibps(raw.max_fee);
+        min_fee.check()?;
+        max_fee.check()?;
+        if min_fee > max_fee {
+            return Err(Error::LpPool(LpPoolError::MinFeeGreaterThanMaxFee {
+                min: min_fee,
+                max: max_fee,
+            }));
+        }
+
+        Ok(PoolState {
+            price: Price::from_raw_points(raw.price),
+            min_fee,
+            max_fee,
+            liquidity_target: TokenAmount::from_lamports(raw.liquidity_target),
+            token_amount: TokenAmount::from_lamports(raw.token_amount),
+            staked_token_amount: StakedTokenAmount::from_lamports(raw.staked_token_amount),
+            lp_token_amount: LpTokenAmount::from_lamports(raw.lp_token_amount),
+            fees_collected: TokenAmount::from_lamports(raw.fees_collected),
+            referral_fees_owed: TokenAmount::from_lamports(raw.referral_fees_owed),
+            dust: TokenAmount::from_lamports(raw.dust),
+            protocol_fees_owed: TokenAmount::from_lamports(raw.protocol_fees_owed),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PoolFixture;
+
+    fn state() -> PoolState {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(10, 900)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool;
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(5_000))
+            .unwrap();
+        lp_pool.state()
+    }
+
+    #[test]
+    fn it_round_trips_state_through_raw_layout() {
+        let state = state();
+
+        assert_eq!(PoolState::try_from(RawPoolState::from(state)), Ok(state));
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn it_round_trips_state_through_bytes() {
+        let state = state();
+        let raw = RawPoolState::from(state);
+        let bytes = bytemuck::bytes_of(&raw);
+
+        assert_eq!(bytes.len(), 88);
+        assert_eq!(&bytes[16..24], &u64::from(state.token_amount).to_ne_bytes());
+        assert_eq!(&bytes[72..76], &state.min_fee.centibps.to_ne_bytes());
+        assert_eq!(
+            PoolState::try_from(bytemuck::pod_read_unaligned::<RawPoolState>(bytes)),
+            Ok(state)
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_raw_state() {
+        let raw = RawPoolState::from(state());
+
+        assert_eq!(
+            PoolState::try_from(RawPoolState {
+                layout_version: 1,
+                ..raw
+            }),
+            Err(Error::LpPool(LpPoolError::UnsupportedStateVersion {
+                found: 1,
+                supported: RAW_LAYOUT_VERSION,
+            }))
+        );
+        assert_eq!(
+            PoolState::try_from(RawPoolState {
+                max_fee: 1_000_001,
+                ..raw
+            }),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(
+                Fee::from_centibps(1_000_001)
+            )))
+        );
+        assert_eq!(
+            PoolState::try_from(RawPoolState {
+                min_fee: raw.max_fee + 1,
+                ..raw
+            }),
+            Err(Error::LpPool(LpPoolError::MinFeeGreaterThanMaxFee {
+                min: Fee::from_centibps(raw.max_fee + 1),
+                max: Fee::from_centibps(raw.max_fee),
+            }))
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! One line summaries of a pool for CLI output and logs.
+
+use core::fmt::Write;
+
+use crate::format::write_tokens_fixed;
+use crate::lp_pool::{consts::DEFAULT_DECIMALS, LpPool};
+
+/// What `LpPool::status_line_with` prints. The default prints amounts with three decimals and
+/// the current fee, without the sequence number.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct StatusFormat {
+    /// Digits after the decimal point of amounts, the rest is truncated.
+    pub decimals: u8,
+    /// Adds `fee=`, the swap fee at the current balances.
+    pub include_fees: bool,
+    /// Adds `seq=`, the pool's sequence number.
+    pub include_sequence: bool,
+}
+
+impl Default for StatusFormat {
+    fn default() -> Self {
+        Self {
+            decimals: 3,
+            include_fees: true,
+            include_sequence: false,
+        }
+    }
+}
+
+impl LpPool {
+    /// `status_line_with` the default format, like
+    /// `tok=100.000 sol staked=3.996 msol lp=99.900 target=90.000 fee=0.10% util=111%`.
+    #[must_use]
+    pub fn status_line(&self) -> String {
+        self.status_line_with(StatusFormat::default())
+    }
+
+    /// Balances on one line. `util` is the available tokens as a percentage of the liquidity
+    /// target, rounded down.
+    #[must_use]
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "writing to a `String` never fails"
+    )]
+    pub fn status_line_with(&self, format: StatusFormat) -> String {
+        let mut line = String::new();
+        // PANIC SAFETY: `fmt::Write` for `String` never returns an error.
+        self.write_status(&mut line, format)
+            .expect("writing to a String succeeds");
+        line
+    }
+
+    fn write_status(&self, out: &mut String, format: StatusFormat) -> core::fmt::Result {
+        let decimals = u32::from(format.decimals);
+        let available = self.available_tokens();
+
+        write!(out, "tok=")?;
+        write_fixed(out, self.token_amount.into(), decimals)?;
+        write!(out, " sol staked=")?;
+        write_fixed(out, self.staked_token_amount.into(), decimals)?;
+        write!(out, " msol lp=")?;
+        write_fixed(out, self.lp_token_amount.into(), decimals)?;
+        write!(out, " target=")?;
+        write_fixed(out, self.liquidity_target.into(), decimals)?;
+        if format.include_fees {
+            match self.calculate_fee(available) {
+                Ok(fee) => write!(out, " fee={fee}")?,
+                Err(_) => write!(out, " fee=-")?,
+            }
+        }
+        let util = u128::from(u64::from(available)) * 100
+            / u128::from(u64::from(self.liquidity_target)).max(1);
+        write!(out, " util={util}%")?;
+        if format.include_sequence {
+            write!(out, " seq={}", self.sequence)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_fixed(out: &mut String, lamports: u64, decimals: u32) -> core::fmt::Result {
+    write_tokens_fixed(out, lamports, DEFAULT_DECIMALS, decimals)
+}
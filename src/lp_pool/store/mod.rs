@@ -0,0 +1,329 @@
+//! Saving and restoring `PoolState` outside the pool.
+
+#[cfg(feature = "persist")]
+use crate::error::PersistenceContext;
+use crate::error::Result;
+#[cfg(feature = "persist")]
+use crate::lp_pool::state::VersionedPoolState;
+use crate::lp_pool::{state::PoolState, LpPool};
+
+pub trait PoolStore {
+    /// Replaces the saved state with `state`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the store's error when the state could not be saved.
+    fn save(&mut self, state: &PoolState) -> Result<()>;
+    /// `None` when nothing has been saved yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns the store's error when a saved state could not be read.
+    fn load(&self) -> Result<Option<PoolState>>;
+}
+
+/// Keeps the last saved state in memory, for tests.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStore {
+    state: Option<PoolState>,
+}
+
+impl MemoryStore {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PoolStore for MemoryStore {
+    fn save(&mut self, state: &PoolState) -> Result<()> {
+        self.state = Some(*state);
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Option<PoolState>> {
+        Ok(self.state)
+    }
+}
+
+/// Saves the state as JSON, writing a temp file next to `path` and renaming it over `path`
+/// so a failed save leaves the previous state in place.
+#[cfg(feature = "persist")]
+#[derive(Debug, Clone)]
+pub struct JsonFileStore {
+    path: std::path::PathBuf,
+}
+
+#[cfg(feature = "persist")]
+impl JsonFileStore {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn temp_path(&self) -> std::path::PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".tmp");
+        path.into()
+    }
+
+    fn write_atomically(
+        &self,
+        bytes: &[u8],
+        write: impl FnOnce(&mut std::fs::File, &[u8]) -> std::io::Result<()>,
+    ) -> Result<()> {
+        let temp_path = self.temp_path();
+        let written = std::fs::File::create(&temp_path)
+            .and_then(|mut file| {
+                write(&mut file, bytes)?;
+                file.sync_all()
+            })
+            .and_then(|()| std::fs::rename(&temp_path, &self.path));
+        if written.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+        written.with_context(|| format!("saving {}", self.path.display()))
+    }
+}
+
+#[cfg(feature = "persist")]
+impl PoolStore for JsonFileStore {
+    fn save(&mut self, state: &PoolState) -> Result<()> {
+        let bytes = VersionedPoolState::from(*state).to_json()?;
+        self.write_atomically(&bytes, std::io::Write::write_all)
+    }
+
+    fn load(&self) -> Result<Option<PoolState>> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => VersionedPoolState::from_json(&bytes).map(|state| Some(state.migrate())),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+        .with_context(|| format!("loading {}", self.path.display()))
+    }
+}
+
+impl LpPool {
+    /// Saves `LpPool::state` to `store`.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `PoolStore::save`.
+    pub fn persist_to(&self, store: &mut dyn PoolStore) -> Result<()> {
+        store.save(&self.state())
+    }
+
+    /// Rebuilds a pool from the stored state with `LpPool::from_state`. Only `PoolState` is
+    /// stored, settings applied with the `with_*` builders have to be applied again.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `PoolStore::load` and `LpPool::from_state`.
+    pub fn restore_from(store: &dyn PoolStore) -> Result<Option<LpPool>> {
+        store.load()?.map(LpPool::from_state).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::lp_pool::{
+        data::{
+            fee::Fee,
+            token::{StakedTokenAmount, TokenAmount},
+        },
+        error::{Error as LpPoolError, InvariantViolation},
+    };
+    use crate::test_utils::PoolFixture;
+
+    fn pool() -> LpPool {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(100, 900)
+            .seeded_with_lamports(10_000)
+            .build()
+            .pool;
+        lp_pool.swap(StakedTokenAmount::from_lamports(500)).unwrap();
+        lp_pool
+    }
+
+    #[test]
+    fn it_round_trips_pool_through_memory_store() {
+        let lp_pool = pool();
+        let mut store = MemoryStore::new();
+
+        assert!(LpPool::restore_from(&store).unwrap().is_none());
+        lp_pool.persist_to(&mut store).unwrap();
+
+        let restored = LpPool::restore_from(&store).unwrap().unwrap();
+        assert_eq!(restored.state(), lp_pool.state());
+    }
+
+    #[test]
+    fn it_rejects_restoring_insolvent_state() {
+        let mut state = pool().state();
+        state.fees_collected = state.token_amount + TokenAmount::from_lamports(1);
+        let mut store = MemoryStore::new();
+        store.save(&state).unwrap();
+
+        assert_eq!(
+            LpPool::restore_from(&store).map(|lp_pool| lp_pool.is_some()),
+            Err(Error::LpPool(LpPoolError::StateInvalid(
+                InvariantViolation::FeesExceedBalance {
+                    fees_collected: state.fees_collected,
+                    token_amount: state.token_amount,
+                }
+            )))
+        );
+
+        state.min_fee = Fee::from_basis_points(1_000);
+        store.save(&state).unwrap();
+        assert_eq!(
+            LpPool::restore_from(&store).map(|lp_pool| lp_pool.is_some()),
+            Err(Error::LpPool(LpPoolError::MinFeeGreaterThanMaxFee {
+                min: state.min_fee,
+                max: state.max_fee,
+            }))
+        );
+    }
+
+    #[cfg(feature = "persist")]
+    fn temp_store(name: &str) -> JsonFileStore {
+        let path =
+            std::env::temp_dir().join(format!("liquidity-pool-{}-{name}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        JsonFileStore::new(path)
+    }
+
+    #[cfg(feature = "persist")]
+    #[test]
+    fn it_round_trips_pool_through_json_file_store() {
+        let lp_pool = pool();
+        let mut store = temp_store("round-trip");
+
+        assert!(LpPool::restore_from(&store).unwrap().is_none());
+        lp_pool.persist_to(&mut store).unwrap();
+
+        let restored = LpPool::restore_from(&store).unwrap().unwrap();
+        assert_eq!(restored.state(), lp_pool.state());
+        std::fs::remove_file(&store.path).unwrap();
+    }
+
+    #[cfg(feature = "persist")]
+    #[test]
+    fn it_keeps_previous_state_when_save_fails_midway() {
+        let mut lp_pool = pool();
+        let mut store = temp_store("failed-save");
+        lp_pool.persist_to(&mut store).unwrap();
+        let saved = lp_pool.state();
+
+        lp_pool.swap(StakedTokenAmount::from_lamports(500)).unwrap();
+        let bytes = VersionedPoolState::from(lp_pool.state()).to_json().unwrap();
+        let result = store.write_atomically(&bytes, |file, bytes| {
+            std::io::Write::write_all(file, &bytes[..bytes.len() / 2])?;
+            Err(std::io::Error::other("disk full"))
+        });
+
+        assert_eq!(
+            result,
+            Err(Error::Persistence {
+                context: format!("saving {}: I/O", store.path.display()),
+                source: Box::new(std::io::Error::other("disk full")),
+            })
+        );
+        assert_eq!(store.load(), Ok(Some(saved)));
+        assert!(!store.temp_path().exists());
+        std::fs::remove_file(&store.path).unwrap();
+    }
+
+    #[cfg(feature = "persist")]
+    #[test]
+    fn it_chains_io_error_and_path_of_failed_persistence() {
+        use core::error::Error as _;
+
+        let path = std::env::temp_dir()
+            .join(format!("liquidity-pool-{}-missing", std::process::id()))
+            .join("pool.json");
+        let error = pool()
+            .persist_to(&mut JsonFileStore::new(&path))
+            .unwrap_err();
+
+        let io_error = error
+            .source()
+            .and_then(|source| source.downcast_ref::<std::io::Error>())
+            .unwrap();
+        assert_eq!(io_error.kind(), std::io::ErrorKind::NotFound);
+        assert!(error
+            .display_chain()
+            .to_string()
+            .starts_with(&format!("Persistence(saving {}: I/O): ", path.display())));
+        assert_eq!(error.category(), crate::error::ErrorCategory::Persistence);
+
+        let corrupt = temp_store("corrupt");
+        std::fs::write(&corrupt.path, b"{").unwrap();
+        let error = corrupt.load().unwrap_err();
+        assert!(error
+            .source()
+            .is_some_and(|source| source.is::<serde_json::Error>()));
+        assert!(error
+            .to_string()
+            .contains(&corrupt.path.display().to_string()));
+        std::fs::remove_file(&corrupt.path).unwrap();
+    }
+
+    #[cfg(feature = "persist")]
+    #[test]
+    fn it_restores_v1_fixture_as_pool_behaving_like_reference() {
+        use crate::lp_pool::{
+            data::{fee::FeeRange, price::Price, token::LpTokenAmount},
+            Pool, PoolParams,
+        };
+        use crate::test_utils::SCENARIOS;
+
+        let store = temp_store("v1-fixture");
+        std::fs::write(
+            &store.path,
+            include_bytes!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/pool_state_v1.json"
+            )),
+        )
+        .unwrap();
+        let mut migrated = LpPool::restore_from(&store).unwrap().unwrap();
+        let mut reference = LpPool::init(PoolParams {
+            price: Price::from_raw_points(1),
+            fee_range: FeeRange::new(Fee::from_basis_points(10), Fee::from_basis_points(900))
+                .unwrap(),
+            liquidity_target: TokenAmount::from_lamports(90_000),
+        })
+        .unwrap()
+        .with_balances_unchecked(
+            TokenAmount::from_lamports(100_000),
+            StakedTokenAmount::from_lamports(0),
+            LpTokenAmount::from_lamports(100_000),
+            TokenAmount::from_lamports(0),
+        );
+
+        assert_eq!(migrated.state(), reference.state());
+        for (name, steps) in SCENARIOS {
+            for step in *steps {
+                assert_eq!(
+                    step.run(&mut migrated as &mut dyn Pool),
+                    step.run(&mut reference as &mut dyn Pool),
+                    "{name}: {step:?}"
+                );
+            }
+        }
+        assert_eq!(migrated.state(), reference.state());
+
+        std::fs::write(&store.path, r#"{"version":4}"#).unwrap();
+        assert_eq!(
+            store.load(),
+            Err(Error::LpPool(LpPoolError::UnsupportedStateVersion {
+                found: 4,
+                supported: 3,
+            }))
+        );
+        std::fs::remove_file(&store.path).unwrap();
+    }
+}
@@ -0,0 +1,306 @@
+//! Proptest strategies for the pool types, for property tests downstream.
+
+use proptest::prelude::*;
+
+use super::data::{
+    fee::{Fee, FeeRange},
+    price::Price,
+    token::{LpTokenAmount, StakedTokenAmount, TokenAmount, LAMPORTS_PER_SOL},
+};
+use super::operation::{Balances, Operation, PoolOperation};
+use super::state::PoolState;
+use super::{LpPool, PoolParams};
+
+/// Largest deposit generated per provider, keeps sums of deposits far from overflowing.
+pub const MAX_DEPOSIT: u64 = 1_000_000 * LAMPORTS_PER_SOL;
+/// Largest price generated, in points.
+pub const MAX_PRICE_POINTS: u64 = 10 * Price::SCALE;
+/// Most providers in a generated pool.
+pub const MAX_PROVIDERS: usize = 8;
+
+pub fn token_amount() -> impl Strategy<Value = TokenAmount> {
+    (0..=MAX_DEPOSIT).prop_map(TokenAmount::from_lamports)
+}
+
+pub fn staked_token_amount() -> impl Strategy<Value = StakedTokenAmount> {
+    (0..=MAX_DEPOSIT).prop_map(StakedTokenAmount::from_lamports)
+}
+
+pub fn lp_token_amount() -> impl Strategy<Value = LpTokenAmount> {
+    (0..=MAX_DEPOSIT).prop_map(LpTokenAmount::from_lamports)
+}
+
+pub fn fee() -> impl Strategy<Value = Fee> {
+    (0..=Fee::MAX_CENTIBPS).prop_map(Fee::from_centibps)
+}
+
+/// Non-zero price up to `MAX_PRICE_POINTS`.
+pub fn price() -> impl Strategy<Value = Price> {
+    (1..=MAX_PRICE_POINTS).prop_map(Price::from_raw_points)
+}
+
+/// Freshly initialized pool with `min_fee <= max_fee` and a non-zero target.
+#[allow(
+    clippy::missing_panics_doc,
+    reason = "generated parameters are always valid"
+)]
+pub fn empty_pool() -> impl Strategy<Value = LpPool> {
+    (price(), fee(), fee(), 1..=MAX_DEPOSIT).prop_map(|(price, a, b, target)| {
+        LpPool::init(PoolParams {
+            price,
+            fee_range: FeeRange::new(a.min(b), a.max(b)).expect("generated fees are ordered"),
+            liquidity_target: TokenAmount::from_lamports(target),
+        })
+        .expect("generated pool parameters are valid")
+    })
+}
+
+/// Pool funded by up to `MAX_PROVIDERS` deposits, with the LP tokens each provider holds.
+#[allow(
+    clippy::missing_panics_doc,
+    reason = "generated deposits always fit the pool"
+)]
+pub fn pool_with_liquidity() -> impl Strategy<Value = (LpPool, Vec<LpTokenAmount>)> {
+    (
+        empty_pool(),
+        prop::collection::vec(1..=MAX_DEPOSIT, 1..=MAX_PROVIDERS),
+    )
+        .prop_map(|(mut pool, deposits)| {
+            let positions = deposits
+                .into_iter()
+                .map(|deposit| {
+                    pool.add_liquidity(TokenAmount::from_lamports(deposit))
+                        .expect("generated deposits fit the pool")
+                })
+                .collect();
+            (pool, positions)
+        })
+}
+
+/// `pool_with_liquidity` after up to four swaps, failed ones included, and a price update,
+/// so it holds staked tokens and fees.
+#[allow(
+    clippy::missing_panics_doc,
+    reason = "the expects hold for every generated pool"
+)]
+pub fn traded_pool() -> impl Strategy<Value = (LpPool, Vec<LpTokenAmount>)> {
+    (
+        pool_with_liquidity(),
+        prop::collection::vec(staked_token_amount(), 0..=4),
+        price(),
+    )
+        .prop_map(|((mut pool, positions), swaps, price)| {
+            for staked_tokens in swaps {
+                let _ = pool.swap(staked_tokens);
+            }
+            pool.update_price(price)
+                .expect("generated prices are non-zero");
+            (pool, positions)
+        })
+}
+
+/// State a pool can be restored from, either a `traded_pool` or one mixing in boundary
+/// values: `u64::MAX` balances and target, 1-lamport dust, the maximum fee and the
+/// 1-point price.
+pub fn pool_state() -> impl Strategy<Value = PoolState> {
+    prop_oneof![
+        traded_pool().prop_map(|(pool, _)| pool.state()),
+        boundary_state(),
+    ]
+}
+
+fn boundary_state() -> impl Strategy<Value = PoolState> {
+    let boundary_fee = || prop_oneof![Just(Fee::MAX), fee()];
+    let lamports = || prop_oneof![Just(1), Just(u64::MAX), 1..=MAX_DEPOSIT];
+    (
+        prop_oneof![Just(Price::from_raw_points(1)), price()],
+        (boundary_fee(), boundary_fee()),
+        (lamports(), lamports(), lamports(), lamports()),
+        (
+            prop_oneof![Just(0), 0..=MAX_DEPOSIT],
+            0..=MAX_DEPOSIT,
+            0..=1_u64,
+            0..=MAX_DEPOSIT,
+        ),
+    )
+        .prop_map(
+            |(
+                price,
+                (a, b),
+                (liquidity_target, token_amount, lp_token_amount, referral_fees_owed),
+                (staked_token_amount, fees_collected, dust, protocol_fees_owed),
+            )| {
+                // Leaves at least a lamport backing the LP supply.
+                let fees_collected = fees_collected.min(token_amount);
+                let protocol_fees_owed = protocol_fees_owed.min(token_amount - 1);
+                let referral_fees_owed =
+                    referral_fees_owed.min(token_amount - 1 - protocol_fees_owed);
+                PoolState {
+                    price,
+                    min_fee: a.min(b),
+                    max_fee: a.max(b),
+                    liquidity_target: TokenAmount::from_lamports(liquidity_target),
+                    token_amount: TokenAmount::from_lamports(token_amount),
+                    staked_token_amount: StakedTokenAmount::from_lamports(staked_token_amount),
+                    lp_token_amount: LpTokenAmount::from_lamports(lp_token_amount),
+                    fees_collected: TokenAmount::from_lamports(fees_collected),
+                    referral_fees_owed: TokenAmount::from_lamports(referral_fees_owed),
+                    dust: TokenAmount::from_lamports(dust),
+                    protocol_fees_owed: TokenAmount::from_lamports(protocol_fees_owed),
+                }
+            },
+        )
+        .prop_filter("state a pool accepts", |state| {
+            LpPool::from_state(*state).is_ok()
+        })
+}
+
+/// Any operation, fees in range but not necessarily ordered.
+pub fn operation() -> impl Strategy<Value = Operation> {
+    prop_oneof![
+        (price(), fee(), fee(), token_amount()).prop_map(
+            |(price, min_fee, max_fee, liquidity_target)| Operation::Init {
+                price,
+                min_fee,
+                max_fee,
+                liquidity_target,
+            }
+        ),
+        token_amount().prop_map(Operation::AddLiquidity),
+        lp_token_amount().prop_map(Operation::RemoveLiquidity),
+        staked_token_amount().prop_map(Operation::Swap),
+        price().prop_map(Operation::UpdatePrice),
+    ]
+}
+
+/// `operation()` with arbitrary outcome fields, not one a pool necessarily produced.
+pub fn pool_operation() -> impl Strategy<Value = PoolOperation> {
+    (
+        any::<u64>(),
+        operation(),
+        token_amount(),
+        (
+            price(),
+            token_amount(),
+            staked_token_amount(),
+            lp_token_amount(),
+            token_amount(),
+        ),
+        any::<u64>(),
+        any::<Option<u64>>(),
+    )
+        .prop_map(
+            |(
+                sequence,
+                operation,
+                fee,
+                (price, token_amount, staked_token_amount, lp_token_amount, fees_collected),
+                timestamp,
+                recorded_at,
+            )| PoolOperation {
+                sequence,
+                operation,
+                fee,
+                post_balances: Balances {
+                    price,
+                    token_amount,
+                    staked_token_amount,
+                    lp_token_amount,
+                    fees_collected,
+                },
+                timestamp,
+                recorded_at,
+            },
+        )
+}
+
+impl Arbitrary for TokenAmount {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        token_amount().boxed()
+    }
+}
+
+impl Arbitrary for StakedTokenAmount {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        staked_token_amount().boxed()
+    }
+}
+
+impl Arbitrary for LpTokenAmount {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        lp_token_amount().boxed()
+    }
+}
+
+impl Arbitrary for Fee {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        fee().boxed()
+    }
+}
+
+impl Arbitrary for Price {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        price().boxed()
+    }
+}
+
+impl Arbitrary for LpPool {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        pool_with_liquidity().prop_map(|(pool, _)| pool).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn it_generates_positions_matching_lp_supply((pool, positions) in pool_with_liquidity()) {
+            let supply = positions
+                .into_iter()
+                .fold(LpTokenAmount::from_lamports(0), |supply, position| {
+                    supply.checked_add(position).unwrap()
+                });
+
+            prop_assert_eq!(pool.state().lp_token_amount, supply);
+        }
+
+        #[test]
+        fn it_quotes_withdrawal_within_pool_balance(
+            (pool, positions) in pool_with_liquidity(),
+        ) {
+            for position in positions {
+                if let Ok(quote) = pool.quote_remove_liquidity(position) {
+                    prop_assert!(quote.amount_out.tokens <= pool.state().token_amount);
+                }
+            }
+        }
+
+        #[test]
+        fn it_keeps_curve_fee_between_min_and_max(pool: LpPool, amount: TokenAmount) {
+            let state = pool.state();
+            let fee = pool.calculate_fee(amount).unwrap();
+
+            prop_assert!(state.min_fee <= fee && fee <= state.max_fee);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Swap fee discounts for providers with a high cumulative volume.
+
+use crate::error::{Error, Result};
+use crate::lp_pool::{
+    data::{
+        fee::Fee,
+        token::{StakedTokenAmount, TokenAmount},
+    },
+    error::Error as LpPoolError,
+    overflow, FeeDiscount, LpPool,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct ProviderId(pub u64);
+
+/// Discounts by cumulative swap volume, the highest threshold reached applies.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FeeTiers {
+    tiers: Vec<(TokenAmount, Fee)>,
+    floor: Fee,
+}
+
+impl FeeTiers {
+    /// `tiers` are `(volume_threshold, discount_basis_points)` pairs with strictly increasing
+    /// thresholds. Discounts never take the fee below `floor`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FeeTierThresholdsUnsorted` for thresholds out of order and
+    /// `BasisPointsOverflow` for a floor or discount above 100%.
+    pub fn new(tiers: Vec<(TokenAmount, u32)>, floor: Fee) -> Result<Self> {
+        floor.check()?;
+        if let Some(index) = tiers.windows(2).position(|pair| pair[0].0 >= pair[1].0) {
+            return Err(Error::LpPool(LpPoolError::FeeTierThresholdsUnsorted {
+                index: index + 1,
+            }));
+        }
+        let tiers = tiers
+            .into_iter()
+            .map(|(threshold, discount_basis_points)| {
+                let discount = Fee::from_basis_points(discount_basis_points);
+                discount.check()?;
+                Ok((threshold, discount))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { tiers, floor })
+    }
+
+    #[must_use]
+    pub fn discount(&self, volume: TokenAmount) -> Fee {
+        self.tiers
+            .iter()
+            .rev()
+            .find(|(threshold, _)| *threshold <= volume)
+            .map_or(Fee::ZERO, |(_, discount)| *discount)
+    }
+}
+
+impl LpPool {
+    #[must_use]
+    pub fn with_fee_tiers(mut self, fee_tiers: Option<FeeTiers>) -> Self {
+        self.fee_tiers = fee_tiers;
+        self
+    }
+
+    /// Tokens paid out to `provider` by `swap_for` so far, before fees.
+    #[must_use]
+    pub fn provider_volume(&self, provider: ProviderId) -> TokenAmount {
+        self.provider_volume
+            .get(&provider)
+            .copied()
+            .unwrap_or(TokenAmount::from_lamports(0))
+    }
+
+    /// `swap` charging the fee discounted by the tier of the provider's volume before it.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `swap`, or with `ArithmeticOverflow` when the provider's volume would
+    /// overflow.
+    pub fn swap_for(
+        &mut self,
+        provider: ProviderId,
+        staked_tokens_to_swap: StakedTokenAmount,
+    ) -> Result<TokenAmount> {
+        let volume = self.provider_volume(provider);
+        let discount = self
+            .fee_tiers
+            .as_ref()
+            .map_or(FeeDiscount::NONE, |tiers| FeeDiscount {
+                discount: tiers.discount(volume),
+                floor: tiers.floor,
+            });
+
+        let swapped = self.swap_discounted(staked_tokens_to_swap, Fee::ZERO, discount)?;
+        let swapped_volume = swapped.tokens_out + swapped.pool_fee + swapped.referral_fee;
+        let volume = volume
+            .checked_add(swapped_volume)
+            .ok_or_else(|| overflow("provider volume + swap", volume, swapped_volume))?;
+        self.provider_volume.insert(provider, volume);
+        Ok(swapped.tokens_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PoolFixture;
+
+    fn pool() -> LpPool {
+        PoolFixture::new()
+            .price_points(100)
+            .fees(100, 100)
+            .seeded_with_lamports(1_000_000)
+            .build()
+            .pool
+    }
+
+    fn tiers() -> FeeTiers {
+        FeeTiers::new(
+            vec![
+                (TokenAmount::from_lamports(10_000), 40),
+                (TokenAmount::from_lamports(50_000), 90),
+            ],
+            Fee::from_basis_points(20),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn it_applies_new_tier_on_swap_after_crossing_threshold() {
+        let mut lp_pool = pool().with_fee_tiers(Some(tiers()));
+        let provider = ProviderId(7);
+        let swap = StakedTokenAmount::from_lamports(10_000);
+
+        assert_eq!(
+            lp_pool.swap_for(provider, swap),
+            Ok(TokenAmount::from_lamports(9_900))
+        );
+        assert_eq!(
+            lp_pool.swap_for(provider, swap),
+            Ok(TokenAmount::from_lamports(9_940))
+        );
+        assert_eq!(
+            lp_pool.provider_volume(provider),
+            TokenAmount::from_lamports(20_000)
+        );
+        for _ in 0..3 {
+            lp_pool.swap_for(provider, swap).unwrap();
+        }
+        // 90 bps off a 100 bps fee stops at the 20 bps floor
+        assert_eq!(
+            lp_pool.swap_for(provider, swap),
+            Ok(TokenAmount::from_lamports(9_980))
+        );
+        assert_eq!(
+            lp_pool.swap_for(ProviderId(8), swap),
+            Ok(TokenAmount::from_lamports(9_900))
+        );
+    }
+
+    #[test]
+    fn it_keeps_anonymous_swaps_undiscounted() {
+        let mut tiered = pool().with_fee_tiers(Some(tiers()));
+        let mut plain = pool();
+        let provider = ProviderId(1);
+        tiered
+            .swap_for(provider, StakedTokenAmount::from_lamports(60_000))
+            .unwrap();
+        plain
+            .swap(StakedTokenAmount::from_lamports(60_000))
+            .unwrap();
+
+        let swap = StakedTokenAmount::from_lamports(1_000);
+        assert_eq!(tiered.swap(swap), plain.swap(swap));
+        assert_eq!(tiered.state(), plain.state());
+    }
+
+    #[test]
+    fn it_validates_tier_table() {
+        assert_eq!(
+            FeeTiers::new(
+                vec![
+                    (TokenAmount::from_lamports(10), 1),
+                    (TokenAmount::from_lamports(10), 2),
+                ],
+                Fee::ZERO,
+            ),
+            Err(Error::LpPool(LpPoolError::FeeTierThresholdsUnsorted {
+                index: 1
+            }))
+        );
+        assert_eq!(
+            FeeTiers::new(vec![(TokenAmount::from_lamports(10), 10_001)], Fee::ZERO),
+            Err(Error::LpPool(LpPoolError::BasisPointsOverflow(
+                Fee::from_basis_points(10_001)
+            )))
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Cumulative price accumulator for time-weighted average prices.
+
+use crate::error::{Error, Result};
+use crate::lp_pool::{
+    data::price::Price, error::Error as LpPoolError, operation::Operation, overflow, LpPool,
+};
+
+/// Price points summed over time up to `timestamp`. The sum wraps around `u128`, averages
+/// stay exact as long as a window accumulates less than `2^128`, which takes over
+/// `2^64` seconds even at the highest price.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PriceCheckpoint {
+    pub cumulative: u128,
+    pub timestamp: u64,
+}
+
+impl LpPool {
+    /// `update_price` at `timestamp`, seconds or epochs chosen by the caller. The previous
+    /// price is accumulated for the time since the last timestamped update, the first one
+    /// only starts the clock. `update_price` without a time counts the new price from the
+    /// last timestamp on.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PriceTimestampOutOfOrder` for a timestamp before the last one and
+    /// `PriceIncorrect` for a zero price.
+    pub fn update_price_at(&mut self, price: Price, timestamp: u64) -> Result<()> {
+        let cumulative = match self.price_timestamp {
+            Some(last) if timestamp < last => {
+                return self.log_rejection(
+                    Operation::UpdatePrice(price),
+                    Err(Error::LpPool(LpPoolError::PriceTimestampOutOfOrder {
+                        last,
+                        given: timestamp,
+                    })),
+                )
+            }
+            Some(last) => self
+                .price_cumulative
+                .wrapping_add(u128::from(u64::from(self.price)) * u128::from(timestamp - last)),
+            None => self.price_cumulative,
+        };
+        self.set_price(price, Some(timestamp))?;
+        self.price_cumulative = cumulative;
+        self.price_timestamp = Some(timestamp);
+        Ok(())
+    }
+
+    /// Accumulator as of the last `update_price_at`, zero before the first one. Post the
+    /// current price again to move the checkpoint to the present.
+    #[must_use]
+    pub fn checkpoint(&self) -> PriceCheckpoint {
+        PriceCheckpoint {
+            cumulative: self.price_cumulative,
+            timestamp: self.price_timestamp.unwrap_or(0),
+        }
+    }
+
+    /// Average price between two checkpoints, rounded down. `to` must be later than `from`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PriceTimestampOutOfOrder` unless `to` is later than `from`, and
+    /// `ArithmeticOverflow` when the average does not fit `u64` points.
+    pub fn twap_between(&self, from: PriceCheckpoint, to: PriceCheckpoint) -> Result<Price> {
+        if to.timestamp <= from.timestamp {
+            return Err(Error::LpPool(LpPoolError::PriceTimestampOutOfOrder {
+                last: from.timestamp,
+                given: to.timestamp,
+            }));
+        }
+        let elapsed = to.timestamp - from.timestamp;
+        let accumulated = to.cumulative.wrapping_sub(from.cumulative);
+        // checkpoints of this pool average a `u64` price, ones from elsewhere may not
+        let average = u64::try_from(accumulated / u128::from(elapsed)).map_err(|_| {
+            overflow(
+                "(to.cumulative - from.cumulative) / elapsed",
+                u64::try_from(accumulated).unwrap_or(u64::MAX),
+                elapsed,
+            )
+        })?;
+        Ok(Price::from_raw_points(average))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PoolFixture;
+
+    fn pool() -> LpPool {
+        PoolFixture::new().build().pool
+    }
+
+    #[test]
+    fn it_averages_constant_price_to_itself() {
+        let mut lp_pool = pool();
+        let price = Price::from_raw_points(150);
+        lp_pool.update_price_at(price, 1_000).unwrap();
+        let from = lp_pool.checkpoint();
+        lp_pool.update_price_at(price, 1_600).unwrap();
+        lp_pool.update_price_at(price, 4_000).unwrap();
+
+        assert_eq!(lp_pool.twap_between(from, lp_pool.checkpoint()), Ok(price));
+    }
+
+    #[test]
+    fn it_averages_step_change_to_midpoint() {
+        let mut lp_pool = pool();
+        lp_pool
+            .update_price_at(Price::from_raw_points(100), 1_000)
+            .unwrap();
+        let from = lp_pool.checkpoint();
+        lp_pool
+            .update_price_at(Price::from_raw_points(200), 1_500)
+            .unwrap();
+        lp_pool
+            .update_price_at(Price::from_raw_points(200), 2_000)
+            .unwrap();
+
+        assert_eq!(
+            lp_pool.twap_between(from, lp_pool.checkpoint()),
+            Ok(Price::from_raw_points(150))
+        );
+    }
+
+    #[test]
+    fn it_rejects_out_of_order_timestamps() {
+        let mut lp_pool = pool();
+        lp_pool
+            .update_price_at(Price::from_raw_points(100), 1_000)
+            .unwrap();
+        let checkpoint = lp_pool.checkpoint();
+        let sequence = lp_pool.sequence();
+
+        assert_eq!(
+            lp_pool.update_price_at(Price::from_raw_points(200), 999),
+            Err(Error::LpPool(LpPoolError::PriceTimestampOutOfOrder {
+                last: 1_000,
+                given: 999,
+            }))
+        );
+        assert_eq!(lp_pool.checkpoint(), checkpoint);
+        assert_eq!(lp_pool.sequence(), sequence);
+        assert_eq!(
+            lp_pool.twap_between(checkpoint, checkpoint),
+            Err(Error::LpPool(LpPoolError::PriceTimestampOutOfOrder {
+                last: 1_000,
+                given: 1_000,
+            }))
+        );
+    }
+
+    #[test]
+    fn it_averages_across_accumulator_wrap_around() {
+        let mut lp_pool = pool();
+        lp_pool.price_cumulative = u128::MAX - 50;
+        lp_pool
+            .update_price_at(Price::from_raw_points(100), 0)
+            .unwrap();
+        let from = lp_pool.checkpoint();
+        lp_pool
+            .update_price_at(Price::from_raw_points(100), 10)
+            .unwrap();
+
+        assert!(lp_pool.checkpoint().cumulative < from.cumulative);
+        assert_eq!(
+            lp_pool.twap_between(from, lp_pool.checkpoint()),
+            Ok(Price::from_raw_points(100))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "strict-math")]
+    fn it_rejects_checkpoints_averaging_above_any_price() {
+        let lp_pool = pool();
+        let from = PriceCheckpoint::default();
+        let to = PriceCheckpoint {
+            cumulative: u128::MAX,
+            timestamp: 1,
+        };
+
+        assert_eq!(
+            lp_pool.twap_between(from, to),
+            Err(Error::LpPool(LpPoolError::ArithmeticOverflow {
+                op: "(to.cumulative - from.cumulative) / elapsed",
+                lhs: u64::MAX,
+                rhs: 1,
+            }))
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Delayed unstake tickets, the slow alternative to swapping staked tokens in the pool.
+
+use crate::error::{Error, Result};
+use crate::lp_pool::{
+    data::{
+        price::Price,
+        token::{StakedTokenAmount, TokenAmount},
+    },
+    error::Error as LpPoolError,
+    overflow, LpPool,
+};
+
+/// Epochs a ticket waits before it can be claimed, Marinade pays out after the next epoch.
+pub const DEFAULT_COOLDOWN_EPOCHS: u64 = 1;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash)]
+pub struct TicketId(u64);
+
+impl From<TicketId> for u64 {
+    fn from(val: TicketId) -> Self {
+        val.0
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Ticket {
+    pub staked_tokens: StakedTokenAmount,
+    /// Price at request time, the ticket is worth `staked_tokens` at this price whatever the
+    /// price is when it is claimed.
+    pub price: Price,
+    pub claimable_at: u64,
+    pub claimed: bool,
+}
+
+impl Ticket {
+    /// Tokens paid out on claim, rounded down.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArithmeticOverflow` when the value does not fit `u64`.
+    pub fn value(&self) -> Result<TokenAmount> {
+        TokenAmount::from_staked_tokens(self.staked_tokens, self.price)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnstakeQueue {
+    tickets: Vec<Ticket>,
+    cooldown_epochs: u64,
+}
+
+impl Default for UnstakeQueue {
+    fn default() -> Self {
+        Self {
+            tickets: Vec::new(),
+            cooldown_epochs: DEFAULT_COOLDOWN_EPOCHS,
+        }
+    }
+}
+
+impl UnstakeQueue {
+    #[must_use]
+    pub fn ticket(&self, id: TicketId) -> Option<&Ticket> {
+        usize::try_from(id.0)
+            .ok()
+            .and_then(|index| self.tickets.get(index))
+    }
+
+    /// Staked tokens taken out of circulation by unclaimed tickets.
+    #[must_use]
+    pub fn staked_tokens_pending(&self) -> StakedTokenAmount {
+        StakedTokenAmount::from_lamports(
+            self.tickets
+                .iter()
+                .filter(|ticket| !ticket.claimed)
+                .map(|ticket| u64::from(ticket.staked_tokens))
+                .sum(),
+        )
+    }
+
+    pub(super) fn value_claimable_at(&self, epoch: u64) -> Result<TokenAmount> {
+        self.tickets
+            .iter()
+            .filter(|ticket| !ticket.claimed && ticket.claimable_at == epoch)
+            .try_fold(TokenAmount::from_lamports(0), |total, ticket| {
+                let value = ticket.value()?;
+                total
+                    .checked_add(value)
+                    .ok_or_else(|| overflow("maturing value + ticket value", total, value))
+            })
+    }
+}
+
+impl LpPool {
+    #[must_use]
+    pub fn with_unstake_cooldown(mut self, cooldown_epochs: u64) -> Self {
+        self.unstake_queue.cooldown_epochs = cooldown_epochs;
+        self
+    }
+
+    #[must_use]
+    pub fn unstake_queue(&self) -> &UnstakeQueue {
+        &self.unstake_queue
+    }
+
+    /// Takes `staked_tokens` out of circulation for a ticket worth them at the current price,
+    /// claimable `cooldown_epochs` after `current_epoch`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ArithmeticOverflow` when the ticket's value does not fit `u64`.
+    pub fn request_delayed_unstake(
+        &mut self,
+        staked_tokens: StakedTokenAmount,
+        current_epoch: u64,
+    ) -> Result<TicketId> {
+        let ticket = Ticket {
+            staked_tokens,
+            price: self.price,
+            claimable_at: current_epoch.saturating_add(self.unstake_queue.cooldown_epochs),
+            claimed: false,
+        };
+        ticket.value()?;
+
+        let id = TicketId(self.unstake_queue.tickets.len() as u64);
+        self.unstake_queue.tickets.push(ticket);
+        Ok(id)
+    }
+
+    /// Pays out ticket `id` once `current_epoch` reaches its `claimable_at`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TicketNotFound`, `TicketAlreadyClaimed` or `TicketNotClaimable` before the
+    /// ticket matures.
+    pub fn claim_ticket(&mut self, id: TicketId, current_epoch: u64) -> Result<TokenAmount> {
+        let ticket = usize::try_from(id.0)
+            .ok()
+            .and_then(|index| self.unstake_queue.tickets.get_mut(index))
+            .ok_or(LpPoolError::TicketNotFound(id.0))?;
+        if ticket.claimed {
+            return Err(Error::LpPool(LpPoolError::TicketAlreadyClaimed(id.0)));
+        }
+        if current_epoch < ticket.claimable_at {
+            return Err(Error::LpPool(LpPoolError::TicketNotClaimable {
+                ticket: id.0,
+                claimable_at: ticket.claimable_at,
+                current_epoch,
+            }));
+        }
+
+        let value = ticket.value()?;
+        ticket.claimed = true;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PoolFixture;
+
+    fn pool() -> LpPool {
+        PoolFixture::new()
+            .price_points(200)
+            .seeded_with_lamports(1_000)
+            .build()
+            .pool
+            .with_unstake_cooldown(2)
+    }
+
+    #[test]
+    fn it_rejects_claims_before_cooldown() {
+        let mut lp_pool = pool();
+        let ticket = lp_pool
+            .request_delayed_unstake(StakedTokenAmount::from_lamports(10), lp_pool.epoch())
+            .unwrap();
+        lp_pool.advance_epoch().unwrap();
+
+        assert_eq!(
+            lp_pool.claim_ticket(ticket, lp_pool.epoch()),
+            Err(Error::LpPool(LpPoolError::TicketNotClaimable {
+                ticket: 0,
+                claimable_at: 2,
+                current_epoch: 1,
+            }))
+        );
+        assert_eq!(
+            lp_pool.advance_epoch().unwrap().tickets_maturing,
+            TokenAmount::from_lamports(20)
+        );
+        assert_eq!(
+            lp_pool.claim_ticket(ticket, lp_pool.epoch()),
+            Ok(TokenAmount::from_lamports(20))
+        );
+    }
+
+    #[test]
+    fn it_rejects_second_claim_of_ticket() {
+        let mut lp_pool = pool();
+        let ticket = lp_pool
+            .request_delayed_unstake(StakedTokenAmount::from_lamports(10), 5)
+            .unwrap();
+
+        lp_pool.claim_ticket(ticket, 7).unwrap();
+
+        assert_eq!(
+            lp_pool.claim_ticket(ticket, 8),
+            Err(Error::LpPool(LpPoolError::TicketAlreadyClaimed(0)))
+        );
+        assert_eq!(
+            lp_pool.claim_ticket(TicketId(1), 8),
+            Err(Error::LpPool(LpPoolError::TicketNotFound(1)))
+        );
+    }
+
+    #[test]
+    fn it_conserves_value_across_ticket_lifecycle() {
+        let mut lp_pool = pool();
+        let state_before = lp_pool.state();
+        let tickets = [(10, 0), (25, 0), (7, 1)].map(|(staked, epoch)| {
+            let value = lp_pool.price.mul_by_price(staked).unwrap();
+            let ticket = lp_pool
+                .request_delayed_unstake(StakedTokenAmount::from_lamports(staked), epoch)
+                .unwrap();
+            (ticket, value)
+        });
+        lp_pool.update_price(Price::from_raw_points(300)).unwrap();
+
+        assert_eq!(
+            lp_pool.unstake_queue().staked_tokens_pending(),
+            StakedTokenAmount::from_lamports(42)
+        );
+        let claimed: u64 = tickets
+            .iter()
+            .map(|(ticket, _)| u64::from(lp_pool.claim_ticket(*ticket, 3).unwrap()))
+            .sum();
+
+        assert_eq!(claimed, tickets.iter().map(|(_, value)| value).sum::<u64>());
+        assert_eq!(
+            lp_pool.unstake_queue().staked_tokens_pending(),
+            StakedTokenAmount::from_lamports(0)
+        );
+        let state_after = lp_pool.state();
+        assert_eq!(
+            (state_after.token_amount, state_after.staked_token_amount),
+            (state_before.token_amount, state_before.staked_token_amount)
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Read-only access to a pool for code that must not change it.
+
+use crate::error::Result;
+#[cfg(feature = "std")]
+use crate::lp_pool::operation::PoolOperation;
+use crate::lp_pool::{
+    consts::MAX_BASIS_POINTS,
+    data::{
+        fee::{Fee, Rounding},
+        token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    },
+    quote::{Quote, RemovedLiquidity},
+    state::PoolState,
+    FeeDiscount, LpPool,
+};
+
+/// Borrows a pool exposing only methods that cannot change it. Quotes are implemented here
+/// and `LpPool` delegates to them.
+#[derive(Debug, Clone, Copy)]
+pub struct LpPoolView<'a> {
+    pool: &'a LpPool,
+}
+
+impl LpPoolView<'_> {
+    #[must_use]
+    pub fn state(&self) -> PoolState {
+        self.pool.state()
+    }
+
+    #[must_use]
+    pub fn sequence(&self) -> u64 {
+        self.pool.sequence
+    }
+
+    /// What `LpPool::swap` of `staked_tokens_to_swap` pays out.
+    ///
+    /// # Errors
+    ///
+    /// Fails exactly when `LpPool::swap` would.
+    pub fn quote_swap(
+        &self,
+        staked_tokens_to_swap: StakedTokenAmount,
+    ) -> Result<Quote<StakedTokenAmount, TokenAmount>> {
+        let swap = self
+            .pool
+            .swap_amounts(staked_tokens_to_swap, Fee::ZERO, FeeDiscount::NONE)?;
+        Ok(Quote {
+            amount_in: staked_tokens_to_swap,
+            amount_out: swap.tokens_with_fee,
+            fee: swap.fee,
+            fee_amount: swap.tokens_without_fee - swap.tokens_with_fee,
+            pool_sequence: self.pool.sequence,
+        })
+    }
+
+    /// Deposits are free, the quote's fee is always zero.
+    ///
+    /// # Errors
+    ///
+    /// Fails exactly when `LpPool::add_liquidity` would.
+    pub fn quote_add_liquidity(
+        &self,
+        tokens_to_add: TokenAmount,
+    ) -> Result<Quote<TokenAmount, LpTokenAmount>> {
+        let deposit = self.pool.deposit(tokens_to_add)?;
+        Ok(Quote {
+            amount_in: tokens_to_add,
+            amount_out: deposit.lp_tokens,
+            fee: Fee::ZERO,
+            fee_amount: TokenAmount::from_lamports(0),
+            pool_sequence: self.pool.sequence,
+        })
+    }
+
+    /// The fee is charged on the token leg only, staked tokens are paid out in full.
+    ///
+    /// # Errors
+    ///
+    /// Fails exactly when `LpPool::remove_liquidity` would.
+    pub fn quote_remove_liquidity(
+        &self,
+        lp_tokens_to_remove: LpTokenAmount,
+    ) -> Result<Quote<LpTokenAmount, RemovedLiquidity>> {
+        let withdrawal = self.pool.withdrawal(lp_tokens_to_remove)?;
+        Ok(Quote {
+            amount_in: lp_tokens_to_remove,
+            amount_out: RemovedLiquidity {
+                tokens: withdrawal.tokens_with_fee,
+                staked_tokens: withdrawal.unstaked_tokens,
+            },
+            fee: withdrawal.fee,
+            fee_amount: withdrawal.tokens_without_fee - withdrawal.tokens_with_fee,
+            pool_sequence: self.pool.sequence,
+        })
+    }
+
+    /// Fee on the curve once the pool holds `amount_after` available tokens, zero from
+    /// `surplus_threshold` above the liquidity target.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BasisPointsOverflow` for fee bounds above 100%, which `LpPool::init` rejects.
+    pub fn fee_at(&self, amount_after: TokenAmount) -> Result<Fee> {
+        if self
+            .pool
+            .surplus_line()
+            .is_some_and(|line| u64::from(amount_after) >= line)
+        {
+            return Ok(Fee::ZERO);
+        }
+        let fee = self.pool.fee_range.interpolate(
+            amount_after.into(),
+            self.pool.liquidity_target.into(),
+            Rounding::Up,
+        );
+        fee.check()?;
+        Ok(fee)
+    }
+
+    /// Tokens held by the pool, including referral and protocol fees owed and dust.
+    #[must_use]
+    pub fn total_value(&self) -> TokenAmount {
+        self.pool.token_amount
+    }
+
+    /// How far available tokens are below the liquidity target, in basis points of the
+    /// target. 0 at or above the target, 10 000 when the pool is drained.
+    #[must_use]
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "utilization is clamped to 10 000 basis points"
+    )]
+    pub fn utilization_bps(&self) -> u32 {
+        let target = u64::from(self.pool.liquidity_target);
+        let available = u64::from(self.pool.available_tokens()).min(target);
+        let drawn = u128::from(target - available) * u128::from(MAX_BASIS_POINTS)
+            / u128::from(target.max(1));
+        // PANIC SAFETY: `available` is clamped to `target`, so at most 10 000.
+        u32::try_from(drawn).expect("at most 10 000 basis points")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> LpPoolView<'a> {
+    /// `LpPool::history`, borrowed for as long as the view.
+    #[must_use]
+    pub fn history(&self) -> impl ExactSizeIterator<Item = &'a PoolOperation> + 'a {
+        self.pool.history()
+    }
+}
+
+impl LpPool {
+    #[must_use]
+    pub fn view(&self) -> LpPoolView<'_> {
+        LpPoolView { pool: self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PoolFixture;
+
+    fn pool() -> LpPool {
+        let mut lp_pool = PoolFixture::new()
+            .price_points(100)
+            .fees(10, 900)
+            .target_lamports(100_000)
+            .seeded_with_lamports(100_000)
+            .build()
+            .pool;
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(25_000))
+            .unwrap();
+        lp_pool
+    }
+
+    #[test]
+    fn it_quotes_through_view_like_owner() {
+        let lp_pool = pool();
+        let view = lp_pool.view();
+        let staked = StakedTokenAmount::from_lamports(1_000);
+        let lp_tokens = LpTokenAmount::from_lamports(10_000);
+        let amount_after = TokenAmount::from_lamports(50_000);
+
+        assert_eq!(view.state(), lp_pool.state());
+        assert_eq!(view.sequence(), lp_pool.sequence());
+        assert_eq!(view.quote_swap(staked), lp_pool.quote_swap(staked));
+        assert_eq!(
+            view.quote_add_liquidity(amount_after),
+            lp_pool.quote_add_liquidity(amount_after)
+        );
+        assert_eq!(
+            view.quote_remove_liquidity(lp_tokens),
+            lp_pool.quote_remove_liquidity(lp_tokens)
+        );
+        assert_eq!(
+            view.fee_at(amount_after),
+            lp_pool.calculate_fee(amount_after)
+        );
+        assert_eq!(view.total_value(), lp_pool.total_value());
+        assert!(view
+            .quote_swap(StakedTokenAmount::from_lamports(1_000_000))
+            .is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn it_reads_history_through_view() {
+        let mut lp_pool =
+            pool().with_history_policy(crate::lp_pool::history::HistoryPolicy::Unbounded);
+        lp_pool
+            .swap(StakedTokenAmount::from_lamports(1_000))
+            .unwrap();
+        let view = lp_pool.view();
+
+        assert_eq!(view.history().len(), 1);
+        assert!(view.history().eq(lp_pool.history()));
+    }
+
+    #[test]
+    fn it_reports_utilization_below_target() {
+        let lp_pool = pool();
+        let available = u64::from(lp_pool.available_tokens());
+
+        assert_eq!(
+            u64::from(lp_pool.view().utilization_bps()),
+            (100_000 - available) * 10_000 / 100_000
+        );
+        assert_eq!(
+            PoolFixture::new()
+                .target_lamports(100_000)
+                .seeded_with_lamports(200_000)
+                .build()
+                .pool
+                .view()
+                .utilization_bps(),
+            0
+        );
+    }
+}
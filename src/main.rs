@@ -1,39 +1,34 @@
-use lp_pool::{
-    data::{
-        fee::Fee,
-        price::Price,
-        token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
-    },
-    LpPool,
-};
-
-mod calc;
-mod error;
-mod lp_pool;
-
-fn main() {
+use liquidity_pool::prelude::*;
+use std::process::ExitCode;
+
+mod cli;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    cli::run(&args, demo)
+}
+
+fn demo() {
     //LpPool::init(price=1.5, min_fee=0.1%, max_fee9%, liquidity_target=90.0 Token)
-    let mut lp_pool: LpPool = LpPool::init(
-        Price::try_from(1.5).unwrap(),
-        Fee::from_basis_points(10),
-        Fee::from_basis_points(900),
-        TokenAmount::from_lamports(90_000),
-    )
+    let mut lp_pool: LpPool = LpPool::init(PoolParams {
+        price: Price::try_from(1.5).unwrap(),
+        fee_range: FeeRange::new(Fee::from_basis_points(10), Fee::from_basis_points(900)).unwrap(),
+        liquidity_target: TokenAmount::from_lamports(90_000),
+    })
     .unwrap();
 
-    lp_pool
-        .add_liquidity(TokenAmount::from_lamports(100_000_000))
-        .unwrap();
+    demo_scenario(&mut lp_pool);
+}
 
-    lp_pool
-        .swap(StakedTokenAmount::from_lamports(6_000))
+fn demo_scenario(pool: &mut dyn Pool) {
+    pool.add_liquidity(TokenAmount::from_lamports(100_000_000))
         .unwrap();
 
-    lp_pool
-        .add_liquidity(TokenAmount::from_lamports(10_000))
+    pool.swap(StakedTokenAmount::from_lamports(6_000)).unwrap();
+
+    pool.add_liquidity(TokenAmount::from_lamports(10_000))
         .unwrap();
 
-    lp_pool
-        .remove_liquidity(LpTokenAmount::from_lamports(2_000))
+    pool.remove_liquidity(LpTokenAmount::from_lamports(2_000))
         .unwrap();
 }
@@ -0,0 +1,146 @@
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::lp_pool::data::price::Price;
+
+pub const MARINADE_PRICE_URL: &str = "https://api.marinade.finance/msol/price_sol";
+
+/// Fetches the mSOL price from an endpoint returning it as the whole JSON
+/// document, like [`MARINADE_PRICE_URL`].
+///
+/// # Errors
+///
+/// Fails like `fetch_price`.
+pub fn fetch_msol_price(url: &str) -> Result<Price> {
+    fetch_price(url, "")
+}
+
+/// Fetches a price from any JSON endpoint, `json_pointer` (RFC 6901) selects
+/// the field holding the price as a JSON number or decimal string.
+///
+/// # Errors
+///
+/// Returns `Error::PriceFetch` when the request fails, the response is not JSON or the
+/// selected field is not a valid price.
+pub fn fetch_price(url: &str, json_pointer: &str) -> Result<Price> {
+    let body = ureq::get(url)
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|error| Error::PriceFetch(format!("request to {url} failed: {error}")))?;
+    price_from_json(&body, json_pointer)
+}
+
+fn price_from_json(body: &str, json_pointer: &str) -> Result<Price> {
+    let document: Value = serde_json::from_str(body)
+        .map_err(|error| Error::PriceFetch(format!("response is not valid JSON: {error}")))?;
+    let decimal = match document.pointer(json_pointer) {
+        Some(Value::Number(number)) => number.to_string(),
+        Some(Value::String(decimal)) => decimal.clone(),
+        Some(other) => {
+            return Err(Error::PriceFetch(format!(
+                "value at `{json_pointer}` is not a number: {other}"
+            )))
+        }
+        None => {
+            return Err(Error::PriceFetch(format!(
+                "no value at JSON pointer `{json_pointer}`"
+            )))
+        }
+    };
+
+    truncate_to_price_resolution(&decimal)
+        .parse::<Price>()
+        .map_err(|error| Error::PriceFetch(format!("`{decimal}` is not a valid price: {error}")))
+}
+
+/// `Price` keeps two decimal places, further digits are dropped instead of
+/// rounded so the parsed price never exceeds the quoted one.
+fn truncate_to_price_resolution(decimal: &str) -> &str {
+    match decimal.find('.') {
+        Some(dot) => &decimal[..decimal.len().min(dot + 3)],
+        None => decimal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn serve_once(status: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        });
+        format!("http://{address}/msol/price_sol")
+    }
+
+    #[test]
+    fn it_fetches_bare_number_price() {
+        let url = serve_once("200 OK", "1.2345678912");
+        assert_eq!(fetch_msol_price(&url), Ok(Price::from_raw_points(123)));
+    }
+
+    #[test]
+    fn it_fetches_price_at_json_pointer() {
+        let url = serve_once("200 OK", r#"{"data":{"price":"1.52"}}"#);
+        assert_eq!(
+            fetch_price(&url, "/data/price"),
+            Ok(Price::from_raw_points(152))
+        );
+    }
+
+    #[test]
+    fn it_returns_price_fetch_error_on_http_failure() {
+        let url = serve_once("500 Internal Server Error", "{}");
+        assert!(matches!(
+            fetch_msol_price(&url),
+            Err(Error::PriceFetch(reason)) if reason.contains("500")
+        ));
+    }
+
+    #[test]
+    fn it_returns_price_fetch_error_on_unreachable_server() {
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        assert!(matches!(
+            fetch_msol_price(&format!("http://{address}/")),
+            Err(Error::PriceFetch(_))
+        ));
+    }
+
+    #[test]
+    fn it_returns_price_fetch_error_on_unexpected_body() {
+        assert!(matches!(
+            price_from_json("<html>", ""),
+            Err(Error::PriceFetch(reason)) if reason.starts_with("response is not valid JSON")
+        ));
+        assert_eq!(
+            price_from_json(r#"{"price":1.5}"#, "/missing"),
+            Err(Error::PriceFetch(
+                "no value at JSON pointer `/missing`".to_string()
+            ))
+        );
+        assert!(matches!(
+            price_from_json(r#"{"price":true}"#, "/price"),
+            Err(Error::PriceFetch(_))
+        ));
+        assert!(matches!(
+            price_from_json(r#""-1.5""#, ""),
+            Err(Error::PriceFetch(_))
+        ));
+    }
+}
@@ -0,0 +1,7 @@
+//! The types most code using the pool needs, `use liquidity_pool::prelude::*;`.
+
+pub use crate::error::{Error, Result};
+pub use crate::lp_pool::{
+    Fee, FeeRange, LpPool, LpPoolError, LpTokenAmount, Pool, PoolParams, PoolState, Price,
+    StakedTokenAmount, TokenAmount,
+};
@@ -0,0 +1,293 @@
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::error::Error;
+use crate::lp_pool::{
+    data::{
+        fee::{Fee, FeeRange},
+        price::Price,
+        token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    },
+    error::Error as PoolError,
+    LpPool, PoolParams,
+};
+
+create_exception!(liquidity_pool, LpPoolError, PyException);
+
+fn variant_name(error: &Error) -> &'static str {
+    match error {
+        Error::CalculationError { .. } => "CalculationError",
+        #[cfg(feature = "net")]
+        Error::PriceFetch(_) => "PriceFetch",
+        #[cfg(feature = "persist")]
+        Error::Persistence { .. } => "Persistence",
+        #[cfg(feature = "ledger-sqlite")]
+        Error::Ledger(_) => "Ledger",
+        #[cfg(feature = "ledger-sqlite")]
+        Error::LedgerDivergence { .. } => "LedgerDivergence",
+        Error::Redacted(error) => variant_name(error),
+        Error::LpPool(error) => match error {
+            PoolError::LiquidityTargetIncorrect(_) => "LiquidityTargetIncorrect",
+            PoolError::PriceIncorrect(_) => "PriceIncorrect",
+            PoolError::PriceConversionFailure { .. } => "PriceConversionFailure",
+            PoolError::FeeConversionFailure { .. } => "FeeConversionFailure",
+            PoolError::AmountConversionFailure { .. } => "AmountConversionFailure",
+            PoolError::MaxSwapSizeIncorrect(_) => "MaxSwapSizeIncorrect",
+            PoolError::TokenCapIncorrect(_) => "TokenCapIncorrect",
+            PoolError::PriceRatioIncorrect { .. } => "PriceRatioIncorrect",
+            PoolError::AccountDataInvalid { .. } => "AccountDataInvalid",
+            PoolError::BasisPointsOverflow(_) => "BasisPointsOverflow",
+            PoolError::MinFeeGreaterThanMaxFee { .. } => "MinFeeGreaterThanMaxFee",
+            PoolError::InitialLiquidityIncorrect { .. } => "InitialLiquidityIncorrect",
+            PoolError::EmptyPool => "EmptyPool",
+            PoolError::InsufficientTokenLiquidity { .. } => "InsufficientTokenLiquidity",
+            PoolError::InsufficientStakedLiquidity { .. } => "InsufficientStakedLiquidity",
+            PoolError::InsufficientLpTokens { .. } => "InsufficientLpTokens",
+            PoolError::ArithmeticOverflow { .. } => "ArithmeticOverflow",
+            PoolError::SwapTooLarge { .. } => "SwapTooLarge",
+            PoolError::PoolCapExceeded { .. } => "PoolCapExceeded",
+            PoolError::ReferralFeeTooHigh { .. } => "ReferralFeeTooHigh",
+            PoolError::FeeTierThresholdsUnsorted { .. } => "FeeTierThresholdsUnsorted",
+            PoolError::TicketNotFound(_) => "TicketNotFound",
+            PoolError::TicketNotClaimable { .. } => "TicketNotClaimable",
+            PoolError::TicketAlreadyClaimed(_) => "TicketAlreadyClaimed",
+            PoolError::NoRoute => "NoRoute",
+            PoolError::StaleQuote { .. } => "StaleQuote",
+            PoolError::DustNotCollectable => "DustNotCollectable",
+            PoolError::PoolNotEmpty(_) => "PoolNotEmpty",
+            PoolError::SlippageExceeded { .. } => "SlippageExceeded",
+            PoolError::AmountOutOfRange(_) => "AmountOutOfRange",
+            PoolError::StateInvalid(_) => "StateInvalid",
+            PoolError::UnsupportedStateVersion { .. } => "UnsupportedStateVersion",
+            PoolError::OperationEncodingInvalid { .. } => "OperationEncodingInvalid",
+            PoolError::HoldingPeriodNotMet { .. } => "HoldingPeriodNotMet",
+            PoolError::FeeBucketsUnsorted { .. } => "FeeBucketsUnsorted",
+            PoolError::IdempotencyKeyReused { .. } => "IdempotencyKeyReused",
+            PoolError::ZeroAmount => "ZeroAmount",
+            PoolError::ReserveFloorIncorrect { .. } => "ReserveFloorIncorrect",
+            PoolError::ReserveFloorBreached { .. } => "ReserveFloorBreached",
+            PoolError::PriceTimestampOutOfOrder { .. } => "PriceTimestampOutOfOrder",
+            PoolError::OutputBelowMinimum { .. } => "OutputBelowMinimum",
+            PoolError::ReconfigurePriceMismatch { .. } => "ReconfigurePriceMismatch",
+        },
+    }
+}
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> Self {
+        LpPoolError::new_err((variant_name(&error), error.display_chain().to_string()))
+    }
+}
+
+/// Decimal input accepted from Python, floats go through their shortest
+/// decimal representation so `1.15` is parsed exactly.
+#[derive(FromPyObject)]
+enum Decimal {
+    Str(String),
+    Float(f64),
+}
+
+impl Decimal {
+    fn into_string(self) -> String {
+        match self {
+            Decimal::Str(value) => value,
+            Decimal::Float(value) => value.to_string(),
+        }
+    }
+}
+
+#[pyclass(name = "Fee", frozen)]
+#[derive(Clone, Copy)]
+pub struct PyFee(Fee);
+
+#[pymethods]
+#[allow(
+    clippy::trivially_copy_pass_by_ref,
+    reason = "pyo3 methods borrow `self`"
+)]
+impl PyFee {
+    #[new]
+    fn new(percent: Decimal) -> PyResult<Self> {
+        Ok(Self(percent.into_string().parse::<Fee>()?))
+    }
+
+    #[staticmethod]
+    fn from_basis_points(basis_points: u32) -> PyResult<Self> {
+        let fee = Fee::from_basis_points(basis_points);
+        fee.check()?;
+        Ok(Self(fee))
+    }
+
+    #[staticmethod]
+    fn from_percent(percent: f64) -> PyResult<Self> {
+        Ok(Self(Fee::from_percent(percent)?))
+    }
+
+    #[getter]
+    fn basis_points(&self) -> f64 {
+        f64::from(self.0.centibps) / f64::from(Fee::CENTIBPS_PER_BASIS_POINT)
+    }
+
+    fn as_percent(&self) -> f64 {
+        self.0.as_percent()
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Fee('{}')", self.0)
+    }
+}
+
+#[pyclass(name = "Price", frozen)]
+#[derive(Clone, Copy)]
+pub struct PyPrice(Price);
+
+#[pymethods]
+#[allow(
+    clippy::trivially_copy_pass_by_ref,
+    reason = "pyo3 methods borrow `self`"
+)]
+impl PyPrice {
+    #[new]
+    fn new(price: Decimal) -> PyResult<Self> {
+        Ok(Self(price.into_string().parse::<Price>()?))
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Price('{}')", self.0)
+    }
+}
+
+#[pyclass(name = "LpPool")]
+pub struct PyLpPool {
+    pool: LpPool,
+}
+
+#[pymethods]
+impl PyLpPool {
+    #[staticmethod]
+    fn init(
+        price: PyPrice,
+        min_fee: PyFee,
+        max_fee: PyFee,
+        liquidity_target: u64,
+    ) -> PyResult<Self> {
+        let pool = LpPool::init(PoolParams {
+            price: price.0,
+            fee_range: FeeRange::new(min_fee.0, max_fee.0)?,
+            liquidity_target: TokenAmount::from_lamports(liquidity_target),
+        })?;
+        Ok(Self { pool })
+    }
+
+    fn add_liquidity(&mut self, lamports: u64) -> PyResult<u64> {
+        Ok(self
+            .pool
+            .add_liquidity(TokenAmount::from_lamports(lamports))?
+            .into())
+    }
+
+    fn remove_liquidity(&mut self, lp_lamports: u64) -> PyResult<(u64, u64)> {
+        let (tokens, staked_tokens) = self
+            .pool
+            .remove_liquidity(LpTokenAmount::from_lamports(lp_lamports))?;
+        Ok((tokens.into(), staked_tokens.into()))
+    }
+
+    fn swap(&mut self, staked_lamports: u64) -> PyResult<u64> {
+        Ok(self
+            .pool
+            .swap(StakedTokenAmount::from_lamports(staked_lamports))?
+            .into())
+    }
+
+    fn quote_swap(&self, staked_lamports: u64) -> PyResult<u64> {
+        Ok(self
+            .pool
+            .quote_swap(StakedTokenAmount::from_lamports(staked_lamports))?
+            .amount_out
+            .into())
+    }
+
+    #[getter]
+    fn state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let state = self.pool.state();
+        let dict = PyDict::new(py);
+        dict.set_item("price", state.price.to_string())?;
+        dict.set_item("min_fee_centibps", state.min_fee.centibps)?;
+        dict.set_item("max_fee_centibps", state.max_fee.centibps)?;
+        dict.set_item("liquidity_target", u64::from(state.liquidity_target))?;
+        dict.set_item("token_amount", u64::from(state.token_amount))?;
+        dict.set_item("staked_token_amount", u64::from(state.staked_token_amount))?;
+        dict.set_item("lp_token_amount", u64::from(state.lp_token_amount))?;
+        dict.set_item("fees_collected", u64::from(state.fees_collected))?;
+        dict.set_item("referral_fees_owed", u64::from(state.referral_fees_owed))?;
+        dict.set_item("dust", u64::from(state.dust))?;
+        dict.set_item("protocol_fees_owed", u64::from(state.protocol_fees_owed))?;
+        Ok(dict)
+    }
+}
+
+#[pymodule]
+fn liquidity_pool(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyLpPool>()?;
+    m.add_class::<PyFee>()?;
+    m.add_class::<PyPrice>()?;
+    m.add("LpPoolError", m.py().get_type::<LpPoolError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(code: &std::ffi::CStr) -> PyResult<()> {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "liquidity_pool")?;
+            liquidity_pool(&module)?;
+            let globals = module.dict();
+            py.run(code, Some(&globals), None)
+        })
+    }
+
+    #[test]
+    fn it_runs_full_scenario_from_python() {
+        run(c"
+pool = LpPool.init(Price('1'), Fee(0.1), Fee('9%'), 90_000)
+assert pool.add_liquidity(1_000_000) == 1_000_000
+before = pool.state
+assert pool.quote_swap(60) == pool.swap(60)
+assert pool.state['staked_token_amount'] == before['staked_token_amount'] + 60
+tokens, staked = pool.remove_liquidity(100_000)
+assert tokens > 0 and staked > 0
+state = pool.state
+assert state['min_fee_centibps'] == 1000
+assert state['lp_token_amount'] == 900_000
+")
+        .unwrap();
+    }
+
+    #[test]
+    fn it_raises_lp_pool_error_with_variant_name() {
+        run(c"
+try:
+    LpPool.init(Price(1.5), Fee('1'), Fee('0.5'), 90_000)
+    raise AssertionError('expected LpPoolError')
+except LpPoolError as error:
+    variant, message = error.args
+    assert variant == 'MinFeeGreaterThanMaxFee', variant
+    assert 'MinFeeGreaterThanMaxFee' in message, message
+")
+        .unwrap();
+    }
+}
@@ -0,0 +1,265 @@
+//! Routing swaps across pools holding the same pair.
+
+use crate::error::{Error, Result};
+use crate::lp_pool::{
+    data::token::{StakedTokenAmount, TokenAmount},
+    error::Error as LpPoolError,
+    Pool,
+};
+
+/// Index of a pool registered with a `Router`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash)]
+pub struct PoolId(usize);
+
+/// Part of a split order sent to one pool.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Split {
+    pub pool: PoolId,
+    pub staked_tokens: StakedTokenAmount,
+    pub tokens_out: TokenAmount,
+}
+
+#[derive(Default)]
+pub struct Router {
+    pools: Vec<Box<dyn Pool>>,
+}
+
+impl Router {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, pool: Box<dyn Pool>) -> PoolId {
+        self.pools.push(pool);
+        PoolId(self.pools.len() - 1)
+    }
+
+    pub fn pool(&self, id: PoolId) -> Option<&dyn Pool> {
+        self.pools.get(id.0).map(AsRef::as_ref)
+    }
+
+    /// Pool paying out the most for the swap, pools failing to quote it are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NoRoute` when no pool quotes the swap.
+    pub fn best_quote(
+        &self,
+        staked_tokens_to_swap: StakedTokenAmount,
+    ) -> Result<(PoolId, TokenAmount)> {
+        self.pools
+            .iter()
+            .enumerate()
+            .filter_map(|(index, pool)| {
+                let tokens = pool.quote_swap(staked_tokens_to_swap).ok()?;
+                Some((PoolId(index), tokens))
+            })
+            // `max_by_key` keeps the last maximum, reverse so ties go to the first pool
+            .rev()
+            .max_by_key(|(_, tokens)| *tokens)
+            .ok_or(Error::LpPool(LpPoolError::NoRoute))
+    }
+
+    /// Swaps on the pool of `best_quote`.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `best_quote`, or with the error of the chosen pool's swap.
+    pub fn execute_best(
+        &mut self,
+        staked_tokens_to_swap: StakedTokenAmount,
+    ) -> Result<(PoolId, TokenAmount)> {
+        let (id, _) = self.best_quote(staked_tokens_to_swap)?;
+        let tokens = self.pools[id.0].swap(staked_tokens_to_swap)?;
+        Ok((id, tokens))
+    }
+
+    /// Splits the order into `max_splits` equal chunks and sends each to the pool adding the
+    /// most output for it, one swap per pool. Falls back to the single best pool when the
+    /// split pays out less.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `best_quote`.
+    pub fn split_quote(
+        &self,
+        staked_tokens_to_swap: StakedTokenAmount,
+        max_splits: usize,
+    ) -> Result<Vec<Split>> {
+        let (best, best_tokens) = self.best_quote(staked_tokens_to_swap)?;
+        let single = vec![Split {
+            pool: best,
+            staked_tokens: staked_tokens_to_swap,
+            tokens_out: best_tokens,
+        }];
+
+        let total = u64::from(staked_tokens_to_swap);
+        let chunks = u64::try_from(max_splits.max(1))
+            .unwrap_or(u64::MAX)
+            .min(total.max(1));
+        let mut splits: Vec<Split> = Vec::new();
+        for chunk in 0..chunks {
+            let chunk_size = total / chunks + u64::from(chunk < total % chunks);
+            let Some((pool, staked_tokens, tokens_out)) = self
+                .pools
+                .iter()
+                .enumerate()
+                .filter_map(|(index, pool)| {
+                    let assigned = splits.iter().find(|split| split.pool.0 == index);
+                    let staked_tokens =
+                        assigned.map_or(0, |split| u64::from(split.staked_tokens)) + chunk_size;
+                    let staked_tokens = StakedTokenAmount::from_lamports(staked_tokens);
+                    let tokens_out = pool.quote_swap(staked_tokens).ok()?;
+                    let gained = u64::from(tokens_out)
+                        .checked_sub(assigned.map_or(0, |split| u64::from(split.tokens_out)))?;
+                    Some((gained, PoolId(index), staked_tokens, tokens_out))
+                })
+                .rev()
+                .max_by_key(|(gained, ..)| *gained)
+                .map(|(_, pool, staked_tokens, tokens_out)| (pool, staked_tokens, tokens_out))
+            else {
+                return Ok(single);
+            };
+
+            match splits.iter_mut().find(|split| split.pool == pool) {
+                Some(split) => {
+                    split.staked_tokens = staked_tokens;
+                    split.tokens_out = tokens_out;
+                }
+                None => splits.push(Split {
+                    pool,
+                    staked_tokens,
+                    tokens_out,
+                }),
+            }
+        }
+
+        let split_tokens: u64 = splits.iter().map(|split| u64::from(split.tokens_out)).sum();
+        if split_tokens > u64::from(best_tokens) {
+            Ok(splits)
+        } else {
+            Ok(single)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lp_pool::{
+        data::fee::{Fee, FeeRange},
+        LpPool, PoolParams,
+    };
+    use crate::test_utils::PoolFixture;
+
+    fn pool(min_bps: u32, max_bps: u32, liquidity: u64) -> Box<dyn Pool> {
+        Box::new(
+            PoolFixture::new()
+                .price_points(100)
+                .fees(min_bps, max_bps)
+                .target_lamports(liquidity)
+                .seeded_with_lamports(liquidity)
+                .build()
+                .pool,
+        )
+    }
+
+    fn swap(staked: u64) -> StakedTokenAmount {
+        StakedTokenAmount::from_lamports(staked)
+    }
+
+    #[test]
+    fn it_routes_to_pool_with_best_output() {
+        let mut router = Router::new();
+        let deep = router.register(pool(50, 50, 1_000_000));
+        let shallow = router.register(pool(0, 2_000, 10_000));
+
+        assert_eq!(router.best_quote(swap(200)).unwrap().0, shallow);
+        assert_eq!(router.best_quote(swap(5_000)).unwrap().0, deep);
+
+        let quote = router.best_quote(swap(5_000)).unwrap();
+        let staked_before = router.pool(deep).unwrap().state().staked_token_amount;
+        assert_eq!(router.execute_best(swap(5_000)), Ok(quote));
+        assert_eq!(
+            router.pool(deep).unwrap().state().staked_token_amount,
+            staked_before + swap(5_000)
+        );
+    }
+
+    #[test]
+    fn it_skips_pools_that_cannot_quote() {
+        let mut router = Router::new();
+        router.register(pool(0, 0, 100));
+        let deep = router.register(pool(50, 50, 1_000_000));
+
+        assert_eq!(router.best_quote(swap(1_000)).unwrap().0, deep);
+    }
+
+    #[test]
+    fn it_returns_no_route_when_no_pool_quotes() {
+        let mut router = Router::new();
+        assert_eq!(
+            router.best_quote(swap(1)),
+            Err(Error::LpPool(LpPoolError::NoRoute))
+        );
+
+        router.register(pool(0, 0, 100));
+        router.register(Box::new(
+            LpPool::init(PoolParams {
+                price: "1".parse().unwrap(),
+                fee_range: FeeRange::new(Fee::ZERO, Fee::ZERO).unwrap(),
+                liquidity_target: TokenAmount::from_lamports(1),
+            })
+            .unwrap(),
+        ));
+        assert_eq!(
+            router.execute_best(swap(1_000)),
+            Err(Error::LpPool(LpPoolError::NoRoute))
+        );
+        assert_eq!(
+            router.split_quote(swap(1_000), 4),
+            Err(Error::LpPool(LpPoolError::NoRoute))
+        );
+    }
+
+    #[test]
+    fn it_splits_order_when_it_improves_output() {
+        let mut router = Router::new();
+        let first = router.register(pool(0, 2_000, 10_000));
+        let second = router.register(pool(0, 2_000, 10_000));
+
+        let (_, single) = router.best_quote(swap(2_000)).unwrap();
+        let splits = router.split_quote(swap(2_000), 2).unwrap();
+
+        assert_eq!(single, TokenAmount::from_lamports(1_920));
+        assert_eq!(
+            splits,
+            vec![
+                Split {
+                    pool: first,
+                    staked_tokens: swap(1_000),
+                    tokens_out: TokenAmount::from_lamports(980),
+                },
+                Split {
+                    pool: second,
+                    staked_tokens: swap(1_000),
+                    tokens_out: TokenAmount::from_lamports(980),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_keeps_single_pool_when_splitting_does_not_help() {
+        let mut router = Router::new();
+        let deep = router.register(pool(50, 50, 1_000_000));
+        router.register(pool(0, 2_000, 10_000));
+
+        let splits = router.split_quote(swap(5_000), 10).unwrap();
+
+        assert_eq!(splits.len(), 1);
+        assert_eq!(splits[0].pool, deep);
+        assert_eq!(splits[0].staked_tokens, swap(5_000));
+    }
+}
@@ -0,0 +1,328 @@
+//! Replays one recorded operation stream against two pool configurations side by side, to
+//! see how fee settings change the outcome.
+
+use core::fmt;
+
+use crate::error::Result;
+use crate::lp_pool::{
+    data::{
+        fee::{Fee, FeeRange},
+        price::Price,
+        token::{LpTokenAmount, SignedTokenAmount, StakedTokenAmount, TokenAmount},
+    },
+    operation::{Operation, PoolOperation},
+    quote::RemovedLiquidity,
+    LpPool, PoolParams,
+};
+
+/// Settings of a pool under comparison. Deserialized, the price is in price points, fees in
+/// hundredths of a basis point and amounts in lamports.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct PoolConfig {
+    pub price: Price,
+    pub min_fee: Fee,
+    pub max_fee: Fee,
+    pub liquidity_target: TokenAmount,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_swap_size: Option<StakedTokenAmount>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub token_cap: Option<TokenAmount>,
+}
+
+impl PoolConfig {
+    /// Pool with this configuration, before any deposit.
+    ///
+    /// # Errors
+    ///
+    /// Fails like `LpPool::init` and the `with_*` builders for an invalid configuration.
+    pub fn build(&self) -> Result<LpPool> {
+        LpPool::init(PoolParams {
+            price: self.price,
+            fee_range: FeeRange::new(self.min_fee, self.max_fee)?,
+            liquidity_target: self.liquidity_target,
+        })?
+        .with_max_swap_size(self.max_swap_size)?
+        .with_token_cap(self.token_cap)
+    }
+}
+
+/// What an executed operation returned.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Output {
+    LpTokens(LpTokenAmount),
+    Removed(RemovedLiquidity),
+    Tokens(TokenAmount),
+    /// `Init` and `UpdatePrice` return nothing.
+    Nothing,
+}
+
+impl Output {
+    /// The amount compared between configurations, the token leg of a removal.
+    fn lamports(self) -> u64 {
+        match self {
+            Output::LpTokens(lp_tokens) => lp_tokens.into(),
+            Output::Removed(removed) => removed.tokens.into(),
+            Output::Tokens(tokens) => tokens.into(),
+            Output::Nothing => 0,
+        }
+    }
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Output::LpTokens(lp_tokens) => write!(f, "{lp_tokens} lp"),
+            Output::Removed(removed) => write!(f, "{removed}"),
+            Output::Tokens(tokens) => write!(f, "{tokens}"),
+            Output::Nothing => write!(f, "-"),
+        }
+    }
+}
+
+/// Which configurations rejected an operation.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Rejected {
+    Neither,
+    OnlyA,
+    OnlyB,
+    Both,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct OperationComparison {
+    /// `PoolOperation::sequence` of the recorded operation.
+    pub sequence: u64,
+    pub operation: Operation,
+    pub outcome_a: Result<Output>,
+    pub outcome_b: Result<Output>,
+    pub rejected: Rejected,
+    /// Output under B less output under A in lamports, `None` unless both executed.
+    pub output_delta: Option<i128>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ScenarioTotals {
+    pub fees_collected: TokenAmount,
+    pub total_value: TokenAmount,
+    pub rejected: u64,
+}
+
+impl ScenarioTotals {
+    fn of(lp_pool: &LpPool, rejected: u64) -> Self {
+        Self {
+            fees_collected: lp_pool.state().fees_collected,
+            total_value: lp_pool.total_value(),
+            rejected,
+        }
+    }
+}
+
+/// Deltas are B less A.
+#[derive(Debug, PartialEq)]
+pub struct ComparisonReport {
+    pub operations: Vec<OperationComparison>,
+    pub a: ScenarioTotals,
+    pub b: ScenarioTotals,
+    pub fees_collected_delta: SignedTokenAmount,
+    pub total_value_delta: SignedTokenAmount,
+    pub rejected_delta: i64,
+}
+
+impl ComparisonReport {
+    /// Operations rejected under one configuration but executed under the other.
+    pub fn diverging(&self) -> impl Iterator<Item = &OperationComparison> {
+        self.operations
+            .iter()
+            .filter(|compared| matches!(compared.rejected, Rejected::OnlyA | Rejected::OnlyB))
+    }
+}
+
+impl fmt::Display for ComparisonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = |outcome: &Result<Output>| match outcome {
+            Ok(output) => output.to_string(),
+            Err(error) => format!("rejected ({})", error.display_chain()),
+        };
+        for compared in &self.operations {
+            write!(
+                f,
+                "#{} {:?}: a {} | b {}",
+                compared.sequence,
+                compared.operation,
+                outcome(&compared.outcome_a),
+                outcome(&compared.outcome_b)
+            )?;
+            match (compared.rejected, compared.output_delta) {
+                (Rejected::OnlyA, _) => writeln!(f, " | rejected only by a")?,
+                (Rejected::OnlyB, _) => writeln!(f, " | rejected only by b")?,
+                (_, Some(delta)) => writeln!(f, " | delta {delta:+}")?,
+                (_, None) => writeln!(f)?,
+            }
+        }
+        writeln!(
+            f,
+            "fees collected: a {} | b {} | delta {}",
+            self.a.fees_collected, self.b.fees_collected, self.fees_collected_delta
+        )?;
+        writeln!(
+            f,
+            "total value: a {} | b {} | delta {}",
+            self.a.total_value, self.b.total_value, self.total_value_delta
+        )?;
+        write!(
+            f,
+            "rejected: a {} | b {} | delta {:+}",
+            self.a.rejected, self.b.rejected, self.rejected_delta
+        )
+    }
+}
+
+/// Runs `ops` against a pool built from each configuration. An `Init` in the stream resets
+/// the pool to its configuration instead of the recorded settings. Rejected operations leave
+/// that pool unchanged and the stream goes on.
+///
+/// # Errors
+///
+/// Fails like `PoolConfig::build` for an invalid configuration, rejected operations are
+/// counted instead.
+pub fn compare_scenarios(
+    ops: &[PoolOperation],
+    config_a: &PoolConfig,
+    config_b: &PoolConfig,
+) -> Result<ComparisonReport> {
+    let mut pool_a = config_a.build()?;
+    let mut pool_b = config_b.build()?;
+    let (mut rejected_a, mut rejected_b) = (0u64, 0u64);
+
+    let operations = ops
+        .iter()
+        .map(|recorded| {
+            let outcome_a = execute(&mut pool_a, config_a, recorded.operation);
+            let outcome_b = execute(&mut pool_b, config_b, recorded.operation);
+            rejected_a += u64::from(outcome_a.is_err());
+            rejected_b += u64::from(outcome_b.is_err());
+            let (rejected, output_delta) = match (&outcome_a, &outcome_b) {
+                (Ok(a), Ok(b)) => (
+                    Rejected::Neither,
+                    Some(i128::from(b.lamports()) - i128::from(a.lamports())),
+                ),
+                (Err(_), Ok(_)) => (Rejected::OnlyA, None),
+                (Ok(_), Err(_)) => (Rejected::OnlyB, None),
+                (Err(_), Err(_)) => (Rejected::Both, None),
+            };
+            OperationComparison {
+                sequence: recorded.sequence,
+                operation: recorded.operation,
+                outcome_a,
+                outcome_b,
+                rejected,
+                output_delta,
+            }
+        })
+        .collect();
+
+    let a = ScenarioTotals::of(&pool_a, rejected_a);
+    let b = ScenarioTotals::of(&pool_b, rejected_b);
+    Ok(ComparisonReport {
+        operations,
+        fees_collected_delta: TokenAmount::signed_diff(b.fees_collected, a.fees_collected),
+        total_value_delta: TokenAmount::signed_diff(b.total_value, a.total_value),
+        rejected_delta: count(b.rejected) - count(a.rejected),
+        a,
+        b,
+    })
+}
+
+/// Rejections are counted per operation, a slice never holds `i64::MAX` of them.
+fn count(rejected: u64) -> i64 {
+    // PANIC SAFETY: see above, the count is bounded by a slice length.
+    i64::try_from(rejected).expect("fewer rejections than operations")
+}
+
+fn execute(lp_pool: &mut LpPool, config: &PoolConfig, operation: Operation) -> Result<Output> {
+    match operation {
+        Operation::Init { .. } => {
+            *lp_pool = config.build()?;
+            Ok(Output::Nothing)
+        }
+        Operation::AddLiquidity(tokens) => lp_pool.add_liquidity(tokens).map(Output::LpTokens),
+        Operation::RemoveLiquidity(lp_tokens) => {
+            let (tokens, staked_tokens) = lp_pool.remove_liquidity(lp_tokens)?;
+            Ok(Output::Removed(RemovedLiquidity {
+                tokens,
+                staked_tokens,
+            }))
+        }
+        Operation::Swap(staked_tokens) => lp_pool.swap(staked_tokens).map(Output::Tokens),
+        Operation::UpdatePrice(price) => {
+            lp_pool.update_price(price)?;
+            Ok(Output::Nothing)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_fee_basis_points: u32) -> PoolConfig {
+        PoolConfig {
+            price: Price::from_raw_points(Price::SCALE),
+            min_fee: Fee::from_basis_points(10),
+            max_fee: Fee::from_basis_points(max_fee_basis_points),
+            liquidity_target: TokenAmount::from_lamports(100_000),
+            max_swap_size: None,
+            token_cap: None,
+        }
+    }
+
+    fn recorded(operations: &[Operation]) -> Vec<PoolOperation> {
+        let mut lp_pool = Some(config(900).build().unwrap());
+        operations
+            .iter()
+            .map(|operation| PoolOperation::execute(&mut lp_pool, *operation, 0).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn it_surfaces_rejections_of_one_config() {
+        let ops = recorded(&[
+            Operation::AddLiquidity(TokenAmount::from_lamports(100_000)),
+            Operation::Swap(StakedTokenAmount::from_lamports(50_000)),
+        ]);
+        let capped = PoolConfig {
+            token_cap: Some(TokenAmount::from_lamports(50_000)),
+            ..config(300)
+        };
+
+        let report = compare_scenarios(&ops, &config(900), &capped).unwrap();
+
+        assert_eq!(report.operations[0].rejected, Rejected::OnlyB);
+        assert_eq!(report.operations[0].output_delta, None);
+        assert_eq!(report.operations[1].rejected, Rejected::OnlyB);
+        assert_eq!(report.diverging().count(), 2);
+        assert_eq!((report.a.rejected, report.b.rejected), (0, 2));
+        assert_eq!(report.rejected_delta, 2);
+        assert_eq!(
+            report.total_value_delta,
+            TokenAmount::signed_diff(report.b.total_value, report.a.total_value)
+        );
+    }
+
+    #[test]
+    fn it_diffs_outputs_of_executed_operations() {
+        let ops = recorded(&[
+            Operation::AddLiquidity(TokenAmount::from_lamports(100_000)),
+            Operation::Swap(StakedTokenAmount::from_lamports(50_000)),
+        ]);
+
+        let report = compare_scenarios(&ops, &config(900), &config(300)).unwrap();
+
+        assert_eq!(report.operations[0].output_delta, Some(0));
+        let delta = report.operations[1].output_delta.unwrap();
+        assert!(delta > 0, "a lower max fee pays out more, got {delta}");
+        assert!(report.fees_collected_delta < SignedTokenAmount::ZERO);
+        assert_eq!(report.diverging().count(), 0);
+    }
+}
@@ -0,0 +1,321 @@
+//! Canned stress scenarios against a pool configuration, reporting how far they pushed it.
+
+use core::fmt;
+
+use super::{compare::PoolConfig, Distribution, Rng};
+use crate::error::Result;
+use crate::lp_pool::{
+    consts::MAX_BASIS_POINTS,
+    data::{
+        fee::Fee,
+        price::Price,
+        token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    },
+    error::InvariantViolation,
+    state::PoolState,
+    LpPool,
+};
+
+/// Liquidity providers funding the pool before every preset.
+const PROVIDERS: u64 = 5;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StressPreset {
+    /// Twelve swaps of 10 to 30% of the funded liquidity each, draining the pool toward zero.
+    MassUnstake,
+    /// A few swaps, then every provider withdraws everything in random order.
+    BankRun,
+    /// Swaps around a 5% price move up or down, then one provider withdraws.
+    PriceShock,
+}
+
+impl StressPreset {
+    pub const ALL: [StressPreset; 3] = [
+        StressPreset::MassUnstake,
+        StressPreset::BankRun,
+        StressPreset::PriceShock,
+    ];
+
+    /// Kebab-case name, as the CLI takes it.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            StressPreset::MassUnstake => "mass-unstake",
+            StressPreset::BankRun => "bank-run",
+            StressPreset::PriceShock => "price-shock",
+        }
+    }
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StressReport {
+    pub preset: StressPreset,
+    /// Highest fee charged by an executed swap or withdrawal.
+    pub max_fee: Fee,
+    /// Lowest `token_amount` once the providers funded the pool.
+    pub min_token_amount: TokenAmount,
+    pub ops_executed: u64,
+    pub ops_rejected: u64,
+    pub solvency: core::result::Result<(), InvariantViolation>,
+    pub final_state: PoolState,
+}
+
+impl fmt::Display for StressReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "preset: {}", self.preset.name())?;
+        writeln!(f, "max fee: {}", self.max_fee)?;
+        writeln!(f, "min token amount: {}", self.min_token_amount)?;
+        writeln!(
+            f,
+            "operations: {} executed | {} rejected",
+            self.ops_executed, self.ops_rejected
+        )?;
+        match &self.solvency {
+            Ok(()) => write!(f, "solvency: ok"),
+            Err(violation) => write!(f, "solvency: {violation}"),
+        }
+    }
+}
+
+/// Funds a pool built from `pool_config` with `PROVIDERS` deposits adding up to about its
+/// liquidity target, then runs `preset`. The same `seed` produces the same report.
+///
+/// # Errors
+///
+/// Fails like `PoolConfig::build` for an invalid configuration, rejected operations are
+/// counted in the report.
+pub fn run(preset: StressPreset, pool_config: &PoolConfig, seed: u64) -> Result<StressReport> {
+    let mut stress = Stress::fund(pool_config, seed)?;
+    match preset {
+        StressPreset::MassUnstake => {
+            for _ in 0..12 {
+                stress.swap(1_000, 3_000);
+            }
+        }
+        StressPreset::BankRun => {
+            for _ in 0..3 {
+                stress.swap(500, 1_000);
+            }
+            for index in stress.shuffled_providers() {
+                stress.withdraw(index);
+            }
+        }
+        StressPreset::PriceShock => {
+            for _ in 0..4 {
+                stress.swap(200, 500);
+            }
+            stress.shock_price(500);
+            for _ in 0..4 {
+                stress.swap(200, 500);
+            }
+            let index = stress.shuffled_providers()[0];
+            stress.withdraw(index);
+        }
+    }
+
+    Ok(StressReport {
+        preset,
+        max_fee: stress.max_fee,
+        min_token_amount: stress.min_token_amount,
+        ops_executed: stress.ops_executed,
+        ops_rejected: stress.ops_rejected,
+        solvency: stress.pool.check_solvency(),
+        final_state: stress.pool.state(),
+    })
+}
+
+struct Stress {
+    rng: Rng,
+    pool: LpPool,
+    positions: Vec<LpTokenAmount>,
+    /// `token_amount` right after funding, swap sizes are shares of it.
+    funded: u64,
+    max_fee: Fee,
+    min_token_amount: TokenAmount,
+    ops_executed: u64,
+    ops_rejected: u64,
+}
+
+impl Stress {
+    fn fund(pool_config: &PoolConfig, seed: u64) -> Result<Self> {
+        let mut stress = Self {
+            rng: Rng(seed),
+            pool: pool_config.build()?,
+            positions: Vec::new(),
+            funded: 0,
+            max_fee: Fee::ZERO,
+            min_token_amount: TokenAmount::from_lamports(0),
+            ops_executed: 0,
+            ops_rejected: 0,
+        };
+        let share = u64::from(pool_config.liquidity_target) / PROVIDERS;
+        for _ in 0..PROVIDERS {
+            let deposit = Distribution::Uniform {
+                min: share / 2,
+                max: share + share / 2,
+            }
+            .sample(&mut stress.rng);
+            let added = stress
+                .pool
+                .add_liquidity(TokenAmount::from_lamports(deposit.max(1)));
+            if let Ok(lp_tokens) = added {
+                stress.positions.push(lp_tokens);
+            }
+            stress.count(added.is_ok());
+        }
+        let funded = stress.pool.state().token_amount;
+        stress.funded = funded.into();
+        stress.min_token_amount = funded;
+        Ok(stress)
+    }
+
+    /// Swaps staked tokens worth `min_bps` to `max_bps` of the funded liquidity.
+    fn swap(&mut self, min_bps: u64, max_bps: u64) {
+        let bps = Distribution::Uniform {
+            min: min_bps,
+            max: max_bps,
+        }
+        .sample(&mut self.rng);
+        let value = u128::from(self.funded) * u128::from(bps) / u128::from(MAX_BASIS_POINTS);
+        let price = u64::from(self.pool.state().price);
+        let staked = value * u128::from(Price::SCALE) / u128::from(price);
+        // below a price of 1 a large pool's share can be more staked tokens than fit, the
+        // pool rejects the swap
+        let staked_tokens =
+            StakedTokenAmount::from_lamports(u64::try_from(staked).unwrap_or(u64::MAX));
+        let swapped = self
+            .pool
+            .quote_swap(staked_tokens)
+            .and_then(|quote| self.pool.execute_quote(&quote).map(|_| quote.fee));
+        self.record(swapped.ok());
+    }
+
+    /// Withdraws the whole position of provider `index`.
+    fn withdraw(&mut self, index: usize) {
+        let lp_tokens = self.positions[index];
+        let removed = self
+            .pool
+            .quote_remove_liquidity(lp_tokens)
+            .and_then(|quote| {
+                self.pool
+                    .execute_remove_liquidity_quote(&quote)
+                    .map(|_| quote.fee)
+            });
+        if removed.is_ok() {
+            self.positions[index] = LpTokenAmount::from_lamports(0);
+        }
+        self.record(removed.ok());
+    }
+
+    /// Moves the price `bps` up or down, the direction drawn from the seed.
+    fn shock_price(&mut self, bps: u64) {
+        let points = u128::from(u64::from(self.pool.state().price));
+        let all = u64::from(MAX_BASIS_POINTS);
+        let factor = if self.rng.next_u64().is_multiple_of(2) {
+            all + bps
+        } else {
+            all - bps
+        };
+        let points = points * u128::from(factor) / u128::from(all);
+        let price = Price::from_raw_points(u64::try_from(points).unwrap_or(u64::MAX).max(1));
+        let updated = self.pool.update_price(price);
+        self.count(updated.is_ok());
+    }
+
+    /// Provider indices in a seeded random order, Fisher-Yates.
+    fn shuffled_providers(&mut self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.positions.len()).collect();
+        for last in (1..order.len()).rev() {
+            let len = last as u64 + 1;
+            // PANIC SAFETY: below `order.len()`, a `usize`.
+            let pick = usize::try_from(self.rng.next_u64() % len).expect("index below len");
+            order.swap(last, pick);
+        }
+        order
+    }
+
+    /// Counts an operation, `fee` is what it charged or `None` if the pool rejected it.
+    fn record(&mut self, fee: Option<Fee>) {
+        if let Some(fee) = fee {
+            self.max_fee = self.max_fee.max(fee);
+        }
+        self.count(fee.is_some());
+        self.min_token_amount = self.min_token_amount.min(self.pool.state().token_amount);
+    }
+
+    fn count(&mut self, executed: bool) {
+        if executed {
+            self.ops_executed += 1;
+        } else {
+            self.ops_rejected += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small() -> PoolConfig {
+        PoolConfig {
+            price: Price::from_raw_points(Price::SCALE),
+            min_fee: Fee::from_basis_points(10),
+            max_fee: Fee::from_basis_points(900),
+            liquidity_target: TokenAmount::from_lamports(1_000_000),
+            max_swap_size: None,
+            token_cap: None,
+        }
+    }
+
+    #[test]
+    fn it_reproduces_report_for_seed() {
+        for preset in StressPreset::ALL {
+            assert_eq!(run(preset, &small(), 11), run(preset, &small(), 11));
+            assert_eq!(StressPreset::from_name(preset.name()), Some(preset));
+        }
+        assert_eq!(StressPreset::from_name("panic"), None);
+    }
+
+    #[test]
+    fn it_drains_pool_under_mass_unstake() {
+        let report = run(StressPreset::MassUnstake, &small(), 3).unwrap();
+
+        assert_eq!(report.ops_executed + report.ops_rejected, 17);
+        assert!(report.ops_rejected > 0);
+        assert!(report.max_fee > Fee::from_basis_points(500));
+        assert!(u64::from(report.min_token_amount) < 1_000_000 / 5);
+        assert_eq!(report.solvency, Ok(()));
+    }
+
+    #[test]
+    fn it_empties_pool_under_bank_run() {
+        let report = run(StressPreset::BankRun, &small(), 3).unwrap();
+
+        assert_eq!(report.ops_executed, 13);
+        assert_eq!(report.ops_rejected, 0);
+        assert_eq!(
+            report.final_state.lp_token_amount,
+            LpTokenAmount::from_lamports(0)
+        );
+        assert_eq!(report.min_token_amount, report.final_state.token_amount);
+        assert_eq!(report.solvency, Ok(()));
+    }
+
+    #[test]
+    fn it_moves_price_five_percent_under_price_shock() {
+        let report = run(StressPreset::PriceShock, &small(), 3).unwrap();
+        let points = u64::from(report.final_state.price);
+
+        assert!(points == Price::SCALE * 95 / 100 || points == Price::SCALE * 105 / 100);
+        assert_eq!(report.ops_executed + report.ops_rejected, 15);
+        assert_eq!(report.ops_rejected, 0);
+        assert!(report.max_fee > Fee::from_basis_points(10));
+        assert!(report.max_fee < Fee::from_basis_points(900));
+        assert_eq!(report.solvency, Ok(()));
+    }
+}
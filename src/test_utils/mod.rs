@@ -0,0 +1,281 @@
+//! Pool fixtures and assertions shared by the unit tests and downstream crates.
+
+use crate::error::Result;
+#[cfg(feature = "std")]
+use crate::lp_pool::bulk::{OpOutput, PoolOp};
+use crate::lp_pool::{
+    data::{
+        fee::{Fee, FeeRange},
+        price::Price,
+        token::{LpTokenAmount, StakedTokenAmount, TokenAmount, LAMPORTS_PER_SOL},
+    },
+    LpPool, Pool, PoolParams,
+};
+
+/// Builder for a pool, optionally funded with a first deposit.
+///
+/// Defaults to a price of 1, no fees, a 1 SOL target and no liquidity.
+#[derive(Debug, Clone)]
+pub struct PoolFixture {
+    price: Price,
+    min_fee: Fee,
+    max_fee: Fee,
+    liquidity_target: TokenAmount,
+    seed: TokenAmount,
+}
+
+/// Pool built by `PoolFixture` with the result of its first deposit.
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub pool: LpPool,
+    pub seeded_tokens: TokenAmount,
+    pub seeded_lp_tokens: LpTokenAmount,
+}
+
+impl Default for PoolFixture {
+    fn default() -> Self {
+        Self {
+            price: Price::from_raw_points(Price::SCALE),
+            min_fee: Fee::ZERO,
+            max_fee: Fee::ZERO,
+            liquidity_target: TokenAmount::from_lamports(LAMPORTS_PER_SOL),
+            seed: TokenAmount::from_lamports(0),
+        }
+    }
+}
+
+impl PoolFixture {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pool price as a decimal, e.g. `"1.5"`.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `price` parses as a `Price`.
+    #[must_use]
+    #[track_caller]
+    pub fn price(self, price: &str) -> Self {
+        let price = price.parse().expect("fixture price is valid");
+        Self { price, ..self }
+    }
+
+    #[must_use]
+    pub fn price_points(self, points: u64) -> Self {
+        Self {
+            price: Price::from_raw_points(points),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn fees(self, min_basis_points: u32, max_basis_points: u32) -> Self {
+        Self {
+            min_fee: Fee::from_basis_points(min_basis_points),
+            max_fee: Fee::from_basis_points(max_basis_points),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn target_sol(self, sol: u64) -> Self {
+        self.target_lamports(sol * LAMPORTS_PER_SOL)
+    }
+
+    #[must_use]
+    pub fn target_lamports(self, lamports: u64) -> Self {
+        Self {
+            liquidity_target: TokenAmount::from_lamports(lamports),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn seeded_with_sol(self, sol: u64) -> Self {
+        self.seeded_with_lamports(sol * LAMPORTS_PER_SOL)
+    }
+
+    #[must_use]
+    pub fn seeded_with_lamports(self, lamports: u64) -> Self {
+        Self {
+            seed: TokenAmount::from_lamports(lamports),
+            ..self
+        }
+    }
+
+    /// Pool with the fixture's settings, funded with the seed deposit if there is one.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `LpPool::init` or the seed deposit rejects the fixture.
+    #[must_use]
+    #[track_caller]
+    pub fn build(self) -> Fixture {
+        let mut pool = LpPool::init(PoolParams {
+            price: self.price,
+            fee_range: FeeRange::new(self.min_fee, self.max_fee).expect("fixture fees are ordered"),
+            liquidity_target: self.liquidity_target,
+        })
+        .expect("fixture pool parameters are valid");
+
+        let seeded_lp_tokens = if self.seed == TokenAmount::from_lamports(0) {
+            LpTokenAmount::from_lamports(0)
+        } else {
+            pool.add_liquidity(self.seed)
+                .expect("fixture seed deposit succeeds")
+        };
+
+        Fixture {
+            pool,
+            seeded_tokens: self.seed,
+            seeded_lp_tokens,
+        }
+    }
+}
+
+/// Asserts the LP supply is backed at least 1:1 by the pool's tokens plus its staked tokens
+/// valued at the current price.
+///
+/// # Panics
+///
+/// Panics when the pool is insolvent or its staked value overflows.
+#[track_caller]
+pub fn assert_solvent(pool: &LpPool) {
+    let state = pool.state();
+    let staked_value = state
+        .price
+        .checked_mul_by_price(state.staked_token_amount.into())
+        .expect("staked tokens value fits u64");
+    let value = u128::from(u64::from(state.token_amount)) + u128::from(staked_value);
+    let lp_supply = u128::from(u64::from(state.lp_token_amount));
+
+    assert!(
+        value >= lp_supply,
+        "pool is insolvent: value {value} < LP supply {lp_supply} ({state:?})"
+    );
+}
+
+/// Asserts the pool's token, staked token and LP token balances, in lamports.
+///
+/// # Panics
+///
+/// Panics when any balance differs.
+#[track_caller]
+pub fn assert_balances(pool: &LpPool, token: u64, staked: u64, lp: u64) {
+    let state = pool.state();
+    assert_eq!(
+        (
+            state.token_amount,
+            state.staked_token_amount,
+            state.lp_token_amount
+        ),
+        (
+            TokenAmount::from_lamports(token),
+            StakedTokenAmount::from_lamports(staked),
+            LpTokenAmount::from_lamports(lp)
+        ),
+        "(token, staked, lp) balances"
+    );
+}
+
+/// An operation a thread ran against a shared pool and the result it got back.
+#[cfg(feature = "std")]
+pub type Observation = (PoolOp, Result<OpOutput>);
+
+/// Whether some interleaving of the threads' `observations`, each thread's in its own
+/// order, replayed one at a time on a copy of `initial` reproduces every observed result.
+/// A wrapper sharing a pool between threads must always pass; one that lets an operation
+/// see the pool half-way through another does not. The search tries every interleaving,
+/// so keep to a handful of operations per thread.
+#[must_use]
+#[cfg(feature = "std")]
+pub fn verify_linearizable(observations: &[Vec<Observation>], initial: &LpPool) -> bool {
+    fn search(observations: &[Vec<Observation>], next: &mut [usize], pool: &LpPool) -> bool {
+        let mut done = true;
+        for thread in 0..observations.len() {
+            let Some((op, observed)) = observations[thread].get(next[thread]) else {
+                continue;
+            };
+            done = false;
+            let mut replayed = pool.clone();
+            if replayed.run_op(*op) != *observed {
+                continue;
+            }
+            next[thread] += 1;
+            let found = search(observations, next, &replayed);
+            next[thread] -= 1;
+            if found {
+                return true;
+            }
+        }
+        done
+    }
+
+    search(observations, &mut vec![0; observations.len()], initial)
+}
+
+/// Single operation of a scenario, replayed against any `Pool`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Step {
+    AddLiquidity(TokenAmount),
+    RemoveLiquidity(LpTokenAmount),
+    Swap(StakedTokenAmount),
+}
+
+impl Step {
+    /// Runs the step, returning the tokens and staked tokens it paid out. Minted LP tokens
+    /// are reported as tokens.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the pool method the step runs.
+    pub fn run(self, pool: &mut dyn Pool) -> Result<(TokenAmount, StakedTokenAmount)> {
+        let no_staked_tokens = StakedTokenAmount::from_lamports(0);
+        match self {
+            Step::AddLiquidity(tokens) => pool.add_liquidity(tokens).map(|lp_tokens| {
+                (
+                    TokenAmount::from_lamports(lp_tokens.into()),
+                    no_staked_tokens,
+                )
+            }),
+            Step::RemoveLiquidity(lp_tokens) => pool.remove_liquidity(lp_tokens),
+            Step::Swap(staked_tokens) => pool
+                .swap(staked_tokens)
+                .map(|tokens| (tokens, no_staked_tokens)),
+        }
+    }
+}
+
+/// Scenarios every `Pool` implementation is expected to get through, failing steps included.
+pub const SCENARIOS: &[(&str, &[Step])] = &[
+    (
+        "deposits and withdrawals",
+        &[
+            Step::AddLiquidity(TokenAmount::from_lamports(10_000)),
+            Step::AddLiquidity(TokenAmount::from_lamports(2_500)),
+            Step::RemoveLiquidity(LpTokenAmount::from_lamports(4_000)),
+            Step::RemoveLiquidity(LpTokenAmount::from_lamports(1)),
+        ],
+    ),
+    (
+        "swaps draining liquidity",
+        &[
+            Step::AddLiquidity(TokenAmount::from_lamports(10_000)),
+            Step::Swap(StakedTokenAmount::from_lamports(1_000)),
+            Step::Swap(StakedTokenAmount::from_lamports(3_000)),
+            Step::Swap(StakedTokenAmount::from_lamports(10_000)),
+            Step::RemoveLiquidity(LpTokenAmount::from_lamports(2_000)),
+        ],
+    ),
+    (
+        "invalid operations",
+        &[
+            Step::Swap(StakedTokenAmount::from_lamports(1)),
+            Step::RemoveLiquidity(LpTokenAmount::from_lamports(1)),
+            Step::AddLiquidity(TokenAmount::from_lamports(100)),
+            Step::RemoveLiquidity(LpTokenAmount::from_lamports(1_000)),
+        ],
+    ),
+];
@@ -0,0 +1,131 @@
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::lp_pool::{
+    data::{
+        fee::{Fee, FeeRange},
+        price::Price,
+        token::{LpTokenAmount, StakedTokenAmount, TokenAmount},
+    },
+    LpPool, PoolParams,
+};
+
+#[allow(
+    clippy::needless_pass_by_value,
+    reason = "passed to `map_err`, which hands over the error"
+)]
+fn to_js_error(error: Error) -> JsValue {
+    JsValue::from_str(&error.display_chain().to_string())
+}
+
+#[wasm_bindgen]
+pub struct WasmPool {
+    pool: LpPool,
+}
+
+#[wasm_bindgen]
+impl WasmPool {
+    /// Pool at `price`, with fees in basis points and a target in lamports.
+    ///
+    /// # Errors
+    ///
+    /// Throws for an unparsable price or parameters `LpPool::init` rejects.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        price: &str,
+        min_fee_bps: u32,
+        max_fee_bps: u32,
+        target_lamports: u64,
+    ) -> Result<WasmPool, JsValue> {
+        let price: Price = price.parse().map_err(to_js_error)?;
+        let fee_range = FeeRange::new(
+            Fee::from_basis_points(min_fee_bps),
+            Fee::from_basis_points(max_fee_bps),
+        )
+        .map_err(to_js_error)?;
+        let pool = LpPool::init(PoolParams {
+            price,
+            fee_range,
+            liquidity_target: TokenAmount::from_lamports(target_lamports),
+        })
+        .map_err(to_js_error)?;
+        Ok(Self { pool })
+    }
+
+    /// Lamports a swap of `staked_lamports` pays out, without changing the pool.
+    ///
+    /// # Errors
+    ///
+    /// Throws the error `LpPool::quote_swap` returns.
+    pub fn quote_swap(&self, staked_lamports: u64) -> Result<u64, JsValue> {
+        self.pool
+            .quote_swap(StakedTokenAmount::from_lamports(staked_lamports))
+            .map(|quote| quote.amount_out.into())
+            .map_err(to_js_error)
+    }
+
+    /// Deposits `lamports`, returning the LP lamports minted.
+    ///
+    /// # Errors
+    ///
+    /// Throws the error `LpPool::add_liquidity` returns.
+    pub fn add_liquidity(&mut self, lamports: u64) -> Result<u64, JsValue> {
+        self.pool
+            .add_liquidity(TokenAmount::from_lamports(lamports))
+            .map(u64::from)
+            .map_err(to_js_error)
+    }
+
+    /// Returns `[tokens, staked_tokens]` paid out for the burned LP tokens.
+    ///
+    /// # Errors
+    ///
+    /// Throws the error `LpPool::remove_liquidity` returns.
+    pub fn remove_liquidity(&mut self, lp_lamports: u64) -> Result<Vec<u64>, JsValue> {
+        let (tokens, staked_tokens) = self
+            .pool
+            .remove_liquidity(LpTokenAmount::from_lamports(lp_lamports))
+            .map_err(to_js_error)?;
+        Ok(vec![tokens.into(), staked_tokens.into()])
+    }
+
+    /// `LpPool::state` as JSON.
+    ///
+    /// # Errors
+    ///
+    /// Throws when the state cannot be serialized.
+    pub fn state_json(&self) -> Result<String, JsValue> {
+        serde_json::to_string(&self.pool.state()).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+}
+
+/// Tests that never build a `JsValue` also run natively, the rest need a wasm runner.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn it_quotes_swap_across_the_boundary() {
+        let mut pool = WasmPool::new("1", 100, 1000, 10).unwrap();
+        pool.add_liquidity(1_000_000).unwrap();
+
+        assert_eq!(pool.quote_swap(50).unwrap(), 50);
+        assert!(pool
+            .state_json()
+            .unwrap()
+            .contains(r#""token_amount":1000000"#));
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn it_surfaces_errors_as_display_strings() {
+        let error = WasmPool::new("1.5", 101, 100, 10).err().unwrap();
+        assert!(error
+            .as_string()
+            .unwrap()
+            .contains("MinFeeGreaterThanMaxFee"));
+    }
+}
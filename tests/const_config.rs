@@ -0,0 +1,37 @@
+//! Pool configuration built in `const` contexts. Everything here is evaluated by the compiler,
+//! so a helper losing its `const` fails the build rather than a test.
+
+use liquidity_pool::calc::checked_bps_of;
+use liquidity_pool::prelude::*;
+use liquidity_pool::{LAMPORTS_PER_SOL, MAX_BASIS_POINTS};
+
+const PARAMS: PoolParams = PoolParams {
+    price: Price::checked_from_points(150).unwrap(),
+    fee_range: FeeRange::checked_new(Fee::from_basis_points(10), Fee::from_basis_points(900))
+        .unwrap(),
+    liquidity_target: TokenAmount::from_lamports(90 * LAMPORTS_PER_SOL),
+};
+
+const RESTORED_PRICE: Price = Price::from_raw_points(150);
+
+const _: () = {
+    assert!(Fee::from_basis_points(MAX_BASIS_POINTS).is_valid());
+    assert!(!Fee::from_basis_points(MAX_BASIS_POINTS + 1).is_valid());
+    assert!(FeeRange::checked_new(Fee::from_basis_points(2), Fee::from_basis_points(1)).is_none());
+    assert!(PARAMS.fee_range.delta_bps() == 890);
+    assert!(PARAMS.fee_range.min().centibps == 1_000);
+    assert!(Price::checked_from_points(0).is_none());
+    assert!(matches!(
+        checked_bps_of(LAMPORTS_PER_SOL, 25),
+        Some(2_500_000)
+    ));
+    assert!(checked_bps_of(u64::MAX, MAX_BASIS_POINTS + 1).is_none());
+};
+
+#[test]
+fn it_initializes_pool_from_const_params() {
+    let lp_pool = liquidity_pool::lp_pool::LpPool::init(PARAMS).unwrap();
+
+    assert_eq!(lp_pool.state().price, PARAMS.price);
+    assert_eq!(lp_pool.state().price, RESTORED_PRICE);
+}
@@ -0,0 +1,114 @@
+//! Runs operations from several threads against a pool shared behind a lock, as
+//! `examples/server.rs` does, and checks the results with `verify_linearizable`. A wrapper
+//! quoting outside the lock shows the check catches lock-scope bugs.
+
+#![cfg(all(feature = "std", feature = "test-utils"))]
+
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+
+use liquidity_pool::lp_pool::bulk::{OpOutput, PoolOp};
+use liquidity_pool::prelude::*;
+use liquidity_pool::test_utils::{verify_linearizable, Observation, PoolFixture};
+
+fn pool() -> LpPool {
+    PoolFixture::new()
+        .fees(10, 900)
+        .target_lamports(100_000)
+        .seeded_with_lamports(100_000)
+        .build()
+        .pool
+}
+
+fn swap(lamports: u64) -> PoolOp {
+    PoolOp::Swap(StakedTokenAmount::from_lamports(lamports))
+}
+
+/// Starts every thread at once and collects what each observed, in thread order.
+fn run_threads<W: Send + Sync + 'static>(
+    wrapper: W,
+    threads: Vec<Vec<PoolOp>>,
+    run_op: fn(&W, PoolOp) -> Result<OpOutput>,
+) -> Vec<Vec<Observation>> {
+    let wrapper = Arc::new(wrapper);
+    let start = Arc::new(Barrier::new(threads.len()));
+    let handles: Vec<_> = threads
+        .into_iter()
+        .map(|ops| {
+            let wrapper = Arc::clone(&wrapper);
+            let start = Arc::clone(&start);
+            thread::spawn(move || {
+                start.wait();
+                ops.into_iter()
+                    .map(|op| (op, run_op(&wrapper, op)))
+                    .collect()
+            })
+        })
+        .collect();
+    handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect()
+}
+
+fn locked_run_op(pool: &Mutex<LpPool>, op: PoolOp) -> Result<OpOutput> {
+    pool.lock().unwrap().run_op(op)
+}
+
+#[test]
+fn it_linearizes_operations_on_locked_pool() {
+    for _ in 0..20 {
+        let observations = run_threads(
+            Mutex::new(pool()),
+            vec![
+                vec![swap(1_000), swap(2_000), swap(1_500)],
+                vec![
+                    PoolOp::AddLiquidity(TokenAmount::from_lamports(5_000)),
+                    swap(3_000),
+                    PoolOp::RemoveLiquidity(LpTokenAmount::from_lamports(4_000)),
+                ],
+                vec![
+                    PoolOp::RemoveLiquidity(LpTokenAmount::from_lamports(2_000)),
+                    swap(500),
+                    PoolOp::AddLiquidity(TokenAmount::from_lamports(1_000)),
+                ],
+            ],
+            locked_run_op,
+        );
+
+        assert!(verify_linearizable(&observations, &pool()));
+    }
+}
+
+/// Quotes a swap under the lock, releases it and swaps under a second lock, answering with
+/// the quote. `quoted` holds every thread between the two, so each one quotes the same
+/// pool.
+struct QuoteOutsideLock {
+    pool: Mutex<LpPool>,
+    quoted: Barrier,
+}
+
+fn quote_outside_lock_run_op(wrapper: &QuoteOutsideLock, op: PoolOp) -> Result<OpOutput> {
+    let PoolOp::Swap(staked_tokens) = op else {
+        return locked_run_op(&wrapper.pool, op);
+    };
+    let quote = wrapper.pool.lock().unwrap().quote_swap(staked_tokens)?;
+    wrapper.quoted.wait();
+    wrapper.pool.lock().unwrap().swap(staked_tokens)?;
+    Ok(OpOutput::Tokens(quote.amount_out))
+}
+
+#[test]
+fn it_rejects_observations_of_swap_quoted_outside_lock() {
+    let observations = run_threads(
+        QuoteOutsideLock {
+            pool: Mutex::new(pool()),
+            quoted: Barrier::new(2),
+        },
+        vec![vec![swap(10_000)], vec![swap(10_000)]],
+        quote_outside_lock_run_op,
+    );
+
+    assert_eq!(observations[0], observations[1]);
+    assert!(!verify_linearizable(&observations, &pool()));
+}
@@ -0,0 +1,199 @@
+//! Checks the pool on Marinade liquidity pool scenarios in
+//! `tests/fixtures/marinade_vectors.csv`, append a row there to add a case. The expected
+//! results are derived from Marinade's formulas, not taken from its test output.
+
+use liquidity_pool::lp_pool::{
+    data::fee::WithdrawalFeeMode,
+    store::{MemoryStore, PoolStore},
+    DustMode,
+};
+use liquidity_pool::prelude::*;
+
+const VECTORS: &str = include_str!("fixtures/marinade_vectors.csv");
+
+#[derive(Debug)]
+struct Vector<'a> {
+    name: &'a str,
+    op: &'a str,
+    state: PoolState,
+    amount: u64,
+    fee: Option<Fee>,
+    whole_bps: Option<u32>,
+    out: Option<u64>,
+    staked_out: Option<u64>,
+}
+
+fn lamports(field: &str) -> Option<u64> {
+    (field != "-").then(|| {
+        field
+            .replace('_', "")
+            .parse()
+            .unwrap_or_else(|_| panic!("`{field}` is not an amount"))
+    })
+}
+
+/// Basis points with up to two decimals, like `210.01`.
+fn fee(field: &str) -> Option<Fee> {
+    (field != "-").then(|| {
+        let (whole, fraction) = field.split_once('.').unwrap_or((field, ""));
+        assert!(fraction.len() <= 2, "`{field}` has more than two decimals");
+        let digits = |digits: &str| {
+            digits
+                .parse::<u32>()
+                .unwrap_or_else(|_| panic!("`{field}` is not a fee in basis points"))
+        };
+        Fee::from_centibps(
+            digits(whole) * Fee::CENTIBPS_PER_BASIS_POINT + digits(&format!("{fraction:0<2}")),
+        )
+    })
+}
+
+fn vectors() -> Vec<Vector<'static>> {
+    let mut lines = VECTORS
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+    let header = lines.next().expect("vector file has a header");
+    assert_eq!(
+        header,
+        "name,op,price,target,min_fee_bps,max_fee_bps,tokens,staked,lp_supply,amount,fee_bps,whole_bps,out,staked_out"
+    );
+
+    lines
+        .map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+            let [name, op, price, target, min_fee, max_fee, tokens, staked, lp_supply, amount, fee_bps, whole_bps, out, staked_out] =
+                fields[..]
+            else {
+                panic!("vector `{line}` does not have 14 fields");
+            };
+            let bps = |field| {
+                lamports(field).map(|bps| u32::try_from(bps).expect("basis points fit u32"))
+            };
+            let price: Price = price.parse().expect("vector price is valid");
+
+            Vector {
+                name,
+                op,
+                state: PoolState {
+                    price,
+                    min_fee: Fee::from_basis_points(bps(min_fee).unwrap()),
+                    max_fee: Fee::from_basis_points(bps(max_fee).unwrap()),
+                    liquidity_target: TokenAmount::from_lamports(lamports(target).unwrap()),
+                    token_amount: TokenAmount::from_lamports(lamports(tokens).unwrap()),
+                    staked_token_amount: StakedTokenAmount::from_lamports(
+                        lamports(staked).unwrap(),
+                    ),
+                    lp_token_amount: LpTokenAmount::from_lamports(lamports(lp_supply).unwrap()),
+                    fees_collected: TokenAmount::from_lamports(0),
+                    referral_fees_owed: TokenAmount::from_lamports(0),
+                    dust: TokenAmount::from_lamports(0),
+                    protocol_fees_owed: TokenAmount::from_lamports(0),
+                },
+                amount: lamports(amount).unwrap(),
+                fee: fee(fee_bps),
+                whole_bps: bps(whole_bps),
+                out: lamports(out),
+                staked_out: lamports(staked_out),
+            }
+        })
+        .collect()
+}
+
+/// Pool in the vector's state, withdrawing without a fee and paying out zero outputs like
+/// Marinade.
+fn pool(vector: &Vector) -> LpPool {
+    let mut store = MemoryStore::new();
+    store.save(&vector.state).unwrap();
+    LpPool::restore_from(&store)
+        .unwrap()
+        .expect("state was saved")
+        .with_withdrawal_fee(Some(Fee::ZERO), WithdrawalFeeMode::Replace)
+        .unwrap()
+        .with_dust_mode(DustMode::AllowZeroOutput, TokenAmount::from_lamports(0))
+}
+
+/// Checks the vector's fee, and that rounding it up to a whole basis point gives what
+/// Marinade's basis point formula charges.
+fn assert_fee(pool: &LpPool, vector: &Vector, available_after: u64) {
+    let name = vector.name;
+    let expected = vector.fee.unwrap();
+    assert_eq!(
+        pool.calculate_fee(TokenAmount::from_lamports(available_after)),
+        Ok(expected),
+        "{name}"
+    );
+    assert_eq!(
+        Some(expected.centibps.div_ceil(Fee::CENTIBPS_PER_BASIS_POINT)),
+        vector.whole_bps,
+        "{name}"
+    );
+}
+
+#[test]
+fn it_matches_marinade_vectors() {
+    let vectors = vectors();
+    assert!(!vectors.is_empty());
+
+    for vector in &vectors {
+        let mut pool = pool(vector);
+        let name = vector.name;
+        match vector.op {
+            "fee" => assert_fee(&pool, vector, vector.amount),
+            "swap" => {
+                let staked_tokens = StakedTokenAmount::from_lamports(vector.amount);
+                let value = u64::from(vector.state.price) * vector.amount / Price::SCALE;
+                assert_fee(&pool, vector, u64::from(vector.state.token_amount) - value);
+                assert_eq!(
+                    pool.swap(staked_tokens),
+                    Ok(TokenAmount::from_lamports(vector.out.unwrap())),
+                    "{name}"
+                );
+            }
+            "add" => {
+                assert_eq!(
+                    pool.add_liquidity(TokenAmount::from_lamports(vector.amount)),
+                    Ok(LpTokenAmount::from_lamports(vector.out.unwrap())),
+                    "{name}"
+                );
+                assert_eq!(pool.state().fees_collected, vector.state.fees_collected);
+            }
+            "remove" => {
+                assert_eq!(
+                    pool.remove_liquidity(LpTokenAmount::from_lamports(vector.amount)),
+                    Ok((
+                        TokenAmount::from_lamports(vector.out.unwrap()),
+                        StakedTokenAmount::from_lamports(vector.staked_out.unwrap())
+                    )),
+                    "{name}"
+                );
+            }
+            op => panic!("{name}: unknown op `{op}`"),
+        }
+        assert_eq!(pool.check_solvency(), Ok(()), "{name}");
+    }
+}
+
+#[cfg(feature = "audit")]
+#[test]
+fn it_never_rounds_toward_users_on_marinade_vectors() {
+    for vector in &vectors() {
+        let mut pool = pool(vector);
+        match vector.op {
+            "swap" => {
+                pool.swap(StakedTokenAmount::from_lamports(vector.amount))
+                    .unwrap();
+            }
+            "add" => {
+                pool.add_liquidity(TokenAmount::from_lamports(vector.amount))
+                    .unwrap();
+            }
+            "remove" => {
+                pool.remove_liquidity(LpTokenAmount::from_lamports(vector.amount))
+                    .unwrap();
+            }
+            _ => continue,
+        }
+        let report = pool.audit_report();
+        assert_eq!(report.toward_user(), 0, "{}: {report:?}", vector.name);
+    }
+}
@@ -0,0 +1,267 @@
+//! Calls every public `LpPool` method with adversarial amounts on pools at the edges of
+//! their configuration, asserting that failures come back as errors and no panic escapes.
+//! Without `strict-math` overflow panics by design, so the battery needs it.
+
+#![cfg(all(feature = "std", feature = "strict-math"))]
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use liquidity_pool::lp_pool::{
+    clock::ManualClock, events::EventFilter, operation::Operation, tiers::ProviderId,
+    twap::PriceCheckpoint, DustMode,
+};
+use liquidity_pool::prelude::*;
+use liquidity_pool::LAMPORTS_PER_SOL;
+
+const AMOUNTS: [u64; 8] = [
+    0,
+    1,
+    2,
+    999,
+    LAMPORTS_PER_SOL,
+    u64::MAX / 2,
+    u64::MAX - 1,
+    u64::MAX,
+];
+
+const PRICE_POINTS: [u64; 4] = [1, 100, 137, u64::MAX];
+
+const FEES_BPS: [(u32, u32); 3] = [(0, 0), (10, 900), (10_000, 10_000)];
+
+const TARGETS: [u64; 2] = [1, u64::MAX];
+
+/// Funding the pools, and swapping into them once funded.
+const SEED_AMOUNTS: [u64; 3] = [1, LAMPORTS_PER_SOL, u64::MAX];
+
+fn price(points: u64) -> Price {
+    Price::try_from_points(points).unwrap()
+}
+
+/// Pools reachable through the public API: every price, fee range and target, empty,
+/// funded, and funded with staked tokens swapped in and repriced to either end.
+fn pools() -> Vec<LpPool> {
+    let mut pools = Vec::new();
+    for points in PRICE_POINTS {
+        for (min_bps, max_bps) in FEES_BPS {
+            for target in TARGETS {
+                let empty = LpPool::init(PoolParams {
+                    price: price(points),
+                    fee_range: FeeRange::new(
+                        Fee::from_basis_points(min_bps),
+                        Fee::from_basis_points(max_bps),
+                    )
+                    .unwrap(),
+                    liquidity_target: TokenAmount::from_lamports(target),
+                })
+                .unwrap();
+                pools.push(empty.clone());
+                for deposit in SEED_AMOUNTS {
+                    let mut funded = empty.clone();
+                    if funded
+                        .add_liquidity(TokenAmount::from_lamports(deposit))
+                        .is_err()
+                    {
+                        continue;
+                    }
+                    pools.push(funded.clone());
+                    for staked in SEED_AMOUNTS {
+                        let mut swapped = funded.clone();
+                        if swapped
+                            .swap(StakedTokenAmount::from_lamports(staked))
+                            .is_err()
+                        {
+                            continue;
+                        }
+                        for repriced in [1, u64::MAX] {
+                            let mut repriced_pool = swapped.clone();
+                            repriced_pool.update_price(price(repriced)).unwrap();
+                            pools.push(repriced_pool);
+                        }
+                        pools.push(swapped);
+                    }
+                }
+            }
+        }
+    }
+    pools
+}
+
+/// Runs `call` on a copy of `lp_pool`, failing the test if it panics.
+fn assert_no_panic(lp_pool: &LpPool, method: &str, lamports: u64, call: impl FnOnce(&mut LpPool)) {
+    let mut copy = lp_pool.clone();
+    let result = catch_unwind(AssertUnwindSafe(|| call(&mut copy)));
+    assert!(
+        result.is_ok(),
+        "{method}({lamports}) panicked on {:?}",
+        lp_pool.state()
+    );
+}
+
+#[test]
+fn it_returns_errors_instead_of_panicking() {
+    let pools = pools();
+    assert!(pools.len() > 100);
+
+    for lp_pool in &pools {
+        assert_no_panic(lp_pool, "views", 0, |lp_pool| {
+            let _ = lp_pool.state();
+            let _ = lp_pool.total_value();
+            let _ = lp_pool.lp_price();
+            let _ = lp_pool.lp_attributable_value();
+            let _ = lp_pool.max_swap_in();
+            let _ = lp_pool.fee_schedule(&[
+                TokenAmount::from_lamports(1),
+                TokenAmount::from_lamports(LAMPORTS_PER_SOL),
+                TokenAmount::from_lamports(u64::MAX),
+            ]);
+            let _ = lp_pool.check_solvency();
+            let _ = lp_pool.view().utilization_bps();
+            let _ = lp_pool.epoch_stats();
+            let _ = lp_pool.status_line();
+        });
+        assert_no_panic(lp_pool, "remove_all_liquidity", 0, |lp_pool| {
+            let _ = lp_pool.remove_all_liquidity();
+        });
+        assert_no_panic(lp_pool, "close", 0, |lp_pool| {
+            let _ = lp_pool.clone().close();
+        });
+        assert_no_panic(lp_pool, "collect_dust", 0, |lp_pool| {
+            let _ = lp_pool.collect_dust();
+            let _ = lp_pool.withdraw_referral_fees();
+            let _ = lp_pool.claim_protocol_fees();
+        });
+
+        for lamports in AMOUNTS {
+            let tokens = TokenAmount::from_lamports(lamports);
+            let staked = StakedTokenAmount::from_lamports(lamports);
+            let lp_tokens = LpTokenAmount::from_lamports(lamports);
+
+            assert_no_panic(lp_pool, "add_liquidity", lamports, |lp_pool| {
+                let _ = lp_pool.add_liquidity(tokens);
+            });
+            assert_no_panic(lp_pool, "deposit_bonus", lamports, |lp_pool| {
+                if lp_pool.set_incentive_bps_at_empty(Some(10_000)).is_ok() {
+                    let _ = lp_pool.deposit_bonus(tokens);
+                    let _ = lp_pool.add_liquidity(tokens);
+                }
+            });
+            assert_no_panic(
+                lp_pool,
+                "execute_add_liquidity_quote",
+                lamports,
+                |lp_pool| {
+                    if let Ok(quote) = lp_pool.quote_add_liquidity(tokens) {
+                        let _ = lp_pool.execute_add_liquidity_quote(&quote);
+                    }
+                },
+            );
+            assert_no_panic(lp_pool, "remove_liquidity", lamports, |lp_pool| {
+                let _ = lp_pool.remove_liquidity(lp_tokens);
+            });
+            assert_no_panic(lp_pool, "remove_liquidity_for", lamports, |lp_pool| {
+                let _ = lp_pool.add_liquidity_for(ProviderId(1), tokens);
+                let _ = lp_pool.remove_liquidity_for(ProviderId(1), lp_tokens);
+            });
+            assert_no_panic(
+                lp_pool,
+                "execute_remove_liquidity_quote",
+                lamports,
+                |lp_pool| {
+                    if let Ok(quote) = lp_pool.quote_remove_liquidity(lp_tokens) {
+                        let _ = lp_pool.execute_remove_liquidity_quote(&quote);
+                    }
+                },
+            );
+            assert_no_panic(lp_pool, "swap", lamports, |lp_pool| {
+                let _ = lp_pool.swap(staked);
+            });
+            assert_no_panic(lp_pool, "apply_batch", lamports, |lp_pool| {
+                let _ = lp_pool.apply_batch(&[
+                    Operation::Swap(staked),
+                    Operation::UpdatePrice(price(lamports.max(1))),
+                    Operation::AddLiquidity(tokens),
+                    Operation::RemoveLiquidity(lp_tokens),
+                ]);
+            });
+            assert_no_panic(lp_pool, "execute_quote", lamports, |lp_pool| {
+                if let Ok(quote) = lp_pool.quote_swap(staked) {
+                    let _ = lp_pool.execute_quote(&quote);
+                }
+            });
+            assert_no_panic(lp_pool, "swap_with_referral", lamports, |lp_pool| {
+                let _ = lp_pool.swap_with_referral(staked, Fee::from_basis_points(100));
+            });
+            assert_no_panic(lp_pool, "swap_partial", lamports, |lp_pool| {
+                let _ = lp_pool.swap_partial(staked);
+            });
+            assert_no_panic(lp_pool, "swap_with_min_out", lamports, |lp_pool| {
+                let _ = lp_pool.swap_with_min_out(staked, tokens);
+            });
+            assert_no_panic(lp_pool, "post_swap_token_amount", lamports, |lp_pool| {
+                let _ = lp_pool.post_swap_token_amount(staked);
+            });
+            assert_no_panic(lp_pool, "share_bps", lamports, |lp_pool| {
+                let _ = lp_pool.share_bps(lp_tokens);
+                let _ = lp_pool.amounts_for_share_bps(u32::try_from(lamports).unwrap_or(u32::MAX));
+            });
+            assert_no_panic(lp_pool, "calculate_fee", lamports, |lp_pool| {
+                let _ = lp_pool.calculate_fee(tokens);
+                let _ = lp_pool.view().fee_at(tokens);
+                let _ = lp_pool.current_fee();
+                let _ = lp_pool.tokens_until_fee(Fee::from_centibps(
+                    u32::try_from(lamports).unwrap_or(u32::MAX),
+                ));
+            });
+            assert_no_panic(lp_pool, "reconcile", lamports, |lp_pool| {
+                let _ = lp_pool.reconcile(tokens, TokenAmount::from_lamports(u64::MAX - lamports));
+            });
+            assert_no_panic(lp_pool, "update_price", lamports, |lp_pool| {
+                lp_pool.subscribe(EventFilter::ALL, |_| {});
+                let _ = lp_pool.update_price(price(lamports.max(1)));
+                let _ = lp_pool.swap(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL));
+                let _ = lp_pool.remove_all_liquidity();
+            });
+            assert_no_panic(lp_pool, "twap_between", lamports, |lp_pool| {
+                *lp_pool = lp_pool.clone().with_clock(ManualClock::new(lamports));
+                let _ = lp_pool.update_price(price(lamports.max(1)));
+                let from = lp_pool.checkpoint();
+                let _ = lp_pool.update_price_at(price(lamports.max(1)), lamports);
+                let _ = lp_pool.twap_between(from, lp_pool.checkpoint());
+                let _ = lp_pool.twap_between(
+                    from,
+                    PriceCheckpoint {
+                        cumulative: u128::from(lamports) << 64,
+                        timestamp: from.timestamp.saturating_add(1),
+                    },
+                );
+            });
+            assert_no_panic(lp_pool, "limits", lamports, |lp_pool| {
+                let _ = lp_pool.set_max_swap_size(Some(staked));
+                let _ = lp_pool.set_token_cap(Some(tokens));
+                let _ = lp_pool.set_min_reserve(Some(tokens));
+                lp_pool.set_surplus_threshold(Some(tokens));
+                lp_pool.set_dust_mode(DustMode::RejectZeroOutput, tokens);
+                let _ = lp_pool.swap(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL));
+                let _ = lp_pool.add_liquidity(TokenAmount::from_lamports(LAMPORTS_PER_SOL));
+                let _ = lp_pool.remove_all_liquidity();
+            });
+            assert_no_panic(lp_pool, "reconfigure", lamports, |lp_pool| {
+                let _ = lp_pool.reconfigure(PoolParams {
+                    price: lp_pool.state().price,
+                    fee_range: FeeRange::new(Fee::ZERO, Fee::MAX).unwrap(),
+                    liquidity_target: tokens,
+                });
+            });
+            assert_no_panic(lp_pool, "delayed_unstake", lamports, |lp_pool| {
+                if let Ok(ticket) = lp_pool.request_delayed_unstake(staked, lamports) {
+                    let _ = lp_pool.claim_ticket(ticket, u64::MAX);
+                }
+            });
+            assert_no_panic(lp_pool, "advance_epoch", lamports, |lp_pool| {
+                let fee = Fee::from_basis_points(u32::try_from(lamports).unwrap_or(u32::MAX));
+                let _ = lp_pool.set_management_fee(fee);
+                let _ = lp_pool.advance_epoch();
+            });
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! The prelude, the flat `lp_pool::` paths and the defining modules name the same types, so
+//! code using any of them keeps compiling.
+
+use liquidity_pool::{error, lp_pool, prelude};
+
+#[test]
+fn it_resolves_flat_and_deep_paths_to_same_types() {
+    let fee: lp_pool::data::fee::Fee = prelude::Fee::ZERO;
+    let _: lp_pool::Fee = fee;
+    let fee_range: lp_pool::data::fee::FeeRange = prelude::FeeRange::new(fee, fee).unwrap();
+    let price: lp_pool::data::price::Price = prelude::Price::try_from_points(1).unwrap();
+    let tokens: lp_pool::data::token::TokenAmount = prelude::TokenAmount::from_lamports(1);
+    let _: lp_pool::data::token::StakedTokenAmount = lp_pool::StakedTokenAmount::from_lamports(1);
+    let _: lp_pool::data::token::LpTokenAmount = lp_pool::LpTokenAmount::from_lamports(1);
+    let lp_pool: prelude::LpPool = lp_pool::LpPool::init(prelude::PoolParams {
+        price,
+        fee_range,
+        liquidity_target: tokens,
+    })
+    .unwrap();
+    let _: lp_pool::state::PoolState = <prelude::LpPool as prelude::Pool>::state(&lp_pool);
+    let error: error::Error = prelude::Error::LpPool(prelude::LpPoolError::ZeroAmount);
+    let _: lp_pool::error::Error = lp_pool::LpPoolError::ZeroAmount;
+    let _: error::Result<()> = prelude::Result::<()>::Err(error);
+}
@@ -0,0 +1,132 @@
+//! Replays the minimized operation sequences in `tests/fixtures/regressions`, each of which
+//! once panicked or broke an invariant. Drop a file there to add a case: `operations` are
+//! replayed in order and `error`, if present, is the code of the error the replay must stop
+//! with.
+
+#![cfg(feature = "serde")]
+
+use std::{fs, path::Path};
+
+use liquidity_pool::lp_pool::operation::{Operation, PoolOperation};
+use liquidity_pool::prelude::*;
+use serde::Deserialize;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/regressions");
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Regression {
+    #[allow(dead_code)]
+    description: String,
+    operations: Vec<Operation>,
+    #[serde(default)]
+    error: Option<u32>,
+}
+
+fn read_fixture(path: &Path) -> Regression {
+    let json = fs::read_to_string(path)
+        .unwrap_or_else(|error| panic!("reading {}: {error}", path.display()));
+    serde_json::from_str(&json)
+        .unwrap_or_else(|error| panic!("parsing {}: {error}", path.display()))
+}
+
+/// Replays the operations of the fixture at `path`, stopping at the first that fails.
+/// Panics if the file cannot be read or parsed.
+fn replay_fixture(path: impl AsRef<Path>) -> Result<LpPool> {
+    let mut lp_pool = None;
+    for operation in read_fixture(path.as_ref()).operations {
+        PoolOperation::execute(&mut lp_pool, operation, 0)?;
+    }
+    Ok(lp_pool.expect("fixture without operations"))
+}
+
+fn fixture(name: &str) -> String {
+    format!("{FIXTURES}/{name}.json")
+}
+
+#[test]
+fn it_replays_every_fixture_to_its_documented_outcome() {
+    let mut paths: Vec<_> = fs::read_dir(FIXTURES)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let expected = read_fixture(&path).error;
+        match replay_fixture(&path) {
+            Ok(lp_pool) => {
+                assert_eq!(expected, None, "{} replayed without error", path.display());
+                assert_eq!(lp_pool.check_solvency(), Ok(()), "{}", path.display());
+            }
+            Err(error) => assert_eq!(
+                Some(error.code()),
+                expected,
+                "{}: {}",
+                path.display(),
+                error.display_chain()
+            ),
+        }
+    }
+}
+
+#[test]
+fn it_rejects_swap_worth_more_than_pool_tokens() {
+    assert!(matches!(
+        replay_fixture(fixture("swap_underflow")),
+        Err(Error::LpPool(
+            LpPoolError::InsufficientTokenLiquidity { .. }
+        ))
+    ));
+}
+
+#[test]
+fn it_rejects_removal_from_pool_without_deposits() {
+    assert!(matches!(
+        replay_fixture(fixture("empty_pool_remove")),
+        Err(Error::LpPool(LpPoolError::EmptyPool))
+    ));
+}
+
+#[test]
+fn it_reports_overflow_at_top_of_price_scale() {
+    assert!(matches!(
+        replay_fixture(fixture("price_scale_mismatch")),
+        Err(Error::LpPool(LpPoolError::ArithmeticOverflow { .. }))
+    ));
+
+    // The same swap at the intended price of 1.37
+    let mut lp_pool = None;
+    for operation in [
+        Operation::Init {
+            price: "1.37".parse().unwrap(),
+            min_fee: Fee::from_basis_points(10),
+            max_fee: Fee::from_basis_points(900),
+            liquidity_target: TokenAmount::from_lamports(1_000_000),
+        },
+        Operation::AddLiquidity(TokenAmount::from_lamports(1_000_000)),
+        Operation::Swap(StakedTokenAmount::from_lamports(1_000)),
+    ] {
+        PoolOperation::execute(&mut lp_pool, operation, 0).unwrap();
+    }
+    // 1_000 * 1.37 = 1_370 paid out, less the 0.1% fee of 1 kept by the pool
+    let state = lp_pool.unwrap().state();
+    assert_eq!(state.price, Price::try_from_points(137).unwrap());
+    assert_eq!(
+        state.token_amount,
+        TokenAmount::from_lamports(1_000_000 - (1_370 - 1))
+    );
+}
+
+#[test]
+fn it_rejects_fee_above_one_hundred_percent() {
+    assert!(matches!(
+        replay_fixture(fixture("fee_over_100_percent")),
+        Err(Error::LpPool(LpPoolError::BasisPointsOverflow(fee))) if fee > Fee::MAX
+    ));
+}
@@ -0,0 +1,53 @@
+//! Counts allocations made while `LpPool::run_ops` executes successful operations.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use liquidity_pool::lp_pool::bulk::PoolOp;
+use liquidity_pool::prelude::*;
+use liquidity_pool::LAMPORTS_PER_SOL;
+
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+#[test]
+fn it_runs_successful_ops_without_allocating() {
+    let mut pool = LpPool::init(PoolParams {
+        price: "1.5".parse().unwrap(),
+        fee_range: FeeRange::new("0.1".parse().unwrap(), "9".parse().unwrap()).unwrap(),
+        liquidity_target: TokenAmount::from_lamports(90 * LAMPORTS_PER_SOL),
+    })
+    .unwrap();
+    pool.add_liquidity(TokenAmount::from_lamports(100 * LAMPORTS_PER_SOL))
+        .unwrap();
+    let ops = (0..10_000u64).map(|index| match index % 3 {
+        0 => PoolOp::AddLiquidity(TokenAmount::from_lamports(LAMPORTS_PER_SOL / 100)),
+        1 => PoolOp::Swap(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL / 200)),
+        _ => PoolOp::RemoveLiquidity(LpTokenAmount::from_lamports(LAMPORTS_PER_SOL / 100)),
+    });
+    let mut failures = 0;
+
+    let before = ALLOCATIONS.with(Cell::get);
+    pool.run_ops(ops, |_, result| failures += usize::from(result.is_err()));
+    let allocations = ALLOCATIONS.with(Cell::get) - before;
+
+    assert_eq!(failures, 0);
+    assert_eq!(allocations, 0);
+}
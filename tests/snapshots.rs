@@ -0,0 +1,140 @@
+//! Pins the `Display` and serialized output of the public types, run `cargo insta review`
+//! (or `INSTA_UPDATE=always cargo test`) to accept an intentional format change.
+
+#[cfg(feature = "std")]
+use liquidity_pool::lp_pool::status::StatusFormat;
+use liquidity_pool::prelude::*;
+use liquidity_pool::LAMPORTS_PER_SOL;
+
+fn pool() -> LpPool {
+    LpPool::init(PoolParams {
+        price: "1.5".parse().unwrap(),
+        fee_range: FeeRange::new("0.1".parse().unwrap(), "9".parse().unwrap()).unwrap(),
+        liquidity_target: TokenAmount::from_lamports(90 * LAMPORTS_PER_SOL),
+    })
+    .unwrap()
+}
+
+fn extreme_state() -> PoolState {
+    PoolState {
+        price: "184467440737095516.15".parse().unwrap(),
+        min_fee: Fee::MAX,
+        max_fee: Fee::MAX,
+        liquidity_target: TokenAmount::from_lamports(u64::MAX),
+        token_amount: TokenAmount::from_lamports(u64::MAX),
+        staked_token_amount: StakedTokenAmount::from_lamports(u64::MAX),
+        lp_token_amount: LpTokenAmount::from_lamports(u64::MAX),
+        fees_collected: TokenAmount::from_lamports(u64::MAX),
+        referral_fees_owed: TokenAmount::from_lamports(u64::MAX),
+        dust: TokenAmount::from_lamports(u64::MAX),
+        protocol_fees_owed: TokenAmount::from_lamports(u64::MAX),
+    }
+}
+
+#[test]
+fn empty_pool_display() {
+    insta::assert_snapshot!(pool());
+}
+
+#[test]
+fn pool_after_swap_display() {
+    let mut pool = pool();
+    pool.add_liquidity(TokenAmount::from_lamports(100 * LAMPORTS_PER_SOL))
+        .unwrap();
+    pool.swap(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL / 10))
+        .unwrap();
+
+    insta::assert_snapshot!(pool);
+}
+
+#[test]
+fn pool_after_swap_alternate_display() {
+    let mut pool = pool();
+    pool.add_liquidity(TokenAmount::from_lamports(100 * LAMPORTS_PER_SOL))
+        .unwrap();
+    pool.swap(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL / 10))
+        .unwrap();
+
+    insta::assert_snapshot!(format!("{pool:#}"));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn pool_after_swap_status_line() {
+    let mut pool = pool();
+    pool.add_liquidity(TokenAmount::from_lamports(100 * LAMPORTS_PER_SOL))
+        .unwrap();
+    pool.swap(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL / 10))
+        .unwrap();
+
+    insta::assert_snapshot!(pool.status_line());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn pool_after_swap_custom_status_line() {
+    let mut pool = pool();
+    pool.add_liquidity(TokenAmount::from_lamports(100 * LAMPORTS_PER_SOL))
+        .unwrap();
+    pool.swap(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL / 7))
+        .unwrap();
+
+    insta::assert_snapshot!(pool.status_line_with(StatusFormat {
+        decimals: 6,
+        include_fees: false,
+        include_sequence: true,
+    }));
+}
+
+#[test]
+fn extreme_state_display() {
+    insta::assert_snapshot!(extreme_state());
+}
+
+#[test]
+fn fee_and_price_display() {
+    let fees = [
+        Fee::ZERO,
+        Fee::from_centibps(1),
+        Fee::from_centibps(25),
+        Fee::from_centibps(2_530),
+        Fee::from_basis_points(30),
+        Fee::MAX,
+        Fee::from_centibps(u32::MAX),
+    ];
+    let prices = [
+        Price::try_from(0u64).unwrap(),
+        Price::try_from_points(5).unwrap(),
+        Price::try_from_points(150).unwrap(),
+        "184467440737095516.15".parse().unwrap(),
+    ];
+    let rendered: Vec<String> = fees
+        .iter()
+        .map(|fee| format!("fee {fee:?} = {fee}"))
+        .chain(
+            prices
+                .iter()
+                .map(|price| format!("price {price:?} = {price}")),
+        )
+        .collect();
+
+    insta::assert_snapshot!(rendered.join("\n"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn pool_state_json() {
+    let mut pool = pool();
+    pool.add_liquidity(TokenAmount::from_lamports(100 * LAMPORTS_PER_SOL))
+        .unwrap();
+    pool.swap(StakedTokenAmount::from_lamports(LAMPORTS_PER_SOL / 10))
+        .unwrap();
+
+    insta::assert_snapshot!(serde_json::to_string_pretty(&pool.state()).unwrap());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn extreme_state_json() {
+    insta::assert_snapshot!(serde_json::to_string_pretty(&extreme_state()).unwrap());
+}